@@ -0,0 +1,166 @@
+//! Differential testing: evaluates queries with this engine and with a
+//! reference database, reporting where the two disagree.
+
+use crate::engine::engine::{format_num, to_text, CalcResult, Engine};
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+
+/// Database the engine's results are checked against.
+pub enum Reference {
+    Sqlite(Connection),
+}
+
+impl Reference {
+    /// Opens `sqlite::memory:` or `sqlite:<path>`.
+    pub fn connect(connection: &str) -> Result<Self, String> {
+        let path = match connection.strip_prefix("sqlite:") {
+            Some(path) => path,
+            None => {
+                return Err(format!(
+                    "unsupported reference database {}, expected sqlite:<path> or sqlite::memory:",
+                    connection
+                ))
+            }
+        };
+
+        let conn = match path {
+            ":memory:" => Connection::open_in_memory(),
+            path => Connection::open(path),
+        };
+        conn.map(Reference::Sqlite).map_err(|err| err.to_string())
+    }
+
+    /// The rows `query` returns, one per line with their columns separated
+    /// by commas.
+    fn query(&self, query: &str) -> Result<String, String> {
+        match self {
+            Reference::Sqlite(conn) => sqlite_rows(conn, query)
+                // SQLite appends the failing offset on a line of its own
+                .map_err(|err| err.to_string().replace("\n ", " ")),
+        }
+    }
+}
+
+fn sqlite_rows(conn: &Connection, query: &str) -> rusqlite::Result<String> {
+    let mut statement = conn.prepare(query)?;
+    let width = statement.column_count();
+    let mut rows = statement.query([])?;
+    let mut lines = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(width);
+        for i in 0..width {
+            values.push(match row.get_ref(i)? {
+                ValueRef::Null => String::from("NULL"),
+                ValueRef::Integer(int) => int.to_string(),
+                ValueRef::Real(real) => format_num(real),
+                ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+                ValueRef::Blob(_) => String::from("<blob>"),
+            });
+        }
+        lines.push(values.join(", "));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// A query whose results differ; each side is either the rendered value or
+/// `error: <message>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub query: String,
+    pub ours: String,
+    pub reference: String,
+}
+
+/// Renders a value the way SQLite would return it, booleans being 1 and 0,
+/// the values of a row separated by commas and the rows of a table on
+/// lines of their own.
+fn normalize(value: &CalcResult) -> String {
+    match value {
+        CalcResult::Num(num) => format_num(*num),
+        CalcResult::Bool(boolean) => String::from(if *boolean { "1" } else { "0" }),
+        CalcResult::Row(values) => {
+            let values: Vec<_> = values.iter().map(normalize).collect();
+            values.join(", ")
+        }
+        CalcResult::Table(rows) => {
+            let rows: Vec<_> = rows
+                .iter()
+                .map(|row| normalize(&CalcResult::Row(row.clone())))
+                .collect();
+            rows.join("\n")
+        }
+        value => to_text(value),
+    }
+}
+
+/// Runs `query` on both sides. Values must match exactly; two errors count
+/// as agreement whatever their messages.
+pub fn compare(engine: &Engine, reference: &Reference, query: &str) -> Option<Divergence> {
+    let ours = engine
+        .exec(query)
+        .map(|value| normalize(&value))
+        .map_err(|err| err.to_string());
+    let theirs = reference.query(query);
+
+    match (ours, theirs) {
+        (Ok(ours), Ok(theirs)) if ours == theirs => None,
+        (Err(_), Err(_)) => None,
+        (ours, theirs) => {
+            let render =
+                |res: Result<String, String>| res.unwrap_or_else(|err| format!("error: {}", err));
+            Some(Divergence {
+                query: query.to_string(),
+                ours: render(ours),
+                reference: render(theirs),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqlite() -> Reference {
+        Reference::connect("sqlite::memory:").unwrap()
+    }
+
+    #[test]
+    fn compare_agreeing() {
+        for query in [
+            "SELECT 1 + 2 * 3",
+            "SELECT 2 > 3",
+            "SELECT 'abc'",
+            "SELECT 0.5 + 1",
+            "SELECT 1, 'a', NULL",
+            "SELECT * FROM (VALUES (1, 'a'), (2, NULL)) t",
+        ] {
+            assert_eq!(compare(&Engine::default(), &sqlite(), query), None);
+        }
+    }
+
+    #[test]
+    fn compare_diverging() {
+        assert_eq!(
+            compare(&Engine::default(), &sqlite(), "SELECT 1 / 0"),
+            Some(Divergence {
+                query: String::from("SELECT 1 / 0"),
+                ours: String::from("error: [Division By Zero]: Division by zero"),
+                reference: String::from("NULL"),
+            })
+        );
+    }
+
+    #[test]
+    fn compare_both_errors() {
+        assert_eq!(
+            compare(&Engine::default(), &sqlite(), "Give the data"),
+            None
+        );
+    }
+
+    #[test]
+    fn connect_unsupported() {
+        assert!(Reference::connect("postgres://localhost/db").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Aggregate functions (`COUNT`, `SUM` and the like) and the groups of rows
+//! a `GROUP BY` computes them over.
+
+use super::engine::CalcResult;
+use super::query::Field;
+use sqlparser::ast::{Expr, Function, FunctionArg};
+use std::rc::Rc;
+
+pub const AGGREGATE_FUNCTIONS: [&str; 5] = ["AVG", "COUNT", "MAX", "MIN", "SUM"];
+
+/// The rows sharing the values of the `GROUP BY` expressions, the `key`.
+#[derive(Clone, Debug)]
+pub struct Group {
+    pub exprs: Rc<[Expr]>,
+    pub key: Vec<CalcResult>,
+    pub fields: Rc<[Field]>,
+    pub rows: Vec<Rc<[CalcResult]>>,
+}
+
+impl Group {
+    /// The key value of `expr` when it is one of the `GROUP BY`
+    /// expressions.
+    pub fn value(&self, expr: &Expr) -> Option<&CalcResult> {
+        let i = self.exprs.iter().position(|grouped| grouped == expr)?;
+        self.key.get(i)
+    }
+}
+
+/// Whether `func` calls an aggregate function, as opposed to a window
+/// function over the rows of a partition.
+pub fn is_aggregate(func: &Function) -> bool {
+    if func.over.is_some() {
+        return false;
+    }
+    match &func.name.0[..] {
+        [name] => AGGREGATE_FUNCTIONS.contains(&name.value.to_uppercase().as_str()),
+        _ => false,
+    }
+}
+
+/// Whether `expr` calls an aggregate function, outside of any subquery.
+pub fn contains_aggregate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(func) if is_aggregate(func) => true,
+        expr => children(expr).into_iter().any(contains_aggregate),
+    }
+}
+
+/// The expressions `expr` is made of, those of its subqueries aside.
+pub fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::UnaryOp { expr, .. }
+        | Expr::Cast { expr, .. }
+        | Expr::TryCast { expr, .. }
+        | Expr::Extract { expr, .. }
+        | Expr::Collate { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::InSubquery { expr, .. } => vec![expr],
+        Expr::MapAccess { column, .. } => vec![column],
+        Expr::IsDistinctFrom(left, right)
+        | Expr::IsNotDistinctFrom(left, right)
+        | Expr::BinaryOp { left, right, .. } => vec![left, right],
+        Expr::InList { expr, list, .. } => {
+            let mut children = vec![expr.as_ref()];
+            children.extend(list);
+            children
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => vec![expr, low, high],
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+        } => {
+            let mut children = vec![expr.as_ref()];
+            children.extend(substring_from.as_deref());
+            children.extend(substring_for.as_deref());
+            children
+        }
+        Expr::Trim { expr, trim_where } => {
+            let mut children = vec![expr.as_ref()];
+            children.extend(trim_where.as_ref().map(|(_, chars)| chars.as_ref()));
+            children
+        }
+        Expr::Function(func) => func
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => arg,
+            })
+            .collect(),
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            let mut children: Vec<_> = operand.as_deref().into_iter().collect();
+            children.extend(conditions);
+            children.extend(results);
+            children.extend(else_result.as_deref());
+            children
+        }
+        Expr::ListAgg(list_agg) => vec![&list_agg.expr],
+        Expr::GroupingSets(sets) | Expr::Cube(sets) | Expr::Rollup(sets) => {
+            sets.iter().flatten().collect()
+        }
+        Expr::Identifier(_)
+        | Expr::Wildcard
+        | Expr::QualifiedWildcard(_)
+        | Expr::CompoundIdentifier(_)
+        | Expr::Value(_)
+        | Expr::TypedString { .. }
+        | Expr::Exists(_)
+        | Expr::Subquery(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn parse(expr: &str) -> Expr {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, expr).tokenize().unwrap();
+        Parser::new(tokens, &dialect).parse_expr().unwrap()
+    }
+
+    #[test]
+    fn contains_aggregate_finds_nested_calls() {
+        assert!(contains_aggregate(&parse("1 + COUNT(*)")));
+        assert!(contains_aggregate(&parse("CASE WHEN a THEN sum(b) END")));
+        assert!(contains_aggregate(&parse("ROUND(AVG(x), 2)")));
+        assert!(!contains_aggregate(&parse("a + SQRT(b)")));
+        assert!(!contains_aggregate(&parse("(SELECT COUNT(*)) + 1")));
+        assert!(!contains_aggregate(&parse("SUM(x) OVER (PARTITION BY y)")));
+    }
+}
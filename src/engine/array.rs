@@ -0,0 +1,332 @@
+//! Arrays: `ARRAY[1, 2, 3]` literals and `a[i]` subscripts. The parser
+//! knows neither syntax, so [`desugar`] first rewrites the tokens into calls
+//! of the array functions: `ARRAY[1, 2][2]` is parsed as
+//! `ARRAY_EXTRACT(ARRAY(1, 2), 2)`.
+
+use super::decimal;
+use super::engine::{CalcError, CalcResult};
+use super::operators::ValueType;
+use super::text::{self, Collation};
+use sqlparser::keywords::{Keyword, RESERVED_FOR_COLUMN_ALIAS};
+use sqlparser::tokenizer::Token;
+use std::cmp::Ordering;
+
+/// Keywords that never name a function or a column, so a subscript after
+/// `NOT (x)` applies to `(x)` alone.
+const OPERATOR_KEYWORDS: [Keyword; 25] = [
+    Keyword::ALL,
+    Keyword::AND,
+    Keyword::ANY,
+    Keyword::AS,
+    Keyword::ASSERT,
+    Keyword::BETWEEN,
+    Keyword::CASE,
+    Keyword::DISTINCT,
+    Keyword::ELSE,
+    Keyword::END,
+    Keyword::ESCAPE,
+    Keyword::EXISTS,
+    Keyword::FALSE,
+    Keyword::ILIKE,
+    Keyword::IN,
+    Keyword::INTERVAL,
+    Keyword::IS,
+    Keyword::LIKE,
+    Keyword::NOT,
+    Keyword::NULL,
+    Keyword::ON,
+    Keyword::OR,
+    Keyword::THEN,
+    Keyword::TRUE,
+    Keyword::WHEN,
+];
+
+/// Rewrites `ARRAY[...]` into `ARRAY(...)` and a subscript `x[i]` into
+/// `ARRAY_EXTRACT(x, i)`. Subscripts apply to names, literals, function
+/// calls and parenthesized expressions; other brackets, like the empty ones
+/// of `INT[]`, are left to the parser.
+pub fn desugar(mut tokens: Vec<Token>) -> Vec<Token> {
+    for i in 0..tokens.len() {
+        let after_array = previous(&tokens, i).is_some_and(|prev| is_array(&tokens[prev]));
+        if tokens[i] == Token::LBracket && after_array {
+            if let Some(close) = closing_bracket(&tokens, i) {
+                tokens[i] = Token::LParen;
+                tokens[close] = Token::RParen;
+            }
+        }
+    }
+
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i] == Token::LBracket {
+            let start = previous(&tokens, i).and_then(|end| operand_start(&tokens, end));
+            let close = closing_bracket(&tokens, i);
+            if let (Some(start), Some(close)) = (start, close) {
+                if next(&tokens, i) != Some(close) {
+                    tokens[i] = Token::Comma;
+                    tokens[close] = Token::RParen;
+                    let call = [Token::make_word("ARRAY_EXTRACT", None), Token::LParen];
+                    tokens.splice(start..start, call);
+                    i += 2;
+                }
+            }
+        }
+        i += 1;
+    }
+    tokens
+}
+
+fn previous(tokens: &[Token], i: usize) -> Option<usize> {
+    tokens[..i]
+        .iter()
+        .rposition(|token| !matches!(token, Token::Whitespace(_)))
+}
+
+fn next(tokens: &[Token], i: usize) -> Option<usize> {
+    tokens[i + 1..]
+        .iter()
+        .position(|token| !matches!(token, Token::Whitespace(_)))
+        .map(|position| i + 1 + position)
+}
+
+/// Index of the `]` matching the `[` at `open`.
+fn closing_bracket(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::LBracket => depth += 1,
+            Token::RBracket if depth == 1 => return Some(i),
+            Token::RBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Index of the `(` matching the `)` at `close`.
+fn opening_paren(tokens: &[Token], close: usize) -> Option<usize> {
+    let mut depth = 0;
+    for i in (0..=close).rev() {
+        match tokens[i] {
+            Token::RParen => depth += 1,
+            Token::LParen if depth == 1 => return Some(i),
+            Token::LParen => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_array(token: &Token) -> bool {
+    match token {
+        Token::Word(word) => word.keyword == Keyword::ARRAY && word.quote_style.is_none(),
+        _ => false,
+    }
+}
+
+fn is_name(token: &Token) -> bool {
+    match token {
+        Token::Word(word) => {
+            word.quote_style.is_some()
+                || !(OPERATOR_KEYWORDS.contains(&word.keyword)
+                    || RESERVED_FOR_COLUMN_ALIAS.contains(&word.keyword))
+        }
+        _ => false,
+    }
+}
+
+/// Where the operand ending at `end` starts, including the name of a
+/// function call and the qualifiers of `a.b`, or `None` when the token
+/// cannot end an operand.
+fn operand_start(tokens: &[Token], end: usize) -> Option<usize> {
+    let name = match &tokens[end] {
+        Token::RParen => {
+            let open = opening_paren(tokens, end)?;
+            match previous(tokens, open) {
+                Some(name) if is_name(&tokens[name]) => name,
+                _ => return Some(open),
+            }
+        }
+        Token::Number(_, _) | Token::SingleQuotedString(_) => return Some(end),
+        token if is_name(token) => end,
+        _ => return None,
+    };
+
+    let mut start = name;
+    while let Some(period) = previous(tokens, start).filter(|i| tokens[*i] == Token::Period) {
+        match previous(tokens, period) {
+            Some(qualifier) if is_name(&tokens[qualifier]) => start = qualifier,
+            _ => break,
+        }
+    }
+    Some(start)
+}
+
+/// The element at a 1-based `index`, NULL outside of the array.
+pub fn extract(items: &[CalcResult], index: &CalcResult) -> Result<CalcResult, CalcError> {
+    let index = match index {
+        CalcResult::Int(int) => *int,
+        index => match index.as_f64() {
+            Some(num) if num.fract() == 0.0 => num as i64,
+            _ => {
+                return Err(CalcError::InvalidType(String::from(
+                    "Array subscripts must be integers",
+                )))
+            }
+        },
+    };
+    let element = usize::try_from(index)
+        .ok()
+        .and_then(|index| index.checked_sub(1))
+        .and_then(|index| items.get(index));
+    Ok(element.cloned().unwrap_or(CalcResult::Null))
+}
+
+/// `left || right` where either side is an array: two arrays are joined and
+/// a single value is appended or prepended.
+pub fn concat(left: &CalcResult, right: &CalcResult) -> Result<CalcResult, CalcError> {
+    let mut items = match left {
+        CalcResult::Array(items) => items.clone(),
+        CalcResult::Row(_) | CalcResult::Table(_) => return Err(not_an_element(left)),
+        value => vec![value.clone()],
+    };
+    match right {
+        CalcResult::Array(more) => items.extend(more.iter().cloned()),
+        CalcResult::Row(_) | CalcResult::Table(_) => return Err(not_an_element(right)),
+        value => items.push(value.clone()),
+    }
+    Ok(CalcResult::Array(items))
+}
+
+fn not_an_element(value: &CalcResult) -> CalcError {
+    CalcError::InvalidType(format!(
+        "Cannot concatenate {} with an Array",
+        ValueType::of(value)
+    ))
+}
+
+/// Whether the array holds `value`, comparing numbers by value whatever
+/// their kind and strings by `collation`. An array without `value` but with
+/// a NULL might still hold it, so the result is NULL.
+pub fn contains(items: &[CalcResult], value: &CalcResult, collation: Collation) -> CalcResult {
+    if items.iter().any(|item| equal(item, value, collation)) {
+        CalcResult::Bool(true)
+    } else if items.contains(&CalcResult::Null) {
+        CalcResult::Null
+    } else {
+        CalcResult::Bool(false)
+    }
+}
+
+fn equal(left: &CalcResult, right: &CalcResult, collation: Collation) -> bool {
+    match (left, right) {
+        (CalcResult::Str(l), CalcResult::Str(r)) => {
+            text::compare(l, r, collation) == Ordering::Equal
+        }
+        _ => match (decimal::of(left), decimal::of(right)) {
+            (Some(l), Some(r)) => l == r,
+            _ => match (left.as_f64(), right.as_f64()) {
+                (Some(l), Some(r)) => l == r,
+                _ => left == right,
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn rewrite(sql: &str) -> String {
+        let tokens = Tokenizer::new(&GenericDialect {}, sql).tokenize().unwrap();
+        desugar(tokens).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn desugars_arrays_and_subscripts() {
+        let cases = [
+            ("SELECT ARRAY[1, 2]", "SELECT ARRAY(1, 2)"),
+            ("SELECT ARRAY[]", "SELECT ARRAY()"),
+            (
+                "SELECT ARRAY[1, 2][2]",
+                "SELECT ARRAY_EXTRACT(ARRAY(1, 2),2)",
+            ),
+            (
+                "SELECT ARRAY[ARRAY[1], ARRAY[2]][1][1]",
+                "SELECT ARRAY_EXTRACT(ARRAY_EXTRACT(ARRAY(ARRAY(1), ARRAY(2)),1),1)",
+            ),
+            ("SELECT a.b[1 + 1]", "SELECT ARRAY_EXTRACT(a.b,1 + 1)"),
+            ("SELECT f(x)[1]", "SELECT ARRAY_EXTRACT(f(x),1)"),
+            (
+                "SELECT NOT (ARRAY[TRUE])[1]",
+                "SELECT NOT ARRAY_EXTRACT((ARRAY(TRUE)),1)",
+            ),
+            (
+                "SELECT ARRAY[1, 2][ARRAY[2][1]]",
+                "SELECT ARRAY_EXTRACT(ARRAY(1, 2),ARRAY_EXTRACT(ARRAY(2),1))",
+            ),
+            ("SELECT CAST(x AS INT[])", "SELECT CAST(x AS INT[])"),
+            ("SELECT 'ab'[1]", "SELECT ARRAY_EXTRACT('ab',1)"),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(rewrite(sql), expected, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn extracts_elements() {
+        let items = [CalcResult::Int(10), CalcResult::Int(20)];
+        assert_eq!(
+            extract(&items, &CalcResult::Int(2)),
+            Ok(CalcResult::Int(20))
+        );
+        assert_eq!(
+            extract(&items, &CalcResult::Num(1.0)),
+            Ok(CalcResult::Int(10))
+        );
+        for index in [0, 3, -1] {
+            assert_eq!(
+                extract(&items, &CalcResult::Int(index)),
+                Ok(CalcResult::Null),
+                "{}",
+                index
+            );
+        }
+        assert!(extract(&items, &CalcResult::Num(1.5)).is_err());
+    }
+
+    #[test]
+    fn concat_rejects_rows() {
+        let array = CalcResult::Array(vec![CalcResult::Int(1)]);
+        assert_eq!(
+            concat(&array, &CalcResult::Row(vec![CalcResult::Int(2)])),
+            Err(CalcError::InvalidType(String::from(
+                "Cannot concatenate Row with an Array"
+            )))
+        );
+    }
+
+    #[test]
+    fn contains_compares_values() {
+        let items = [CalcResult::Int(1), CalcResult::Str(String::from("A"))];
+        let contains = |value: CalcResult, collation| contains(&items, &value, collation);
+        assert_eq!(
+            contains(CalcResult::Num(1.0), Collation::Binary),
+            CalcResult::Bool(true)
+        );
+        assert_eq!(
+            contains(CalcResult::Str(String::from("a")), Collation::Binary),
+            CalcResult::Bool(false)
+        );
+        assert_eq!(
+            contains(CalcResult::Str(String::from("a")), Collation::NoCase),
+            CalcResult::Bool(true)
+        );
+        assert_eq!(
+            super::contains(&[CalcResult::Null], &CalcResult::Int(1), Collation::Binary),
+            CalcResult::Null
+        );
+    }
+}
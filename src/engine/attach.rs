@@ -0,0 +1,234 @@
+//! `ATTACH 'fixtures.db' AS fx`: the tables of an SQLite database file,
+//! opened read-only and queryable as `fx.<table>`.
+
+use sqlparser::ast::Ident;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::Token;
+
+/// A parsed `ATTACH` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub path: String,
+    /// The name the tables of the database are qualified with.
+    pub schema: Ident,
+}
+
+/// Parses `ATTACH [DATABASE] '<path>' AS <schema>`.
+pub fn parse_attachment(parser: &mut Parser) -> Result<Option<Attachment>, ParserError> {
+    match parser.peek_token() {
+        Token::Word(word)
+            if word.quote_style.is_none() && word.value.eq_ignore_ascii_case("ATTACH") =>
+        {
+            parser.next_token();
+        }
+        _ => return Ok(None),
+    }
+
+    let _ = parser.parse_keyword(Keyword::DATABASE);
+    let path = parser.parse_literal_string()?;
+    parser.expect_keyword(Keyword::AS)?;
+    let schema = parser.parse_identifier()?;
+    while parser.consume_token(&Token::SemiColon) {}
+    if parser.peek_token() != Token::EOF {
+        return Err(ParserError::ParserError(format!(
+            "Expected end of statement, found: {}",
+            parser.peek_token()
+        )));
+    }
+
+    Ok(Some(Attachment { path, schema }))
+}
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::open;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::super::engine::{CalcError, CalcResult};
+    use super::super::operators::ValueType;
+    use super::super::table::{Column, Row, TableProvider};
+    use rusqlite::types::ValueRef;
+    use rusqlite::{Connection, OpenFlags};
+    use sqlparser::ast::Expr;
+    use std::sync::{Arc, Mutex};
+
+    /// A table of an attached database. Rows are read from it on every
+    /// scan, so changes made by other processes show up.
+    pub struct SqliteTable {
+        connection: Arc<Mutex<Connection>>,
+        name: String,
+        columns: Vec<Column>,
+    }
+
+    /// Opens the database at `path` read-only, giving each of its tables
+    /// with its name.
+    pub fn open(path: &str) -> Result<Vec<(String, SqliteTable)>, CalcError> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|err| failed(path, err))?;
+        let names = table_names(&connection).map_err(|err| failed(path, err))?;
+        let mut tables = Vec::with_capacity(names.len());
+        for name in &names {
+            let columns = columns(&connection, name).map_err(|err| failed(path, err))?;
+            tables.push((name.clone(), columns));
+        }
+
+        let connection = Arc::new(Mutex::new(connection));
+        Ok(tables
+            .into_iter()
+            .map(|(name, columns)| {
+                let table = SqliteTable {
+                    connection: connection.clone(),
+                    name: name.clone(),
+                    columns,
+                };
+                (name, table)
+            })
+            .collect())
+    }
+
+    fn failed(path: &str, err: impl std::fmt::Display) -> CalcError {
+        CalcError::FunctionFailed(format!("ATTACH {}: {}", path, err))
+    }
+
+    fn table_names(connection: &Connection) -> rusqlite::Result<Vec<String>> {
+        let mut statement = connection.prepare(
+            "SELECT name FROM sqlite_schema WHERE type IN ('table', 'view') \
+             AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )?;
+        let names = statement.query_map([], |row| row.get(0))?;
+        names.collect()
+    }
+
+    fn columns(connection: &Connection, table: &str) -> rusqlite::Result<Vec<Column>> {
+        let mut statement = connection.prepare(&format!(
+            "SELECT name, type FROM pragma_table_info({})",
+            quote(table, '\'')
+        ))?;
+        let columns = statement.query_map([], |row| {
+            let name: String = row.get(0)?;
+            let declared: String = row.get(1)?;
+            Ok(Column::new(&name, affinity(&declared)))
+        })?;
+        columns.collect()
+    }
+
+    /// The type of the values a column declared as `declared` holds, by
+    /// SQLite's rules for column affinity.
+    fn affinity(declared: &str) -> ValueType {
+        let declared = declared.to_uppercase();
+        if declared.contains("INT") {
+            ValueType::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|name| declared.contains(name))
+        {
+            ValueType::String
+        } else if declared.starts_with("BOOL") {
+            ValueType::Boolean
+        } else {
+            ValueType::Number
+        }
+    }
+
+    /// `name` quoted with `quote`, any quote in it doubled.
+    fn quote(name: &str, quote: char) -> String {
+        let doubled = format!("{}{}", quote, quote);
+        format!("{}{}{}", quote, name.replace(quote, &doubled), quote)
+    }
+
+    impl TableProvider for SqliteTable {
+        fn schema(&self) -> Vec<Column> {
+            self.columns.clone()
+        }
+
+        fn scan(
+            &self,
+            _filters: &[Expr],
+            projection: Option<&[usize]>,
+        ) -> Result<Vec<Row>, CalcError> {
+            let indices: Vec<_> = match projection {
+                Some(columns) => columns.to_vec(),
+                None => (0..self.columns.len()).collect(),
+            };
+            let mut columns = Vec::with_capacity(indices.len());
+            for i in &indices {
+                match self.columns.get(*i) {
+                    Some(column) => columns.push(quote(&column.name, '"')),
+                    None => {
+                        return Err(CalcError::InvalidType(format!(
+                            "Column index {} is out of range for {} column(s)",
+                            i,
+                            self.columns.len()
+                        )))
+                    }
+                }
+            }
+            // SQLite needs something to select; the rows are what counts.
+            let columns = match columns.is_empty() {
+                true => String::from("NULL"),
+                false => columns.join(", "),
+            };
+
+            let connection = self.connection.lock().map_err(|_| CalcError::Unexpected)?;
+            let query = format!("SELECT {} FROM {}", columns, quote(&self.name, '"'));
+            let read = |connection: &Connection| -> rusqlite::Result<Vec<Vec<CalcResult>>> {
+                let mut statement = connection.prepare(&query)?;
+                let mut rows = statement.query([])?;
+                let mut read = Vec::new();
+                while let Some(row) = rows.next()? {
+                    let mut values = Vec::with_capacity(indices.len());
+                    for i in 0..indices.len() {
+                        values.push(to_value(row.get_ref(i)?));
+                    }
+                    read.push(values);
+                }
+                Ok(read)
+            };
+            read(&connection).map_err(|err| failed(&self.name, err))
+        }
+    }
+
+    /// BLOBs have no value to convert to, so they read as their bytes in
+    /// hexadecimal, as `HEX()` would give them.
+    fn to_value(value: ValueRef) -> CalcResult {
+        match value {
+            ValueRef::Null => CalcResult::Null,
+            ValueRef::Integer(int) => CalcResult::Int(int),
+            ValueRef::Real(real) => CalcResult::Num(real),
+            ValueRef::Text(text) => CalcResult::Str(String::from_utf8_lossy(text).into_owned()),
+            ValueRef::Blob(bytes) => {
+                CalcResult::Str(bytes.iter().map(|byte| format!("{:02X}", byte)).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn parse(sql: &str) -> Result<Option<Attachment>, ParserError> {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, sql).tokenize().unwrap();
+        parse_attachment(&mut Parser::new(tokens, &dialect))
+    }
+
+    #[test]
+    fn parse_attachments() {
+        let attachment = parse("ATTACH DATABASE 'fixtures.db' AS fx;")
+            .unwrap()
+            .unwrap();
+        assert_eq!(attachment.path, "fixtures.db");
+        assert_eq!(attachment.schema, Ident::new("fx"));
+        assert_eq!(
+            parse("attach 'a.db' as a").unwrap().unwrap().schema,
+            Ident::new("a")
+        );
+        assert_eq!(parse("SELECT 1").unwrap(), None);
+        assert!(parse("ATTACH 1 AS fx").is_err());
+        assert!(parse("ATTACH 'a.db' fx").is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! `CAST(expr AS type)`: conversions between the value types.
+
+use super::decimal;
+use super::engine::{format_num, to_text, CalcError, CalcResult, OnOverflow};
+use super::operators::ValueType;
+use super::temporal::{self, Zone};
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+use sqlparser::ast::DataType;
+
+/// The value type an SQL data type converts to, or `None` when the engine
+/// has no values of that type.
+pub fn target(data_type: &DataType) -> Option<ValueType> {
+    match data_type {
+        DataType::TinyInt(_) | DataType::SmallInt(_) | DataType::Int(_) | DataType::BigInt(_) => {
+            Some(ValueType::Integer)
+        }
+        DataType::Float(_) | DataType::Real | DataType::Double => Some(ValueType::Number),
+        DataType::Decimal(_, _) => Some(ValueType::Decimal),
+        DataType::Char(_) | DataType::Varchar(_) | DataType::Text | DataType::String => {
+            Some(ValueType::String)
+        }
+        DataType::Boolean => Some(ValueType::Boolean),
+        DataType::Date => Some(ValueType::Date),
+        DataType::Timestamp => Some(ValueType::Timestamp),
+        DataType::Interval => Some(ValueType::Interval),
+        _ => None,
+    }
+}
+
+/// Converts `value` to `data_type`, integers overflowing as `overflow`
+/// says.
+pub fn convert(
+    value: CalcResult,
+    data_type: &DataType,
+    zone: &Zone,
+    overflow: OnOverflow,
+) -> Result<CalcResult, CalcError> {
+    match (to_type(value, data_type, zone)?, integer_bits(data_type)) {
+        (CalcResult::Num(num), Some(bits)) => to_integer(num, bits, data_type, overflow),
+        (CalcResult::Decimal(decimal), Some(bits)) => {
+            let rounded = decimal::round(&decimal, 0, RoundingMode::HalfUp);
+            // beyond i128 only saturating gives the right value
+            let int = rounded
+                .to_i128()
+                .unwrap_or(match rounded < BigDecimal::zero() {
+                    true => i128::MIN,
+                    false => i128::MAX,
+                });
+            narrow(int, bits, overflow, || {
+                out_of_range(&decimal::format(&decimal), data_type)
+            })
+        }
+        (CalcResult::Int(int), Some(bits)) => narrow(int as i128, bits, overflow, || {
+            out_of_range(&int.to_string(), data_type)
+        }),
+        (CalcResult::Decimal(decimal), None) => to_decimal(decimal, data_type),
+        (res, _) => Ok(res),
+    }
+}
+
+/// Like [`convert`], but a value that does not convert gives NULL. Casting
+/// to a type the engine has no values of is still an error.
+pub fn try_convert(
+    value: CalcResult,
+    data_type: &DataType,
+    zone: &Zone,
+    overflow: OnOverflow,
+) -> Result<CalcResult, CalcError> {
+    match target(data_type) {
+        Some(_) => Ok(convert(value, data_type, zone, overflow).unwrap_or(CalcResult::Null)),
+        None => convert(value, data_type, zone, overflow),
+    }
+}
+
+/// The width in bits of an integer type.
+fn integer_bits(data_type: &DataType) -> Option<i32> {
+    match data_type {
+        DataType::TinyInt(_) => Some(8),
+        DataType::SmallInt(_) => Some(16),
+        DataType::Int(_) => Some(32),
+        DataType::BigInt(_) => Some(64),
+        _ => None,
+    }
+}
+
+/// `int` as a signed integer of `bits` bits, overflowing as `overflow` says.
+fn narrow(
+    int: i128,
+    bits: i32,
+    overflow: OnOverflow,
+    describe: impl FnOnce() -> String,
+) -> Result<CalcResult, CalcError> {
+    let bound = 1i128 << (bits - 1);
+    if (-bound..bound).contains(&int) {
+        return Ok(CalcResult::Int(int as i64));
+    }
+    match overflow {
+        OnOverflow::Error => Err(CalcError::Overflow(describe())),
+        OnOverflow::Wrap => {
+            let shift = 128 - bits as u32;
+            Ok(CalcResult::Int(((int << shift) >> shift) as i64))
+        }
+        OnOverflow::Saturate => Ok(CalcResult::Int(int.clamp(-bound, bound - 1) as i64)),
+    }
+}
+
+/// Rounds half away from zero, as PostgreSQL does.
+fn to_integer(
+    num: f64,
+    bits: i32,
+    data_type: &DataType,
+    overflow: OnOverflow,
+) -> Result<CalcResult, CalcError> {
+    if !num.is_finite() {
+        return Err(CalcError::InvalidType(out_of_range(
+            &format_num(num),
+            data_type,
+        )));
+    }
+    narrow(num.round() as i128, bits, overflow, || {
+        out_of_range(&format_num(num), data_type)
+    })
+}
+
+/// Rounds to the scale of `DECIMAL(p, s)`, failing past its precision.
+fn to_decimal(decimal: BigDecimal, data_type: &DataType) -> Result<CalcResult, CalcError> {
+    let (precision, scale) = match data_type {
+        DataType::Decimal(Some(precision), scale) => (*precision as i64, scale.unwrap_or(0) as i64),
+        _ => return Ok(CalcResult::Decimal(decimal)),
+    };
+    let rounded = decimal::round(&decimal, scale, RoundingMode::HalfUp);
+    match rounded.is_zero() || rounded.order_of_magnitude() < precision - scale {
+        true => Ok(CalcResult::Decimal(rounded)),
+        false => Err(CalcError::InvalidType(out_of_range(
+            &decimal::format(&decimal),
+            data_type,
+        ))),
+    }
+}
+
+fn out_of_range(value: &str, data_type: &DataType) -> String {
+    format!("Value {} is out of range for {}", value, data_type)
+}
+
+fn to_type(value: CalcResult, data_type: &DataType, zone: &Zone) -> Result<CalcResult, CalcError> {
+    let target = target(data_type)
+        .ok_or_else(|| CalcError::InvalidType(format!("CAST to {} is not supported", data_type)))?;
+    let unparsable =
+        |str: &str| CalcError::InvalidType(format!("Cannot CAST '{}' AS {}", str, data_type));
+
+    match (value, target) {
+        (CalcResult::Null, _) => Ok(CalcResult::Null),
+        (value, target) if ValueType::of(&value) == target => Ok(value),
+        (value, ValueType::String) => Ok(CalcResult::Str(to_text(&value))),
+        (CalcResult::Str(str), ValueType::Number | ValueType::Integer) => {
+            let trimmed = str.trim();
+            match (target, trimmed.parse::<i64>(), trimmed.parse::<f64>()) {
+                (ValueType::Integer, Ok(int), _) => Ok(CalcResult::Int(int)),
+                (_, _, Ok(num)) => Ok(CalcResult::Num(num)),
+                _ => Err(unparsable(&str)),
+            }
+        }
+        (CalcResult::Str(str), ValueType::Decimal) => match decimal::parse(&str) {
+            Some(decimal) => Ok(CalcResult::Decimal(decimal)),
+            None => Err(unparsable(&str)),
+        },
+        (CalcResult::Int(int), ValueType::Number) => Ok(CalcResult::Num(int as f64)),
+        // rounded by `convert`
+        (CalcResult::Num(num), ValueType::Integer) => Ok(CalcResult::Num(num)),
+        (value @ CalcResult::Decimal(_), ValueType::Number) => {
+            Ok(CalcResult::Num(value.as_f64().unwrap_or_default()))
+        }
+        // rounded by `convert`
+        (value @ CalcResult::Decimal(_), ValueType::Integer) => Ok(value),
+        (CalcResult::Int(int), ValueType::Decimal) => {
+            Ok(CalcResult::Decimal(BigDecimal::from(int)))
+        }
+        (CalcResult::Num(num), ValueType::Decimal) => match decimal::from_f64(num) {
+            Some(decimal) => Ok(CalcResult::Decimal(decimal)),
+            None => Err(CalcError::InvalidType(out_of_range(
+                &format_num(num),
+                data_type,
+            ))),
+        },
+        (CalcResult::Str(str), ValueType::Boolean) => match parse_bool(&str) {
+            Some(boolean) => Ok(CalcResult::Bool(boolean)),
+            None => Err(unparsable(&str)),
+        },
+        (CalcResult::Str(str), ValueType::Date) => temporal::parse_date(&str).map(CalcResult::Date),
+        (CalcResult::Str(str), ValueType::Timestamp) => {
+            temporal::parse_timestamp(&str, zone).map(CalcResult::Timestamp)
+        }
+        (CalcResult::Str(str), ValueType::Interval) => {
+            temporal::parse_interval(&str, None).map(CalcResult::Interval)
+        }
+        (CalcResult::Num(num), ValueType::Boolean) if !num.is_nan() => {
+            Ok(CalcResult::Bool(num != 0.0))
+        }
+        (CalcResult::Int(int), ValueType::Boolean) => Ok(CalcResult::Bool(int != 0)),
+        (CalcResult::Decimal(decimal), ValueType::Boolean) => {
+            Ok(CalcResult::Bool(!decimal.is_zero()))
+        }
+        (CalcResult::Bool(boolean), ValueType::Number) => {
+            Ok(CalcResult::Num(if boolean { 1.0 } else { 0.0 }))
+        }
+        (CalcResult::Bool(boolean), ValueType::Integer) => Ok(CalcResult::Int(boolean as i64)),
+        (CalcResult::Bool(boolean), ValueType::Decimal) => {
+            Ok(CalcResult::Decimal(BigDecimal::from(boolean as i64)))
+        }
+        (CalcResult::Date(date), ValueType::Timestamp) => match date.and_hms_opt(0, 0, 0) {
+            Some(timestamp) => Ok(CalcResult::Timestamp(timestamp)),
+            None => Err(CalcError::Unexpected),
+        },
+        (CalcResult::Timestamp(timestamp), ValueType::Date) => {
+            Ok(CalcResult::Date(timestamp.date()))
+        }
+        (value, _) => Err(CalcError::InvalidType(format!(
+            "Cannot CAST {} AS {}",
+            ValueType::of(&value),
+            data_type
+        ))),
+    }
+}
+
+/// The boolean spellings PostgreSQL accepts.
+fn parse_bool(str: &str) -> Option<bool> {
+    match str.trim().to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "on" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cast(value: CalcResult, data_type: DataType) -> Result<CalcResult, CalcError> {
+        convert(value, &data_type, &Zone::default(), OnOverflow::Error)
+    }
+
+    #[test]
+    fn convert_matrix() {
+        assert_eq!(
+            cast(CalcResult::Num(3.7), DataType::Text),
+            Ok(CalcResult::Str(String::from("3.7")))
+        );
+        assert_eq!(
+            cast(CalcResult::Str(String::from(" 2.5 ")), DataType::Double),
+            Ok(CalcResult::Num(2.5))
+        );
+        assert_eq!(
+            cast(CalcResult::Str(String::from("TRUE")), DataType::Boolean),
+            Ok(CalcResult::Bool(true))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(0.0), DataType::Boolean),
+            Ok(CalcResult::Bool(false))
+        );
+        assert_eq!(
+            cast(CalcResult::Bool(true), DataType::BigInt(None)),
+            Ok(CalcResult::Int(1))
+        );
+        assert_eq!(
+            cast(CalcResult::Int(3), DataType::Real),
+            Ok(CalcResult::Num(3.0))
+        );
+        assert_eq!(cast(CalcResult::Null, DataType::Date), Ok(CalcResult::Null));
+    }
+
+    #[test]
+    fn convert_to_integer() {
+        let int = DataType::Int(None);
+        for (num, expected) in [(2.9, 3), (2.5, 3), (-2.5, -3), (2.4, 2), (-0.4, 0)] {
+            assert_eq!(
+                cast(CalcResult::Num(num), int.clone()),
+                Ok(CalcResult::Int(expected)),
+                "{}",
+                num
+            );
+        }
+        assert_eq!(
+            cast(CalcResult::Num(2147483647.0), int.clone()),
+            Ok(CalcResult::Int(2147483647))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(2147483647.5), int.clone()),
+            Err(CalcError::Overflow(String::from(
+                "Value 2147483647.5 is out of range for INT"
+            )))
+        );
+        assert_eq!(
+            cast(CalcResult::Int(-2147483648), int.clone()),
+            Ok(CalcResult::Int(-2147483648))
+        );
+        assert_eq!(
+            cast(CalcResult::Int(-2147483649), int.clone()),
+            Err(CalcError::Overflow(String::from(
+                "Value -2147483649 is out of range for INT"
+            )))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(300.0), DataType::TinyInt(None)),
+            Err(CalcError::Overflow(String::from(
+                "Value 300 is out of range for TINYINT"
+            )))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(1e18), DataType::BigInt(None)),
+            Ok(CalcResult::Int(1_000_000_000_000_000_000))
+        );
+        // parsed as an integer, without a detour through f64
+        assert_eq!(
+            cast(
+                CalcResult::Str(String::from("9223372036854775807")),
+                DataType::BigInt(None)
+            ),
+            Ok(CalcResult::Int(i64::MAX))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(f64::NAN), DataType::BigInt(None)),
+            Err(CalcError::InvalidType(String::from(
+                "Value NaN is out of range for BIGINT"
+            )))
+        );
+    }
+
+    #[test]
+    fn convert_to_decimal() {
+        let decimal = |text: &str| CalcResult::Decimal(decimal::parse(text).unwrap());
+        assert_eq!(
+            cast(
+                CalcResult::Str(String::from(" 0.10 ")),
+                DataType::Decimal(None, None)
+            ),
+            Ok(decimal("0.1"))
+        );
+        // the shortest text of the f64, not its binary expansion
+        assert_eq!(
+            cast(CalcResult::Num(0.1), DataType::Decimal(None, None)),
+            Ok(decimal("0.1"))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(1.005), DataType::Decimal(Some(5), Some(2))),
+            Ok(decimal("1.01"))
+        );
+        assert_eq!(
+            cast(CalcResult::Int(-7), DataType::Decimal(Some(3), None)),
+            Ok(decimal("-7"))
+        );
+        assert_eq!(
+            cast(decimal("999.95"), DataType::Decimal(Some(4), Some(1))),
+            Err(CalcError::InvalidType(String::from(
+                "Value 999.95 is out of range for NUMERIC(4,1)"
+            )))
+        );
+        assert_eq!(
+            cast(decimal("-2.5"), DataType::Int(None)),
+            Ok(CalcResult::Int(-3))
+        );
+        assert_eq!(
+            cast(decimal("2147483647.5"), DataType::Int(None)),
+            Err(CalcError::Overflow(String::from(
+                "Value 2147483647.5 is out of range for INT"
+            )))
+        );
+        assert_eq!(
+            cast(decimal("0.25"), DataType::Double),
+            Ok(CalcResult::Num(0.25))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(f64::NAN), DataType::Decimal(None, None)),
+            Err(CalcError::InvalidType(String::from(
+                "Value NaN is out of range for NUMERIC"
+            )))
+        );
+    }
+
+    #[test]
+    fn try_convert_gives_null() {
+        let int = DataType::Int(None);
+        assert_eq!(
+            try_convert(
+                CalcResult::Str(String::from("abc")),
+                &int,
+                &Zone::default(),
+                OnOverflow::Error
+            ),
+            Ok(CalcResult::Null)
+        );
+        assert_eq!(
+            try_convert(
+                CalcResult::Num(1e10),
+                &int,
+                &Zone::default(),
+                OnOverflow::Error
+            ),
+            Ok(CalcResult::Null)
+        );
+        assert_eq!(
+            try_convert(
+                CalcResult::Str(String::from("42")),
+                &int,
+                &Zone::default(),
+                OnOverflow::Error
+            ),
+            Ok(CalcResult::Int(42))
+        );
+        assert!(try_convert(
+            CalcResult::Num(1.0),
+            &DataType::Uuid,
+            &Zone::default(),
+            OnOverflow::Error
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn convert_errors() {
+        assert_eq!(
+            cast(CalcResult::Str(String::from("maybe")), DataType::Boolean),
+            Err(CalcError::InvalidType(String::from(
+                "Cannot CAST 'maybe' AS BOOLEAN"
+            )))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(1.0), DataType::Date),
+            Err(CalcError::InvalidType(String::from(
+                "Cannot CAST Number AS DATE"
+            )))
+        );
+        assert_eq!(
+            cast(CalcResult::Num(1.0), DataType::Uuid),
+            Err(CalcError::InvalidType(String::from(
+                "CAST to UUID is not supported"
+            )))
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Tables created with `CREATE TABLE`, kept for as long as the engine. Rows
+//! inserted into them are converted to the declared column types, as
+//! `CAST` would. The catalog also holds the results of `WITH` queries
+//! while the query naming them runs.
+
+use super::cast;
+use super::engine::{CalcError, OnOverflow};
+use super::query::Relation;
+use super::table::{Column, MemTable, Row, TableProvider};
+use super::temporal::Zone;
+use sqlparser::ast::{DataType, Ident};
+use std::collections::BTreeMap;
+
+struct SessionTable {
+    columns: Vec<(Ident, DataType)>,
+    rows: MemTable,
+}
+
+/// Session tables by name, keyed as identifiers are looked up.
+#[derive(Default)]
+pub struct Catalog {
+    tables: BTreeMap<String, SessionTable>,
+    /// Common table expressions in reach, innermost last.
+    bound: Vec<(String, Relation)>,
+}
+
+impl Catalog {
+    /// Adds an empty table with `columns`, replacing any table `key` names.
+    pub fn create(
+        &mut self,
+        key: String,
+        columns: Vec<(Ident, DataType)>,
+    ) -> Result<(), CalcError> {
+        let mut schema = Vec::with_capacity(columns.len());
+        for (name, data_type) in &columns {
+            match cast::target(data_type) {
+                Some(ty) => schema.push(Column::new(&name.value, ty)),
+                None => {
+                    return Err(CalcError::InvalidType(format!(
+                        "Column {} has unsupported type {}",
+                        name, data_type
+                    )))
+                }
+            }
+        }
+        let rows = MemTable::new(schema, Vec::new())?;
+        self.tables.insert(key, SessionTable { columns, rows });
+        Ok(())
+    }
+
+    /// Removes the table `key` names, returning whether there was one.
+    pub fn drop(&mut self, key: &str) -> bool {
+        self.tables.remove(key).is_some()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.tables.contains_key(key)
+    }
+
+    /// The columns of the table `key` names, with their declared types.
+    pub fn columns(&self, key: &str) -> Option<&[(Ident, DataType)]> {
+        self.tables.get(key).map(|table| table.columns.as_slice())
+    }
+
+    /// Every row of the table `key` names.
+    pub fn rows(&self, key: &str) -> Option<Result<Vec<Row>, CalcError>> {
+        self.tables.get(key).map(|table| table.rows.scan(&[], None))
+    }
+
+    /// Appends `rows`, each holding a value per column, converting every
+    /// value to its column's type (timestamps in `zone`, integers
+    /// overflowing as `overflow` says). Nothing is appended when a value
+    /// does not convert.
+    pub fn insert(
+        &mut self,
+        key: &str,
+        rows: Vec<Row>,
+        zone: &Zone,
+        overflow: OnOverflow,
+    ) -> Result<usize, CalcError> {
+        let table = match self.tables.get_mut(key) {
+            Some(table) => table,
+            None => {
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "Unknown table {}",
+                    key
+                )))
+            }
+        };
+        let count = rows.len();
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .zip(&table.columns)
+                    .map(|(value, (_, data_type))| cast::convert(value, data_type, zone, overflow))
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        table.rows.insert(rows)?;
+        Ok(count)
+    }
+
+    /// Makes `relation` the table `key` names until it is unbound, hiding
+    /// any table or earlier binding of the same name.
+    pub fn bind(&mut self, key: String, relation: Relation) {
+        self.bound.push((key, relation));
+    }
+
+    /// Removes the `count` latest bindings.
+    pub fn unbind(&mut self, count: usize) {
+        let len = self.bound.len().saturating_sub(count);
+        self.bound.truncate(len);
+    }
+
+    /// The relation bound to `key` most recently, if any.
+    pub fn bound(&self, key: &str) -> Option<&Relation> {
+        self.bound
+            .iter()
+            .rev()
+            .find(|(bound, _)| bound == key)
+            .map(|(_, relation)| relation)
+    }
+
+    /// Names of the tables, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.tables.keys().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::CalcResult;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::default();
+        catalog
+            .create(
+                String::from("T"),
+                vec![
+                    (Ident::new("a"), DataType::Int(None)),
+                    (Ident::new("b"), DataType::Text),
+                ],
+            )
+            .unwrap();
+        catalog
+    }
+
+    #[test]
+    fn insert_converts_values() {
+        let mut catalog = catalog();
+        let rows = vec![
+            vec![CalcResult::Str(String::from("1")), CalcResult::Int(2)],
+            vec![CalcResult::Num(2.5), CalcResult::Null],
+        ];
+        assert_eq!(
+            catalog.insert("T", rows, &Zone::default(), OnOverflow::Error),
+            Ok(2)
+        );
+        assert_eq!(
+            catalog.rows("T").unwrap().unwrap(),
+            vec![
+                vec![CalcResult::Int(1), CalcResult::Str(String::from("2"))],
+                vec![CalcResult::Int(3), CalcResult::Null],
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_is_atomic() {
+        let mut catalog = catalog();
+        let rows = vec![
+            vec![CalcResult::Int(1), CalcResult::Null],
+            vec![CalcResult::Str(String::from("x")), CalcResult::Null],
+        ];
+        assert!(catalog
+            .insert("T", rows, &Zone::default(), OnOverflow::Error)
+            .is_err());
+        assert_eq!(catalog.rows("T").unwrap().unwrap(), Vec::<Row>::new());
+    }
+
+    #[test]
+    fn create_and_drop() {
+        let mut catalog = catalog();
+        assert_eq!(catalog.names(), vec!["T"]);
+        assert_eq!(
+            catalog
+                .create(String::from("U"), vec![(Ident::new("x"), DataType::Uuid)])
+                .unwrap_err(),
+            CalcError::InvalidType(String::from("Column x has unsupported type UUID"))
+        );
+        assert!(catalog.drop("T"));
+        assert!(!catalog.drop("T"));
+        assert!(!catalog.contains("T"));
+    }
+
+    #[test]
+    fn bindings_shadow_each_other() {
+        let mut catalog = catalog();
+        let relation = |value| Relation::values(vec![vec![CalcResult::Int(value)]]).unwrap();
+        catalog.bind(String::from("T"), relation(1));
+        catalog.bind(String::from("T"), relation(2));
+        assert_eq!(catalog.bound("T"), Some(&relation(2)));
+        catalog.unbind(1);
+        assert_eq!(catalog.bound("T"), Some(&relation(1)));
+        catalog.unbind(1);
+        assert_eq!(catalog.bound("T"), None);
+        assert!(catalog.contains("T"));
+    }
+}
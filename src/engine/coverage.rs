@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Kind of engine surface a feature belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Clause,
+    Operator,
+    Function,
+    Type,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Category::Clause => "clause",
+            Category::Operator => "operator",
+            Category::Function => "function",
+            Category::Type => "type",
+        };
+        f.pad(name)
+    }
+}
+
+/// How often each feature was exercised, accumulated across queries.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    hits: BTreeMap<(Category, String), usize>,
+}
+
+impl Coverage {
+    pub fn record(&mut self, category: Category, name: &str) {
+        *self.hits.entry((category, name.to_string())).or_insert(0) += 1;
+    }
+
+    /// Adds the hits recorded by `other`.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (feature, hits) in &other.hits {
+            *self.hits.entry(feature.clone()).or_insert(0) += hits;
+        }
+    }
+
+    pub fn hits(&self, category: Category, name: &str) -> usize {
+        self.hits
+            .get(&(category, name.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Renders one line per feature of `surface` with its hit count, marking
+    /// the ones never exercised.
+    pub fn report(&self, surface: &[(Category, String)]) -> String {
+        let mut lines = Vec::new();
+        let mut covered = 0;
+        for (category, name) in surface {
+            let hits = self.hits(*category, name);
+            if hits > 0 {
+                covered += 1;
+                lines.push(format!("{:<9} {:<8} {}", category, name, hits));
+            } else {
+                lines.push(format!("{:<9} {:<8} UNTESTED", category, name));
+            }
+        }
+        lines.push(format!(
+            "{} of {} features exercised",
+            covered,
+            surface.len()
+        ));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_accumulates() {
+        let mut coverage = Coverage::default();
+        coverage.record(Category::Operator, "+");
+        coverage.record(Category::Operator, "+");
+        assert_eq!(coverage.hits(Category::Operator, "+"), 2);
+        assert_eq!(coverage.hits(Category::Operator, "-"), 0);
+    }
+
+    #[test]
+    fn report_marks_untested() {
+        let mut coverage = Coverage::default();
+        coverage.record(Category::Function, "SQRT");
+        let surface = [
+            (Category::Function, String::from("SQRT")),
+            (Category::Type, String::from("NULL")),
+        ];
+        assert_eq!(
+            coverage.report(&surface),
+            "function  SQRT     1\ntype      NULL     UNTESTED\n1 of 2 features exercised"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! `read_csv`: CSV files as tables. The first record names the columns;
+//! every other is a row. A column whose fields all read as integers holds
+//! integers, then numbers, then booleans, or else strings; an empty field
+//! is NULL.
+
+use super::engine::{CalcError, CalcResult};
+use super::operators::ValueType;
+use super::table::{Column, MemTable};
+
+/// Parses `text` into a table, `source` naming it in errors.
+pub fn parse(text: &str, source: &str) -> Result<MemTable, CalcError> {
+    let failed = |line: usize, message: String| {
+        CalcError::FunctionFailed(format!("READ_CSV {}:{}: {}", source, line, message))
+    };
+
+    let mut records = records(text).map_err(|(line, message)| failed(line, message))?;
+    if records.is_empty() {
+        return Err(failed(1, String::from("expected a header of column names")));
+    }
+    let header = records.remove(0).1;
+    for (line, record) in &records {
+        if record.len() != header.len() {
+            return Err(failed(
+                *line,
+                format!("expected {} field(s), got {}", header.len(), record.len()),
+            ));
+        }
+    }
+
+    let types: Vec<_> = (0..header.len())
+        .map(|i| {
+            column_type(
+                records
+                    .iter()
+                    .filter_map(|(_, record)| record[i].as_deref()),
+            )
+        })
+        .collect();
+    let schema = header
+        .iter()
+        .zip(&types)
+        .map(|(name, ty)| Column::new(name.as_deref().unwrap_or_default(), *ty))
+        .collect();
+    let rows = records
+        .into_iter()
+        .map(|(_, record)| {
+            record
+                .into_iter()
+                .zip(&types)
+                .map(|(field, ty)| match field {
+                    Some(field) => value(field, *ty),
+                    None => CalcResult::Null,
+                })
+                .collect()
+        })
+        .collect();
+    MemTable::new(schema, rows)
+}
+
+/// A field, `None` when it is empty and unquoted.
+type Field = Option<String>;
+
+/// A record with the line it starts on.
+type Record = (usize, Vec<Field>);
+
+/// The records of `text`, split as RFC 4180
+/// says: fields separated by commas, quoted with `"` when they hold a
+/// comma, a quote (doubled) or a line break. Blank lines are skipped.
+fn records(text: &str) -> Result<Vec<Record>, (usize, String)> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut record = Vec::new();
+        loop {
+            let mut field = String::new();
+            let mut quoted = false;
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(ch) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            field.push(ch);
+                        }
+                        None => return Err((start, String::from("unterminated quoted field"))),
+                    }
+                }
+            }
+            while let Some(&ch) = chars.peek() {
+                if ch == ',' || ch == '\n' || ch == '\r' {
+                    break;
+                } else if quoted {
+                    return Err((line, format!("unexpected {} after a quoted field", ch)));
+                }
+                field.push(ch);
+                chars.next();
+            }
+            record.push(match quoted || !field.is_empty() {
+                true => Some(field),
+                false => None,
+            });
+
+            match chars.next() {
+                Some(',') => continue,
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                _ => (),
+            }
+            line += 1;
+            break;
+        }
+        if record != [None] {
+            records.push((start, record));
+        }
+    }
+    Ok(records)
+}
+
+/// The narrowest type all of a column's fields read as.
+fn column_type<'a>(mut fields: impl Iterator<Item = &'a str> + Clone) -> ValueType {
+    if fields
+        .clone()
+        .all(|field| field.trim().parse::<i64>().is_ok())
+    {
+        ValueType::Integer
+    } else if fields
+        .clone()
+        .all(|field| field.trim().parse::<f64>().is_ok())
+    {
+        ValueType::Number
+    } else if fields.all(|field| ["true", "false"].contains(&field.trim().to_lowercase().as_str()))
+    {
+        ValueType::Boolean
+    } else {
+        ValueType::String
+    }
+}
+
+fn value(field: String, ty: ValueType) -> CalcResult {
+    let trimmed = field.trim();
+    match ty {
+        ValueType::Integer => trimmed.parse().map_or(CalcResult::Null, CalcResult::Int),
+        ValueType::Number => trimmed.parse().map_or(CalcResult::Null, CalcResult::Num),
+        ValueType::Boolean => CalcResult::Bool(trimmed.eq_ignore_ascii_case("true")),
+        _ => CalcResult::Str(field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::table::TableProvider;
+
+    #[test]
+    fn parse_infers_types() {
+        let table = parse(
+            "id,score,ok,name\r\n1,2.5,true,\"a, \"\"b\"\"\"\n2,,FALSE,\n\n3,4,false,\"two\nlines\"\n",
+            "t.csv",
+        )
+        .unwrap();
+        let types: Vec<_> = table.schema().into_iter().map(|column| column.ty).collect();
+        assert_eq!(
+            types,
+            [
+                ValueType::Integer,
+                ValueType::Number,
+                ValueType::Boolean,
+                ValueType::String
+            ]
+        );
+        let str = |str: &str| CalcResult::Str(String::from(str));
+        assert_eq!(
+            table.scan(&[], None).unwrap(),
+            vec![
+                vec![
+                    CalcResult::Int(1),
+                    CalcResult::Num(2.5),
+                    CalcResult::Bool(true),
+                    str("a, \"b\"")
+                ],
+                vec![
+                    CalcResult::Int(2),
+                    CalcResult::Null,
+                    CalcResult::Bool(false),
+                    CalcResult::Null
+                ],
+                vec![
+                    CalcResult::Int(3),
+                    CalcResult::Num(4.0),
+                    CalcResult::Bool(false),
+                    str("two\nlines")
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse("a,b\n1,2\n3\n", "t.csv").unwrap_err(),
+            CalcError::FunctionFailed(String::from("READ_CSV t.csv:3: expected 2 field(s), got 1"))
+        );
+        assert_eq!(
+            parse("a\n\"x", "t.csv").unwrap_err(),
+            CalcError::FunctionFailed(String::from("READ_CSV t.csv:2: unterminated quoted field"))
+        );
+        assert!(parse("", "t.csv").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Exact decimal numbers, for `Numeric::Decimal` and `CAST(x AS DECIMAL)`.
+
+use super::engine::{comparison, format_num, CalcError, CalcResult};
+use bigdecimal::{BigDecimal, One, RoundingMode, ToPrimitive, Zero};
+use sqlparser::ast::BinaryOperator;
+use std::str::FromStr;
+
+/// Significant digits kept of a quotient: `1 / 3` is
+/// 0.3333333333333333333333333333.
+const DIVISION_DIGITS: u64 = 28;
+
+/// Decimals are below 1e1000 in magnitude; fractional digits past the
+/// 1000th are rounded off.
+const MAX_DIGITS: i64 = 1000;
+
+/// Parses a decimal literal like `-2.50` or `1e-3`.
+pub fn parse(text: &str) -> Option<BigDecimal> {
+    bounded(BigDecimal::from_str(text.trim()).ok()?)
+}
+
+/// The decimal with the shortest text of the f64, so `0.1` converts to 0.1
+/// rather than to the binary fraction it stands for.
+pub fn from_f64(num: f64) -> Option<BigDecimal> {
+    match num.is_finite() {
+        true => parse(&format_num(num)),
+        false => None,
+    }
+}
+
+/// The decimal value of an integer or a decimal.
+pub fn of(value: &CalcResult) -> Option<BigDecimal> {
+    match value {
+        CalcResult::Int(int) => Some(BigDecimal::from(*int)),
+        CalcResult::Decimal(decimal) => Some(decimal.clone()),
+        _ => None,
+    }
+}
+
+/// Renders a decimal without trailing zeros or an exponent, as
+/// [`format_num`] renders numbers: `0.3`, `2`, `0.0001`.
+pub fn format(decimal: &BigDecimal) -> String {
+    decimal.normalized().to_plain_string()
+}
+
+/// Rounds to `scale` fractional digits (tens, hundreds, ... for a negative
+/// `scale`), padding with zeros when there are fewer.
+pub fn round(decimal: &BigDecimal, scale: i64, mode: RoundingMode) -> BigDecimal {
+    decimal.with_scale_round(scale.clamp(-MAX_DIGITS - 1, MAX_DIGITS), mode)
+}
+
+/// Rounds off fractional digits past [`MAX_DIGITS`], or `None` when the
+/// decimal is too large.
+fn bounded(decimal: BigDecimal) -> Option<BigDecimal> {
+    if decimal.order_of_magnitude() >= MAX_DIGITS {
+        None
+    } else if decimal.fractional_digit_count() > MAX_DIGITS {
+        Some(round(&decimal, MAX_DIGITS, RoundingMode::HalfUp))
+    } else {
+        Some(decimal)
+    }
+}
+
+fn quotient(dividend: &BigDecimal, divisor: &BigDecimal) -> Result<BigDecimal, CalcError> {
+    match divisor.is_zero() {
+        true => Err(CalcError::DivisionByZero),
+        false => Ok((dividend / divisor).with_prec(DIVISION_DIGITS)),
+    }
+}
+
+/// Raises to an integral power by repeated squaring, each step bounded so
+/// huge powers fail early instead of growing without limit.
+fn power(base: &BigDecimal, exponent: i64) -> Result<Option<BigDecimal>, CalcError> {
+    let mut res = BigDecimal::one();
+    let mut square = base.clone();
+    let mut remaining = exponent.unsigned_abs();
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            res = match bounded(&res * &square) {
+                Some(res) => res,
+                None => return Ok(None),
+            };
+        }
+        remaining >>= 1;
+        if remaining > 0 {
+            square = match bounded(square.square()) {
+                Some(square) => square,
+                None => return Ok(None),
+            };
+        }
+    }
+    match exponent < 0 {
+        true => quotient(&BigDecimal::one(), &res).map(Some),
+        false => Ok(Some(res)),
+    }
+}
+
+/// Applies `operator` to two decimals, or `None` when the result is not
+/// exact.
+pub fn apply(
+    operator: &BinaryOperator,
+    first_val: &BigDecimal,
+    second_val: &BigDecimal,
+) -> Option<Result<CalcResult, CalcError>> {
+    if let Some(compared) = comparison(operator, first_val.cmp(second_val)) {
+        return Some(Ok(CalcResult::Bool(compared)));
+    }
+
+    let res = match operator {
+        BinaryOperator::Plus => Ok(first_val + second_val),
+        BinaryOperator::Minus => Ok(first_val - second_val),
+        BinaryOperator::Multiply => Ok(first_val * second_val),
+        BinaryOperator::Divide => quotient(first_val, second_val),
+        BinaryOperator::Modulo if second_val.is_zero() => Err(CalcError::DivisionByZero),
+        BinaryOperator::Modulo => Ok(first_val % second_val),
+        BinaryOperator::BitwiseXor if first_val.is_zero() && second_val < &BigDecimal::zero() => {
+            Err(CalcError::DivisionByZero)
+        }
+        BinaryOperator::BitwiseXor if second_val.is_integer() => match second_val.to_i64() {
+            Some(exponent) if exponent.unsigned_abs() <= MAX_DIGITS as u64 => {
+                power(first_val, exponent).and_then(|res| {
+                    res.ok_or_else(|| out_of_range(first_val, operator, second_val))
+                })
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(res.and_then(|res| {
+        bounded(res)
+            .map(CalcResult::Decimal)
+            .ok_or_else(|| out_of_range(first_val, operator, second_val))
+    }))
+}
+
+fn out_of_range(
+    first_val: &BigDecimal,
+    operator: &BinaryOperator,
+    second_val: &BigDecimal,
+) -> CalcError {
+    CalcError::InvalidType(format!(
+        "Decimal {} {} {} is out of range",
+        format(first_val),
+        operator,
+        format(second_val)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str) -> BigDecimal {
+        parse(text).unwrap()
+    }
+
+    fn calc(
+        operator: BinaryOperator,
+        first_val: &str,
+        second_val: &str,
+    ) -> Result<String, CalcError> {
+        match apply(&operator, &decimal(first_val), &decimal(second_val)) {
+            Some(Ok(CalcResult::Decimal(res))) => Ok(format(&res)),
+            Some(Ok(res)) => Ok(res.to_string()),
+            Some(Err(e)) => Err(e),
+            None => Ok(String::from("f64")),
+        }
+    }
+
+    #[test]
+    fn parse_and_format() {
+        for (text, expected) in [
+            ("0.1", "0.1"),
+            (" -2.50 ", "-2.5"),
+            ("1e-3", "0.001"),
+            ("1.5E2", "150"),
+            ("0.000", "0"),
+            (
+                "12345678901234567890.123456789",
+                "12345678901234567890.123456789",
+            ),
+        ] {
+            assert_eq!(
+                parse(text).map(|res| format(&res)),
+                Some(String::from(expected)),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(parse("abc"), None);
+        assert_eq!(parse("1e1000"), None);
+        assert_eq!(
+            parse("1e-1001").map(|res| format(&res)),
+            Some(String::from("0"))
+        );
+        assert_eq!(from_f64(0.1), Some(decimal("0.1")));
+        assert_eq!(from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn arithmetic_is_exact() {
+        for (operator, first_val, second_val, expected) in [
+            (BinaryOperator::Plus, "0.1", "0.2", "0.3"),
+            (BinaryOperator::Minus, "1", "0.9", "0.1"),
+            (BinaryOperator::Multiply, "1.1", "1.1", "1.21"),
+            (BinaryOperator::Divide, "1", "4", "0.25"),
+            (
+                BinaryOperator::Divide,
+                "1",
+                "3",
+                "0.3333333333333333333333333333",
+            ),
+            (
+                BinaryOperator::Divide,
+                "2",
+                "3",
+                "0.6666666666666666666666666667",
+            ),
+            (BinaryOperator::Modulo, "5.5", "2", "1.5"),
+            (BinaryOperator::Modulo, "-5.5", "2", "-1.5"),
+            (BinaryOperator::BitwiseXor, "1.1", "2", "1.21"),
+            (BinaryOperator::BitwiseXor, "2", "-2", "0.25"),
+            (BinaryOperator::BitwiseXor, "2", "0.5", "f64"),
+            (BinaryOperator::Eq, "0.30", "0.3", "Result: true"),
+            (BinaryOperator::Lt, "0.1", "0.2", "Result: true"),
+        ] {
+            assert_eq!(
+                calc(operator.clone(), first_val, second_val),
+                Ok(String::from(expected)),
+                "{} {} {}",
+                first_val,
+                operator,
+                second_val
+            );
+        }
+    }
+
+    #[test]
+    fn arithmetic_errors() {
+        assert_eq!(
+            calc(BinaryOperator::Divide, "1", "0"),
+            Err(CalcError::DivisionByZero)
+        );
+        assert_eq!(
+            calc(BinaryOperator::Modulo, "1", "0.0"),
+            Err(CalcError::DivisionByZero)
+        );
+        assert_eq!(
+            calc(BinaryOperator::BitwiseXor, "0", "-1"),
+            Err(CalcError::DivisionByZero)
+        );
+        assert_eq!(
+            calc(BinaryOperator::Multiply, "1e500", "1e500"),
+            Err(CalcError::InvalidType(format!(
+                "Decimal 1{0} * 1{0} is out of range",
+                "0".repeat(500)
+            )))
+        );
+        assert!(calc(BinaryOperator::BitwiseXor, "10", "1000").is_err());
+    }
+}
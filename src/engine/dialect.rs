@@ -0,0 +1,242 @@
+use sqlparser::ast::Ident;
+use sqlparser::dialect::{
+    self as parser, GenericDialect, MsSqlDialect, SQLiteDialect, SnowflakeDialect,
+};
+use std::fmt;
+
+/// Behavior of the database being emulated, both how queries are parsed and
+/// how they evaluate where databases disagree. Every method has the generic
+/// behavior as default, so a dialect only overrides what differs.
+pub trait Dialect: fmt::Debug + Send + Sync {
+    /// sqlparser dialect used to tokenize and parse queries.
+    fn parser_dialect(&self) -> &dyn parser::Dialect {
+        &GenericDialect {}
+    }
+
+    /// Whether `'a' + 'b'` concatenates instead of failing.
+    fn plus_concatenates_strings(&self) -> bool {
+        false
+    }
+
+    /// Prefixes starting a comment running to the end of the line.
+    fn line_comment_prefixes(&self) -> &[&'static str] {
+        &["--"]
+    }
+
+    /// Key an identifier is looked up by: unquoted identifiers are
+    /// case-insensitive, quoted ones are taken literally.
+    fn normalize_identifier(&self, ident: &Ident) -> String {
+        match ident.quote_style {
+            Some(_) => ident.value.clone(),
+            None => ident.value.to_uppercase(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Generic;
+
+impl Dialect for Generic {}
+
+/// `+` concatenates strings.
+#[derive(Debug, Default)]
+pub struct SqlServer;
+
+impl Dialect for SqlServer {
+    fn parser_dialect(&self) -> &dyn parser::Dialect {
+        &MsSqlDialect {}
+    }
+
+    fn plus_concatenates_strings(&self) -> bool {
+        true
+    }
+}
+
+/// Accepts `//` line comments.
+#[derive(Debug, Default)]
+pub struct Snowflake;
+
+impl Dialect for Snowflake {
+    fn parser_dialect(&self) -> &dyn parser::Dialect {
+        &SnowflakeDialect {}
+    }
+
+    fn line_comment_prefixes(&self) -> &[&'static str] {
+        &["--", "//"]
+    }
+}
+
+/// Identifiers are case-insensitive even when quoted.
+#[derive(Debug, Default)]
+pub struct Sqlite;
+
+impl Dialect for Sqlite {
+    fn parser_dialect(&self) -> &dyn parser::Dialect {
+        &SQLiteDialect {}
+    }
+
+    fn normalize_identifier(&self, ident: &Ident) -> String {
+        ident.value.to_uppercase()
+    }
+}
+
+/// Rewrites the dialect's extra comment prefixes to `--` (padded to the same
+/// width, so error positions still match the input), leaving string
+/// literals and quoted identifiers alone.
+pub fn normalize_comments(query: &str, prefixes: &[&str]) -> String {
+    let mut out = String::with_capacity(query.len());
+    let mut quote = None;
+    let mut rest = query;
+
+    while let Some(ch) = rest.chars().next() {
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => (),
+            None if ch == '\'' || ch == '"' => quote = Some(ch),
+            None => {
+                if let Some(prefix) = prefixes
+                    .iter()
+                    .find(|prefix| **prefix != "--" && rest.starts_with(**prefix))
+                {
+                    out.push_str("--");
+                    out.push_str(&" ".repeat(prefix.len().saturating_sub(2)));
+                    rest = &rest[prefix.len()..];
+                    continue;
+                }
+            }
+        }
+        out.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    out
+}
+
+/// A statement of a script, with the line it starts on.
+type Located<'a> = (usize, &'a str);
+
+/// Splits a script at the semicolons ending its statements, skipping those
+/// in string literals, quoted identifiers and comments (`--`, the other
+/// `prefixes` and `/* */`). Each statement comes with the line it starts
+/// on; parts holding nothing but whitespace and comments are left out.
+pub fn split_statements<'a>(script: &'a str, prefixes: &[&str]) -> Vec<(usize, &'a str)> {
+    let (mut statements, _, last) = scan(script, prefixes);
+    statements.extend(last);
+    statements
+}
+
+/// Splits off the statements of a script that a semicolon ends, found as
+/// `split_statements` finds them, from the rest of it: the text after the
+/// last of those semicolons, which may hold the start of another.
+pub fn split_terminated<'a>(
+    script: &'a str,
+    prefixes: &[&str],
+) -> (Vec<(usize, &'a str)>, &'a str) {
+    let (statements, rest, _) = scan(script, prefixes);
+    (statements, &script[rest..])
+}
+
+/// The statements a semicolon ends, where the text after the last of them
+/// begins, and the statement in that text if there is one.
+fn scan<'a>(script: &'a str, prefixes: &[&str]) -> (Vec<Located<'a>>, usize, Option<Located<'a>>) {
+    let mut statements = Vec::new();
+    let mut rest_start = 0;
+    let mut start = None;
+    let mut line = 1;
+    let mut quote = None;
+    let mut i = 0;
+
+    while let Some(ch) = script[i..].chars().next() {
+        let rest = &script[i..];
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => (),
+            None if rest.starts_with("/*") => {
+                let end = rest[2..].find("*/").map_or(script.len(), |end| i + end + 4);
+                line += script[i..end].matches('\n').count();
+                i = end;
+                continue;
+            }
+            None if prefixes
+                .iter()
+                .chain(&["--"])
+                .any(|prefix| rest.starts_with(prefix)) =>
+            {
+                i = rest.find('\n').map_or(script.len(), |end| i + end);
+                continue;
+            }
+            None if ch == ';' => {
+                if let Some((first, start)) = start.take() {
+                    statements.push((first, &script[start..i]));
+                }
+                i += 1;
+                rest_start = i;
+                continue;
+            }
+            None if ch == '\'' || ch == '"' => quote = Some(ch),
+            None => (),
+        }
+        if start.is_none() && !ch.is_whitespace() {
+            start = Some((line, i));
+        }
+        if ch == '\n' {
+            line += 1;
+        }
+        i += ch.len_utf8();
+    }
+    let last = start.map(|(first, start)| (first, &script[start..]));
+
+    (statements, rest_start, last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_comments_outside_literals() {
+        assert_eq!(
+            normalize_comments("SELECT '//' // note", &["--", "//"]),
+            "SELECT '//' -- note"
+        );
+        assert_eq!(
+            normalize_comments("SELECT 1 // 2", &["--"]),
+            "SELECT 1 // 2"
+        );
+    }
+
+    #[test]
+    fn split_statements_outside_literals() {
+        let script =
+            "SELECT ';';\n-- a; comment\n\nSELECT \"a;b\" /* ; */ FROM t;\n  ; // x;\nSELECT 3";
+        assert_eq!(
+            split_statements(script, &["--", "//"]),
+            [
+                (1, "SELECT ';'"),
+                (4, "SELECT \"a;b\" /* ; */ FROM t"),
+                (6, "SELECT 3")
+            ]
+        );
+        assert_eq!(split_statements(" ; -- only a comment", &["--"]), []);
+    }
+
+    #[test]
+    fn split_terminated_keeps_the_rest() {
+        assert_eq!(
+            split_terminated("SELECT 1; SELECT ';'\n;SELECT\n 2", &["--"]),
+            (vec![(1, "SELECT 1"), (1, "SELECT ';'\n")], "SELECT\n 2")
+        );
+        assert_eq!(
+            split_terminated("SELECT 'a;\nb", &["--"]),
+            (vec![], "SELECT 'a;\nb")
+        );
+        assert_eq!(split_terminated("SELECT 1; -- x", &["--"]).1, " -- x");
+    }
+
+    #[test]
+    fn normalize_identifier_rules() {
+        let quoted = Ident::with_quote('"', "sqrt");
+        assert_eq!(Generic.normalize_identifier(&quoted), "sqrt");
+        assert_eq!(Sqlite.normalize_identifier(&quoted), "SQRT");
+    }
+}
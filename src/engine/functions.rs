@@ -0,0 +1,980 @@
+use super::array;
+use super::decimal;
+use super::dialect::{Dialect, Generic};
+use super::engine::{format_num, to_text, CalcError, CalcResult, Options};
+use super::temporal;
+use super::text::{self, StringSemantics};
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use md5::Md5;
+use regex::Regex;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use sqlparser::ast::ObjectName;
+use std::collections::HashMap;
+
+pub type ScalarFn = dyn Fn(&[CalcResult]) -> Result<CalcResult, CalcError>;
+
+pub struct ScalarFunction {
+    /// Arguments every call passes.
+    pub arity: usize,
+    /// Trailing arguments a call may leave out; `usize::MAX` for functions
+    /// taking any number of them.
+    pub optional: usize,
+    /// Whether a NULL argument makes the result NULL without calling
+    /// `eval`, as for a PostgreSQL `STRICT` function.
+    pub strict: bool,
+    pub eval: Box<ScalarFn>,
+}
+
+impl ScalarFunction {
+    pub fn call(&self, args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+        match self.strict && args.contains(&CalcResult::Null) {
+            true => Ok(CalcResult::Null),
+            false => (self.eval)(args),
+        }
+    }
+
+    /// The error for a call passing `count` arguments, if that is not
+    /// allowed.
+    pub fn check_arity(&self, name: &str, count: usize) -> Result<(), CalcError> {
+        if count >= self.arity && count <= self.arity.saturating_add(self.optional) {
+            return Ok(());
+        }
+        let expected = match self.optional {
+            0 => self.arity.to_string(),
+            usize::MAX => format!("at least {}", self.arity),
+            optional => format!("{} to {}", self.arity, self.arity + optional),
+        };
+        Err(CalcError::InvalidType(format!(
+            "{} expects {} argument(s), got {}",
+            name, expected, count
+        )))
+    }
+}
+
+/// Functions callable from SQL, keyed by their normalized name.
+pub struct FunctionRegistry {
+    functions: HashMap<String, ScalarFunction>,
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        FunctionRegistry::new(&Options::default())
+    }
+}
+
+impl FunctionRegistry {
+    /// The built-in functions, behaving as `options` ask (e.g. what a
+    /// character is for `LENGTH`).
+    pub fn new(options: &Options) -> Self {
+        let mut registry = FunctionRegistry {
+            functions: HashMap::new(),
+        };
+        registry.register("SQRT", 1, numeric("SQRT", f64::sqrt));
+        registry.register("POWER", 2, |args: &[CalcResult]| power("POWER", args));
+        registry.register("POW", 2, |args: &[CalcResult]| power("POW", args));
+        registry.register("MOD", 2, modulo_args);
+        let abs = numeric("ABS", f64::abs);
+        registry.register("ABS", 1, move |args: &[CalcResult]| match args {
+            [CalcResult::Int(int)] => int
+                .checked_abs()
+                .map(CalcResult::Int)
+                .ok_or_else(|| CalcError::Overflow(format!("ABS({}) is out of range", int))),
+            [CalcResult::Decimal(decimal)] => Ok(CalcResult::Decimal(decimal.abs())),
+            _ => abs(args),
+        });
+        registry.register(
+            "FLOOR",
+            1,
+            rounding("FLOOR", f64::floor, RoundingMode::Floor),
+        );
+        registry.register(
+            "CEIL",
+            1,
+            rounding("CEIL", f64::ceil, RoundingMode::Ceiling),
+        );
+        registry.register(
+            "CEILING",
+            1,
+            rounding("CEILING", f64::ceil, RoundingMode::Ceiling),
+        );
+        let float_sign = numeric("SIGN", sign);
+        registry.register("SIGN", 1, move |args: &[CalcResult]| match args {
+            [CalcResult::Int(int)] => Ok(CalcResult::Int(int.signum())),
+            [CalcResult::Decimal(decimal)] => {
+                Ok(CalcResult::Int(decimal.cmp(&BigDecimal::zero()) as i64))
+            }
+            _ => float_sign(args),
+        });
+        registry.register_optional(
+            "ROUND",
+            1,
+            1,
+            rounding("ROUND", f64::round, RoundingMode::HalfUp),
+        );
+        registry.register_optional(
+            "TRUNC",
+            1,
+            1,
+            rounding("TRUNC", f64::trunc, RoundingMode::Down),
+        );
+        registry.register("SIN", 1, real("SIN", f64::sin));
+        registry.register("COS", 1, real("COS", f64::cos));
+        registry.register("TAN", 1, real("TAN", f64::tan));
+        registry.register("ASIN", 1, real("ASIN", f64::asin));
+        registry.register("ACOS", 1, real("ACOS", f64::acos));
+        registry.register("ATAN", 1, real("ATAN", f64::atan));
+        registry.register("ATAN2", 2, atan2);
+        registry.register("EXP", 1, numeric("EXP", f64::exp));
+        registry.register("LN", 1, |args: &[CalcResult]| log("LN", args, f64::ln));
+        registry.register("LOG10", 1, |args: &[CalcResult]| {
+            log("LOG10", args, f64::log10)
+        });
+        registry.register("LOG2", 1, |args: &[CalcResult]| {
+            log("LOG2", args, f64::log2)
+        });
+        registry.register_optional("LOG", 1, 1, log_base);
+        registry.register("PI", 0, |_: &[CalcResult]| {
+            Ok(CalcResult::Num(std::f64::consts::PI))
+        });
+        registry.register("E", 0, |_: &[CalcResult]| {
+            Ok(CalcResult::Num(std::f64::consts::E))
+        });
+
+        let semantics = options.string_semantics;
+        registry.register(
+            "UPPER",
+            1,
+            string("UPPER", |str| CalcResult::Str(str.to_uppercase())),
+        );
+        registry.register(
+            "LOWER",
+            1,
+            string("LOWER", |str| CalcResult::Str(str.to_lowercase())),
+        );
+        registry.register(
+            "LENGTH",
+            1,
+            string("LENGTH", move |str| {
+                CalcResult::Int(text::length(str, semantics) as i64)
+            }),
+        );
+        registry.register(
+            "REVERSE",
+            1,
+            string("REVERSE", move |str| {
+                CalcResult::Str(text::reverse(str, semantics))
+            }),
+        );
+
+        let max_len = options.limits.max_string_len;
+        registry.register("REPLACE", 3, move |args: &[CalcResult]| {
+            replace(args, max_len)
+        });
+        registry.register_optional("LPAD", 2, 1, move |args: &[CalcResult]| {
+            pad("LPAD", args, true, semantics, max_len)
+        });
+        registry.register_optional("RPAD", 2, 1, move |args: &[CalcResult]| {
+            pad("RPAD", args, false, semantics, max_len)
+        });
+        registry.register("REGEXP_MATCHES", 2, regexp_matches);
+        registry.register("REGEXP_REPLACE", 3, move |args: &[CalcResult]| {
+            regexp_replace(args, max_len)
+        });
+        registry.register_optional("REGEXP_EXTRACT", 2, 1, regexp_extract);
+        registry.register("DATE_TRUNC", 2, date_trunc);
+        registry.register("MD5", 1, string("MD5", digest::<Md5>));
+        registry.register("SHA1", 1, string("SHA1", digest::<Sha1>));
+        registry.register("SHA256", 1, string("SHA256", digest::<Sha256>));
+        registry.register_variadic("CONCAT", 1, move |args: &[CalcResult]| {
+            concat("", args, max_len)
+        });
+        registry.register_variadic("CONCAT_WS", 2, move |args: &[CalcResult]| match &args[0] {
+            CalcResult::Null => Ok(CalcResult::Null),
+            separator => concat(&display(separator), &args[1..], max_len),
+        });
+
+        registry.register_variadic("ARRAY", 0, |args: &[CalcResult]| {
+            Ok(CalcResult::Array(args.to_vec()))
+        });
+        registry.register_optional("ARRAY_LENGTH", 1, 1, array_length);
+        registry.register("ARRAY_EXTRACT", 2, |args: &[CalcResult]| match args {
+            [CalcResult::Array(items), index] => array::extract(items, index),
+            _ => Err(CalcError::InvalidType(String::from(
+                "ARRAY_EXTRACT supports only Array",
+            ))),
+        });
+        let collation = options.collation;
+        registry.register("ARRAY_CONTAINS", 2, move |args: &[CalcResult]| match args {
+            [CalcResult::Array(items), value] => Ok(array::contains(items, value, collation)),
+            _ => Err(CalcError::InvalidType(String::from(
+                "ARRAY_CONTAINS supports only Array",
+            ))),
+        });
+        registry
+    }
+
+    pub fn register<F>(&mut self, name: &str, arity: usize, eval: F)
+    where
+        F: Fn(&[CalcResult]) -> Result<CalcResult, CalcError> + 'static,
+    {
+        self.register_optional(name, arity, 0, eval);
+    }
+
+    /// Registers a function whose last `optional` arguments may be left
+    /// out; `eval` gets only the arguments actually passed. The function is
+    /// strict: `eval` never sees a NULL.
+    pub fn register_optional<F>(&mut self, name: &str, arity: usize, optional: usize, eval: F)
+    where
+        F: Fn(&[CalcResult]) -> Result<CalcResult, CalcError> + 'static,
+    {
+        self.insert(name, arity, optional, true, eval);
+    }
+
+    /// Registers a function taking `arity` or more arguments. Unlike the
+    /// others it gets NULL arguments too, to skip them as `CONCAT` does.
+    pub fn register_variadic<F>(&mut self, name: &str, arity: usize, eval: F)
+    where
+        F: Fn(&[CalcResult]) -> Result<CalcResult, CalcError> + 'static,
+    {
+        self.insert(name, arity, usize::MAX, false, eval);
+    }
+
+    fn insert<F>(&mut self, name: &str, arity: usize, optional: usize, strict: bool, eval: F)
+    where
+        F: Fn(&[CalcResult]) -> Result<CalcResult, CalcError> + 'static,
+    {
+        self.functions.insert(
+            name.to_uppercase(),
+            ScalarFunction {
+                arity,
+                optional,
+                strict,
+                eval: Box::new(eval),
+            },
+        );
+    }
+
+    /// Looks up the function an SQL name refers to. Unquoted parts are
+    /// case-insensitive; a qualified name only matches a function registered
+    /// under that same qualified name.
+    pub fn resolve(&self, name: &ObjectName) -> Result<(String, &ScalarFunction), CalcError> {
+        self.resolve_with(name, &Generic)
+    }
+
+    /// Like `resolve`, following the identifier case rules of `dialect`.
+    pub fn resolve_with(
+        &self,
+        name: &ObjectName,
+        dialect: &dyn Dialect,
+    ) -> Result<(String, &ScalarFunction), CalcError> {
+        if name.0.is_empty() {
+            return Err(CalcError::UnsupportedFunc(String::from(
+                "Function name is empty",
+            )));
+        }
+
+        let normalized: Vec<_> = name
+            .0
+            .iter()
+            .map(|ident| dialect.normalize_identifier(ident))
+            .collect();
+        let normalized = normalized.join(".");
+
+        match self.functions.get(&normalized) {
+            Some(function) => Ok((normalized, function)),
+            None => Err(CalcError::UnsupportedFunc(format!(
+                "Unknown function {}",
+                name
+            ))),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// The arguments with integers and decimals converted to f64, for functions
+/// computing in f64 either way.
+fn floats(args: &[CalcResult]) -> Vec<CalcResult> {
+    args.iter()
+        .map(|arg| match arg {
+            CalcResult::Int(_) | CalcResult::Decimal(_) => {
+                CalcResult::Num(arg.as_f64().unwrap_or_default())
+            }
+            arg => arg.clone(),
+        })
+        .collect()
+}
+
+/// A function of one Number.
+fn numeric(
+    name: &'static str,
+    f: fn(f64) -> f64,
+) -> impl Fn(&[CalcResult]) -> Result<CalcResult, CalcError> {
+    move |args| match &floats(args)[..] {
+        [CalcResult::Num(num)] => Ok(CalcResult::Num(f(*num))),
+        _ => Err(CalcError::InvalidType(format!(
+            "{} supports only Number",
+            name
+        ))),
+    }
+}
+
+/// Like `numeric`, for functions defined only on part of the real line:
+/// an argument outside of it is an error instead of a NaN result.
+fn real(
+    name: &'static str,
+    f: fn(f64) -> f64,
+) -> impl Fn(&[CalcResult]) -> Result<CalcResult, CalcError> {
+    let eval = numeric(name, f);
+    move |args| match (&floats(args)[..], eval(args)?) {
+        ([CalcResult::Num(arg)], CalcResult::Num(num)) if num.is_nan() && !arg.is_nan() => Err(
+            CalcError::InvalidType(format!("{}({}) is not a real number", name, arg)),
+        ),
+        (_, result) => Ok(result),
+    }
+}
+
+/// A function of one String.
+fn string(
+    name: &'static str,
+    f: impl Fn(&str) -> CalcResult,
+) -> impl Fn(&[CalcResult]) -> Result<CalcResult, CalcError> {
+    move |args| match args {
+        [CalcResult::Str(str)] => Ok(f(str)),
+        _ => Err(CalcError::InvalidType(format!(
+            "{} supports only String",
+            name
+        ))),
+    }
+}
+
+/// Fails before building a string longer than `max_len` bytes; the engine
+/// would reject it afterwards anyway, but only once it is in memory.
+fn check_len(len: usize, max_len: usize) -> Result<(), CalcError> {
+    match len > max_len {
+        true => Err(CalcError::ResourceLimit(format!(
+            "string of {} bytes exceeds the {} byte limit",
+            len, max_len
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// The text of a value as `CONCAT` includes it.
+fn display(value: &CalcResult) -> String {
+    match value {
+        CalcResult::Null => String::new(),
+        value => to_text(value),
+    }
+}
+
+/// Joins the text of every value but NULLs with `separator`.
+fn concat(separator: &str, args: &[CalcResult], max_len: usize) -> Result<CalcResult, CalcError> {
+    let parts: Vec<_> = args
+        .iter()
+        .filter(|arg| **arg != CalcResult::Null)
+        .map(display)
+        .collect();
+    let len = parts.iter().map(String::len).sum::<usize>()
+        + separator.len() * parts.len().saturating_sub(1);
+    check_len(len, max_len)?;
+    Ok(CalcResult::Str(parts.join(separator)))
+}
+
+fn regex(name: &str, pattern: &str) -> Result<Regex, CalcError> {
+    Regex::new(pattern).map_err(|err| {
+        CalcError::InvalidType(format!(
+            "{}: invalid regular expression {}: {}",
+            name, pattern, err
+        ))
+    })
+}
+
+/// `REGEXP_MATCHES(str, pattern)`: whether `pattern` matches anywhere in
+/// `str`.
+fn regexp_matches(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    match args {
+        [CalcResult::Str(str), CalcResult::Str(pattern)] => Ok(CalcResult::Bool(
+            regex("REGEXP_MATCHES", pattern)?.is_match(str),
+        )),
+        _ => Err(CalcError::InvalidType(String::from(
+            "REGEXP_MATCHES supports only String",
+        ))),
+    }
+}
+
+/// `REGEXP_REPLACE(str, pattern, replacement)`: every match replaced, with
+/// `$1` or `${name}` in `replacement` standing for a capture group.
+fn regexp_replace(args: &[CalcResult], max_len: usize) -> Result<CalcResult, CalcError> {
+    match args {
+        [CalcResult::Str(str), CalcResult::Str(pattern), CalcResult::Str(replacement)] => {
+            let regex = regex("REGEXP_REPLACE", pattern)?;
+            let count = regex.find_iter(str).count();
+            check_len(str.len() + count * replacement.len(), max_len)?;
+            Ok(CalcResult::Str(
+                regex.replace_all(str, replacement.as_str()).into_owned(),
+            ))
+        }
+        _ => Err(CalcError::InvalidType(String::from(
+            "REGEXP_REPLACE supports only String",
+        ))),
+    }
+}
+
+/// `REGEXP_EXTRACT(str, pattern[, group])`: the first match, or the given
+/// capture group of it; NULL when nothing matches.
+fn regexp_extract(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    let args = floats(args);
+    let (str, pattern, group) = match &args[..] {
+        [CalcResult::Str(str), CalcResult::Str(pattern)] => (str, pattern, 0.0),
+        [CalcResult::Str(str), CalcResult::Str(pattern), CalcResult::Num(group)] => {
+            (str, pattern, *group)
+        }
+        _ => {
+            return Err(CalcError::InvalidType(String::from(
+                "REGEXP_EXTRACT expects two Strings and an optional Number",
+            )))
+        }
+    };
+    let regex = regex("REGEXP_EXTRACT", pattern)?;
+    if group < 0.0 || group.fract() != 0.0 || group as usize >= regex.captures_len() {
+        return Err(CalcError::InvalidType(format!(
+            "REGEXP_EXTRACT: {} has no group {}",
+            pattern,
+            format_num(group)
+        )));
+    }
+    Ok(regex
+        .captures(str)
+        .and_then(|captures| captures.get(group as usize))
+        .map_or(CalcResult::Null, |found| {
+            CalcResult::Str(found.as_str().to_string())
+        }))
+}
+
+/// The lowercase hex digest of the UTF-8 bytes of `str`.
+fn digest<D: Digest>(str: &str) -> CalcResult {
+    let hash = D::digest(str.as_bytes());
+    CalcResult::Str(hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// `DATE_TRUNC(unit, value)`, a timestamp also for a date.
+fn date_trunc(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    match args {
+        [CalcResult::Str(unit), value] => match temporal::as_timestamp(value) {
+            Some(timestamp) => Ok(CalcResult::Timestamp(temporal::truncate(unit, &timestamp)?)),
+            None => Err(CalcError::InvalidType(String::from(
+                "DATE_TRUNC expects a unit and a Date or Timestamp",
+            ))),
+        },
+        _ => Err(CalcError::InvalidType(String::from(
+            "DATE_TRUNC expects a unit and a Date or Timestamp",
+        ))),
+    }
+}
+
+/// `REPLACE(str, from, to)`: every occurrence of `from` replaced by `to`.
+fn replace(args: &[CalcResult], max_len: usize) -> Result<CalcResult, CalcError> {
+    match args {
+        [CalcResult::Str(str), CalcResult::Str(from), CalcResult::Str(to)] => {
+            if from.is_empty() {
+                return Ok(CalcResult::Str(str.clone()));
+            }
+            let count = str.matches(from.as_str()).count();
+            check_len(str.len() - count * from.len() + count * to.len(), max_len)?;
+            Ok(CalcResult::Str(str.replace(from.as_str(), to)))
+        }
+        _ => Err(CalcError::InvalidType(String::from(
+            "REPLACE supports only String",
+        ))),
+    }
+}
+
+/// `LPAD(str, len[, fill])` and `RPAD`, filling with spaces by default. A
+/// negative length gives an empty string.
+fn pad(
+    name: &str,
+    args: &[CalcResult],
+    left: bool,
+    semantics: StringSemantics,
+    max_len: usize,
+) -> Result<CalcResult, CalcError> {
+    let args = floats(args);
+    let (str, len, fill) = match &args[..] {
+        [CalcResult::Str(str), CalcResult::Num(len)] => (str, *len, " "),
+        [CalcResult::Str(str), CalcResult::Num(len), CalcResult::Str(fill)] => {
+            (str, *len, fill.as_str())
+        }
+        _ => {
+            return Err(CalcError::InvalidType(format!(
+                "{} expects a String, a Number and an optional String",
+                name
+            )))
+        }
+    };
+    // every character takes at least a byte
+    let len = len.max(0.0) as usize;
+    check_len(len, max_len)?;
+    Ok(CalcResult::Str(text::pad(str, len, fill, left, semantics)))
+}
+
+fn sign(num: f64) -> f64 {
+    if num == 0.0 {
+        0.0
+    } else {
+        num.signum()
+    }
+}
+
+/// Applies `f` to the number scaled by 10^precision (default 0), so
+/// `ROUND(1.25, 1)` rounds at the first decimal and `ROUND(125, -1)` at the
+/// tens.
+fn with_precision(
+    name: &str,
+    args: &[CalcResult],
+    f: fn(f64) -> f64,
+) -> Result<CalcResult, CalcError> {
+    let (num, precision) = match &floats(args)[..] {
+        [CalcResult::Num(num)] => (*num, 0.0),
+        [CalcResult::Num(num), CalcResult::Num(precision)] => (*num, precision.trunc()),
+        _ => {
+            return Err(CalcError::InvalidType(format!(
+                "{} supports only Number",
+                name
+            )))
+        }
+    };
+    let scale = 10f64.powf(precision);
+    let scaled = f(num * scale) / scale;
+    // beyond f64 precision the scaled value is no longer exact
+    Ok(CalcResult::Num(if scaled.is_finite() {
+        scaled
+    } else {
+        num
+    }))
+}
+
+/// `ARRAY_LENGTH(array[, dimension])`, NULL for a dimension other than 1
+/// as arrays have just the one.
+fn array_length(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    match args {
+        [CalcResult::Array(items)] | [CalcResult::Array(items), CalcResult::Int(1)] => {
+            Ok(CalcResult::Int(items.len() as i64))
+        }
+        [CalcResult::Array(_), CalcResult::Int(_)] => Ok(CalcResult::Null),
+        _ => Err(CalcError::InvalidType(String::from(
+            "ARRAY_LENGTH supports only Array",
+        ))),
+    }
+}
+
+/// `ROUND`, `TRUNC`, `FLOOR` and `CEIL`: exact for a decimal, rounding in
+/// `mode` at the given number of fractional digits, and otherwise applying
+/// `f` as `with_precision` does.
+fn rounding(
+    name: &'static str,
+    f: fn(f64) -> f64,
+    mode: RoundingMode,
+) -> impl Fn(&[CalcResult]) -> Result<CalcResult, CalcError> {
+    move |args| match args {
+        [CalcResult::Decimal(num)] => Ok(CalcResult::Decimal(decimal::round(num, 0, mode))),
+        [CalcResult::Decimal(num), precision] => match precision.as_f64() {
+            Some(precision) => Ok(CalcResult::Decimal(decimal::round(
+                num,
+                precision.trunc() as i64,
+                mode,
+            ))),
+            None => with_precision(name, args, f),
+        },
+        _ => with_precision(name, args, f),
+    }
+}
+
+fn power(name: &str, args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    match &floats(args)[..] {
+        [CalcResult::Num(base), CalcResult::Num(exponent)] => pow(*base, *exponent),
+        _ => Err(CalcError::InvalidType(format!(
+            "{} supports only Number",
+            name
+        ))),
+    }
+}
+
+fn modulo_args(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    if let [CalcResult::Int(dividend), CalcResult::Int(divisor)] = args {
+        return match divisor {
+            0 => Err(CalcError::DivisionByZero),
+            // only i64::MIN % -1 overflows, and its remainder is 0
+            _ => Ok(CalcResult::Int(dividend.checked_rem(*divisor).unwrap_or(0))),
+        };
+    }
+    match &floats(args)[..] {
+        [CalcResult::Num(dividend), CalcResult::Num(divisor)] => modulo(*dividend, *divisor),
+        _ => Err(CalcError::InvalidType(String::from(
+            "MOD supports only Number",
+        ))),
+    }
+}
+
+/// Truncated remainder, shared by `MOD` and the `%` operator: the result
+/// takes the sign of the dividend, as in SQL Server and PostgreSQL (`-7 % 3`
+/// is -1).
+pub fn modulo(dividend: f64, divisor: f64) -> Result<CalcResult, CalcError> {
+    if divisor == 0.0 {
+        Err(CalcError::DivisionByZero)
+    } else {
+        Ok(CalcResult::Num(dividend % divisor))
+    }
+}
+
+/// Logarithms are only defined for positive numbers; `LN(0)` is an error
+/// rather than negative infinity.
+fn log(name: &str, args: &[CalcResult], f: fn(f64) -> f64) -> Result<CalcResult, CalcError> {
+    match &floats(args)[..] {
+        [CalcResult::Num(num)] if *num > 0.0 => Ok(CalcResult::Num(f(*num))),
+        [CalcResult::Num(num)] if num.is_nan() => Ok(CalcResult::Num(*num)),
+        [CalcResult::Num(num)] => Err(CalcError::InvalidType(format!(
+            "Cannot take the logarithm of {}",
+            num
+        ))),
+        _ => Err(CalcError::InvalidType(format!(
+            "{} supports only Number",
+            name
+        ))),
+    }
+}
+
+/// `LOG(x)` is the base 10 logarithm, `LOG(b, x)` the base `b` one.
+fn log_base(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    match &floats(args)[..] {
+        [_] => log("LOG", args, f64::log10),
+        [CalcResult::Num(base), CalcResult::Num(_)] => {
+            if *base <= 0.0 || *base == 1.0 {
+                return Err(CalcError::InvalidType(format!(
+                    "Logarithm base must be positive and not 1, got {}",
+                    base
+                )));
+            }
+            let ln = log("LOG", &args[1..], f64::ln)?;
+            match ln {
+                CalcResult::Num(ln) => Ok(CalcResult::Num(ln / base.ln())),
+                other => Ok(other),
+            }
+        }
+        _ => Err(CalcError::InvalidType(String::from(
+            "LOG supports only Number",
+        ))),
+    }
+}
+
+/// The angle of the point (x, y), called as `ATAN2(y, x)`.
+fn atan2(args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+    match &floats(args)[..] {
+        [CalcResult::Num(y), CalcResult::Num(x)] => Ok(CalcResult::Num(y.atan2(*x))),
+        _ => Err(CalcError::InvalidType(String::from(
+            "ATAN2 supports only Number",
+        ))),
+    }
+}
+
+/// `base` raised to `exponent`, shared by `POWER`, `POW` and the `^`
+/// operator.
+/// Results that are not real numbers are errors rather than infinity or NaN.
+pub fn pow(base: f64, exponent: f64) -> Result<CalcResult, CalcError> {
+    if base == 0.0 && exponent < 0.0 {
+        Err(CalcError::DivisionByZero)
+    } else if base < 0.0 && exponent.fract() != 0.0 {
+        Err(CalcError::InvalidType(format!(
+            "{} raised to the non-integer power {} is not a real number",
+            base, exponent
+        )))
+    } else {
+        Ok(CalcResult::Num(base.powf(exponent)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::ast::Ident;
+
+    #[test]
+    fn math_functions() {
+        let registry = FunctionRegistry::default();
+        let call = |name: &str, args: &[f64]| {
+            let (_, function) = registry
+                .resolve(&ObjectName(vec![Ident::new(name)]))
+                .unwrap();
+            let args: Vec<_> = args.iter().map(|arg| CalcResult::Num(*arg)).collect();
+            function.call(&args).unwrap()
+        };
+        let cases: [(&str, &[f64], f64); 12] = [
+            ("ABS", &[-2.5], 2.5),
+            ("FLOOR", &[-2.5], -3.0),
+            ("CEIL", &[2.1], 3.0),
+            ("SIGN", &[-3.0], -1.0),
+            ("SIGN", &[0.0], 0.0),
+            ("ROUND", &[2.5], 3.0),
+            ("ROUND", &[-2.5], -3.0),
+            ("ROUND", &[1.2345, 2.0], 1.23),
+            ("ROUND", &[1250.0, -2.0], 1300.0),
+            ("TRUNC", &[-2.7], -2.0),
+            ("TRUNC", &[1.789, 1.0], 1.7),
+            ("ROUND", &[1e300, 10.0], 1e300),
+        ];
+        for (name, args, expected) in cases {
+            assert_eq!(
+                call(name, args),
+                CalcResult::Num(expected),
+                "{}{:?}",
+                name,
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn trigonometric_functions() {
+        let registry = FunctionRegistry::default();
+        let call = |name: &str, args: &[f64]| {
+            let (_, function) = registry
+                .resolve(&ObjectName(vec![Ident::new(name)]))
+                .unwrap();
+            let args: Vec<_> = args.iter().map(|arg| CalcResult::Num(*arg)).collect();
+            function.call(&args)
+        };
+        let pi = std::f64::consts::PI;
+        assert_eq!(call("SIN", &[0.0]), Ok(CalcResult::Num(0.0)));
+        assert_eq!(call("COS", &[0.0]), Ok(CalcResult::Num(1.0)));
+        assert_eq!(call("ACOS", &[-1.0]), Ok(CalcResult::Num(pi)));
+        assert_eq!(call("ATAN", &[1.0]), Ok(CalcResult::Num(pi / 4.0)));
+        assert_eq!(call("ATAN2", &[1.0, -1.0]), Ok(CalcResult::Num(pi * 0.75)));
+        assert_eq!(
+            call("ASIN", &[2.0]),
+            Err(CalcError::InvalidType(String::from(
+                "ASIN(2) is not a real number"
+            )))
+        );
+        assert!(matches!(call("SIN", &[f64::NAN]), Ok(CalcResult::Num(num)) if num.is_nan()));
+    }
+
+    #[test]
+    fn logarithms() {
+        let registry = FunctionRegistry::default();
+        let call = |name: &str, args: &[f64]| {
+            let (_, function) = registry
+                .resolve(&ObjectName(vec![Ident::new(name)]))
+                .unwrap();
+            let args: Vec<_> = args.iter().map(|arg| CalcResult::Num(*arg)).collect();
+            function.call(&args)
+        };
+        assert_eq!(call("LN", &[1.0]), Ok(CalcResult::Num(0.0)));
+        assert_eq!(call("LOG", &[1000.0]), Ok(CalcResult::Num(3.0)));
+        assert_eq!(call("LOG", &[2.0, 8.0]), Ok(CalcResult::Num(3.0)));
+        assert_eq!(call("LOG10", &[0.01]), Ok(CalcResult::Num(-2.0)));
+        assert_eq!(call("LOG2", &[1024.0]), Ok(CalcResult::Num(10.0)));
+        assert_eq!(call("EXP", &[0.0]), Ok(CalcResult::Num(1.0)));
+        assert_eq!(
+            call("LN", &[0.0]),
+            Err(CalcError::InvalidType(String::from(
+                "Cannot take the logarithm of 0"
+            )))
+        );
+        assert!(call("LOG", &[1.0, 5.0]).is_err());
+        assert!(call("LOG", &[-2.0, 5.0]).is_err());
+        assert_eq!(
+            registry
+                .resolve(&ObjectName(vec![Ident::new("LOG")]))
+                .unwrap()
+                .1
+                .call(&[CalcResult::Null, CalcResult::Num(1.0)]),
+            Ok(CalcResult::Null)
+        );
+    }
+
+    #[test]
+    fn concat_skips_nulls() {
+        let registry = FunctionRegistry::default();
+        let call = |name: &str, args: &[CalcResult]| {
+            let (_, function) = registry
+                .resolve(&ObjectName(vec![Ident::new(name)]))
+                .unwrap();
+            function.call(args)
+        };
+        let str = |value: &str| CalcResult::Str(value.to_string());
+        assert_eq!(
+            call(
+                "CONCAT",
+                &[
+                    str("a"),
+                    CalcResult::Num(1.5),
+                    CalcResult::Null,
+                    CalcResult::Bool(true)
+                ]
+            ),
+            Ok(str("a1.5true"))
+        );
+        assert_eq!(
+            call(
+                "CONCAT_WS",
+                &[str(", "), str("a"), CalcResult::Null, CalcResult::Num(2.0)]
+            ),
+            Ok(str("a, 2"))
+        );
+        assert_eq!(
+            call("CONCAT_WS", &[CalcResult::Null, str("a")]),
+            Ok(CalcResult::Null)
+        );
+        assert_eq!(call("CONCAT", &[CalcResult::Null]), Ok(str("")));
+    }
+
+    #[test]
+    fn regexp_functions() {
+        let registry = FunctionRegistry::default();
+        let call = |name: &str, args: &[CalcResult]| {
+            let (_, function) = registry
+                .resolve(&ObjectName(vec![Ident::new(name)]))
+                .unwrap();
+            function.call(args)
+        };
+        let str = |value: &str| CalcResult::Str(value.to_string());
+        assert_eq!(
+            call("REGEXP_MATCHES", &[str("abc123"), str("^[a-z]+[0-9]+$")]),
+            Ok(CalcResult::Bool(true))
+        );
+        assert_eq!(
+            call("REGEXP_REPLACE", &[str("a1b2"), str("[0-9]"), str("")]),
+            Ok(str("ab"))
+        );
+        assert_eq!(
+            call(
+                "REGEXP_REPLACE",
+                &[str("2024-05"), str("(\\d+)-(\\d+)"), str("$2/$1")]
+            ),
+            Ok(str("05/2024"))
+        );
+        assert_eq!(
+            call(
+                "REGEXP_EXTRACT",
+                &[str("id=42;"), str("id=(\\d+)"), CalcResult::Num(1.0)]
+            ),
+            Ok(str("42"))
+        );
+        assert_eq!(
+            call("REGEXP_EXTRACT", &[str("abc"), str("[0-9]+")]),
+            Ok(CalcResult::Null)
+        );
+        assert!(matches!(
+            call(
+                "REGEXP_EXTRACT",
+                &[str("abc"), str("b"), CalcResult::Num(1.0)]
+            ),
+            Err(CalcError::InvalidType(_))
+        ));
+        assert!(matches!(
+            call("REGEXP_MATCHES", &[str("abc"), str("(")]),
+            Err(CalcError::InvalidType(message)) if message.starts_with("REGEXP_MATCHES: invalid regular expression (")
+        ));
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            digest::<Md5>(""),
+            CalcResult::Str(String::from("d41d8cd98f00b204e9800998ecf8427e"))
+        );
+        assert_eq!(
+            digest::<Sha1>("abc"),
+            CalcResult::Str(String::from("a9993e364706816aba3e25717850c26c9cd0d89d"))
+        );
+        assert_eq!(
+            digest::<Sha256>("abc"),
+            CalcResult::Str(String::from(
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            ))
+        );
+    }
+
+    #[test]
+    fn check_arity_optional() {
+        let registry = FunctionRegistry::default();
+        let (_, round) = registry
+            .resolve(&ObjectName(vec![Ident::new("round")]))
+            .unwrap();
+        assert!(round.check_arity("ROUND", 2).is_ok());
+        let (_, concat) = registry
+            .resolve(&ObjectName(vec![Ident::new("concat_ws")]))
+            .unwrap();
+        assert!(concat.check_arity("CONCAT_WS", 20).is_ok());
+        assert_eq!(
+            concat.check_arity("CONCAT_WS", 1),
+            Err(CalcError::InvalidType(String::from(
+                "CONCAT_WS expects at least 2 argument(s), got 1"
+            )))
+        );
+        assert_eq!(
+            round.check_arity("ROUND", 3),
+            Err(CalcError::InvalidType(String::from(
+                "ROUND expects 1 to 2 argument(s), got 3"
+            )))
+        );
+    }
+
+    #[test]
+    fn pow_domain() {
+        assert_eq!(pow(2.0, 10.0), Ok(CalcResult::Num(1024.0)));
+        assert_eq!(pow(-2.0, 3.0), Ok(CalcResult::Num(-8.0)));
+        assert_eq!(pow(4.0, -0.5), Ok(CalcResult::Num(0.5)));
+        assert_eq!(pow(0.0, -1.0), Err(CalcError::DivisionByZero));
+        assert!(matches!(pow(-8.0, 0.5), Err(CalcError::InvalidType(_))));
+    }
+
+    #[test]
+    fn resolve_case_insensitive() {
+        let registry = FunctionRegistry::default();
+        let (name, _) = registry
+            .resolve(&ObjectName(vec![Ident::new("sqrt")]))
+            .unwrap();
+        assert_eq!(name, "SQRT");
+    }
+
+    #[test]
+    fn resolve_quoted_is_case_sensitive() {
+        let registry = FunctionRegistry::default();
+        assert!(registry
+            .resolve(&ObjectName(vec![Ident::with_quote('"', "sqrt")]))
+            .is_err());
+    }
+
+    #[test]
+    fn resolve_qualified_unknown() {
+        let registry = FunctionRegistry::default();
+        let name = ObjectName(vec![Ident::new("math"), Ident::new("sqrt")]);
+        assert_eq!(
+            registry.resolve(&name).err().unwrap(),
+            CalcError::UnsupportedFunc(String::from("Unknown function math.sqrt"))
+        );
+    }
+
+    #[test]
+    fn resolve_empty_name() {
+        let registry = FunctionRegistry::default();
+        assert_eq!(
+            registry.resolve(&ObjectName(vec![])).err().unwrap(),
+            CalcError::UnsupportedFunc(String::from("Function name is empty"))
+        );
+    }
+
+    #[test]
+    fn resolve_qualified_registered() {
+        let mut registry = FunctionRegistry::default();
+        registry.register("math.sqrt", 1, numeric("SQRT", f64::sqrt));
+        let name = ObjectName(vec![Ident::new("Math"), Ident::new("Sqrt")]);
+        assert!(registry.resolve(&name).is_ok());
+    }
+}
@@ -0,0 +1,71 @@
+use super::engine::{CalcError, Evaluation};
+use sqlparser::ast::Statement;
+
+pub type RewriteFn = dyn Fn(Statement) -> Result<Statement, CalcError>;
+pub type ObserverFn = dyn Fn(&str, &Result<Evaluation, CalcError>);
+
+/// Embedder code run around every statement: rewrite rules between parsing
+/// and evaluation, and observers once the statement has been evaluated.
+#[derive(Default)]
+pub struct Hooks {
+    rewrites: Vec<Box<RewriteFn>>,
+    observers: Vec<Box<ObserverFn>>,
+}
+
+impl Hooks {
+    pub fn add_rewrite<F>(&mut self, rule: F)
+    where
+        F: Fn(Statement) -> Result<Statement, CalcError> + 'static,
+    {
+        self.rewrites.push(Box::new(rule));
+    }
+
+    pub fn add_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&str, &Result<Evaluation, CalcError>) + 'static,
+    {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Passes every statement through the rules in the order they were
+    /// added, stopping at the first rule that rejects it.
+    pub fn rewrite(&self, ast: Vec<Statement>) -> Result<Vec<Statement>, CalcError> {
+        ast.into_iter()
+            .map(|statement| {
+                self.rewrites
+                    .iter()
+                    .try_fold(statement, |statement, rule| rule(statement))
+            })
+            .collect()
+    }
+
+    pub fn observe(&self, query: &str, result: &Result<Evaluation, CalcError>) {
+        for observer in &self.observers {
+            observer(query, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::engine::parse;
+
+    #[test]
+    fn rewrite_in_order() {
+        let mut hooks = Hooks::default();
+        hooks.add_rewrite(|statement| match statement {
+            Statement::Query(_) => Ok(parse("SELECT 2").unwrap().remove(0)),
+            statement => Ok(statement),
+        });
+        hooks.add_rewrite(Ok);
+        let ast = hooks.rewrite(parse("SELECT 1").unwrap()).unwrap();
+        assert_eq!(ast[0].to_string(), "SELECT 2");
+
+        hooks.add_rewrite(|_| Err(CalcError::Unexpected));
+        assert_eq!(
+            hooks.rewrite(parse("SELECT 1").unwrap()).unwrap_err(),
+            CalcError::Unexpected
+        );
+    }
+}
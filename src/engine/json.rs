@@ -0,0 +1,174 @@
+//! `read_json`: JSON files as tables. A file holds either one object per
+//! line (NDJSON) or a single array of objects. Every field becomes a
+//! column; nested objects are flattened into `parent.child` columns.
+
+use super::engine::{CalcError, CalcResult};
+use super::operators::ValueType;
+use super::table::{Column, MemTable};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Parses `text` into a table, `source` naming it in errors. Columns are in
+/// the order their fields first appear; a field missing from a record is
+/// NULL there.
+pub fn parse(text: &str, source: &str) -> Result<MemTable, CalcError> {
+    let failed = |line: usize, message: String| {
+        CalcError::FunctionFailed(format!("READ_JSON {}:{}: {}", source, line, message))
+    };
+
+    let records: Vec<(usize, Value)> = if text.trim_start().starts_with('[') {
+        match serde_json::from_str(text) {
+            Ok(Value::Array(values)) => values.into_iter().map(|value| (1, value)).collect(),
+            Ok(_) => Vec::new(),
+            Err(err) => return Err(failed(err.line(), err.to_string())),
+        }
+    } else {
+        let mut records = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if !line.trim().is_empty() {
+                let value =
+                    serde_json::from_str(line).map_err(|err| failed(i + 1, err.to_string()))?;
+                records.push((i + 1, value));
+            }
+        }
+        records
+    };
+
+    let mut schema: Vec<Column> = Vec::new();
+    let mut positions = HashMap::new();
+    let mut rows = Vec::with_capacity(records.len());
+    for (line, record) in records {
+        let object = match record {
+            Value::Object(object) => object,
+            other => return Err(failed(line, format!("expected an object, got {}", other))),
+        };
+        let mut fields = Vec::new();
+        flatten(String::new(), object, &mut fields);
+
+        let mut row = vec![CalcResult::Null; schema.len()];
+        for (name, value) in fields {
+            let i = *positions.entry(name.clone()).or_insert_with(|| {
+                schema.push(Column::new(&name, ValueType::Null));
+                row.push(CalcResult::Null);
+                schema.len() - 1
+            });
+            let column = &mut schema[i];
+            column.ty = match (column.ty, ValueType::of(&value)) {
+                (ty, ValueType::Null) => ty,
+                (ValueType::Null, ty) => ty,
+                (ValueType::Integer, ValueType::Number)
+                | (ValueType::Number, ValueType::Integer) => ValueType::Number,
+                (old, new) if old == new => old,
+                (old, new) => {
+                    return Err(CalcError::InvalidType(format!(
+                        "READ_JSON {}:{}: field {} holds both {} and {} values",
+                        source, line, column.name, old, new
+                    )))
+                }
+            };
+            row[i] = value;
+        }
+        rows.push(row);
+    }
+
+    // Fields holding both integers and fractions are read as numbers.
+    for row in &mut rows {
+        row.resize(schema.len(), CalcResult::Null);
+        for (value, column) in row.iter_mut().zip(&schema) {
+            if let (CalcResult::Int(int), ValueType::Number) = (&value, column.ty) {
+                *value = CalcResult::Num(*int as f64);
+            }
+        }
+    }
+    MemTable::new(schema, rows)
+}
+
+/// Appends the fields of `object`, with nested objects flattened, their
+/// names prefixed by `prefix`.
+fn flatten(prefix: String, object: Map<String, Value>, fields: &mut Vec<(String, CalcResult)>) {
+    for (key, value) in object {
+        let name = format!("{}{}", prefix, key);
+        match value {
+            Value::Object(object) => flatten(format!("{}.", name), object, fields),
+            value => fields.push((name, to_value(value))),
+        }
+    }
+}
+
+/// The engine value of a JSON value. Objects nested in arrays, which have
+/// no columns to be flattened into, are kept as JSON text.
+fn to_value(value: Value) -> CalcResult {
+    match value {
+        Value::Null => CalcResult::Null,
+        Value::Bool(boolean) => CalcResult::Bool(boolean),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => CalcResult::Int(int),
+            None => CalcResult::Num(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(str) => CalcResult::Str(str),
+        Value::Array(items) => CalcResult::Array(items.into_iter().map(to_value).collect()),
+        Value::Object(object) => CalcResult::Str(Value::Object(object).to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::table::TableProvider;
+
+    #[test]
+    fn parse_ndjson() {
+        let text = "{\"id\": 1, \"user\": {\"name\": \"a\"}, \"tags\": [\"x\"]}\n\n{\"id\": 2.5, \"extra\": true}\n";
+        let table = parse(text, "t.ndjson").unwrap();
+        assert_eq!(
+            table.schema(),
+            vec![
+                Column::new("id", ValueType::Number),
+                Column::new("user.name", ValueType::String),
+                Column::new("tags", ValueType::Array),
+                Column::new("extra", ValueType::Boolean),
+            ]
+        );
+        assert_eq!(
+            table.scan(&[], None).unwrap(),
+            vec![
+                vec![
+                    CalcResult::Num(1.0),
+                    CalcResult::Str(String::from("a")),
+                    CalcResult::Array(vec![CalcResult::Str(String::from("x"))]),
+                    CalcResult::Null,
+                ],
+                vec![
+                    CalcResult::Num(2.5),
+                    CalcResult::Null,
+                    CalcResult::Null,
+                    CalcResult::Bool(true),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_array() {
+        let table = parse("[{\"a\": 1}, {\"a\": null}]", "t.json").unwrap();
+        assert_eq!(table.schema(), vec![Column::new("a", ValueType::Integer)]);
+        assert_eq!(
+            table.scan(&[], None).unwrap(),
+            vec![vec![CalcResult::Int(1)], vec![CalcResult::Null]]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| parse(text, "t.json").unwrap_err().to_string();
+        assert_eq!(
+            error("{\"a\": 1}\n{\"a\": \"x\"}"),
+            "[Invalid Type]: READ_JSON t.json:2: field a holds both Integer and String values"
+        );
+        assert_eq!(
+            error("{\"a\": 1}\n[1]"),
+            "[Function Failed]: READ_JSON t.json:2: expected an object, got [1]"
+        );
+        assert!(error("{\"a\": 1}\n{\"a\":").starts_with("[Function Failed]: READ_JSON t.json:2: "));
+    }
+}
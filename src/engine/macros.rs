@@ -0,0 +1,275 @@
+use super::dialect::Dialect;
+use super::functions::ScalarFunction;
+use sqlparser::ast::{Expr, Function, FunctionArg, Ident};
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::Token;
+use std::rc::Rc;
+
+/// A function defined in SQL with `CREATE FUNCTION name(params) AS expr`,
+/// inlined into the queries calling it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+    /// Normalized parameter names.
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+/// A function defined for the rest of the session.
+#[derive(Clone)]
+pub enum SessionFunction {
+    Macro(Macro),
+    /// Compiled from another language, e.g. `LANGUAGE rhai`.
+    Script(Rc<ScalarFunction>),
+}
+
+/// What a `CREATE FUNCTION` statement defines the function as.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Body {
+    Expr(Expr),
+    Script { language: Ident, source: String },
+}
+
+/// A parsed `CREATE FUNCTION` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: Vec<Ident>,
+    pub params: Vec<Ident>,
+    pub body: Body,
+}
+
+/// Parses `CREATE FUNCTION name[(params)] [LANGUAGE lang] AS body`.
+pub fn parse_definition(parser: &mut Parser) -> Result<Option<Definition>, ParserError> {
+    if !parser.parse_keywords(&[Keyword::CREATE, Keyword::FUNCTION]) {
+        return Ok(None);
+    }
+
+    let name = parser.parse_object_name()?.0;
+    let params = if parser.consume_token(&Token::LParen) {
+        if parser.consume_token(&Token::RParen) {
+            Vec::new()
+        } else {
+            let params = parser.parse_comma_separated(Parser::parse_identifier)?;
+            parser.expect_token(&Token::RParen)?;
+            params
+        }
+    } else if matches!(parser.peek_token(), Token::Word(word) if word.keyword == Keyword::LANGUAGE)
+    {
+        Vec::new()
+    } else {
+        return Err(ParserError::ParserError(format!(
+            "Expected a list of parameters in parentheses, found: {}",
+            parser.peek_token()
+        )));
+    };
+
+    let language = match parser.parse_keyword(Keyword::LANGUAGE) {
+        true => Some(parser.parse_identifier()?),
+        false => None,
+    };
+    parser.expect_keyword(Keyword::AS)?;
+    let body = match language {
+        Some(language) if !language.value.eq_ignore_ascii_case("sql") => Body::Script {
+            language,
+            source: parser.parse_literal_string()?,
+        },
+        _ => Body::Expr(parser.parse_expr()?),
+    };
+    while parser.consume_token(&Token::SemiColon) {}
+    if parser.peek_token() != Token::EOF {
+        return Err(ParserError::ParserError(format!(
+            "Expected end of statement, found: {}",
+            parser.peek_token()
+        )));
+    }
+
+    Ok(Some(Definition { name, params, body }))
+}
+
+impl Macro {
+    /// The body with every parameter replaced by the matching argument.
+    pub fn expand(&self, args: Vec<Expr>, dialect: &dyn Dialect) -> Expr {
+        let bindings: Vec<_> = self.params.iter().zip(args).collect();
+        substitute(self.body.clone(), &bindings, dialect)
+    }
+}
+
+fn substitute(expr: Expr, bindings: &[(&String, Expr)], dialect: &dyn Dialect) -> Expr {
+    let walk = |expr: Box<Expr>| Box::new(substitute(*expr, bindings, dialect));
+    match expr {
+        Expr::Identifier(ident) => {
+            let name = dialect.normalize_identifier(&ident);
+            match bindings.iter().find(|(param, _)| **param == name) {
+                Some((_, arg)) => Expr::Nested(Box::new(arg.clone())),
+                None => Expr::Identifier(ident),
+            }
+        }
+        Expr::BinaryOp { left, op, right } => Expr::BinaryOp {
+            left: walk(left),
+            op,
+            right: walk(right),
+        },
+        Expr::UnaryOp { op, expr } => Expr::UnaryOp {
+            op,
+            expr: walk(expr),
+        },
+        Expr::Nested(expr) => Expr::Nested(walk(expr)),
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => Expr::Case {
+            operand: operand.map(walk),
+            conditions: conditions
+                .into_iter()
+                .map(|expr| substitute(expr, bindings, dialect))
+                .collect(),
+            results: results
+                .into_iter()
+                .map(|expr| substitute(expr, bindings, dialect))
+                .collect(),
+            else_result: else_result.map(walk),
+        },
+        Expr::IsNull(expr) => Expr::IsNull(walk(expr)),
+        Expr::IsNotNull(expr) => Expr::IsNotNull(walk(expr)),
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => Expr::InList {
+            expr: walk(expr),
+            list: list
+                .into_iter()
+                .map(|item| substitute(item, bindings, dialect))
+                .collect(),
+            negated,
+        },
+        Expr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => Expr::Between {
+            expr: walk(expr),
+            negated,
+            low: walk(low),
+            high: walk(high),
+        },
+        Expr::Cast { expr, data_type } => Expr::Cast {
+            expr: walk(expr),
+            data_type,
+        },
+        Expr::TryCast { expr, data_type } => Expr::TryCast {
+            expr: walk(expr),
+            data_type,
+        },
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+        } => Expr::Substring {
+            expr: walk(expr),
+            substring_from: substring_from.map(walk),
+            substring_for: substring_for.map(walk),
+        },
+        Expr::Trim { expr, trim_where } => Expr::Trim {
+            expr: walk(expr),
+            trim_where: trim_where.map(|(side, chars)| (side, walk(chars))),
+        },
+        Expr::Extract { field, expr } => Expr::Extract {
+            field,
+            expr: walk(expr),
+        },
+        Expr::Function(func) => Expr::Function(Function {
+            args: func
+                .args
+                .into_iter()
+                .map(|arg| match arg {
+                    FunctionArg::Named { name, arg } => FunctionArg::Named {
+                        name,
+                        arg: substitute(arg, bindings, dialect),
+                    },
+                    FunctionArg::Unnamed(arg) => {
+                        FunctionArg::Unnamed(substitute(arg, bindings, dialect))
+                    }
+                })
+                .collect(),
+            ..func
+        }),
+        expr => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::dialect::Generic;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn parse(query: &str) -> Result<Option<Definition>, ParserError> {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, query).tokenize()?;
+        parse_definition(&mut Parser::new(tokens, &dialect))
+    }
+
+    #[test]
+    fn parse_definition_parts() {
+        let definition = parse("CREATE FUNCTION area(r) AS (r * r)")
+            .unwrap()
+            .unwrap();
+        assert_eq!(definition.name, vec![Ident::new("area")]);
+        assert_eq!(definition.params, vec![Ident::new("r")]);
+        assert!(matches!(definition.body, Body::Expr(body) if body.to_string() == "(r * r)"));
+    }
+
+    #[test]
+    fn parse_definition_script() {
+        let definition = parse("CREATE FUNCTION f LANGUAGE rhai AS '40 + 2'")
+            .unwrap()
+            .unwrap();
+        assert!(definition.params.is_empty());
+        assert_eq!(
+            definition.body,
+            Body::Script {
+                language: Ident::new("rhai"),
+                source: String::from("40 + 2")
+            }
+        );
+        let definition = parse("CREATE FUNCTION f() LANGUAGE sql AS 1")
+            .unwrap()
+            .unwrap();
+        assert!(matches!(definition.body, Body::Expr(_)));
+    }
+
+    #[test]
+    fn parse_definition_other_statement() {
+        assert_eq!(parse("SELECT 1"), Ok(None));
+        assert!(parse("CREATE FUNCTION f AS 1").is_err());
+        assert!(parse("CREATE FUNCTION f(x) AS x x").is_err());
+    }
+
+    #[test]
+    fn expand_substitutes_parameters() {
+        let definition = parse("CREATE FUNCTION f(a, b) AS SQRT(a) - b")
+            .unwrap()
+            .unwrap();
+        let body = match definition.body {
+            Body::Expr(body) => body,
+            _ => panic!(),
+        };
+        let function = Macro {
+            params: vec![String::from("A"), String::from("B")],
+            body,
+        };
+        let args = vec![
+            Expr::Identifier(Ident::new("x")),
+            Expr::Identifier(Ident::new("y")),
+        ];
+        assert_eq!(
+            function.expand(args, &Generic).to_string(),
+            "SQRT((x)) - (y)"
+        );
+    }
+}
@@ -1,32 +1,185 @@
+pub mod aggregate;
+pub mod array;
+pub mod attach;
+pub mod cast;
+pub mod catalog;
+pub mod coverage;
+pub mod csv;
+pub mod decimal;
+pub mod dialect;
+pub mod functions;
+pub mod hooks;
+pub mod json;
+pub mod macros;
+pub mod operators;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod plan;
+pub mod precedence;
+pub mod query;
+#[cfg(feature = "rhai")]
+pub mod script;
+pub mod source;
+pub mod table;
+pub mod temporal;
+pub mod text;
+pub mod variables;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod window;
+
+#[allow(clippy::module_inception)]
 pub mod engine {
+    use super::aggregate::{self, Group, AGGREGATE_FUNCTIONS};
+    use super::array;
+    use super::attach::{self, Attachment};
+    use super::cast;
+    use super::catalog::Catalog;
+    use super::coverage::{Category, Coverage};
+    use super::csv;
+    use super::decimal;
+    use super::dialect::{self, Dialect, Generic};
+    use super::functions::{self, FunctionRegistry, ScalarFunction};
+    use super::hooks::Hooks;
+    use super::json;
+    use super::macros::{self, Body, Definition, Macro, SessionFunction};
+    use super::operators::{OperatorRegistry, ValueType};
+    use super::plan::{self, Profile};
+    use super::precedence;
+    use super::query::{self, Field, Key, Pushdown, Relation, Scope};
+    use super::source::{self, HttpOptions};
+    use super::table::{Row, TableProvider};
+    use super::temporal::{self, Interval, Zone};
+    use super::text::{self, Collation, StringSemantics};
+    use super::variables::{self, Assignment, ZoneSetting};
+    use super::window::{self, Window, WINDOW_FUNCTIONS};
+    use bigdecimal::{BigDecimal, ToPrimitive};
+    use chrono::{NaiveDate, NaiveDateTime};
     use sqlparser::ast::*;
-    use sqlparser::dialect::GenericDialect;
-    use sqlparser::parser::Parser;
+    use sqlparser::dialect::{self as parser, GenericDialect};
+    use sqlparser::keywords::Keyword;
+    use sqlparser::parser::{Parser, ParserError};
+    use sqlparser::tokenizer::{Token, Tokenizer};
+    use std::cell::{Cell, RefCell};
+    use std::cmp::Ordering;
+    use std::collections::hash_map::Entry;
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use std::fmt;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-    #[derive(Debug)]
+    #[derive(Clone, Debug, PartialEq)]
     pub enum CalcResult {
         Num(f64),
+        Int(i64),
+        Decimal(BigDecimal),
         Bool(bool),
         Str(String),
+        Date(NaiveDate),
+        Timestamp(NaiveDateTime),
+        Interval(Interval),
+        Array(Vec<CalcResult>),
+        /// The values of a `SELECT` with several items, one per item.
+        Row(Vec<CalcResult>),
+        /// The rows of a query reading `VALUES` or `FROM`.
+        Table(Vec<Vec<CalcResult>>),
+        Null,
+    }
+
+    /// Formats a number as the shortest string that parses back to the same
+    /// f64, dropping the fraction of integral values (`2`, not `2.0`) and
+    /// writing large exponents as `1e+300`. Every output format goes through
+    /// this so golden files stay comparable.
+    pub fn format_num(num: f64) -> String {
+        if num.is_nan() {
+            return String::from("NaN");
+        } else if num.is_infinite() {
+            return String::from(if num > 0.0 { "Infinity" } else { "-Infinity" });
+        }
+
+        let mut buffer = ryu::Buffer::new();
+        let formatted = buffer.format_finite(num);
+        let formatted = formatted.strip_suffix(".0").unwrap_or(formatted);
+        match formatted.split_once('e') {
+            Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+                format!("{}e+{}", mantissa, exponent)
+            }
+            _ => formatted.to_string(),
+        }
+    }
+
+    impl CalcResult {
+        /// The value of a number of any kind, converted to f64.
+        pub fn as_f64(&self) -> Option<f64> {
+            match self {
+                CalcResult::Num(num) => Some(*num),
+                CalcResult::Int(int) => Some(*int as f64),
+                CalcResult::Decimal(decimal) => decimal.to_f64(),
+                _ => None,
+            }
+        }
     }
 
     impl fmt::Display for CalcResult {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             match self {
-                CalcResult::Num(num) => write!(f, "Result: {}", num),
+                CalcResult::Num(num) => write!(f, "Result: {}", format_num(*num)),
+                CalcResult::Int(int) => write!(f, "Result: {}", int),
+                CalcResult::Decimal(decimal) => write!(f, "Result: {}", decimal::format(decimal)),
                 CalcResult::Bool(boolean) => write!(f, "Result: {}", boolean),
                 CalcResult::Str(str) => write!(f, "Result: {}", str),
+                CalcResult::Date(date) => write!(f, "Result: {}", temporal::format_date(date)),
+                CalcResult::Timestamp(timestamp) => {
+                    write!(f, "Result: {}", temporal::format_timestamp(timestamp))
+                }
+                CalcResult::Interval(interval) => write!(f, "Result: {}", interval),
+                CalcResult::Array(_) => write!(f, "Result: {}", describe(self)),
+                CalcResult::Row(values) => {
+                    let values: Vec<_> = values.iter().map(to_text).collect();
+                    write!(f, "Result: {}", values.join(", "))
+                }
+                CalcResult::Table(rows) => write_rows(f, None, rows),
+                CalcResult::Null => write!(f, "Result: NULL"),
             }
         }
     }
 
+    /// Writes a row count, an optional header of column names, and then
+    /// each row on its own line.
+    fn write_rows(
+        f: &mut fmt::Formatter,
+        columns: Option<&[Option<String>]>,
+        rows: &[Vec<CalcResult>],
+    ) -> fmt::Result {
+        let plural = if rows.len() == 1 { "" } else { "s" };
+        write!(f, "Result: {} row{}", rows.len(), plural)?;
+        if let Some(columns) = columns {
+            let names: Vec<_> = columns
+                .iter()
+                .map(|column| column.as_deref().unwrap_or("?"))
+                .collect();
+            write!(f, "\n{}", names.join(", "))?;
+        }
+        for row in rows {
+            let values: Vec<_> = row.iter().map(to_text).collect();
+            write!(f, "\n{}", values.join(", "))?;
+        }
+        Ok(())
+    }
+
     #[derive(Clone, Debug, PartialEq)]
     pub enum CalcError {
         InvalidType(String),
         UnsupportedOperator(String),
         UnsupportedFunc(String),
         InvalidRequestFormat(String),
+        MultipleErrors(Vec<CalcError>),
+        DivisionByZero,
+        ResourceLimit(String),
+        AssertionFailed(String),
+        FunctionFailed(String),
+        Overflow(String),
         Unexpected,
     }
 
@@ -39,11 +192,494 @@ pub mod engine {
                 CalcError::InvalidRequestFormat(str) => {
                     write!(f, "[Invalid Request Format]: {}", str)
                 }
+                CalcError::DivisionByZero => write!(f, "[Division By Zero]: Division by zero"),
+                CalcError::ResourceLimit(str) => write!(f, "[Resource Limit]: {}", str),
+                CalcError::AssertionFailed(str) => write!(f, "[Assertion Failed]: {}", str),
+                CalcError::FunctionFailed(str) => write!(f, "[Function Failed]: {}", str),
+                CalcError::Overflow(str) => write!(f, "[Integer Overflow]: {}", str),
+                CalcError::MultipleErrors(errors) => {
+                    let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                    write!(f, "[Multiple Errors]: {}", errors.join("; "))
+                }
                 _ => write!(f, "[Unexpected Error]: Something went wrong"),
             }
         }
     }
 
+    /// Lets functions registered with `Engine::register_scalar_fn` fail with
+    /// a plain message.
+    impl From<String> for CalcError {
+        fn from(message: String) -> Self {
+            CalcError::FunctionFailed(message)
+        }
+    }
+
+    impl From<&str> for CalcError {
+        fn from(message: &str) -> Self {
+            CalcError::FunctionFailed(message.to_string())
+        }
+    }
+
+    impl CalcError {
+        /// Stable identifier of the error category. Unlike the display text
+        /// these never change, so harnesses can assert on them.
+        pub fn code(&self) -> &'static str {
+            match self {
+                CalcError::InvalidType(_) => "SQE-1001",
+                CalcError::UnsupportedOperator(_) => "SQE-1002",
+                CalcError::UnsupportedFunc(_) => "SQE-1003",
+                CalcError::InvalidRequestFormat(_) => "SQE-1004",
+                CalcError::MultipleErrors(_) => "SQE-1005",
+                CalcError::DivisionByZero => "SQE-1006",
+                CalcError::ResourceLimit(_) => "SQE-1007",
+                CalcError::AssertionFailed(_) => "SQE-1008",
+                CalcError::FunctionFailed(_) => "SQE-1009",
+                CalcError::Overflow(_) => "SQE-1010",
+                CalcError::Unexpected => "SQE-1000",
+            }
+        }
+
+        /// Process exit status reported by the CLI for this error:
+        ///
+        /// | code     | exit |
+        /// |----------|------|
+        /// | SQE-1000 | 10   |
+        /// | SQE-1001 | 11   |
+        /// | SQE-1002 | 12   |
+        /// | SQE-1003 | 13   |
+        /// | SQE-1004 | 14   |
+        /// | SQE-1005 | 15   |
+        /// | SQE-1006 | 16   |
+        /// | SQE-1007 | 17   |
+        /// | SQE-1008 | 18   |
+        /// | SQE-1009 | 19   |
+        /// | SQE-1010 | 20   |
+        pub fn exit_code(&self) -> i32 {
+            match self {
+                CalcError::InvalidType(_) => 11,
+                CalcError::UnsupportedOperator(_) => 12,
+                CalcError::UnsupportedFunc(_) => 13,
+                CalcError::InvalidRequestFormat(_) => 14,
+                CalcError::MultipleErrors(_) => 15,
+                CalcError::DivisionByZero => 16,
+                CalcError::ResourceLimit(_) => 17,
+                CalcError::AssertionFailed(_) => 18,
+                CalcError::FunctionFailed(_) => 19,
+                CalcError::Overflow(_) => 20,
+                CalcError::Unexpected => 10,
+            }
+        }
+    }
+
+    /// Non-fatal diagnostic raised while evaluating a query.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Warning {
+        IgnoredPrecision(String),
+        ImplicitCoercion(String),
+    }
+
+    impl fmt::Display for Warning {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Warning::IgnoredPrecision(str) => write!(f, "[Ignored Precision]: {}", str),
+                Warning::ImplicitCoercion(str) => write!(f, "[Implicit Coercion]: {}", str),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Evaluation {
+        pub result: CalcResult,
+        /// The name of each column of the result (each value of a row, or
+        /// the only one), `None` for an item without an alias in a query
+        /// reading no table.
+        pub columns: Vec<Option<String>>,
+        /// The heading of each column in tabular output: its name, the text
+        /// of its expression, or `result` for the value of a statement.
+        pub headers: Vec<String>,
+        pub warnings: Vec<Warning>,
+        pub timing: Timing,
+    }
+
+    /// A query's result with its column names and headers, as an
+    /// [`Evaluation`] holds them.
+    type QueryResult = (CalcResult, Vec<Option<String>>, Vec<String>);
+
+    /// How long the engine took to parse a statement and then to evaluate
+    /// it, measured with a monotonic clock whatever the engine's clock
+    /// (which only gives `NOW()`) says.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct Timing {
+        pub parse: Duration,
+        pub eval: Duration,
+    }
+
+    impl Evaluation {
+        /// The rows of the result: those of a table, or else the one row of
+        /// its values.
+        pub fn rows(&self) -> Vec<&[CalcResult]> {
+            match &self.result {
+                CalcResult::Table(rows) => rows.iter().map(Vec::as_slice).collect(),
+                CalcResult::Row(values) => vec![values],
+                value => vec![std::slice::from_ref(value)],
+            }
+        }
+    }
+
+    /// Prints the result as `CalcResult` does, naming aliased values
+    /// (`Result: total = 2, 3`) and heading tables with their column names.
+    impl fmt::Display for Evaluation {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            if let CalcResult::Table(rows) = &self.result {
+                return write_rows(f, Some(&self.columns), rows);
+            } else if self.columns.iter().all(Option::is_none) {
+                return write!(f, "{}", self.result);
+            }
+            let values: Vec<_> = self.rows()[0]
+                .iter()
+                .zip(&self.columns)
+                .map(|(value, column)| match column {
+                    Some(alias) => format!("{} = {}", alias, to_text(value)),
+                    None => to_text(value),
+                })
+                .collect();
+            write!(f, "Result: {}", values.join(", "))
+        }
+    }
+
+    /// What a division (or other operation) by zero evaluates to.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum OnDivisionByZero {
+        #[default]
+        Error,
+        Null,
+    }
+
+    /// Whether operators implicitly convert strings to numbers: strict
+    /// rejects `'2' + 3`, lenient evaluates it to 5.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum Coercion {
+        #[default]
+        Strict,
+        Lenient,
+    }
+
+    /// How numbers with a fraction compute: in f64, or exactly as decimals,
+    /// so `0.1 + 0.2` is 0.3 and `1 / 4` is 0.25 without rounding error.
+    /// Integers stay integers either way.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum Numeric {
+        #[default]
+        Float,
+        Decimal,
+    }
+
+    /// What integer arithmetic or a cast to an integer type whose result is
+    /// out of range gives: an error, the result wrapped around (two's
+    /// complement), or the nearest integer in range.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum OnOverflow {
+        #[default]
+        Error,
+        Wrap,
+        Saturate,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct Options {
+        pub division_by_zero: OnDivisionByZero,
+        pub overflow: OnOverflow,
+        pub coercion: Coercion,
+        pub numeric: Numeric,
+        pub string_semantics: StringSemantics,
+        pub collation: Collation,
+        pub limits: Limits,
+        pub clock: Clock,
+        /// Whether `EXPLAIN ANALYZE` reports the time each step took, or
+        /// only its loops and rows, so that plans compare across runs.
+        pub explain_timing: bool,
+        /// Seed of the random number generator; `None` seeds from the
+        /// current time.
+        pub seed: Option<u64>,
+        pub dialect: Arc<dyn Dialect>,
+        pub http: HttpOptions,
+        /// The time zone sessions start in, until `SET TIME ZONE`.
+        pub time_zone: Zone,
+    }
+
+    impl Default for Options {
+        fn default() -> Self {
+            Options {
+                division_by_zero: OnDivisionByZero::default(),
+                overflow: OnOverflow::default(),
+                coercion: Coercion::default(),
+                numeric: Numeric::default(),
+                string_semantics: StringSemantics::default(),
+                collation: Collation::default(),
+                limits: Limits::default(),
+                clock: Clock::default(),
+                explain_timing: true,
+                seed: None,
+                dialect: Arc::new(Generic),
+                http: HttpOptions::default(),
+                time_zone: Zone::default(),
+            }
+        }
+    }
+
+    impl Options {
+        /// Options under which every run produces identical output: the
+        /// clock is frozen at 2000-01-01T00:00:00Z, the generator seeded
+        /// with 0 and `EXPLAIN ANALYZE` reports no times.
+        pub fn deterministic() -> Self {
+            Options {
+                clock: Clock::Fixed(UNIX_EPOCH + Duration::from_secs(946_684_800)),
+                explain_timing: false,
+                seed: Some(0),
+                ..Options::default()
+            }
+        }
+    }
+
+    /// Source of the current time.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum Clock {
+        #[default]
+        System,
+        Fixed(SystemTime),
+    }
+
+    impl Clock {
+        pub fn now(&self) -> SystemTime {
+            match self {
+                Clock::System => SystemTime::now(),
+                Clock::Fixed(instant) => *instant,
+            }
+        }
+    }
+
+    /// Stack a statement needs per level of expression nesting. A debug
+    /// build takes about 20 KB a level to evaluate, so `max_depth` levels
+    /// outgrow a thread's stack, which then grows on the heap.
+    const STACK_PER_LEVEL: usize = 64 * 1024;
+
+    /// How much the stack grows at a time while evaluating expressions
+    /// nested deeper than their text, as macros expand.
+    const STACK_GROWTH: usize = 32 * STACK_PER_LEVEL;
+
+    /// Caps protecting the engine from hostile inputs.
+    #[derive(Clone, Debug)]
+    pub struct Limits {
+        /// Deepest allowed expression nesting.
+        pub max_depth: usize,
+        /// Most expression nodes evaluated for a single query.
+        pub max_nodes: usize,
+        /// Longest string value, in bytes.
+        pub max_string_len: usize,
+        /// Most times the recursive part of a `WITH RECURSIVE` query runs.
+        pub max_recursion: usize,
+    }
+
+    impl Default for Limits {
+        fn default() -> Self {
+            Limits {
+                max_depth: 500,
+                max_nodes: 100_000,
+                max_string_len: 1024 * 1024,
+                max_recursion: 1000,
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub struct Engine {
+        options: Options,
+        functions: FunctionRegistry,
+        operators: OperatorRegistry,
+        tables: HashMap<String, Arc<dyn TableProvider>>,
+        hooks: Hooks,
+        warnings: RefCell<Vec<Warning>>,
+        depth: Cell<usize>,
+        nodes: Cell<usize>,
+        coverage: RefCell<Coverage>,
+        rng: Cell<Option<u64>>,
+        macros: RefCell<BTreeMap<String, SessionFunction>>,
+        expansions: Cell<usize>,
+        /// "Now" for the statement being evaluated, read from the clock once
+        /// so every `NOW()` in it agrees.
+        statement_time: Cell<Option<SystemTime>>,
+        /// Rows being evaluated, innermost last, whose columns identifiers
+        /// refer to.
+        scopes: RefCell<Vec<Scope>>,
+        /// The group of rows a grouped `SELECT` is evaluating an item for.
+        group: RefCell<Option<Rc<Group>>>,
+        /// The values of the window function calls for the row a `SELECT`
+        /// is evaluating its items for.
+        window: RefCell<Option<Window>>,
+        catalog: RefCell<Catalog>,
+        /// Tables of the databases attached with `ATTACH`, by the key
+        /// `<schema>.<table>` is looked up by.
+        attached: RefCell<HashMap<String, Arc<dyn TableProvider>>>,
+        /// Session variables set with `SET`, by normalized name.
+        variables: RefCell<BTreeMap<String, CalcResult>>,
+        /// The session time zone, set with `SET TIME ZONE`.
+        time_zone: Cell<Zone>,
+        /// What the query an `EXPLAIN ANALYZE` runs has measured so far.
+        profile: RefCell<Option<Profile>>,
+    }
+
+    /// Built-in functions evaluating their arguments lazily.
+    const CONDITIONAL_FUNCTIONS: [&str; 3] = ["COALESCE", "IFNULL", "NULLIF"];
+
+    /// Built-in functions with a syntax of their own, like
+    /// `SUBSTRING(x FROM 2 FOR 3)`.
+    const SYNTAX_FUNCTIONS: [&str; 3] = ["EXTRACT", "SUBSTRING", "TRIM"];
+
+    /// Built-in functions reading the engine's clock; the `CURRENT_` ones may
+    /// be written without parentheses.
+    const CLOCK_FUNCTIONS: [&str; 3] = ["CURRENT_DATE", "CURRENT_TIMESTAMP", "NOW"];
+
+    /// Built-in functions giving a table, read with `FROM`.
+    const TABLE_FUNCTIONS: &[&str] = &[
+        "READ_CSV",
+        "READ_JSON",
+        #[cfg(feature = "parquet")]
+        "READ_PARQUET",
+    ];
+
+    /// Built-in functions using the engine's random number generator.
+    const RANDOM_FUNCTIONS: [&str; 2] = ["RANDOM", "SETSEED"];
+
+    /// Session functions calling each other nest no deeper than this, which
+    /// stops recursive definitions long before the stack would.
+    const MAX_EXPANSIONS: usize = 64;
+
+    /// Unwraps every result, or fails with all of the errors among them
+    /// (flattening nested `MultipleErrors`). A resource limit aborts the whole
+    /// query, so it is reported on its own.
+    fn collect_errors<T>(results: Vec<Result<T, CalcError>>) -> Result<Vec<T>, CalcError> {
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for res in results {
+            match res {
+                Ok(value) => values.push(value),
+                Err(e @ CalcError::ResourceLimit(_)) => return Err(e),
+                Err(CalcError::MultipleErrors(nested)) => errors.extend(nested),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        match errors.len() {
+            0 => Ok(values),
+            1 => Err(errors.remove(0)),
+            _ => Err(CalcError::MultipleErrors(errors)),
+        }
+    }
+
+    /// Applies an operator to two numbers: exactly to integers and decimals,
+    /// integers giving an integer unless `exact_quotients` asks for their
+    /// decimal quotient, and in f64 otherwise.
+    fn arithmetic(
+        op: BinaryOperator,
+        left: &CalcResult,
+        right: &CalcResult,
+        exact_quotients: bool,
+        overflow: OnOverflow,
+    ) -> Result<CalcResult, CalcError> {
+        match (left, right) {
+            (CalcResult::Int(l), CalcResult::Int(r)) if !exact_quotients => {
+                apply_int(op, *l, *r, overflow)
+            }
+            (l, r) => match (decimal::of(l), decimal::of(r)) {
+                (Some(l), Some(r)) => decimal::apply(&op, &l, &r),
+                _ => None,
+            }
+            .unwrap_or_else(|| {
+                apply(
+                    op,
+                    l.as_f64().unwrap_or_default(),
+                    r.as_f64().unwrap_or_default(),
+                )
+            }),
+        }
+    }
+
+    /// Arithmetic keeping two integers integral: `+`, `-`, `*`, `/` and `%`
+    /// give an integer, `/` truncating toward zero and any of them
+    /// overflowing as `overflow` says, while `^` computes in f64.
+    fn apply_int(
+        operator: BinaryOperator,
+        first_val: i64,
+        second_val: i64,
+        overflow: OnOverflow,
+    ) -> Result<CalcResult, CalcError> {
+        let (checked, wrapped, saturated) = match &operator {
+            BinaryOperator::Plus => (
+                first_val.checked_add(second_val),
+                first_val.wrapping_add(second_val),
+                first_val.saturating_add(second_val),
+            ),
+            BinaryOperator::Minus => (
+                first_val.checked_sub(second_val),
+                first_val.wrapping_sub(second_val),
+                first_val.saturating_sub(second_val),
+            ),
+            BinaryOperator::Multiply => (
+                first_val.checked_mul(second_val),
+                first_val.wrapping_mul(second_val),
+                first_val.saturating_mul(second_val),
+            ),
+            BinaryOperator::Divide | BinaryOperator::Modulo if second_val == 0 => {
+                return Err(CalcError::DivisionByZero)
+            }
+            // truncates toward zero; only i64::MIN / -1 overflows
+            BinaryOperator::Divide => (
+                first_val.checked_div(second_val),
+                first_val.wrapping_div(second_val),
+                first_val.saturating_div(second_val),
+            ),
+            // only i64::MIN % -1 overflows, and its remainder is 0
+            BinaryOperator::Modulo => {
+                let rem = first_val.checked_rem(second_val).unwrap_or(0);
+                (Some(rem), rem, rem)
+            }
+            BinaryOperator::Gt
+            | BinaryOperator::GtEq
+            | BinaryOperator::Lt
+            | BinaryOperator::LtEq
+            | BinaryOperator::Eq
+            | BinaryOperator::NotEq => {
+                let compared = comparison(&operator, first_val.cmp(&second_val));
+                return Ok(CalcResult::Bool(compared.unwrap_or_default()));
+            }
+            _ => return apply(operator, first_val as f64, second_val as f64),
+        };
+        overflowed(overflow, checked, wrapped, saturated, || {
+            format!(
+                "Integer {} {} {} is out of range",
+                first_val, operator, second_val
+            )
+        })
+    }
+
+    /// The integer result of an operation: `checked` when it is in range,
+    /// or else the error described by `describe`, `wrapped` or `saturated`
+    /// as `overflow` says.
+    fn overflowed(
+        overflow: OnOverflow,
+        checked: Option<i64>,
+        wrapped: i64,
+        saturated: i64,
+        describe: impl FnOnce() -> String,
+    ) -> Result<CalcResult, CalcError> {
+        match (checked, overflow) {
+            (Some(int), _) => Ok(CalcResult::Int(int)),
+            (None, OnOverflow::Error) => Err(CalcError::Overflow(describe())),
+            (None, OnOverflow::Wrap) => Ok(CalcResult::Int(wrapped)),
+            (None, OnOverflow::Saturate) => Ok(CalcResult::Int(saturated)),
+        }
+    }
+
     fn apply(
         operator: BinaryOperator,
         first_val: f64,
@@ -53,48 +689,132 @@ pub mod engine {
             BinaryOperator::Plus => Ok(CalcResult::Num(first_val + second_val)),
             BinaryOperator::Minus => Ok(CalcResult::Num(first_val - second_val)),
             BinaryOperator::Multiply => Ok(CalcResult::Num(first_val * second_val)),
+            BinaryOperator::Divide if second_val == 0.0 => Err(CalcError::DivisionByZero),
+            BinaryOperator::Divide => Ok(CalcResult::Num(first_val / second_val)),
+            BinaryOperator::Modulo => functions::modulo(first_val, second_val),
+            // sqlparser reads `^` as XOR; here it is exponentiation, as in
+            // PostgreSQL, binding tighter than `*`.
+            BinaryOperator::BitwiseXor => functions::pow(first_val, second_val),
+            // NaN is not ordered against any number, so comparing it is
+            // unknown
+            BinaryOperator::Gt
+            | BinaryOperator::GtEq
+            | BinaryOperator::Lt
+            | BinaryOperator::LtEq
+            | BinaryOperator::Eq
+            | BinaryOperator::NotEq
+                if first_val.is_nan() || second_val.is_nan() =>
+            {
+                Ok(CalcResult::Null)
+            }
             BinaryOperator::Gt => Ok(CalcResult::Bool(first_val > second_val)),
+            BinaryOperator::GtEq => Ok(CalcResult::Bool(first_val >= second_val)),
+            BinaryOperator::Lt => Ok(CalcResult::Bool(first_val < second_val)),
+            BinaryOperator::LtEq => Ok(CalcResult::Bool(first_val <= second_val)),
+            BinaryOperator::Eq => Ok(CalcResult::Bool(first_val == second_val)),
+            BinaryOperator::NotEq => Ok(CalcResult::Bool(first_val != second_val)),
             _ => Err(CalcError::UnsupportedOperator(String::from(
                 "You try to use unsupported operator",
             ))),
         }
     }
 
-    fn calc_binary_operation(
-        left: Box<Expr>,
-        op: BinaryOperator,
-        right: Box<Expr>,
-    ) -> Result<CalcResult, CalcError> {
-        let parse_num = |expr: Expr| -> Result<f64, CalcError> {
-            match calc(expr) {
-                Ok(CalcResult::Num(res)) => Ok(res),
-                Err(e) => Err(e),
-                _ => Err(CalcError::InvalidType(String::from(
-                    "Binary operators supported by Numbers only",
-                ))),
-            }
-        };
+    fn is_nan(value: &CalcResult) -> bool {
+        matches!(value, CalcResult::Num(num) if num.is_nan())
+    }
 
-        let (numbers, errors): (Vec<_>, Vec<_>) = [left, right]
-            .into_iter()
-            .map(|v| parse_num(*v))
-            .partition(Result::is_ok);
+    /// Renders a value inside a message, quoting strings so `'2'` and `2`
+    /// stay distinguishable.
+    fn describe(value: &CalcResult) -> String {
+        match value {
+            CalcResult::Num(num) => format_num(*num),
+            CalcResult::Int(int) => int.to_string(),
+            CalcResult::Decimal(decimal) => decimal::format(decimal),
+            CalcResult::Bool(boolean) => boolean.to_string(),
+            CalcResult::Str(str) => format!("'{}'", str),
+            CalcResult::Date(date) => format!("DATE '{}'", temporal::format_date(date)),
+            CalcResult::Timestamp(timestamp) => {
+                format!("TIMESTAMP '{}'", temporal::format_timestamp(timestamp))
+            }
+            CalcResult::Interval(interval) => format!("INTERVAL '{}'", interval),
+            CalcResult::Array(items) => {
+                let items: Vec<_> = items.iter().map(describe).collect();
+                format!("[{}]", items.join(", "))
+            }
+            CalcResult::Row(values) => {
+                let values: Vec<_> = values.iter().map(describe).collect();
+                format!("({})", values.join(", "))
+            }
+            CalcResult::Table(rows) => {
+                let rows: Vec<_> = rows
+                    .iter()
+                    .map(|row| describe(&CalcResult::Row(row.clone())))
+                    .collect();
+                rows.join(", ")
+            }
+            CalcResult::Null => String::from("NULL"),
+        }
+    }
 
-        let numbers: Vec<_> = numbers.into_iter().map(Result::unwrap).collect();
-        if numbers.len() == 2 {
-            return apply(op, numbers[0], numbers[1]);
-        } else if !errors.is_empty() {
-            // TODO:
-            let errors: Vec<_> = errors.into_iter().map(Result::unwrap_err).collect();
-            return Err(errors[0].clone());
+    /// Renders a value as the text `||` concatenates. Arrays, rows and
+    /// tables list their values as messages do, so `ARRAY['a', NULL]` is
+    /// `['a', NULL]`.
+    pub fn to_text(value: &CalcResult) -> String {
+        match value {
+            CalcResult::Num(num) => format_num(*num),
+            CalcResult::Int(int) => int.to_string(),
+            CalcResult::Decimal(decimal) => decimal::format(decimal),
+            CalcResult::Bool(boolean) => boolean.to_string(),
+            CalcResult::Str(str) => str.clone(),
+            CalcResult::Date(date) => temporal::format_date(date),
+            CalcResult::Timestamp(timestamp) => temporal::format_timestamp(timestamp),
+            CalcResult::Interval(interval) => interval.to_string(),
+            CalcResult::Array(_) | CalcResult::Row(_) | CalcResult::Table(_) => describe(value),
+            CalcResult::Null => String::from("NULL"),
         }
+    }
 
-        Err(CalcError::Unexpected)
+    /// The outcome of a comparison operator given how its operands order, or
+    /// `None` for any other operator.
+    pub fn comparison(op: &BinaryOperator, ordering: Ordering) -> Option<bool> {
+        match op {
+            BinaryOperator::Gt => Some(ordering == Ordering::Greater),
+            BinaryOperator::GtEq => Some(ordering != Ordering::Less),
+            BinaryOperator::Lt => Some(ordering == Ordering::Less),
+            BinaryOperator::LtEq => Some(ordering != Ordering::Greater),
+            BinaryOperator::Eq => Some(ordering == Ordering::Equal),
+            BinaryOperator::NotEq => Some(ordering != Ordering::Equal),
+            _ => None,
+        }
     }
 
-    fn parse_primitive_value(value: Value) -> Result<CalcResult, CalcError> {
+    fn parse_primitive_value(value: Value, numeric: Numeric) -> Result<CalcResult, CalcError> {
+        let out_of_range = |number: &str| {
+            CalcError::InvalidType(format!("Numeric literal {} is out of range", number))
+        };
         match &value {
-            Value::Number(number, _) => Ok(CalcResult::Num(String::from(number).parse().unwrap())),
+            Value::Number(number, _) if number.parse::<i64>().is_ok() => {
+                Ok(CalcResult::Int(number.parse().unwrap_or_default()))
+            }
+            Value::Number(number, _) => match (numeric, number.parse::<f64>()) {
+                (_, Err(_)) => Err(CalcError::InvalidType(format!(
+                    "Invalid numeric literal {}",
+                    number
+                ))),
+                (Numeric::Decimal, Ok(_)) => decimal::parse(number)
+                    .map(CalcResult::Decimal)
+                    .ok_or_else(|| out_of_range(number)),
+                (Numeric::Float, Ok(num)) if num.is_finite() => Ok(CalcResult::Num(num)),
+                (Numeric::Float, Ok(_)) => Err(out_of_range(number)),
+            },
+            Value::Boolean(boolean) => Ok(CalcResult::Bool(*boolean)),
+            Value::Null => Ok(CalcResult::Null),
+            Value::Interval {
+                value,
+                leading_field,
+                last_field: None,
+                ..
+            } => temporal::parse_interval(value, leading_field.as_ref()).map(CalcResult::Interval),
             Value::DoubleQuotedString(string) => Ok(CalcResult::Str(string.to_string())),
             Value::SingleQuotedString(string) => Ok(CalcResult::Str(string.to_string())),
             _ => Err(CalcError::InvalidType(String::from(
@@ -103,306 +823,7212 @@ pub mod engine {
         }
     }
 
-    fn calc_function(func: Function) -> Result<CalcResult, CalcError> {
-        if func.name.0.get(0).unwrap().value == "SQRT" {
-            let arg = func.args.first();
-            if arg.is_none() {
-                return Err(CalcError::InvalidType(String::from(
-                    "SQRT must has an argument",
-                )));
+    impl Engine {
+        pub fn new(options: Options) -> Self {
+            Engine {
+                functions: FunctionRegistry::new(&options),
+                time_zone: Cell::new(options.time_zone),
+                options,
+                ..Engine::default()
             }
+        }
 
-            let result = {
-                match &arg.unwrap() {
-                    FunctionArg::Named { name: _, arg } => calc(arg.clone()),
-                    FunctionArg::Unnamed(arg) => calc(arg.clone()),
-                }
-            };
-
-            match &result {
-                Err(e) => return Err(e.clone()),
-                Ok(CalcResult::Num(num)) => return Ok(CalcResult::Num(num.sqrt())),
-                _ => {
-                    return Err(CalcError::InvalidType(String::from(
-                        "SQRT supports only Number",
-                    )))
-                }
-            }
+        /// Makes `name` callable from SQL with exactly `arity` arguments,
+        /// replacing any function of the same name. Errors convertible into
+        /// `CalcError` are returned as is; plain messages become
+        /// `FunctionFailed` prefixed with the function name. A call with a
+        /// NULL argument is NULL without calling `eval`.
+        pub fn register_scalar_fn<F, E>(&mut self, name: &str, arity: usize, eval: F)
+        where
+            F: Fn(&[CalcResult]) -> Result<CalcResult, E> + 'static,
+            E: Into<CalcError>,
+        {
+            let prefix = name.to_uppercase();
+            self.functions
+                .register(name, arity, move |args| match eval(args) {
+                    Ok(value) => Ok(value),
+                    Err(err) => match err.into() {
+                        CalcError::FunctionFailed(message) => Err(CalcError::FunctionFailed(
+                            format!("{}: {}", prefix, message),
+                        )),
+                        err => Err(err),
+                    },
+                });
         }
-        Err(CalcError::UnsupportedFunc(String::from(
-            "Only SQRT func is supported",
-        )))
-    }
 
-    fn cast(expr: Expr) -> Result<CalcResult, CalcError> {
-        match calc(expr) {
-            Ok(CalcResult::Str(res)) => match res.parse::<f64>() {
-                Ok(res) => Ok(CalcResult::Num(res)),
-                Err(_) => Err(CalcError::InvalidType(String::from(
-                    "CAST supports only Number",
-                ))),
-            },
-            Err(e) => Err(e),
-            _ => Err(CalcError::Unexpected),
+        /// Implements (or overrides) `left op right` for operands of the given
+        /// types, e.g. `-` on two strings.
+        pub fn register_binary_op<F>(
+            &mut self,
+            op: BinaryOperator,
+            left: ValueType,
+            right: ValueType,
+            eval: F,
+        ) where
+            F: Fn(&CalcResult, &CalcResult) -> Result<CalcResult, CalcError> + 'static,
+        {
+            self.operators.register_binary(op, left, right, eval);
         }
-    }
 
-    fn calc(expr: Expr) -> Result<CalcResult, CalcError> {
-        match expr {
-            Expr::BinaryOp { left, op, right } => calc_binary_operation(left, op, right),
-            Expr::Function(func) => calc_function(func),
-            Expr::Value(value) => parse_primitive_value(value),
-            Expr::Nested(expr) => calc(*expr),
-            Expr::Cast {
-                expr,
-                data_type: DataType::Int(_),
-            } => cast(*expr),
-            _ => Err(CalcError::Unexpected),
+        /// Implements (or overrides) a prefix operator for operands of the
+        /// given type.
+        pub fn register_unary_op<F>(&mut self, op: UnaryOperator, operand: ValueType, eval: F)
+        where
+            F: Fn(&CalcResult) -> Result<CalcResult, CalcError> + 'static,
+        {
+            self.operators.register_unary(op, operand, eval);
         }
-    }
 
-    pub fn exec(query: String) -> Result<CalcResult, CalcError> {
-        let dialect = GenericDialect {};
-        let res = Parser::parse_sql(&dialect, &query);
+        /// Adds a rule rewriting (or rejecting, by returning an error) every
+        /// parsed statement before it is evaluated. Rules run in the order
+        /// they were added, each on the output of the previous one. The
+        /// expression a `SET` or `CREATE FUNCTION` carries, and a function
+        /// body as a call expands it, reach the rules as `SELECT <expr>`.
+        /// `SET TIME ZONE` and `ATTACH` have no [`Statement`] to pass, so
+        /// they skip the rules; observers still see them.
+        pub fn add_rewrite<F>(&mut self, rule: F)
+        where
+            F: Fn(Statement) -> Result<Statement, CalcError> + 'static,
+        {
+            self.hooks.add_rewrite(rule);
+        }
 
-        if res.is_err() {
-            return Err(CalcError::InvalidRequestFormat(String::from(
-                "It is not SQL, man",
-            )));
+        /// Calls `observer` with every query `eval` is given and its outcome,
+        /// e.g. to keep an audit log.
+        pub fn on_statement<F>(&mut self, observer: F)
+        where
+            F: Fn(&str, &Result<Evaluation, CalcError>) + 'static,
+        {
+            self.hooks.add_observer(observer);
         }
 
-        let ast = res.unwrap();
+        /// Exposes `table` to queries as `name`, replacing any table of the
+        /// same name. Names follow the same case rules as identifiers, so
+        /// `"Data"` keeps its case.
+        pub fn register_table(&mut self, name: &str, table: Arc<dyn TableProvider>) {
+            let key = self.table_key(&self.object_name(name));
+            self.tables.insert(key, table);
+        }
 
-        if ast.is_empty() {
-            return Err(CalcError::InvalidRequestFormat(String::from(
-                "It is not SQL, man",
-            )));
+        /// The table an SQL name refers to, registered or attached.
+        pub fn table(&self, name: &ObjectName) -> Option<Arc<dyn TableProvider>> {
+            let key = self.table_key(name);
+            self.tables
+                .get(&key)
+                .cloned()
+                .or_else(|| self.attached.borrow().get(&key).cloned())
         }
 
-        match &ast[0] {
-            Statement::Query(query) => match &query.body {
-                SetExpr::Select(select) => {
-                    let projection = select.projection.first();
-                    if projection.is_none() {
-                        return Err(CalcError::InvalidRequestFormat(String::from(
-                            "only SELECT is supported",
-                        )));
-                    }
+        /// The key tables are looked up by, registered or created alike.
+        fn table_key(&self, name: &ObjectName) -> String {
+            let dialect = self.options.dialect.as_ref();
+            let key: Vec<_> = name
+                .0
+                .iter()
+                .map(|ident| dialect.normalize_identifier(ident))
+                .collect();
+            key.join(".")
+        }
 
-                    match projection.unwrap() {
-                        SelectItem::UnnamedExpr(expr) => calc(expr.clone()),
-                        _ => Err(CalcError::InvalidRequestFormat(String::from(
-                            "only Unnamed expressions are supported",
-                        ))),
-                    }
-                }
-                _ => Err(CalcError::InvalidRequestFormat(String::from(
-                    "only SELECT is supported",
-                ))),
-            },
-            _ => Err(CalcError::InvalidRequestFormat(String::from(
-                "only Queries are supported",
-            ))),
+        /// The table name `name` is written as, e.g. `s."Data"`; text that
+        /// does not parse as one is taken as unquoted parts.
+        fn object_name(&self, name: &str) -> ObjectName {
+            let dialect = self.options.dialect.parser_dialect();
+            let parsed = Tokenizer::new(dialect, name)
+                .tokenize()
+                .ok()
+                .and_then(|tokens| {
+                    let mut parser = Parser::new(tokens, dialect);
+                    let object = parser.parse_object_name().ok()?;
+                    (parser.peek_token() == Token::EOF).then_some(object)
+                });
+            parsed.unwrap_or_else(|| ObjectName(name.split('.').map(Ident::new).collect()))
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+        /// Names of the registered tables, sorted.
+        pub fn table_names(&self) -> Vec<&str> {
+            let mut names: Vec<_> = self.tables.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            names
+        }
 
-        #[test]
-        fn apply_operator_plus() {
-            let res = apply(BinaryOperator::Plus, 1.5, 2.5);
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 4.0);
-            } else {
-                panic!();
-            }
+        /// Names of the tables queries can read, created with `CREATE
+        /// TABLE`, registered or attached, sorted and each listed once.
+        pub fn all_table_names(&self) -> Vec<String> {
+            let catalog = self.catalog.borrow();
+            let mut names: Vec<_> = catalog.names().into_iter().map(String::from).collect();
+            names.extend(self.tables.keys().cloned());
+            names.extend(self.attached.borrow().keys().cloned());
+            names.sort_unstable();
+            names.dedup();
+            names
         }
 
-        #[test]
-        fn apply_operator_minus() {
-            let res = apply(BinaryOperator::Minus, 1.0, 2.0);
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, -1.0);
-            } else {
-                panic!();
+        /// The columns of the table `name` (dot-separated when qualified)
+        /// refers to, with their types: as declared for a session table,
+        /// or as its schema gives them for a registered one.
+        pub fn describe_table(&self, name: &str) -> Option<Vec<(String, String)>> {
+            let name = self.object_name(name);
+            if let Some(columns) = self.catalog.borrow().columns(&self.table_key(&name)) {
+                return Some(
+                    columns
+                        .iter()
+                        .map(|(ident, data_type)| (ident.value.clone(), data_type.to_string()))
+                        .collect(),
+                );
             }
+            let table = self.table(&name)?;
+            Some(
+                table
+                    .schema()
+                    .into_iter()
+                    .map(|column| (column.name, column.ty.to_string()))
+                    .collect(),
+            )
         }
 
-        #[test]
-        fn apply_operator_gt() {
-            let res = apply(BinaryOperator::Gt, 2.0, 1.0);
-            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(false)) {
-                assert!(val);
-            } else {
-                panic!();
+        /// Creates the session table `name` with `columns`, each typed by
+        /// its SQL name (`INT`, `DECIMAL(10, 2)`), and appends `rows` to it
+        /// as `CREATE TABLE` and `INSERT` would. Test fixtures are loaded
+        /// this way.
+        pub fn load_table(
+            &self,
+            name: &str,
+            columns: &[(String, String)],
+            rows: Vec<Row>,
+        ) -> Result<(), CalcError> {
+            let key = self.table_key(&self.object_name(name));
+            if self.tables.contains_key(&key) || self.catalog.borrow().contains(&key) {
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "Table {} already exists",
+                    name
+                )));
             }
-        }
 
-        #[test]
-        fn apply_operator_multiply() {
-            let res = apply(BinaryOperator::Multiply, 3.0, 2.0);
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 6.0);
-            } else {
-                panic!();
+            let dialect = self.options.dialect.as_ref();
+            let mut declared: Vec<(Ident, DataType)> = Vec::with_capacity(columns.len());
+            for (column, type_name) in columns {
+                let column = Ident::new(column);
+                let key = dialect.normalize_identifier(&column);
+                if declared
+                    .iter()
+                    .any(|(other, _)| dialect.normalize_identifier(other) == key)
+                {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Column {} specified more than once",
+                        column
+                    )));
+                }
+                let data_type = self.parse_data_type(type_name).ok_or_else(|| {
+                    CalcError::InvalidType(format!(
+                        "Column {} has unsupported type {}",
+                        column, type_name
+                    ))
+                })?;
+                declared.push((column, data_type));
             }
-        }
 
-        #[test]
-        fn apply_operator_error() {
-            if std::mem::discriminant(&CalcError::UnsupportedOperator(String::from("")))
-                != std::mem::discriminant(&apply(BinaryOperator::GtEq, 3.0, 2.0).unwrap_err())
-            {
-                panic!();
+            self.catalog.borrow_mut().create(key.clone(), declared)?;
+            let inserted = self.catalog.borrow_mut().insert(
+                &key,
+                rows,
+                &self.time_zone.get(),
+                self.options.overflow,
+            );
+            if let Err(err) = inserted {
+                self.catalog.borrow_mut().drop(&key);
+                return Err(err);
             }
+            Ok(())
         }
 
-        #[test]
-        fn parse_primitive_value_number() {
-            let res = parse_primitive_value(Value::Number(5.0.to_string(), false));
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 5.0);
-            } else {
-                panic!();
-            }
+        /// Drops the session table `name`, returning whether there was one.
+        pub fn drop_table(&self, name: &str) -> bool {
+            self.catalog
+                .borrow_mut()
+                .drop(&self.table_key(&self.object_name(name)))
         }
 
-        #[test]
-        fn parse_primitive_value_str() {
-            let res = parse_primitive_value(Value::SingleQuotedString(String::from("string")));
-            if let CalcResult::Str(val) = res.unwrap_or_else(|_| CalcResult::Str(String::from(""))) {
-                assert_eq!(val, String::from("string"));
-            } else {
-                panic!();
-            }
+        /// The type `text` names, when it names exactly one.
+        fn parse_data_type(&self, text: &str) -> Option<DataType> {
+            let dialect = self.options.dialect.parser_dialect();
+            let tokens = Tokenizer::new(dialect, text).tokenize().ok()?;
+            let mut parser = Parser::new(tokens, dialect);
+            let data_type = parser.parse_data_type().ok()?;
+            (parser.peek_token() == Token::EOF).then_some(data_type)
         }
 
-        #[test]
-        fn parse_primitive_value_unsupported() {
-            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
-                != std::mem::discriminant(&parse_primitive_value(Value::Boolean(false)).unwrap_err())
-            {
-                panic!();
-            }
+        /// Names of the functions callable from SQL.
+        pub fn function_names(&self) -> Vec<&str> {
+            let mut names = self.functions.names();
+            names.extend(CONDITIONAL_FUNCTIONS);
+            names.extend(RANDOM_FUNCTIONS);
+            names.extend(SYNTAX_FUNCTIONS);
+            names.extend(CLOCK_FUNCTIONS);
+            names.extend(TABLE_FUNCTIONS);
+            names.extend(AGGREGATE_FUNCTIONS);
+            names.extend(WINDOW_FUNCTIONS);
+            names.sort_unstable();
+            names
         }
 
-        #[test]
-        fn exec_single_operator() {
-            let res = exec(String::from("SELECT 1 + 1"));
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 2.0);
-            } else {
-                panic!();
-            }
+        /// Features exercised by every query this engine evaluated so far.
+        pub fn coverage(&self) -> Coverage {
+            self.coverage.borrow().clone()
+        }
+
+        /// Every feature a coverage report accounts for.
+        pub fn surface(&self) -> Vec<(Category, String)> {
+            let clauses = [
+                "SELECT",
+                "DISTINCT",
+                "FROM",
+                "INNER JOIN",
+                "LEFT JOIN",
+                "RIGHT JOIN",
+                "FULL JOIN",
+                "CROSS JOIN",
+                "WHERE",
+                "GROUP BY",
+                "HAVING",
+                "UNION",
+                "INTERSECT",
+                "EXCEPT",
+                "VALUES",
+                "CREATE TABLE",
+                "INSERT",
+                "DROP TABLE",
+                "ASSERT",
+                "CAST",
+                "TRY_CAST",
+                "CASE",
+                "SUBQUERY",
+                "WITH",
+                "WITH RECURSIVE",
+                "OVER",
+                "SET",
+                "SET TIME ZONE",
+                "ATTACH",
+                "EXPLAIN",
+                "EXPLAIN ANALYZE",
+            ];
+            let operators = [
+                "+", "-", "*", "/", "%", "^", "||", ">", ">=", "<", "<=", "=", "<>", "AND", "OR",
+                "NOT",
+            ];
+            let types = [
+                "Number",
+                "Integer",
+                "Decimal",
+                "Boolean",
+                "String",
+                "Date",
+                "Timestamp",
+                "Interval",
+                "Array",
+                "NULL",
+            ];
+
+            let mut surface = Vec::new();
+            surface.extend(clauses.map(|name| (Category::Clause, name.to_string())));
+            surface.extend(operators.map(|name| (Category::Operator, name.to_string())));
+            surface.extend(
+                self.function_names()
+                    .into_iter()
+                    .map(|name| (Category::Function, name.to_string())),
+            );
+            surface.extend(
+                self.macros
+                    .borrow()
+                    .keys()
+                    .map(|name| (Category::Function, name.clone())),
+            );
+            surface.extend(types.map(|name| (Category::Type, name.to_string())));
+            surface
+        }
+
+        /// The instant queries observe as "now".
+        pub fn now(&self) -> SystemTime {
+            self.options.clock.now()
+        }
+
+        /// Next number of the engine's generator, uniform in `[0, 1)`. The
+        /// sequence is fixed by `Options::seed` (splitmix64).
+        pub fn random(&self) -> f64 {
+            let state = self.rng.get().unwrap_or_else(|| {
+                self.options.seed.unwrap_or_else(|| {
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+                })
+            });
+            let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            self.rng.set(Some(state));
+
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            (z >> 11) as f64 / (1u64 << 53) as f64
+        }
+
+        fn cover(&self, category: Category, name: &str) {
+            self.coverage.borrow_mut().record(category, name);
+        }
+
+        fn calc_binary_operation(
+            &self,
+            left: Box<Expr>,
+            op: BinaryOperator,
+            right: Box<Expr>,
+        ) -> Result<CalcResult, CalcError> {
+            let operands: Vec<_> = [left, right].into_iter().map(|v| self.calc(*v)).collect();
+            self.apply_binary_operation(op, operands)
+        }
+
+        /// Applies `op` to the already evaluated `[left, right]` operands.
+        fn apply_binary_operation(
+            &self,
+            op: BinaryOperator,
+            operands: Vec<Result<CalcResult, CalcError>>,
+        ) -> Result<CalcResult, CalcError> {
+            if let [Ok(l), Ok(r)] = &operands[..] {
+                if let Some(eval) = self.operators.binary(&op, l, r) {
+                    return eval(l, r);
+                }
+            }
+
+            if matches!(op, BinaryOperator::And | BinaryOperator::Or) {
+                let values = collect_errors(
+                    collect_errors(operands)?
+                        .into_iter()
+                        .map(|value| match value {
+                            CalcResult::Bool(value) => Ok(Some(value)),
+                            CalcResult::Null => Ok(None),
+                            value => Err(CalcError::InvalidType(format!(
+                                "{} supports only Boolean, got {}",
+                                op,
+                                describe(&value)
+                            ))),
+                        })
+                        .collect(),
+                )?;
+                // Three-valued logic: NULL is unknown, so it only decides the
+                // result when the other operand does not.
+                let decisive = op == BinaryOperator::Or;
+                return Ok(if values.contains(&Some(decisive)) {
+                    CalcResult::Bool(decisive)
+                } else if values.contains(&None) {
+                    CalcResult::Null
+                } else {
+                    CalcResult::Bool(!decisive)
+                });
+            }
+
+            if op == BinaryOperator::StringConcat {
+                let values = collect_errors(operands)?;
+                if values.contains(&CalcResult::Null) {
+                    return Ok(CalcResult::Null);
+                }
+                if let [l, r] = &values[..] {
+                    if matches!(l, CalcResult::Array(_)) || matches!(r, CalcResult::Array(_)) {
+                        return array::concat(l, r);
+                    }
+                }
+                return Ok(CalcResult::Str(values.iter().map(to_text).collect()));
+            }
+
+            if operands.contains(&Ok(CalcResult::Null)) {
+                collect_errors(operands)?;
+                return Ok(CalcResult::Null);
+            }
+
+            if let [Ok(CalcResult::Str(l)), Ok(CalcResult::Str(r))] = &operands[..] {
+                if op == BinaryOperator::Plus && self.options.dialect.plus_concatenates_strings() {
+                    return Ok(CalcResult::Str(format!("{}{}", l, r)));
+                }
+                let ordering = text::compare(l, r, self.options.collation);
+                if let Some(compared) = comparison(&op, ordering) {
+                    return Ok(CalcResult::Bool(compared));
+                }
+            }
+
+            if let [Ok(l), Ok(r)] = &operands[..] {
+                if let Some(res) = temporal::arithmetic(&op, l, r, &self.time_zone.get()) {
+                    return res;
+                }
+                let ordered = (temporal::as_timestamp(l).is_some()
+                    && temporal::as_timestamp(r).is_some())
+                    || matches!(
+                        (l, r),
+                        (CalcResult::Interval(_), CalcResult::Interval(_))
+                            | (CalcResult::Bool(_), CalcResult::Bool(_))
+                    );
+                if ordered {
+                    if let Some(compared) = comparison(&op, self.compare(l, r)?) {
+                        return Ok(CalcResult::Bool(compared));
+                    }
+                }
+                let composite =
+                    |value: &CalcResult| matches!(value, CalcResult::Array(_) | CalcResult::Row(_));
+                if comparison(&op, Ordering::Equal).is_some() && (composite(l) || composite(r)) {
+                    let equal = match (l, r) {
+                        (CalcResult::Array(l), CalcResult::Array(r))
+                        | (CalcResult::Row(l), CalcResult::Row(r))
+                            if matches!(op, BinaryOperator::Eq | BinaryOperator::NotEq) =>
+                        {
+                            self.equal_items(l, r)?
+                        }
+                        _ => {
+                            return Err(CalcError::InvalidType(format!(
+                                "Cannot compare {} with {} using {}",
+                                ValueType::of(l),
+                                ValueType::of(r),
+                                op
+                            )))
+                        }
+                    };
+                    return Ok(match equal {
+                        Some(equal) => CalcResult::Bool(equal == (op == BinaryOperator::Eq)),
+                        None => CalcResult::Null,
+                    });
+                }
+            }
+
+            let to_num = |res: CalcResult| -> Result<CalcResult, CalcError> {
+                match &res {
+                    CalcResult::Num(_) | CalcResult::Int(_) | CalcResult::Decimal(_) => Ok(res),
+                    CalcResult::Str(str) if self.options.coercion == Coercion::Lenient => {
+                        let trimmed = str.trim();
+                        let unparsable = || {
+                            CalcError::InvalidType(format!(
+                                "Cannot coerce {} to Number",
+                                describe(&res)
+                            ))
+                        };
+                        let num = match (trimmed.parse::<i64>(), self.options.numeric) {
+                            (Ok(int), _) => CalcResult::Int(int),
+                            (Err(_), Numeric::Decimal) => {
+                                CalcResult::Decimal(decimal::parse(trimmed).ok_or_else(unparsable)?)
+                            }
+                            (Err(_), Numeric::Float) => {
+                                CalcResult::Num(trimmed.parse::<f64>().map_err(|_| unparsable())?)
+                            }
+                        };
+                        self.warn(Warning::ImplicitCoercion(format!(
+                            "{} was coerced to Number",
+                            describe(&res)
+                        )));
+                        Ok(num)
+                    }
+                    _ => Err(CalcError::InvalidType(format!(
+                        "Binary operators supported by Numbers only, got {}",
+                        describe(&res)
+                    ))),
+                }
+            };
+
+            // Operands are only checked for numbers once both evaluated, so
+            // a failed one is reported alone.
+            let operands = collect_errors(operands)?;
+            let numbers = collect_errors(operands.into_iter().map(to_num).collect())?;
+
+            let exact_quotients = self.options.numeric == Numeric::Decimal
+                && matches!(op, BinaryOperator::Divide | BinaryOperator::BitwiseXor);
+            self.on_division_by_zero(match &numbers[..] {
+                [l, r] => arithmetic(op, l, r, exact_quotients, self.options.overflow),
+                _ => Err(CalcError::Unexpected),
+            })
+        }
+
+        /// Applies the `division_by_zero` option to a result.
+        fn on_division_by_zero(
+            &self,
+            res: Result<CalcResult, CalcError>,
+        ) -> Result<CalcResult, CalcError> {
+            match res {
+                Err(CalcError::DivisionByZero)
+                    if self.options.division_by_zero == OnDivisionByZero::Null =>
+                {
+                    Ok(CalcResult::Null)
+                }
+                res => res,
+            }
+        }
+
+        fn calc_function(&self, func: Function) -> Result<CalcResult, CalcError> {
+            self.count_calls(|| func.to_string(), 1);
+            let dialect = self.options.dialect.as_ref();
+            let key: Vec<_> = func
+                .name
+                .0
+                .iter()
+                .map(|ident| dialect.normalize_identifier(ident))
+                .collect();
+            let key = key.join(".");
+            if func.over.is_some() {
+                // Calls a `SELECT` item makes are computed before it is.
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "Window function {} is not allowed here",
+                    key
+                )));
+            }
+            let defined = self.macros.borrow().get(&key).cloned();
+            match defined {
+                Some(SessionFunction::Macro(function)) => {
+                    self.calc_macro(&key, &function, func.args)
+                }
+                Some(SessionFunction::Script(function)) => {
+                    self.call_scalar(&key, &function, func.args)
+                }
+                None if CONDITIONAL_FUNCTIONS.contains(&key.as_str()) => {
+                    self.calc_conditional(&key, func.args)
+                }
+                None if RANDOM_FUNCTIONS.contains(&key.as_str()) => {
+                    self.calc_random(&key, func.args)
+                }
+                None if AGGREGATE_FUNCTIONS.contains(&key.as_str()) => {
+                    self.calc_aggregate(&key, func)
+                }
+                None if WINDOW_FUNCTIONS.contains(&key.as_str()) => Err(
+                    CalcError::InvalidRequestFormat(format!("{} requires an OVER clause", key)),
+                ),
+                None if TABLE_FUNCTIONS.contains(&key.as_str()) => {
+                    Err(CalcError::InvalidRequestFormat(format!(
+                        "{} gives a table, read it with FROM",
+                        key
+                    )))
+                }
+                None if CLOCK_FUNCTIONS.contains(&key.as_str()) => {
+                    if !func.args.is_empty() {
+                        return Err(CalcError::InvalidType(format!(
+                            "{} expects 0 argument(s), got {}",
+                            key,
+                            func.args.len()
+                        )));
+                    }
+                    Ok(self.clock_value(&key))
+                }
+                None => {
+                    let (name, function) = self.functions.resolve_with(&func.name, dialect)?;
+                    self.call_scalar(&name, function, func.args)
+                }
+            }
+        }
+
+        /// The value of `NOW()`, `CURRENT_TIMESTAMP` or `CURRENT_DATE`.
+        fn clock_value(&self, name: &str) -> CalcResult {
+            self.cover(Category::Function, name);
+            let now = self.statement_time.get().unwrap_or_else(|| self.now());
+            self.statement_time.set(Some(now));
+            let now = self.time_zone.get().local(temporal::from_system_time(now));
+            match name {
+                "CURRENT_DATE" => CalcResult::Date(now.date()),
+                _ => CalcResult::Timestamp(now),
+            }
+        }
+
+        /// `RANDOM()` draws the next number of the generator; `SETSEED(n)`
+        /// restarts it as `--seed n` would, so the rest of a script is
+        /// reproducible. PostgreSQL's seeds, numbers from -1 to 1, work too,
+        /// each giving a sequence of its own. `SETSEED(NULL)` leaves it alone.
+        fn calc_random(&self, name: &str, args: Vec<FunctionArg>) -> Result<CalcResult, CalcError> {
+            self.cover(Category::Function, name);
+            let arity = match name {
+                "RANDOM" => 0,
+                _ => 1,
+            };
+            if args.len() != arity {
+                return Err(CalcError::InvalidType(format!(
+                    "{} expects {} argument(s), got {}",
+                    name,
+                    arity,
+                    args.len()
+                )));
+            }
+
+            let arg = match args.into_iter().next() {
+                None => return Ok(CalcResult::Num(self.random())),
+                Some(FunctionArg::Named { name: _, arg }) => arg,
+                Some(FunctionArg::Unnamed(arg)) => arg,
+            };
+            let seed = match self.calc(arg)? {
+                CalcResult::Null => return Ok(CalcResult::Null),
+                CalcResult::Int(seed) if seed >= 0 => Some(seed as u64),
+                CalcResult::Decimal(seed) if seed.is_integer() && seed.to_u64().is_some() => {
+                    seed.to_u64()
+                }
+                other => match other.as_f64() {
+                    Some(seed) if seed >= 0.0 && seed.fract() == 0.0 => Some(seed as u64),
+                    Some(seed) if (-1.0..=1.0).contains(&seed) => Some(seed.to_bits()),
+                    _ => {
+                        return Err(CalcError::InvalidType(format!(
+                        "SETSEED expects a non-negative integer or a number from -1 to 1, got {}",
+                        to_text(&other)
+                    )))
+                    }
+                },
+            };
+            self.rng.set(seed);
+            Ok(CalcResult::Null)
+        }
+
+        /// Functions deciding which arguments to evaluate, so they cannot be
+        /// ordinary scalar functions taking evaluated values.
+        fn calc_conditional(
+            &self,
+            name: &str,
+            args: Vec<FunctionArg>,
+        ) -> Result<CalcResult, CalcError> {
+            self.cover(Category::Function, name);
+            let args: Vec<_> = args
+                .into_iter()
+                .map(|arg| match arg {
+                    FunctionArg::Named { name: _, arg } => arg,
+                    FunctionArg::Unnamed(arg) => arg,
+                })
+                .collect();
+            let arity = match name {
+                "COALESCE" => args.len().max(1),
+                _ => 2,
+            };
+            if args.len() != arity {
+                return Err(CalcError::InvalidType(format!(
+                    "{} expects {} argument(s), got {}",
+                    name,
+                    arity,
+                    args.len()
+                )));
+            }
+
+            if name == "NULLIF" {
+                let mut values =
+                    collect_errors(args.into_iter().map(|arg| self.calc(arg)).collect())?;
+                let equal = !values.contains(&CalcResult::Null)
+                    && self.partial_compare(&values[0], &values[1])? == Some(Ordering::Equal);
+                return Ok(match equal {
+                    true => CalcResult::Null,
+                    false => values.remove(0),
+                });
+            }
+
+            // COALESCE and IFNULL stop at the first non-NULL argument
+            for arg in args {
+                let value = self.calc(arg)?;
+                if value != CalcResult::Null {
+                    return Ok(value);
+                }
+            }
+            Ok(CalcResult::Null)
+        }
+
+        /// Inlines a `CREATE FUNCTION ... AS expr` body with `args` in place
+        /// of its parameters.
+        fn calc_macro(
+            &self,
+            name: &str,
+            function: &Macro,
+            args: Vec<FunctionArg>,
+        ) -> Result<CalcResult, CalcError> {
+            self.cover(Category::Function, name);
+            if args.len() != function.params.len() {
+                return Err(CalcError::InvalidType(format!(
+                    "{} expects {} argument(s), got {}",
+                    name,
+                    function.params.len(),
+                    args.len()
+                )));
+            }
+            let args = args
+                .into_iter()
+                .map(|arg| match arg {
+                    FunctionArg::Named { name: _, arg } => arg,
+                    FunctionArg::Unnamed(arg) => arg,
+                })
+                .collect();
+            let expansions = self.expansions.get() + 1;
+            if expansions > MAX_EXPANSIONS {
+                return Err(CalcError::ResourceLimit(format!(
+                    "{} expands deeper than {} levels",
+                    name, MAX_EXPANSIONS
+                )));
+            }
+            self.expansions.set(expansions);
+            let expanded = function.expand(args, self.options.dialect.as_ref());
+            let res = self
+                .rewrite_expr(expanded)
+                .and_then(|expanded| self.calc(expanded));
+            self.expansions.set(expansions - 1);
+            res
+        }
+
+        fn call_scalar(
+            &self,
+            name: &str,
+            function: &ScalarFunction,
+            args: Vec<FunctionArg>,
+        ) -> Result<CalcResult, CalcError> {
+            self.cover(Category::Function, name);
+            function.check_arity(name, args.len())?;
+
+            let args = args
+                .into_iter()
+                .map(|arg| match arg {
+                    FunctionArg::Named { name: _, arg } => self.calc(arg),
+                    FunctionArg::Unnamed(arg) => self.calc(arg),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            self.on_division_by_zero(function.call(&args))
+        }
+
+        fn calc(&self, expr: Expr) -> Result<CalcResult, CalcError> {
+            let limits = &self.options.limits;
+            let depth = self.depth.get() + 1;
+            let nodes = self.nodes.get() + 1;
+            if depth > limits.max_depth {
+                return Err(CalcError::ResourceLimit(format!(
+                    "expression is nested deeper than {} levels",
+                    limits.max_depth
+                )));
+            } else if nodes > limits.max_nodes {
+                return Err(CalcError::ResourceLimit(format!(
+                    "expression has more than {} nodes",
+                    limits.max_nodes
+                )));
+            }
+
+            self.depth.set(depth);
+            self.nodes.set(nodes);
+            let grouped = self
+                .group
+                .borrow()
+                .as_ref()
+                .and_then(|group| group.value(&expr).cloned())
+                .or_else(|| {
+                    self.window
+                        .borrow()
+                        .as_ref()
+                        .and_then(|window| window.value(&expr).cloned())
+                });
+            let res = match grouped {
+                Some(value) => Ok(value),
+                None => stacker::maybe_grow(STACK_PER_LEVEL, STACK_GROWTH, || self.calc_expr(expr)),
+            };
+            if let Ok(value) = &res {
+                self.cover(Category::Type, &ValueType::of(value).to_string());
+            }
+            self.depth.set(depth - 1);
+
+            match res {
+                Ok(CalcResult::Str(str)) if str.len() > limits.max_string_len => {
+                    Err(CalcError::ResourceLimit(format!(
+                        "string of {} bytes exceeds the {} byte limit",
+                        str.len(),
+                        limits.max_string_len
+                    )))
+                }
+                res => res,
+            }
+        }
+
+        /// Whether `value` is [NOT] IN `items`.
+        fn in_items(
+            &self,
+            value: CalcResult,
+            items: &[CalcResult],
+            negated: bool,
+        ) -> Result<CalcResult, CalcError> {
+            if value == CalcResult::Null {
+                return Ok(CalcResult::Null);
+            }
+            let mut saw_null = false;
+            for item in items {
+                if *item == CalcResult::Null {
+                    saw_null = true;
+                } else {
+                    match self.partial_compare(&value, item)? {
+                        Some(Ordering::Equal) => return Ok(CalcResult::Bool(!negated)),
+                        Some(_) => (),
+                        None => saw_null = true,
+                    }
+                }
+            }
+            // `x IN (.., NULL)` is unknown, not false, when nothing matched,
+            // and so is `x IN (.., NaN)`
+            match saw_null {
+                true => Ok(CalcResult::Null),
+                false => Ok(CalcResult::Bool(negated)),
+            }
+        }
+
+        /// The values of a subquery giving a single column, one per row.
+        fn subquery_values(&self, query: &Query) -> Result<Vec<CalcResult>, CalcError> {
+            check_query(query)?;
+            let relation = self.query(query)?;
+            if relation.fields.len() != 1 {
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "Subquery ({}) gives {} columns, expected 1",
+                    query,
+                    relation.fields.len()
+                )));
+            }
+            Ok(relation
+                .rows
+                .into_iter()
+                .map(|mut row| row.remove(0))
+                .collect())
+        }
+
+        fn calc_expr(&self, expr: Expr) -> Result<CalcResult, CalcError> {
+            match expr {
+                Expr::BinaryOp { left, op, right } => {
+                    self.cover(Category::Operator, &op.to_string());
+                    self.calc_binary_operation(left, op, right)
+                }
+                Expr::Function(func) => self.calc_function(func),
+                Expr::Value(value) => parse_primitive_value(value, self.options.numeric),
+                Expr::TypedString {
+                    data_type: DataType::Date,
+                    value,
+                } => temporal::parse_date(&value).map(CalcResult::Date),
+                Expr::TypedString {
+                    data_type: DataType::Timestamp,
+                    value,
+                } => temporal::parse_timestamp(&value, &self.time_zone.get())
+                    .map(CalcResult::Timestamp),
+                Expr::Identifier(ident)
+                    if ident.quote_style.is_none()
+                        && ["CURRENT_DATE", "CURRENT_TIMESTAMP"]
+                            .contains(&ident.value.to_uppercase().as_str()) =>
+                {
+                    Ok(self.clock_value(&ident.value.to_uppercase()))
+                }
+                Expr::Identifier(ident) => self.column(&[ident]),
+                Expr::CompoundIdentifier(idents) => self.column(&idents),
+                Expr::Nested(expr) => self.calc(*expr),
+                Expr::Subquery(query) => {
+                    self.cover(Category::Clause, "SUBQUERY");
+                    let mut values = self.subquery_values(&query)?.into_iter();
+                    match (values.next(), values.next()) {
+                        (Some(value), None) => Ok(value),
+                        (None, _) => Ok(CalcResult::Null),
+                        (Some(_), Some(_)) => Err(CalcError::InvalidRequestFormat(format!(
+                            "Subquery ({}) gives more than one row",
+                            query
+                        ))),
+                    }
+                }
+                Expr::Exists(query) => {
+                    self.cover(Category::Operator, "EXISTS");
+                    check_query(&query)?;
+                    Ok(CalcResult::Bool(!self.query(&query)?.rows.is_empty()))
+                }
+                Expr::InSubquery {
+                    expr,
+                    subquery,
+                    negated,
+                } => {
+                    self.cover(Category::Operator, "IN");
+                    let value = self.calc(*expr)?;
+                    let items = self.subquery_values(&subquery)?;
+                    self.in_items(value, &items, negated)
+                }
+                Expr::UnaryOp { op, expr } => {
+                    self.cover(Category::Operator, &op.to_string());
+                    let operand = self.calc(*expr)?;
+                    if let Some(eval) = self.operators.unary(&op, &operand) {
+                        return eval(&operand);
+                    }
+                    match (&op, operand) {
+                        (UnaryOperator::Not, CalcResult::Bool(value)) => {
+                            Ok(CalcResult::Bool(!value))
+                        }
+                        (UnaryOperator::Minus, CalcResult::Num(num)) => Ok(CalcResult::Num(-num)),
+                        (UnaryOperator::Plus, CalcResult::Num(num)) => Ok(CalcResult::Num(num)),
+                        (UnaryOperator::Minus, CalcResult::Int(int)) => overflowed(
+                            self.options.overflow,
+                            int.checked_neg(),
+                            int.wrapping_neg(),
+                            int.saturating_neg(),
+                            || format!("Integer -({}) is out of range", int),
+                        ),
+                        (UnaryOperator::Plus, CalcResult::Int(int)) => Ok(CalcResult::Int(int)),
+                        (UnaryOperator::Minus, CalcResult::Decimal(decimal)) => {
+                            Ok(CalcResult::Decimal(-decimal))
+                        }
+                        (UnaryOperator::Plus, CalcResult::Decimal(decimal)) => {
+                            Ok(CalcResult::Decimal(decimal))
+                        }
+                        (UnaryOperator::Minus, CalcResult::Interval(interval)) => {
+                            Ok(CalcResult::Interval(interval.negate()))
+                        }
+                        (_, CalcResult::Null) => Ok(CalcResult::Null),
+                        (op, operand) => Err(CalcError::UnsupportedOperator(format!(
+                            "Unary {} is not supported for {}",
+                            op,
+                            ValueType::of(&operand)
+                        ))),
+                    }
+                }
+                Expr::Case {
+                    operand,
+                    conditions,
+                    results,
+                    else_result,
+                } => {
+                    self.cover(Category::Clause, "CASE");
+                    let operand = operand.map(|operand| self.calc(*operand)).transpose()?;
+                    for (condition, result) in conditions.into_iter().zip(results) {
+                        let printed = condition.to_string();
+                        let value = self.calc(condition)?;
+                        let matched = match &operand {
+                            Some(CalcResult::Null) => false,
+                            Some(operand) => {
+                                value != CalcResult::Null
+                                    && self.partial_compare(operand, &value)?
+                                        == Some(Ordering::Equal)
+                            }
+                            None => match value {
+                                CalcResult::Bool(matched) => matched,
+                                CalcResult::Null => false,
+                                _ => {
+                                    return Err(CalcError::InvalidType(format!(
+                                        "CASE condition {} is not Boolean",
+                                        printed
+                                    )))
+                                }
+                            },
+                        };
+                        if matched {
+                            return self.calc(result);
+                        }
+                    }
+                    match else_result {
+                        Some(else_result) => self.calc(*else_result),
+                        None => Ok(CalcResult::Null),
+                    }
+                }
+                Expr::IsNull(expr) => {
+                    self.cover(Category::Operator, "IS NULL");
+                    Ok(CalcResult::Bool(self.calc(*expr)? == CalcResult::Null))
+                }
+                Expr::IsNotNull(expr) => {
+                    self.cover(Category::Operator, "IS NOT NULL");
+                    Ok(CalcResult::Bool(self.calc(*expr)? != CalcResult::Null))
+                }
+                Expr::Between {
+                    expr,
+                    negated,
+                    low,
+                    high,
+                } => {
+                    self.cover(Category::Operator, "BETWEEN");
+                    let values =
+                        collect_errors(vec![self.calc(*expr), self.calc(*low), self.calc(*high)])?;
+                    if values.contains(&CalcResult::Null) {
+                        return Ok(CalcResult::Null);
+                    }
+                    let above = self
+                        .partial_compare(&values[0], &values[1])?
+                        .map(|ordering| ordering != Ordering::Less);
+                    let below = self
+                        .partial_compare(&values[0], &values[2])?
+                        .map(|ordering| ordering != Ordering::Greater);
+                    // unknown (NaN) unless the other bound rules it out
+                    Ok(match (above, below) {
+                        (Some(false), _) | (_, Some(false)) => CalcResult::Bool(negated),
+                        (Some(true), Some(true)) => CalcResult::Bool(!negated),
+                        _ => CalcResult::Null,
+                    })
+                }
+                Expr::InList {
+                    expr,
+                    list,
+                    negated,
+                } => {
+                    self.cover(Category::Operator, "IN");
+                    let value = self.calc(*expr)?;
+                    let items =
+                        collect_errors(list.into_iter().map(|item| self.calc(item)).collect())?;
+                    self.in_items(value, &items, negated)
+                }
+                Expr::Cast { expr, data_type } => {
+                    if let DataType::Int(Some(width)) = data_type {
+                        self.warn(Warning::IgnoredPrecision(format!(
+                            "display width of INT({}) is ignored",
+                            width
+                        )));
+                    }
+                    self.cover(Category::Clause, "CAST");
+                    cast::convert(
+                        self.calc(*expr)?,
+                        &data_type,
+                        &self.time_zone.get(),
+                        self.options.overflow,
+                    )
+                }
+                Expr::TryCast { expr, data_type } => {
+                    self.cover(Category::Clause, "TRY_CAST");
+                    cast::try_convert(
+                        self.calc(*expr)?,
+                        &data_type,
+                        &self.time_zone.get(),
+                        self.options.overflow,
+                    )
+                }
+                Expr::Substring {
+                    expr,
+                    substring_from,
+                    substring_for,
+                } => {
+                    self.cover(Category::Function, "SUBSTRING");
+                    let value = self.calc(*expr)?;
+                    let from = substring_from.map(|from| self.calc(*from)).transpose()?;
+                    let len = substring_for.map(|len| self.calc(*len)).transpose()?;
+                    self.substring(value, from, len)
+                }
+                Expr::Extract { field, expr } => {
+                    self.cover(Category::Function, "EXTRACT");
+                    let value = self.calc(*expr)?;
+                    match temporal::as_timestamp(&value) {
+                        Some(timestamp) => {
+                            Ok(CalcResult::Num(temporal::extract(&field, &timestamp)))
+                        }
+                        None if value == CalcResult::Null => Ok(CalcResult::Null),
+                        None => Err(CalcError::InvalidType(format!(
+                            "EXTRACT supports only Date and Timestamp, got {}",
+                            ValueType::of(&value)
+                        ))),
+                    }
+                }
+                Expr::Trim { expr, trim_where } => {
+                    self.cover(Category::Function, "TRIM");
+                    let value = self.calc(*expr)?;
+                    let (side, chars) = match trim_where {
+                        Some((side, chars)) => (side, self.calc(*chars)?),
+                        None => (TrimWhereField::Both, CalcResult::Str(String::from(" "))),
+                    };
+                    match (value, chars) {
+                        (CalcResult::Str(str), CalcResult::Str(chars)) => {
+                            let strip = |ch: char| chars.contains(ch);
+                            let trimmed = match side {
+                                TrimWhereField::Both => str.trim_matches(strip),
+                                TrimWhereField::Leading => str.trim_start_matches(strip),
+                                TrimWhereField::Trailing => str.trim_end_matches(strip),
+                            };
+                            Ok(CalcResult::Str(trimmed.to_string()))
+                        }
+                        (CalcResult::Null, _) | (_, CalcResult::Null) => Ok(CalcResult::Null),
+                        _ => Err(CalcError::InvalidType(String::from(
+                            "TRIM supports only String",
+                        ))),
+                    }
+                }
+                _ => Err(CalcError::Unexpected),
+            }
+        }
+
+        /// `SUBSTRING(value FROM from FOR len)`, counting from 1. Positions
+        /// before the first character still count towards `len`, as in
+        /// PostgreSQL: `SUBSTRING('abc' FROM 0 FOR 2)` is `'a'`.
+        fn substring(
+            &self,
+            value: CalcResult,
+            from: Option<CalcResult>,
+            len: Option<CalcResult>,
+        ) -> Result<CalcResult, CalcError> {
+            let str = match value {
+                CalcResult::Str(str) => str,
+                CalcResult::Null => return Ok(CalcResult::Null),
+                _ => {
+                    return Err(CalcError::InvalidType(String::from(
+                        "SUBSTRING supports only String",
+                    )))
+                }
+            };
+            let float = |value: Option<CalcResult>| match value {
+                Some(CalcResult::Int(_) | CalcResult::Decimal(_)) => {
+                    value.and_then(|value| value.as_f64()).map(CalcResult::Num)
+                }
+                value => value,
+            };
+            let from = match float(from) {
+                None => 1.0,
+                Some(CalcResult::Num(from)) => from.trunc(),
+                Some(CalcResult::Null) => return Ok(CalcResult::Null),
+                Some(_) => {
+                    return Err(CalcError::InvalidType(String::from(
+                        "SUBSTRING position must be a Number",
+                    )))
+                }
+            };
+            let len = match float(len) {
+                None => None,
+                Some(CalcResult::Num(len)) if len < 0.0 => {
+                    return Err(CalcError::InvalidType(format!(
+                        "SUBSTRING length must not be negative, got {}",
+                        format_num(len)
+                    )))
+                }
+                Some(CalcResult::Num(len)) => Some(len.trunc()),
+                Some(CalcResult::Null) => return Ok(CalcResult::Null),
+                Some(_) => {
+                    return Err(CalcError::InvalidType(String::from(
+                        "SUBSTRING length must be a Number",
+                    )))
+                }
+            };
+
+            let len = match from < 1.0 {
+                true => len.map(|len| (len + from - 1.0).max(0.0)),
+                false => len,
+            };
+            Ok(CalcResult::Str(text::substring(
+                &str,
+                from.max(1.0) as usize,
+                len.map(|len| len as usize),
+                self.options.string_semantics,
+            )))
+        }
+
+        /// Orders two values for sorting, as [`Engine::partial_compare`]
+        /// does, with NaN after every other number.
+        fn compare(&self, left: &CalcResult, right: &CalcResult) -> Result<Ordering, CalcError> {
+            Ok(match self.partial_compare(left, right)? {
+                Some(ordering) => ordering,
+                None => is_nan(left).cmp(&is_nan(right)),
+            })
+        }
+
+        /// Orders two numbers, two strings by the configured collation, two
+        /// booleans (false first), two dates or timestamps (a date being
+        /// midnight of its day), or two intervals by their
+        /// [`Interval::span`]. NaN
+        /// is not ordered against any number, itself included, so it gives
+        /// `None`, which predicates take as unknown.
+        fn partial_compare(
+            &self,
+            left: &CalcResult,
+            right: &CalcResult,
+        ) -> Result<Option<Ordering>, CalcError> {
+            match (left, right) {
+                (CalcResult::Int(l), CalcResult::Int(r)) => Ok(Some(l.cmp(r))),
+                (CalcResult::Int(_) | CalcResult::Decimal(_), CalcResult::Decimal(_))
+                | (CalcResult::Decimal(_), CalcResult::Int(_)) => {
+                    Ok(Some(decimal::of(left).cmp(&decimal::of(right))))
+                }
+                (
+                    CalcResult::Num(_) | CalcResult::Int(_) | CalcResult::Decimal(_),
+                    CalcResult::Num(_) | CalcResult::Int(_) | CalcResult::Decimal(_),
+                ) => {
+                    let (l, r) = (left.as_f64(), right.as_f64());
+                    Ok(l.partial_cmp(&r))
+                }
+                (CalcResult::Str(l), CalcResult::Str(r)) => {
+                    Ok(Some(text::compare(l, r, self.options.collation)))
+                }
+                (CalcResult::Bool(l), CalcResult::Bool(r)) => Ok(Some(l.cmp(r))),
+                (CalcResult::Interval(l), CalcResult::Interval(r)) => {
+                    Ok(Some(l.span().cmp(&r.span())))
+                }
+                _ => match (temporal::as_timestamp(left), temporal::as_timestamp(right)) {
+                    (Some(l), Some(r)) => Ok(Some(l.cmp(&r))),
+                    _ => Err(CalcError::InvalidType(format!(
+                        "Cannot compare {} with {}",
+                        ValueType::of(left),
+                        ValueType::of(right)
+                    ))),
+                },
+            }
+        }
+
+        /// Whether two arrays or rows hold equal items in the same order;
+        /// `None` when that depends on a NULL or NaN item.
+        fn equal_items(
+            &self,
+            left: &[CalcResult],
+            right: &[CalcResult],
+        ) -> Result<Option<bool>, CalcError> {
+            if left.len() != right.len() {
+                return Ok(Some(false));
+            }
+            let mut unknown = false;
+            for (l, r) in left.iter().zip(right) {
+                let equal = match (l, r) {
+                    (CalcResult::Null, _) | (_, CalcResult::Null) => None,
+                    (CalcResult::Array(l), CalcResult::Array(r))
+                    | (CalcResult::Row(l), CalcResult::Row(r)) => self.equal_items(l, r)?,
+                    _ => self
+                        .partial_compare(l, r)?
+                        .map(|ordering| ordering == Ordering::Equal),
+                };
+                match equal {
+                    Some(false) => return Ok(Some(false)),
+                    Some(true) => (),
+                    None => unknown = true,
+                }
+            }
+            Ok(if unknown { None } else { Some(true) })
+        }
+
+        /// Evaluates an `ASSERT` condition. A false condition fails with the
+        /// values of both sides of a comparison, prefixed by the optional
+        /// `AS '<message>'`. Each side is evaluated once, so the values
+        /// reported are the ones compared.
+        fn assert(&self, condition: Expr, message: Option<Expr>) -> Result<CalcResult, CalcError> {
+            let mut operands = None;
+            let result = match &condition {
+                Expr::BinaryOp { left, op, right } => {
+                    self.cover(Category::Operator, &op.to_string());
+                    let values: Vec<_> = [left, right]
+                        .into_iter()
+                        .map(|side| self.calc(*side.clone()))
+                        .collect();
+                    operands = Some(values.clone());
+                    self.apply_binary_operation(op.clone(), values)
+                }
+                condition => self.calc(condition.clone()),
+            };
+            match result? {
+                CalcResult::Bool(true) => return Ok(CalcResult::Bool(true)),
+                CalcResult::Bool(false) | CalcResult::Null => (),
+                _ => {
+                    return Err(CalcError::InvalidType(format!(
+                        "ASSERT condition {} is not Boolean",
+                        condition
+                    )))
+                }
+            }
+
+            let mut detail = condition.to_string();
+            if let Some([Ok(left), Ok(right)]) = operands.as_deref() {
+                detail = format!(
+                    "{} (left: {}, right: {})",
+                    detail,
+                    describe(left),
+                    describe(right)
+                );
+            }
+
+            let message = match message.map(|message| self.calc(message)).transpose()? {
+                Some(CalcResult::Str(message)) => message,
+                Some(message) => describe(&message),
+                None => String::from("ASSERT failed"),
+            };
+            Err(CalcError::AssertionFailed(format!(
+                "{}: {}",
+                message, detail
+            )))
+        }
+
+        fn warn(&self, warning: Warning) {
+            self.warnings.borrow_mut().push(warning);
+        }
+
+        /// Evaluates `query`, returning the result together with the
+        /// warnings raised along the way. A script of several statements
+        /// gives the result of the last, or the error of the first that
+        /// fails, with the warnings and timing of every statement run.
+        pub fn eval(&self, query: &str) -> Result<Evaluation, CalcError> {
+            if self.split(query).len() < 2 {
+                return self.eval_statement(query);
+            }
+            let mut results = self.eval_script(query);
+            let warnings: Vec<_> = results
+                .iter()
+                .flatten()
+                .flat_map(|evaluation| evaluation.warnings.iter().cloned())
+                .collect();
+            let timing = results
+                .iter()
+                .flatten()
+                .fold(Timing::default(), |total, evaluation| Timing {
+                    parse: total.parse + evaluation.timing.parse,
+                    eval: total.eval + evaluation.timing.eval,
+                });
+            let mut last = results.pop().unwrap_or(Err(CalcError::Unexpected));
+            if let Ok(evaluation) = &mut last {
+                evaluation.warnings = warnings;
+                evaluation.timing = timing;
+            }
+            last
+        }
+
+        /// Evaluates each statement of `script` in order, giving a result
+        /// per statement run. The statements after one that fails are not
+        /// run.
+        pub fn eval_script(&self, script: &str) -> Vec<Result<Evaluation, CalcError>> {
+            let mut results = Vec::new();
+            for (_, statement) in self.split(script) {
+                let res = self.eval_statement(statement);
+                let failed = res.is_err();
+                results.push(res);
+                if failed {
+                    break;
+                }
+            }
+            results
+        }
+
+        /// The statements of `script`, each with the line it starts on.
+        pub fn split<'a>(&self, script: &'a str) -> Vec<(usize, &'a str)> {
+            dialect::split_statements(script, self.options.dialect.line_comment_prefixes())
+        }
+
+        /// The statements of `script` a semicolon ends, and the text after
+        /// the last of them.
+        pub fn split_terminated<'a>(&self, script: &'a str) -> (Vec<(usize, &'a str)>, &'a str) {
+            dialect::split_terminated(script, self.options.dialect.line_comment_prefixes())
+        }
+
+        fn eval_statement(&self, query: &str) -> Result<Evaluation, CalcError> {
+            let res = self.with_stack(query, || self.evaluate(query));
+            self.hooks.observe(query, &res);
+            res
+        }
+
+        /// Runs `f`, which parses or evaluates `query`, with enough stack for
+        /// its expressions to nest as deep as `max_depth` allows. Each level
+        /// takes a character at least, so short statements need no more
+        /// than they have.
+        fn with_stack<T>(&self, query: &str, f: impl FnOnce() -> T) -> T {
+            let levels = self.options.limits.max_depth.min(query.len()) + 1;
+            stacker::maybe_grow(levels * STACK_PER_LEVEL, levels * STACK_PER_LEVEL, f)
+        }
+
+        fn evaluate(&self, query: &str) -> Result<Evaluation, CalcError> {
+            self.warnings.borrow_mut().clear();
+            self.depth.set(0);
+            self.nodes.set(0);
+            self.statement_time.set(None);
+            let start = Instant::now();
+            if let Some(definition) = self.parse_definition(query)? {
+                self.check_definition(&definition)?;
+                let parsed = Instant::now();
+                let name = self.define(definition)?;
+                let result = CalcResult::Str(format!("CREATE FUNCTION {}", name));
+                return Ok(self.evaluation(result, vec![None], start, parsed));
+            }
+            if let Some(setting) = self.parse_time_zone(query)? {
+                let parsed = Instant::now();
+                self.set_time_zone(setting)?;
+                let result = CalcResult::Str(String::from("SET TIME ZONE"));
+                return Ok(self.evaluation(result, vec![None], start, parsed));
+            }
+            if let Some(mut assignment) = self.parse_assignment(query)? {
+                assignment.value = self.rewrite_expr(assignment.value)?;
+                let parsed = Instant::now();
+                let name = assignment.name.to_string();
+                self.assign(assignment)?;
+                let result = CalcResult::Str(format!("SET {}", name));
+                return Ok(self.evaluation(result, vec![None], start, parsed));
+            }
+
+            if let Some(attachment) = self.parse_attachment(query)? {
+                let parsed = Instant::now();
+                let schema = attachment.schema.to_string();
+                self.attach(attachment)?;
+                let result = CalcResult::Str(format!("ATTACH {}", schema));
+                return Ok(self.evaluation(result, vec![None], start, parsed));
+            }
+
+            let ast = self.hooks.rewrite(self.parse(query)?)?;
+            let parsed = Instant::now();
+            let mut headers = None;
+            let (result, columns) = match &ast[..] {
+                [Statement::ShowVariable { variable }] if is_show_functions(variable) => (
+                    CalcResult::Str(self.all_function_names().join(", ")),
+                    vec![None],
+                ),
+                [Statement::ShowVariable { variable }] if is_show_time_zone(variable) => (
+                    CalcResult::Str(self.time_zone.get().to_string()),
+                    vec![None],
+                ),
+                [Statement::Assert { condition, message }] => {
+                    self.cover(Category::Clause, "ASSERT");
+                    let message = message.clone().map(precedence::fix);
+                    (
+                        self.assert(precedence::fix(condition.clone()), message)?,
+                        vec![None],
+                    )
+                }
+                [statement @ (Statement::CreateTable { .. }
+                | Statement::Insert { .. }
+                | Statement::Drop { .. })] => {
+                    check_statement(statement)?;
+                    (self.execute(statement)?, vec![None])
+                }
+                [explain @ Statement::Explain { analyze, .. }] => {
+                    let query = explained_query(explain)?;
+                    self.cover(Category::Clause, "EXPLAIN");
+                    let mut plan = plan::query(query);
+                    if *analyze {
+                        self.cover(Category::Clause, "EXPLAIN ANALYZE");
+                        plan.annotate(&self.profiled(query)?);
+                    }
+                    let lines = plan.lines();
+                    (
+                        CalcResult::Table(
+                            lines
+                                .into_iter()
+                                .map(|line| vec![CalcResult::Str(line)])
+                                .collect(),
+                        ),
+                        vec![Some(String::from("QUERY PLAN"))],
+                    )
+                }
+                _ => {
+                    let query = query_statement(&ast)?;
+                    check_query(query)?;
+                    let (result, columns, names) = self.query_result(query)?;
+                    headers = Some(names);
+                    (result, columns)
+                }
+            };
+            let mut evaluation = self.evaluation(result, columns, start, parsed);
+            if let Some(headers) = headers {
+                evaluation.headers = headers;
+            }
+            Ok(evaluation)
+        }
+
+        /// The evaluation of a statement whose parsing began at `start` and
+        /// ended at `parsed`, with the warnings raised since.
+        fn evaluation(
+            &self,
+            result: CalcResult,
+            columns: Vec<Option<String>>,
+            start: Instant,
+            parsed: Instant,
+        ) -> Evaluation {
+            Evaluation {
+                result,
+                headers: columns
+                    .iter()
+                    .map(|column| column.clone().unwrap_or_else(|| String::from("result")))
+                    .collect(),
+                columns,
+                warnings: self.warnings.take(),
+                timing: Timing {
+                    parse: parsed.duration_since(start),
+                    eval: parsed.elapsed(),
+                },
+            }
+        }
+
+        /// Evaluates a query. One without `FROM`, `WHERE`, `GROUP BY`,
+        /// `HAVING` or `VALUES` gives its value, or a row of values, with
+        /// the aliases of its items; any other gives a table with the names
+        /// of its columns. Either comes with the headers of its columns.
+        fn query_result(&self, query: &Query) -> Result<QueryResult, CalcError> {
+            let relation = self.query(query)?;
+            let headers: Vec<_> = relation
+                .fields
+                .iter()
+                .map(|field| field.name.clone())
+                .collect();
+            match &query.body {
+                SetExpr::Select(select)
+                    if select.from.is_empty()
+                        && select.selection.is_none()
+                        && select.group_by.is_empty()
+                        && select.having.is_none() =>
+                {
+                    let columns = select
+                        .projection
+                        .iter()
+                        .map(|item| match item {
+                            SelectItem::ExprWithAlias { alias, .. } => Some(alias.value.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                    let mut values = relation.rows.into_iter().next().unwrap_or_default();
+                    match values.len() {
+                        1 => Ok((values.remove(0), columns, headers)),
+                        _ => Ok((CalcResult::Row(values), columns, headers)),
+                    }
+                }
+                _ => Ok((
+                    CalcResult::Table(relation.rows),
+                    headers.iter().cloned().map(Some).collect(),
+                    headers,
+                )),
+            }
+        }
+
+        /// Evaluates a query (checked by `check_query`) into the relation it
+        /// produces. Its `WITH` queries are evaluated first, in order, each
+        /// readable by name from the ones after it and from the body (and,
+        /// with `RECURSIVE`, from itself).
+        /// Runs `query` measuring every relation it evaluates and every
+        /// function call it makes.
+        fn profiled(&self, query: &Query) -> Result<Profile, CalcError> {
+            let outer = self.profile.replace(Some(Profile::default()));
+            let res = self.query(query);
+            let profile = self.profile.replace(outer);
+            res.map(|_| profile.unwrap_or_default())
+        }
+
+        /// Evaluates a relation, recording its rows and the time it took
+        /// under `EXPLAIN ANALYZE`.
+        fn measure(
+            &self,
+            site: impl FnOnce() -> String,
+            evaluate: impl FnOnce() -> Result<Relation, CalcError>,
+        ) -> Result<Relation, CalcError> {
+            if self.profile.borrow().is_none() {
+                return evaluate();
+            }
+            let start = Instant::now();
+            let res = evaluate();
+            let time = self.options.explain_timing.then(|| start.elapsed());
+            if let (Ok(relation), Some(profile)) = (&res, self.profile.borrow_mut().as_mut()) {
+                profile.relation(site(), relation.rows.len(), time);
+            }
+            res
+        }
+
+        /// Records `count` calls of a function under `EXPLAIN ANALYZE`.
+        fn count_calls(&self, site: impl FnOnce() -> String, count: usize) {
+            if let Some(profile) = self.profile.borrow_mut().as_mut() {
+                profile.calls(site(), count);
+            }
+        }
+
+        fn query(&self, query: &Query) -> Result<Relation, CalcError> {
+            match &query.with {
+                Some(with) => {
+                    self.measure(|| query.to_string(), || self.with_query(with, &query.body))
+                }
+                None => self.set_expr(&query.body),
+            }
+        }
+
+        /// Evaluates `body` with the common table expressions of `with`
+        /// bound to their rows.
+        fn with_query(&self, with: &With, body: &SetExpr) -> Result<Relation, CalcError> {
+            self.cover(Category::Clause, "WITH");
+            let dialect = self.options.dialect.as_ref();
+            let mut bound = 0;
+            let mut evaluate = || {
+                for cte in &with.cte_tables {
+                    let relation = match with.recursive {
+                        true => self.recursive_cte(cte)?,
+                        false => self.query(&cte.query)?.alias(&cte.alias, dialect)?,
+                    };
+                    let key = dialect.normalize_identifier(&cte.alias.name);
+                    self.catalog.borrow_mut().bind(key, relation);
+                    bound += 1;
+                }
+                self.set_expr(body)
+            };
+            let res = evaluate();
+            self.catalog.borrow_mut().unbind(bound);
+            res
+        }
+
+        /// Evaluates a `WITH RECURSIVE` query. One of the form `<anchor>
+        /// UNION [ALL] <step>` starts with the rows of the anchor, then runs
+        /// the step with its name bound to the rows the last run added,
+        /// until a run adds none. Without `ALL`, rows already there are not
+        /// added again. Any other query is evaluated as without `RECURSIVE`.
+        fn recursive_cte(&self, cte: &Cte) -> Result<Relation, CalcError> {
+            let dialect = self.options.dialect.as_ref();
+            let (all, anchor, step) = match &cte.query.body {
+                SetExpr::SetOperation {
+                    op: SetOperator::Union,
+                    all,
+                    left,
+                    right,
+                } if cte.query.with.is_none() => (*all, left, right),
+                _ => return self.query(&cte.query)?.alias(&cte.alias, dialect),
+            };
+            self.measure(
+                || cte.query.body.to_string(),
+                || self.recursion(cte, all, anchor, step),
+            )
+        }
+
+        /// Runs the `<step>` of a recursive query from the rows of its
+        /// `<anchor>` until it adds no rows.
+        fn recursion(
+            &self,
+            cte: &Cte,
+            all: bool,
+            anchor: &SetExpr,
+            step: &SetExpr,
+        ) -> Result<Relation, CalcError> {
+            let dialect = self.options.dialect.as_ref();
+            self.cover(Category::Clause, "WITH RECURSIVE");
+            let key = dialect.normalize_identifier(&cte.alias.name);
+            let max_recursion = self.options.limits.max_recursion;
+
+            let mut result = self.set_expr(anchor)?.alias(&cte.alias, dialect)?;
+            if !all {
+                result = result.distinct();
+            }
+            let mut seen: HashSet<_> = match all {
+                true => HashSet::new(),
+                false => result.rows.iter().map(|row| Key(row.clone())).collect(),
+            };
+            let mut working = result.clone();
+            let mut runs = 0;
+            while !working.rows.is_empty() {
+                runs += 1;
+                if runs > max_recursion {
+                    return Err(CalcError::ResourceLimit(format!(
+                        "recursive query {} did not finish within {} iterations",
+                        cte.alias.name, max_recursion
+                    )));
+                }
+                self.catalog.borrow_mut().bind(key.clone(), working);
+                let rows = self.set_expr(step);
+                self.catalog.borrow_mut().unbind(1);
+                let empty = Relation {
+                    fields: result.fields.clone(),
+                    rows: Vec::new(),
+                };
+                let mut rows = query::set_operation(&SetOperator::Union, all, empty, rows?)?.rows;
+                if !all {
+                    rows.retain(|row| seen.insert(Key(row.clone())));
+                }
+                result.rows.extend(rows.iter().cloned());
+                working = Relation {
+                    fields: result.fields.clone(),
+                    rows,
+                };
+            }
+            Ok(result)
+        }
+
+        fn set_expr(&self, body: &SetExpr) -> Result<Relation, CalcError> {
+            self.measure(|| body.to_string(), || self.set_expr_rows(body))
+        }
+
+        fn set_expr_rows(&self, body: &SetExpr) -> Result<Relation, CalcError> {
+            match body {
+                SetExpr::Select(select) => self.select(select),
+                SetExpr::Values(values) => {
+                    self.cover(Category::Clause, "VALUES");
+                    let rows = values
+                        .0
+                        .iter()
+                        .map(|row| {
+                            row.iter()
+                                .map(|expr| self.calc(precedence::fix(expr.clone())))
+                                .collect()
+                        })
+                        .collect::<Result<_, _>>()?;
+                    Relation::values(rows)
+                }
+                SetExpr::Query(query) => self.query(query),
+                SetExpr::SetOperation {
+                    op,
+                    all,
+                    left,
+                    right,
+                } => {
+                    self.cover(Category::Clause, &op.to_string());
+                    let left = self.set_expr(left)?;
+                    let right = self.set_expr(right)?;
+                    query::set_operation(op, *all, left, right)
+                }
+                _ => Err(CalcError::InvalidRequestFormat(String::from(
+                    "only SELECT is supported",
+                ))),
+            }
+        }
+
+        fn select(&self, select: &Select) -> Result<Relation, CalcError> {
+            // The columns of a subquery are its own, not those of the group
+            // or the window an outer query is evaluating.
+            let outer = self.group.replace(None);
+            let outer_window = self.window.replace(None);
+            let res = self.select_rows(select);
+            self.group.replace(outer);
+            self.window.replace(outer_window);
+            res
+        }
+
+        fn select_rows(&self, select: &Select) -> Result<Relation, CalcError> {
+            self.cover(Category::Clause, "SELECT");
+            let source = match &select.from[..] {
+                [] => Relation {
+                    fields: Vec::new(),
+                    rows: vec![Vec::new()],
+                },
+                tables => {
+                    self.cover(Category::Clause, "FROM");
+                    // With joins a condition may need the columns of another
+                    // table, or hold of the NULLs an outer join pads rows
+                    // with, so only a lone table gets the filters.
+                    let joined = tables.len() > 1 || !tables[0].joins.is_empty();
+                    let filters = match joined {
+                        true => Vec::new(),
+                        false => select.selection.iter().flat_map(conjuncts).collect(),
+                    };
+                    let pushdown = Pushdown::new(select, filters, self.options.dialect.as_ref());
+                    let mut source: Option<Relation> = None;
+                    for table in tables {
+                        let mut relation = self.table_factor(&table.relation, &pushdown)?;
+                        for join in &table.joins {
+                            relation = self.measure(
+                                || join.to_string(),
+                                || {
+                                    let right = self.table_factor(&join.relation, &pushdown)?;
+                                    self.join(relation, right, &join.join_operator)
+                                },
+                            )?;
+                        }
+                        source = Some(match source {
+                            Some(left) => self.join(left, relation, &JoinOperator::CrossJoin)?,
+                            None => relation,
+                        });
+                    }
+                    source.unwrap_or_default()
+                }
+            };
+
+            // Each item is either the index of a column `*` expands to, or
+            // an expression.
+            let dialect = self.options.dialect.as_ref();
+            let mut fields = Vec::new();
+            let mut items = Vec::new();
+            for item in &select.projection {
+                match item {
+                    SelectItem::UnnamedExpr(expr) => {
+                        fields.push(Field::of(expr, dialect));
+                        items.push(Err(precedence::fix(expr.clone())));
+                    }
+                    SelectItem::ExprWithAlias { expr, alias } => {
+                        fields.push(Field::named(alias, dialect));
+                        items.push(Err(precedence::fix(expr.clone())));
+                    }
+                    SelectItem::Wildcard if select.from.is_empty() => {
+                        return Err(CalcError::InvalidRequestFormat(String::from(
+                            "SELECT * with no tables specified is not valid",
+                        )))
+                    }
+                    SelectItem::Wildcard => {
+                        fields.extend(source.fields.iter().cloned());
+                        items.extend((0..source.fields.len()).map(Ok));
+                    }
+                    SelectItem::QualifiedWildcard(table) => {
+                        for i in query::qualified_wildcard(&source.fields, table, dialect)? {
+                            fields.push(source.fields[i].clone());
+                            items.push(Ok(i));
+                        }
+                    }
+                }
+            }
+
+            let selection = select.selection.clone().map(precedence::fix);
+            if selection.is_some() {
+                self.cover(Category::Clause, "WHERE");
+            }
+            let source_fields: Rc<[Field]> = source.fields.into();
+            let mut matching = Vec::with_capacity(source.rows.len());
+            for row in source.rows {
+                let scope = Scope {
+                    fields: source_fields.clone(),
+                    row: row.into(),
+                };
+                let row = scope.row.clone();
+                if self.in_scope(scope, || self.condition(selection.as_ref(), "WHERE"))? {
+                    matching.push(row);
+                }
+            }
+
+            let aggregates = items
+                .iter()
+                .any(|item| matches!(item, Err(expr) if aggregate::contains_aggregate(expr)));
+            let mut calls = Vec::new();
+            for expr in items.iter().filter_map(|item| item.as_ref().err()) {
+                window::calls(expr, &mut calls);
+            }
+            let rows = if aggregates || !select.group_by.is_empty() || select.having.is_some() {
+                if !calls.is_empty() {
+                    return Err(CalcError::InvalidRequestFormat(String::from(
+                        "not yet supported: window functions with GROUP BY or aggregates",
+                    )));
+                }
+                self.group_rows(select, &items, source_fields, matching)?
+            } else {
+                let windows = self.window_values(&calls, &source_fields, &matching)?;
+                let calls: Rc<[Expr]> = calls.into();
+                let mut rows = Vec::with_capacity(matching.len());
+                for (row, values) in matching.into_iter().zip(windows) {
+                    let scope = Scope {
+                        fields: source_fields.clone(),
+                        row: row.clone(),
+                    };
+                    let window = Window {
+                        calls: calls.clone(),
+                        values,
+                    };
+                    let values = self.in_scope(scope, || {
+                        self.in_window(window, || {
+                            items
+                                .iter()
+                                .map(|item| match item {
+                                    Ok(i) => Ok(row[*i].clone()),
+                                    Err(expr) => self.calc(expr.clone()),
+                                })
+                                .collect::<Result<Vec<_>, _>>()
+                        })
+                    })?;
+                    rows.push(values);
+                }
+                rows
+            };
+
+            let relation = Relation { fields, rows };
+            if select.distinct {
+                self.cover(Category::Clause, "DISTINCT");
+                return Ok(relation.distinct());
+            }
+            Ok(relation)
+        }
+
+        /// Joins two relations row by row: each pair of rows passing the
+        /// `ON` condition, then for an outer join the rows of its outer side
+        /// that pass it with none, padded with NULLs.
+        fn join(
+            &self,
+            left: Relation,
+            right: Relation,
+            operator: &JoinOperator,
+        ) -> Result<Relation, CalcError> {
+            let (name, constraint) = match operator {
+                JoinOperator::Inner(constraint) => ("INNER JOIN", Some(constraint)),
+                JoinOperator::LeftOuter(constraint) => ("LEFT JOIN", Some(constraint)),
+                JoinOperator::RightOuter(constraint) => ("RIGHT JOIN", Some(constraint)),
+                JoinOperator::FullOuter(constraint) => ("FULL JOIN", Some(constraint)),
+                JoinOperator::CrossJoin => ("CROSS JOIN", None),
+                JoinOperator::CrossApply | JoinOperator::OuterApply => {
+                    return Err(CalcError::InvalidRequestFormat(String::from(
+                        "not yet supported: APPLY",
+                    )))
+                }
+            };
+            let on = match constraint {
+                Some(JoinConstraint::On(expr)) => Some(precedence::fix(expr.clone())),
+                Some(JoinConstraint::Using(_)) => {
+                    return Err(CalcError::InvalidRequestFormat(String::from(
+                        "not yet supported: JOIN ... USING",
+                    )))
+                }
+                Some(JoinConstraint::Natural) => {
+                    return Err(CalcError::InvalidRequestFormat(String::from(
+                        "not yet supported: NATURAL JOIN",
+                    )))
+                }
+                Some(JoinConstraint::None) | None => None,
+            };
+            self.cover(Category::Clause, name);
+
+            let left_outer = matches!(
+                operator,
+                JoinOperator::LeftOuter(_) | JoinOperator::FullOuter(_)
+            );
+            let right_outer = matches!(
+                operator,
+                JoinOperator::RightOuter(_) | JoinOperator::FullOuter(_)
+            );
+            let fields: Rc<[Field]> = left.fields.iter().chain(&right.fields).cloned().collect();
+            let mut matched = vec![false; right.rows.len()];
+            let mut rows = Vec::new();
+            for left_row in &left.rows {
+                let mut any = false;
+                for (j, right_row) in right.rows.iter().enumerate() {
+                    let row: Row = left_row.iter().chain(right_row).cloned().collect();
+                    let scope = Scope {
+                        fields: fields.clone(),
+                        row: row.clone().into(),
+                    };
+                    if self.in_scope(scope, || self.condition(on.as_ref(), "ON"))? {
+                        any = true;
+                        matched[j] = true;
+                        rows.push(row);
+                    }
+                }
+                if left_outer && !any {
+                    let mut row = left_row.clone();
+                    row.resize(fields.len(), CalcResult::Null);
+                    rows.push(row);
+                }
+            }
+            if right_outer {
+                for (right_row, _) in right.rows.iter().zip(matched).filter(|(_, m)| !m) {
+                    let mut row = vec![CalcResult::Null; left.fields.len()];
+                    row.extend(right_row.iter().cloned());
+                    rows.push(row);
+                }
+            }
+            Ok(Relation {
+                fields: fields.to_vec(),
+                rows,
+            })
+        }
+
+        /// Whether a row passes a `WHERE`, `HAVING` or `ON` condition: only TRUE
+        /// does, NULL being unknown.
+        fn condition(&self, condition: Option<&Expr>, clause: &str) -> Result<bool, CalcError> {
+            let condition = match condition {
+                Some(condition) => condition,
+                None => return Ok(true),
+            };
+            match self.calc(condition.clone())? {
+                CalcResult::Bool(true) => Ok(true),
+                CalcResult::Bool(false) | CalcResult::Null => Ok(false),
+                _ => Err(CalcError::InvalidType(format!(
+                    "{} condition {} is not Boolean",
+                    clause, condition
+                ))),
+            }
+        }
+
+        /// The rows of a grouped `SELECT`: one per group of `rows` sharing
+        /// the values of the `GROUP BY` expressions (a single group of every
+        /// row without them) that passes `HAVING`.
+        fn group_rows(
+            &self,
+            select: &Select,
+            items: &[Result<usize, Expr>],
+            fields: Rc<[Field]>,
+            rows: Vec<Rc<[CalcResult]>>,
+        ) -> Result<Vec<Row>, CalcError> {
+            if items.iter().any(Result::is_ok) {
+                return Err(CalcError::InvalidRequestFormat(String::from(
+                    "not yet supported: * with GROUP BY or aggregates",
+                )));
+            }
+            if !select.group_by.is_empty() {
+                self.cover(Category::Clause, "GROUP BY");
+            }
+            let exprs = select
+                .group_by
+                .iter()
+                .map(|expr| self.group_expr(expr, select, &fields))
+                .collect::<Result<Rc<[_]>, _>>()?;
+
+            let mut groups: Vec<Group> = Vec::new();
+            let mut positions: HashMap<Key, usize> = HashMap::new();
+            for row in rows {
+                let scope = Scope {
+                    fields: fields.clone(),
+                    row: row.clone(),
+                };
+                let key = self.in_scope(scope, || {
+                    exprs
+                        .iter()
+                        .map(|expr| self.calc(expr.clone()))
+                        .collect::<Result<Vec<_>, _>>()
+                })?;
+                match positions.entry(Key(key.clone())) {
+                    Entry::Occupied(entry) => groups[*entry.get()].rows.push(row),
+                    Entry::Vacant(entry) => {
+                        entry.insert(groups.len());
+                        groups.push(Group {
+                            exprs: exprs.clone(),
+                            key,
+                            fields: fields.clone(),
+                            rows: vec![row],
+                        });
+                    }
+                }
+            }
+            if groups.is_empty() && exprs.is_empty() {
+                groups.push(Group {
+                    exprs,
+                    key: Vec::new(),
+                    fields,
+                    rows: Vec::new(),
+                });
+            }
+
+            let having = select.having.clone().map(precedence::fix);
+            if having.is_some() {
+                self.cover(Category::Clause, "HAVING");
+            }
+            let mut result = Vec::with_capacity(groups.len());
+            for group in groups {
+                let values = self.in_group(group, || {
+                    if !self.condition(having.as_ref(), "HAVING")? {
+                        return Ok(None);
+                    }
+                    items
+                        .iter()
+                        .filter_map(|item| item.as_ref().err())
+                        .map(|expr| self.calc(expr.clone()))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(Some)
+                })?;
+                result.extend(values);
+            }
+            Ok(result)
+        }
+
+        /// A `GROUP BY` expression. The position or the alias of a `SELECT`
+        /// item stands for the item.
+        fn group_expr(
+            &self,
+            expr: &Expr,
+            select: &Select,
+            fields: &[Field],
+        ) -> Result<Expr, CalcError> {
+            let dialect = self.options.dialect.as_ref();
+            let item = match expr {
+                Expr::Value(Value::Number(position, _)) => {
+                    let item = position
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|position| position.checked_sub(1))
+                        .and_then(|i| select.projection.get(i));
+                    match item {
+                        Some(SelectItem::UnnamedExpr(expr))
+                        | Some(SelectItem::ExprWithAlias { expr, .. }) => expr,
+                        _ => {
+                            return Err(CalcError::InvalidRequestFormat(format!(
+                                "GROUP BY position {} is not in the select list",
+                                position
+                            )))
+                        }
+                    }
+                }
+                Expr::Identifier(ident) if !fields.iter().any(|field| field.is(ident, dialect)) => {
+                    let aliased = select.projection.iter().find_map(|item| match item {
+                        SelectItem::ExprWithAlias { expr, alias }
+                            if dialect.normalize_identifier(alias)
+                                == dialect.normalize_identifier(ident) =>
+                        {
+                            Some(expr)
+                        }
+                        _ => None,
+                    });
+                    aliased.unwrap_or(expr)
+                }
+                expr => expr,
+            };
+            if aggregate::contains_aggregate(item) {
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "GROUP BY {} contains an aggregate function",
+                    expr
+                )));
+            }
+            Ok(precedence::fix(item.clone()))
+        }
+
+        /// Runs `f` with `group` as the group items are evaluated for.
+        fn in_group<T>(&self, group: Group, f: impl FnOnce() -> T) -> T {
+            let outer = self.group.replace(Some(Rc::new(group)));
+            let res = f();
+            self.group.replace(outer);
+            res
+        }
+
+        /// Computes an aggregate function over the rows of the group being
+        /// evaluated, skipping NULLs; `COUNT(*)` counts every row.
+        fn calc_aggregate(&self, name: &str, func: Function) -> Result<CalcResult, CalcError> {
+            self.cover(Category::Function, name);
+            let group = match self.group.borrow().clone() {
+                Some(group) => group,
+                None => {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Aggregate function {} is not allowed here",
+                        name
+                    )))
+                }
+            };
+            let arg = match &func.args[..] {
+                [FunctionArg::Unnamed(Expr::Wildcard)] if name == "COUNT" && !func.distinct => {
+                    return Ok(CalcResult::Int(group.rows.len() as i64));
+                }
+                [FunctionArg::Unnamed(arg)] | [FunctionArg::Named { arg, .. }] => {
+                    precedence::fix(arg.clone())
+                }
+                args => {
+                    return Err(CalcError::InvalidType(format!(
+                        "{} expects 1 argument(s), got {}",
+                        name,
+                        args.len()
+                    )))
+                }
+            };
+
+            // The argument is evaluated for each row, out of the group, so
+            // aggregates do not nest.
+            let outer = self.group.replace(None);
+            let values = group
+                .rows
+                .iter()
+                .map(|row| {
+                    let scope = Scope {
+                        fields: group.fields.clone(),
+                        row: row.clone(),
+                    };
+                    self.in_scope(scope, || self.calc(arg.clone()))
+                })
+                .collect::<Result<Vec<_>, _>>();
+            self.group.replace(outer);
+            let mut values: Vec<_> = values?
+                .into_iter()
+                .filter(|value| *value != CalcResult::Null)
+                .collect();
+            if func.distinct {
+                let mut seen = HashSet::new();
+                values.retain(|value| seen.insert(Key(vec![value.clone()])));
+            }
+
+            match name {
+                "COUNT" => Ok(CalcResult::Int(values.len() as i64)),
+                "MIN" | "MAX" => {
+                    let mut values = values.into_iter();
+                    let mut best = match values.next() {
+                        Some(value) => value,
+                        None => return Ok(CalcResult::Null),
+                    };
+                    for value in values {
+                        let better = match self.compare(&value, &best)? {
+                            Ordering::Less => name == "MIN",
+                            Ordering::Greater => name == "MAX",
+                            Ordering::Equal => false,
+                        };
+                        if better {
+                            best = value;
+                        }
+                    }
+                    Ok(best)
+                }
+                _ => {
+                    let count = values.len() as i64;
+                    let mut sum: Option<CalcResult> = None;
+                    for value in values {
+                        if value.as_f64().is_none() {
+                            return Err(CalcError::InvalidType(format!(
+                                "{} supports only Numbers, got {}",
+                                name,
+                                to_text(&value)
+                            )));
+                        }
+                        sum = Some(match sum {
+                            Some(sum) => arithmetic(
+                                BinaryOperator::Plus,
+                                &sum,
+                                &value,
+                                false,
+                                self.options.overflow,
+                            )?,
+                            None => value,
+                        });
+                    }
+                    match (name, sum) {
+                        (_, None) => Ok(CalcResult::Null),
+                        ("SUM", Some(sum)) => Ok(sum),
+                        (_, Some(sum)) => {
+                            let decimal = self.options.numeric == Numeric::Decimal;
+                            let sum = match sum {
+                                CalcResult::Int(int) if !decimal => CalcResult::Num(int as f64),
+                                sum => sum,
+                            };
+                            arithmetic(
+                                BinaryOperator::Divide,
+                                &sum,
+                                &CalcResult::Int(count),
+                                decimal,
+                                self.options.overflow,
+                            )
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Runs `f` with `window` as the window values items are evaluated
+        /// with.
+        fn in_window<T>(&self, window: Window, f: impl FnOnce() -> T) -> T {
+            let outer = self.window.replace(Some(window));
+            let res = f();
+            self.window.replace(outer);
+            res
+        }
+
+        /// The values of the window function `calls` for each of `rows`, in
+        /// the order of the calls.
+        fn window_values(
+            &self,
+            calls: &[Expr],
+            fields: &Rc<[Field]>,
+            rows: &[Rc<[CalcResult]>],
+        ) -> Result<Vec<Vec<CalcResult>>, CalcError> {
+            let mut values = vec![Vec::with_capacity(calls.len()); rows.len()];
+            for call in calls {
+                if let Expr::Function(func) = call {
+                    let column = self.window_function(func, fields, rows)?;
+                    self.count_calls(|| call.to_string(), column.len());
+                    for (values, value) in values.iter_mut().zip(column) {
+                        values.push(value);
+                    }
+                }
+            }
+            Ok(values)
+        }
+
+        /// Computes a window function for each of `rows` over the rows of
+        /// its partition, in the order `ORDER BY` gives them. An aggregate
+        /// function covers the whole partition without `ORDER BY`, and with
+        /// it the rows up to the current one and those tied with it.
+        fn window_function(
+            &self,
+            func: &Function,
+            fields: &Rc<[Field]>,
+            rows: &[Rc<[CalcResult]>],
+        ) -> Result<Vec<CalcResult>, CalcError> {
+            let spec = match &func.over {
+                Some(spec) => spec,
+                None => return Err(CalcError::Unexpected),
+            };
+            let name = func.name.to_string().to_uppercase();
+            let arity = match name.as_str() {
+                "ROW_NUMBER" | "RANK" => Some((0, 0)),
+                "LAG" | "LEAD" => Some((1, 3)),
+                name if AGGREGATE_FUNCTIONS.contains(&name) => None,
+                _ => {
+                    return Err(CalcError::UnsupportedFunc(format!(
+                        "Unknown window function {}",
+                        name
+                    )))
+                }
+            };
+            match arity {
+                Some((min, max)) if func.args.len() < min || func.args.len() > max => {
+                    let expected = match min == max {
+                        true => min.to_string(),
+                        false => format!("{} to {}", min, max),
+                    };
+                    return Err(CalcError::InvalidType(format!(
+                        "{} expects {} argument(s), got {}",
+                        name,
+                        expected,
+                        func.args.len()
+                    )));
+                }
+                _ => {}
+            }
+            if spec.window_frame.is_some() {
+                return Err(CalcError::InvalidRequestFormat(String::from(
+                    "not yet supported: window frames",
+                )));
+            }
+            self.cover(Category::Clause, "OVER");
+            if arity.is_some() {
+                self.cover(Category::Function, &name);
+            }
+
+            let args: Vec<_> = func
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => {
+                        precedence::fix(arg.clone())
+                    }
+                })
+                .collect();
+            let partition_by: Vec<_> = spec
+                .partition_by
+                .iter()
+                .map(|expr| precedence::fix(expr.clone()))
+                .collect();
+            let order_by: Vec<_> = spec
+                .order_by
+                .iter()
+                .map(|item| precedence::fix(item.expr.clone()))
+                .collect();
+            let scope = |i: usize| Scope {
+                fields: fields.clone(),
+                row: rows[i].clone(),
+            };
+
+            // Partitions list the indices of their rows, in the order the
+            // first row of each came.
+            let mut partitions: Vec<Vec<usize>> = Vec::new();
+            let mut positions: HashMap<Key, usize> = HashMap::new();
+            let mut keys = Vec::with_capacity(rows.len());
+            for i in 0..rows.len() {
+                let (partition, key) = self.in_scope(scope(i), || {
+                    let calc = |exprs: &[Expr]| {
+                        exprs
+                            .iter()
+                            .map(|expr| self.calc(expr.clone()))
+                            .collect::<Result<Vec<_>, _>>()
+                    };
+                    Ok::<_, CalcError>((calc(&partition_by)?, calc(&order_by)?))
+                })?;
+                match positions.entry(Key(partition)) {
+                    Entry::Occupied(entry) => partitions[*entry.get()].push(i),
+                    Entry::Vacant(entry) => {
+                        entry.insert(partitions.len());
+                        partitions.push(vec![i]);
+                    }
+                }
+                keys.push(key);
+            }
+            let compare = |a: usize, b: usize| -> Result<Ordering, CalcError> {
+                for ((left, right), item) in keys[a].iter().zip(&keys[b]).zip(&spec.order_by) {
+                    let ordering =
+                        window::order(left, right, item.asc, item.nulls_first, |l, r| {
+                            self.compare(l, r)
+                        })?;
+                    if ordering != Ordering::Equal {
+                        return Ok(ordering);
+                    }
+                }
+                Ok(Ordering::Equal)
+            };
+
+            let mut values = vec![CalcResult::Null; rows.len()];
+            for mut partition in partitions {
+                let mut error = None;
+                partition.sort_by(|a, b| {
+                    compare(*a, *b).unwrap_or_else(|err| {
+                        error.get_or_insert(err);
+                        Ordering::Equal
+                    })
+                });
+                if let Some(err) = error {
+                    return Err(err);
+                }
+                // The position of the first row each row is tied with, and
+                // of the row past the last.
+                let len = partition.len();
+                let mut starts = vec![0; len];
+                for k in 1..len {
+                    if compare(partition[k - 1], partition[k])? != Ordering::Equal {
+                        starts[k] = k;
+                    } else {
+                        starts[k] = starts[k - 1];
+                    }
+                }
+                let mut ends = vec![len; len];
+                for k in (0..len.saturating_sub(1)).rev() {
+                    if starts[k + 1] == starts[k] {
+                        ends[k] = ends[k + 1];
+                    } else {
+                        ends[k] = k + 1;
+                    }
+                }
+
+                for (k, &i) in partition.iter().enumerate() {
+                    values[i] = match name.as_str() {
+                        "ROW_NUMBER" => CalcResult::Int(k as i64 + 1),
+                        "RANK" => CalcResult::Int(starts[k] as i64 + 1),
+                        "LAG" | "LEAD" => {
+                            let offset = match args.get(1) {
+                                Some(offset) => {
+                                    self.in_scope(scope(i), || self.calc(offset.clone()))?
+                                }
+                                None => CalcResult::Int(1),
+                            };
+                            let offset = match offset {
+                                CalcResult::Int(offset) if offset >= 0 => offset as usize,
+                                other => {
+                                    return Err(CalcError::InvalidType(format!(
+                                        "{} offset must be a non-negative integer, got {}",
+                                        name,
+                                        to_text(&other)
+                                    )))
+                                }
+                            };
+                            let target = match name.as_str() {
+                                "LAG" => k.checked_sub(offset),
+                                _ => k.checked_add(offset).filter(|t| *t < len),
+                            };
+                            match (target, args.get(2)) {
+                                (Some(t), _) => self
+                                    .in_scope(scope(partition[t]), || self.calc(args[0].clone()))?,
+                                (None, Some(default)) => {
+                                    self.in_scope(scope(i), || self.calc(default.clone()))?
+                                }
+                                (None, None) => CalcResult::Null,
+                            }
+                        }
+                        _ => {
+                            let frame = match order_by.is_empty() {
+                                true => &partition[..],
+                                false => &partition[..ends[k]],
+                            };
+                            let group = Group {
+                                exprs: Rc::from(Vec::new()),
+                                key: Vec::new(),
+                                fields: fields.clone(),
+                                rows: frame.iter().map(|j| rows[*j].clone()).collect(),
+                            };
+                            let func = Function {
+                                over: None,
+                                ..func.clone()
+                            };
+                            self.in_group(group, || self.calc_aggregate(&name, func))?
+                        }
+                    };
+                }
+            }
+            Ok(values)
+        }
+
+        /// The rows of a `FROM` source. A table is given `pushdown` to skip
+        /// rows and columns the query does not need.
+        fn table_factor(
+            &self,
+            factor: &TableFactor,
+            pushdown: &Pushdown,
+        ) -> Result<Relation, CalcError> {
+            self.measure(
+                || factor.to_string(),
+                || self.table_factor_rows(factor, pushdown),
+            )
+        }
+
+        fn table_factor_rows(
+            &self,
+            factor: &TableFactor,
+            pushdown: &Pushdown,
+        ) -> Result<Relation, CalcError> {
+            let dialect = self.options.dialect.as_ref();
+            match factor {
+                TableFactor::Table {
+                    name, alias, args, ..
+                } => {
+                    let relation = match args.is_empty() {
+                        true => self.table_relation(name, pushdown)?,
+                        false => {
+                            let table = self.table_function(name, args)?;
+                            Relation::scan(table.as_ref(), pushdown)?
+                        }
+                    };
+                    match alias {
+                        Some(alias) => relation.alias(alias, dialect),
+                        None => {
+                            let table = name
+                                .0
+                                .last()
+                                .map(|ident| dialect.normalize_identifier(ident));
+                            Ok(relation.qualify(table.unwrap_or_default()))
+                        }
+                    }
+                }
+                TableFactor::Derived {
+                    subquery, alias, ..
+                } => {
+                    let relation = self.query(subquery)?;
+                    match alias {
+                        Some(alias) => relation.alias(alias, dialect),
+                        None => Ok(relation),
+                    }
+                }
+                factor => Err(CalcError::InvalidRequestFormat(format!(
+                    "not yet supported: FROM {}",
+                    factor
+                ))),
+            }
+        }
+
+        /// The rows of a common table expression, a session table, or else
+        /// a registered one.
+        fn table_relation(
+            &self,
+            name: &ObjectName,
+            pushdown: &Pushdown,
+        ) -> Result<Relation, CalcError> {
+            let key = self.table_key(name);
+            let catalog = self.catalog.borrow();
+            if let Some(relation) = catalog.bound(&key) {
+                return Ok(relation.clone());
+            }
+            if let (Some(columns), Some(rows)) = (catalog.columns(&key), catalog.rows(&key)) {
+                let dialect = self.options.dialect.as_ref();
+                return Ok(Relation {
+                    fields: columns
+                        .iter()
+                        .map(|(ident, _)| Field::named(ident, dialect))
+                        .collect(),
+                    rows: rows?,
+                });
+            }
+            drop(catalog);
+
+            match self.table(name) {
+                Some(table) => Relation::scan(table.as_ref(), pushdown),
+                None => Err(CalcError::InvalidRequestFormat(format!(
+                    "Unknown table {}",
+                    name
+                ))),
+            }
+        }
+
+        /// The table a table function such as `READ_JSON('<path>')` reads,
+        /// from a file or an http(s) URL.
+        fn table_function(
+            &self,
+            name: &ObjectName,
+            args: &[FunctionArg],
+        ) -> Result<Box<dyn TableProvider>, CalcError> {
+            let name = name.to_string().to_uppercase();
+            if !TABLE_FUNCTIONS.contains(&name.as_str()) {
+                return Err(CalcError::UnsupportedFunc(format!(
+                    "Unknown table function {}",
+                    name
+                )));
+            }
+            self.cover(Category::Function, &name);
+
+            let path = match args {
+                [FunctionArg::Unnamed(arg)] => self.calc(arg.clone())?,
+                _ => {
+                    return Err(CalcError::InvalidType(format!(
+                        "{} expects 1 argument(s), got {}",
+                        name,
+                        args.len()
+                    )))
+                }
+            };
+            match (name.as_str(), path) {
+                ("READ_CSV", CalcResult::Str(path)) => {
+                    let text = source::read_to_string(&path, &name, &self.options.http)?;
+                    Ok(Box::new(csv::parse(&text, &path)?))
+                }
+                ("READ_JSON", CalcResult::Str(path)) => {
+                    let text = source::read_to_string(&path, &name, &self.options.http)?;
+                    Ok(Box::new(json::parse(&text, &path)?))
+                }
+                #[cfg(feature = "parquet")]
+                ("READ_PARQUET", CalcResult::Str(path)) => {
+                    // The table keeps a download until the query is done with it.
+                    let local = source::local(&path, &name, &self.options.http)?;
+                    Ok(Box::new(super::parquet::ParquetTable::open(&path, local)?))
+                }
+                (_, other) => Err(CalcError::InvalidType(format!(
+                    "{} expects a file path, got {}",
+                    name,
+                    to_text(&other)
+                ))),
+            }
+        }
+
+        /// Runs a `CREATE TABLE`, `INSERT` or `DROP TABLE` statement
+        /// (checked by `check_statement`), giving what it did.
+        fn execute(&self, statement: &Statement) -> Result<CalcResult, CalcError> {
+            match statement {
+                Statement::CreateTable {
+                    name,
+                    columns,
+                    if_not_exists,
+                    or_replace,
+                    ..
+                } => {
+                    self.cover(Category::Clause, "CREATE TABLE");
+                    let key = self.table_key(name);
+                    let registered = self.tables.contains_key(&key);
+                    let exists = registered || self.catalog.borrow().contains(&key);
+                    if exists && *if_not_exists {
+                        return Ok(CalcResult::Str(format!("CREATE TABLE {}", name)));
+                    } else if exists && (registered || !*or_replace) {
+                        return Err(CalcError::InvalidRequestFormat(format!(
+                            "Table {} already exists",
+                            name
+                        )));
+                    }
+
+                    let dialect = self.options.dialect.as_ref();
+                    for (i, column) in columns.iter().enumerate() {
+                        let key = dialect.normalize_identifier(&column.name);
+                        if columns[..i]
+                            .iter()
+                            .any(|other| dialect.normalize_identifier(&other.name) == key)
+                        {
+                            return Err(CalcError::InvalidRequestFormat(format!(
+                                "Column {} specified more than once",
+                                column.name
+                            )));
+                        }
+                    }
+                    let columns = columns
+                        .iter()
+                        .map(|column| (column.name.clone(), column.data_type.clone()))
+                        .collect();
+                    self.catalog.borrow_mut().create(key, columns)?;
+                    Ok(CalcResult::Str(format!("CREATE TABLE {}", name)))
+                }
+                Statement::Insert {
+                    table_name,
+                    columns,
+                    source,
+                    ..
+                } => {
+                    self.cover(Category::Clause, "INSERT");
+                    let count = self.insert(table_name, columns, source)?;
+                    Ok(CalcResult::Str(format!("INSERT {}", count)))
+                }
+                Statement::Drop {
+                    names, if_exists, ..
+                } => {
+                    self.cover(Category::Clause, "DROP TABLE");
+                    for name in names {
+                        let key = self.table_key(name);
+                        if self.tables.contains_key(&key) {
+                            return Err(CalcError::InvalidRequestFormat(format!(
+                                "Table {} is read-only",
+                                name
+                            )));
+                        } else if !self.catalog.borrow().contains(&key) && !*if_exists {
+                            return Err(CalcError::InvalidRequestFormat(format!(
+                                "Unknown table {}",
+                                name
+                            )));
+                        }
+                    }
+                    for name in names {
+                        self.catalog.borrow_mut().drop(&self.table_key(name));
+                    }
+                    Ok(CalcResult::Str(String::from("DROP TABLE")))
+                }
+                _ => Err(CalcError::InvalidRequestFormat(String::from(
+                    "only Queries are supported",
+                ))),
+            }
+        }
+
+        /// Appends the rows of `source` to a session table, placing their
+        /// values in `columns` (every column when empty) and NULL in the
+        /// others. Returns how many rows were inserted.
+        fn insert(
+            &self,
+            table: &ObjectName,
+            columns: &[Ident],
+            source: &Query,
+        ) -> Result<usize, CalcError> {
+            let key = self.table_key(table);
+            let dialect = self.options.dialect.as_ref();
+            let declared: Vec<_> = match self.catalog.borrow().columns(&key) {
+                Some(declared) => declared
+                    .iter()
+                    .map(|(ident, _)| dialect.normalize_identifier(ident))
+                    .collect(),
+                None if self.tables.contains_key(&key) => {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Table {} is read-only",
+                        table
+                    )))
+                }
+                None => {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Unknown table {}",
+                        table
+                    )))
+                }
+            };
+
+            let mut positions = Vec::with_capacity(columns.len());
+            for column in columns {
+                let key = dialect.normalize_identifier(column);
+                match declared.iter().position(|declared| *declared == key) {
+                    Some(i) if positions.contains(&i) => {
+                        return Err(CalcError::InvalidRequestFormat(format!(
+                            "Column {} specified more than once",
+                            column
+                        )))
+                    }
+                    Some(i) => positions.push(i),
+                    None => {
+                        return Err(CalcError::InvalidRequestFormat(format!(
+                            "Unknown column {}",
+                            column
+                        )))
+                    }
+                }
+            }
+            if columns.is_empty() {
+                positions.extend(0..declared.len());
+            }
+
+            let relation = self.query(source)?;
+            if relation.fields.len() != positions.len() {
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "INSERT has {} value(s) for {} column(s)",
+                    relation.fields.len(),
+                    positions.len()
+                )));
+            }
+            let rows = relation
+                .rows
+                .into_iter()
+                .map(|values| {
+                    let mut row = vec![CalcResult::Null; declared.len()];
+                    for (i, value) in positions.iter().zip(values) {
+                        row[*i] = value;
+                    }
+                    row
+                })
+                .collect();
+            self.catalog.borrow_mut().insert(
+                &key,
+                rows,
+                &self.time_zone.get(),
+                self.options.overflow,
+            )
+        }
+
+        /// Evaluates `f` with the columns of `scope` in reach of identifiers.
+        fn in_scope<T>(&self, scope: Scope, f: impl FnOnce() -> T) -> T {
+            self.scopes.borrow_mut().push(scope);
+            let res = f();
+            self.scopes.borrow_mut().pop();
+            res
+        }
+
+        /// The value of the column `name` refers to in the innermost row
+        /// having one, or else of the session variable it names.
+        fn column(&self, name: &[Ident]) -> Result<CalcResult, CalcError> {
+            let dialect = self.options.dialect.as_ref();
+            if let Some(group) = self.group.borrow().as_ref() {
+                let scope = Scope {
+                    fields: group.fields.clone(),
+                    row: vec![CalcResult::Null; group.fields.len()].into(),
+                };
+                if scope.lookup(name, dialect)?.is_some() {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Column {} must appear in GROUP BY or be used in an aggregate function",
+                        ObjectName(name.to_vec())
+                    )));
+                }
+            }
+            for scope in self.scopes.borrow().iter().rev() {
+                if let Some(value) = scope.lookup(name, dialect)? {
+                    return Ok(value.clone());
+                }
+            }
+            if let [ident] = name {
+                let variable = variables::reference(ident);
+                let key = dialect.normalize_identifier(variable.as_ref().unwrap_or(ident));
+                if let Some(value) = self.variables.borrow().get(&key) {
+                    return Ok(value.clone());
+                } else if let Some(variable) = variable {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Unknown variable @{}",
+                        variable
+                    )));
+                }
+            }
+            Err(CalcError::InvalidRequestFormat(format!(
+                "Unknown column {}",
+                ObjectName(name.to_vec())
+            )))
+        }
+
+        pub fn exec(&self, query: &str) -> Result<CalcResult, CalcError> {
+            self.eval(query).map(|evaluation| evaluation.result)
+        }
+
+        /// Parses `query`, refusing inputs nested deeper than the configured
+        /// limit before they reach the (recursive) parser.
+        pub fn parse(&self, query: &str) -> Result<Vec<Statement>, CalcError> {
+            let dialect = self.options.dialect.parser_dialect();
+            let query =
+                dialect::normalize_comments(query, self.options.dialect.line_comment_prefixes());
+            self.with_stack(&query, || {
+                parse_limited(&query, dialect, self.options.limits.max_depth)
+            })
+        }
+
+        /// Parses `query` as a `CREATE FUNCTION` statement, or returns
+        /// `Ok(None)` for any other statement (including ones that do not
+        /// tokenize, which `parse` reports).
+        fn parse_definition(&self, query: &str) -> Result<Option<Definition>, CalcError> {
+            self.parse_special(query, macros::parse_definition)
+        }
+
+        /// Parses `query` as a `SET TIME ZONE` statement, or returns
+        /// `Ok(None)` for any other statement.
+        fn parse_time_zone(&self, query: &str) -> Result<Option<ZoneSetting>, CalcError> {
+            self.parse_special(query, variables::parse_time_zone)
+        }
+
+        /// Parses `query` as a `SET` statement, or returns `Ok(None)` for
+        /// any other statement.
+        fn parse_assignment(&self, query: &str) -> Result<Option<Assignment>, CalcError> {
+            self.parse_special(query, variables::parse_assignment)
+        }
+
+        /// Parses `query` as an `ATTACH` statement, or returns `Ok(None)`
+        /// for any other statement.
+        fn parse_attachment(&self, query: &str) -> Result<Option<Attachment>, CalcError> {
+            self.parse_special(query, attach::parse_attachment)
+        }
+
+        /// Parses `query` with `parse`, a parser for a statement sqlparser
+        /// does not support (well), returning `Ok(None)` for any other.
+        fn parse_special<T>(
+            &self,
+            query: &str,
+            parse: fn(&mut Parser) -> Result<Option<T>, ParserError>,
+        ) -> Result<Option<T>, CalcError> {
+            let dialect = self.options.dialect.parser_dialect();
+            let query =
+                dialect::normalize_comments(query, self.options.dialect.line_comment_prefixes());
+            let tokens = match Tokenizer::new(dialect, &query).tokenize() {
+                Ok(tokens) => array::desugar(tokens),
+                Err(_) => return Ok(None),
+            };
+            check_depth(&tokens, self.options.limits.max_depth)?;
+
+            let mut parser = Parser::new(tokens.clone(), dialect);
+            match parse(&mut parser) {
+                Ok(statement) => Ok(statement),
+                Err(ParserError::ParserError(message))
+                | Err(ParserError::TokenizerError(message)) => {
+                    let (line, col) = locate(&tokens, &mut parser, &message);
+                    Err(CalcError::InvalidRequestFormat(format!(
+                        "{} at Line: {}, Column {}",
+                        message, line, col
+                    )))
+                }
+            }
+        }
+
+        /// Sets the session time zone, which later statements read and write
+        /// timestamps in; timestamps already stored keep their wall-clock
+        /// time.
+        fn set_time_zone(&self, setting: ZoneSetting) -> Result<(), CalcError> {
+            self.cover(Category::Clause, "SET TIME ZONE");
+            let zone = match setting {
+                ZoneSetting::Zone(name) => Zone::parse(&name)?,
+                ZoneSetting::Default => self.options.time_zone,
+            };
+            self.time_zone.set(zone);
+            Ok(())
+        }
+
+        /// Sets a session variable to the value of an expression, evaluated
+        /// now, replacing any earlier value.
+        fn assign(&self, assignment: Assignment) -> Result<(), CalcError> {
+            self.cover(Category::Clause, "SET");
+            let value = self.calc(precedence::fix(assignment.value))?;
+            let key = self.options.dialect.normalize_identifier(&assignment.name);
+            self.variables.borrow_mut().insert(key, value);
+            Ok(())
+        }
+
+        /// Makes the tables of the SQLite database an `ATTACH` statement
+        /// names readable as `<schema>.<table>`, replacing those of any
+        /// database attached under the same name before.
+        #[cfg(feature = "sqlite")]
+        fn attach(&self, attachment: Attachment) -> Result<(), CalcError> {
+            self.cover(Category::Clause, "ATTACH");
+            let tables = attach::open(&attachment.path)?;
+            let dialect = self.options.dialect.as_ref();
+            let schema = dialect.normalize_identifier(&attachment.schema);
+            let prefix = format!("{}.", schema);
+            let mut attached = self.attached.borrow_mut();
+            attached.retain(|key, _| !key.starts_with(&prefix));
+            for (name, table) in tables {
+                let key = format!(
+                    "{}{}",
+                    prefix,
+                    dialect.normalize_identifier(&Ident::new(name))
+                );
+                attached.insert(key, Arc::new(table));
+            }
+            Ok(())
+        }
+
+        #[cfg(not(feature = "sqlite"))]
+        fn attach(&self, _: Attachment) -> Result<(), CalcError> {
+            Err(CalcError::InvalidRequestFormat(String::from(
+                "ATTACH requires the sqlite feature",
+            )))
+        }
+
+        /// Makes a `CREATE FUNCTION` definition callable for the rest of the
+        /// session, replacing any earlier one of the same name, and returns
+        /// its normalized name.
+        /// Passes `expr` through the rewrite rules as `SELECT <expr>`,
+        /// giving the expression they leave in its place.
+        fn rewrite_expr(&self, expr: Expr) -> Result<Expr, CalcError> {
+            let select = Select {
+                distinct: false,
+                top: None,
+                projection: vec![SelectItem::UnnamedExpr(expr)],
+                from: Vec::new(),
+                lateral_views: Vec::new(),
+                selection: None,
+                group_by: Vec::new(),
+                cluster_by: Vec::new(),
+                distribute_by: Vec::new(),
+                sort_by: Vec::new(),
+                having: None,
+            };
+            let statement = Statement::Query(Box::new(Query {
+                with: None,
+                body: SetExpr::Select(Box::new(select)),
+                order_by: Vec::new(),
+                limit: None,
+                offset: None,
+                fetch: None,
+            }));
+            let rewritten = self.hooks.rewrite(vec![statement])?;
+            if let [Statement::Query(query)] = &rewritten[..] {
+                if let SetExpr::Select(select) = &query.body {
+                    if let [SelectItem::UnnamedExpr(expr)] = &select.projection[..] {
+                        return Ok(expr.clone());
+                    }
+                }
+            }
+            Err(CalcError::InvalidRequestFormat(format!(
+                "A rewrite rule turned an expression into {}",
+                rewritten
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )))
+        }
+
+        /// Runs the body of a `CREATE FUNCTION` through the rewrite rules,
+        /// so a rule rejecting it rejects the definition. The body is kept
+        /// as written: calls rewrite it once more as they expand it.
+        fn check_definition(&self, definition: &Definition) -> Result<(), CalcError> {
+            match &definition.body {
+                Body::Expr(body) => self.rewrite_expr(body.clone()).map(|_| ()),
+                Body::Script { .. } => Ok(()),
+            }
+        }
+
+        fn define(&self, definition: Definition) -> Result<String, CalcError> {
+            let dialect = self.options.dialect.as_ref();
+            let name: Vec<_> = definition
+                .name
+                .iter()
+                .map(|ident| dialect.normalize_identifier(ident))
+                .collect();
+            let name = name.join(".");
+            let function = match definition.body {
+                Body::Expr(body) => SessionFunction::Macro(Macro {
+                    params: definition
+                        .params
+                        .iter()
+                        .map(|ident| dialect.normalize_identifier(ident))
+                        .collect(),
+                    body: precedence::fix(body),
+                }),
+                Body::Script { language, source } => {
+                    let params = definition.params.into_iter().map(|ident| ident.value);
+                    SessionFunction::Script(Rc::new(self.compile_script(
+                        &name,
+                        &language,
+                        params.collect(),
+                        &source,
+                    )?))
+                }
+            };
+            self.macros.borrow_mut().insert(name.clone(), function);
+            Ok(name)
+        }
+
+        #[cfg_attr(not(feature = "rhai"), allow(unused_variables))]
+        fn compile_script(
+            &self,
+            name: &str,
+            language: &Ident,
+            params: Vec<String>,
+            source: &str,
+        ) -> Result<ScalarFunction, CalcError> {
+            #[cfg(feature = "rhai")]
+            if language.value.eq_ignore_ascii_case("rhai") {
+                return super::script::compile(name, params, source, &self.options.limits);
+            }
+            Err(CalcError::UnsupportedFunc(format!(
+                "Unsupported function language {}",
+                language
+            )))
+        }
+
+        /// Built-in, registered and session-defined function names, sorted.
+        pub fn all_function_names(&self) -> Vec<String> {
+            let mut names: Vec<_> = self
+                .function_names()
+                .into_iter()
+                .map(String::from)
+                .collect();
+            names.extend(self.macros.borrow().keys().cloned());
+            names.sort_unstable();
+            names.dedup();
+            names
+        }
+
+        /// Checks that `query` (each statement of it) parses and has a shape
+        /// the engine can evaluate, without evaluating it.
+        pub fn validate(&self, query: &str) -> Result<(), CalcError> {
+            self.with_stack(query, || self.validate_statements(query))
+        }
+
+        fn validate_statements(&self, query: &str) -> Result<(), CalcError> {
+            let statements = self.split(query);
+            if statements.len() > 1 {
+                return statements
+                    .into_iter()
+                    .try_for_each(|(_, statement)| self.validate_statements(statement));
+            }
+            match self.parse_time_zone(query)? {
+                Some(ZoneSetting::Zone(zone)) => return Zone::parse(&zone).map(|_| ()),
+                Some(ZoneSetting::Default) => return Ok(()),
+                None => (),
+            }
+            if let Some(definition) = self.parse_definition(query)? {
+                return self.check_definition(&definition);
+            }
+            if let Some(assignment) = self.parse_assignment(query)? {
+                return self.rewrite_expr(assignment.value).map(|_| ());
+            }
+            if self.parse_attachment(query)?.is_some() {
+                return Ok(());
+            }
+            match &self.hooks.rewrite(self.parse(query)?)?[..] {
+                [Statement::ShowVariable { variable }]
+                    if is_show_functions(variable) || is_show_time_zone(variable) =>
+                {
+                    Ok(())
+                }
+                [Statement::Assert { .. }] => Ok(()),
+                [statement @ (Statement::CreateTable { .. }
+                | Statement::Insert { .. }
+                | Statement::Drop { .. })] => check_statement(statement),
+                [explain @ Statement::Explain { .. }] => explained_query(explain).map(|_| ()),
+                ast => check_query(query_statement(ast)?),
+            }
+        }
+    }
+
+    /// Whether `SHOW <variable>` lists the callable functions.
+    fn is_show_functions(variable: &[Ident]) -> bool {
+        matches!(variable, [ident] if ident.value.eq_ignore_ascii_case("FUNCTIONS"))
+    }
+
+    /// `SHOW TIME ZONE`, or `SHOW TIMEZONE` as PostgreSQL also spells it.
+    fn is_show_time_zone(variable: &[Ident]) -> bool {
+        let words: Vec<_> = variable
+            .iter()
+            .map(|ident| ident.value.to_uppercase())
+            .collect();
+        matches!(&words[..], [time, zone] if time == "TIME" && zone == "ZONE")
+            || matches!(&words[..], [word] if word == "TIMEZONE")
+    }
+
+    /// Finds the line and column of the token the parser stopped at, so the
+    /// error can point at it.
+    fn locate(tokens: &[Token], parser: &mut Parser, message: &str) -> (u64, u64) {
+        let mut remaining = 0;
+        while parser.next_token_no_skip().is_some() {
+            remaining += 1;
+        }
+        let index = tokens.len() - remaining;
+
+        let found = message.rsplit("found: ").next().unwrap_or("");
+        let is_significant = |token: &&Token| !matches!(token, Token::Whitespace(_));
+        let consumed = tokens[..index].iter().rposition(|t| is_significant(&t));
+        let position = match consumed {
+            Some(i) if tokens[i].to_string() == found => i,
+            _ => tokens[index..]
+                .iter()
+                .position(|t| is_significant(&t))
+                .map_or(tokens.len(), |i| index + i),
+        };
+
+        let (mut line, mut col) = (1, 1);
+        for token in &tokens[..position] {
+            for ch in token.to_string().chars() {
+                if ch == '\n' {
+                    line += 1;
+                    col = 1;
+                } else {
+                    col += 1;
+                }
+            }
+        }
+        (line, col)
+    }
+
+    pub fn parse(query: &str) -> Result<Vec<Statement>, CalcError> {
+        parse_with(query, &GenericDialect {})
+    }
+
+    /// Refuses `tokens` nesting expressions deeper than `max_depth` before
+    /// they reach the (recursive) parser, and the expression tree the
+    /// evaluator would recurse into. Parentheses and brackets nest, and so
+    /// does each operator of a chain: `1 + 1 + 1` is as deep as `((1))`.
+    fn check_depth(tokens: &[Token], max_depth: usize) -> Result<(), CalcError> {
+        // The operators read at each open parenthesis, and outside them.
+        let mut operators = vec![0];
+        let mut depth: usize = 0;
+        for token in tokens {
+            match token {
+                Token::LParen | Token::LBracket => {
+                    operators.push(0);
+                    depth += 1;
+                }
+                Token::RParen | Token::RBracket if operators.len() > 1 => {
+                    depth -= operators.pop().unwrap_or_default() + 1;
+                }
+                // A new expression begins.
+                Token::Comma | Token::SemiColon => {
+                    depth -= std::mem::take(operators.last_mut().unwrap());
+                }
+                Token::Word(word) if DEPTH_CLAUSES.contains(&word.keyword) => {
+                    depth -= std::mem::take(operators.last_mut().unwrap());
+                }
+                Token::Word(word)
+                    if matches!(word.keyword, Keyword::AND | Keyword::OR | Keyword::NOT) =>
+                {
+                    *operators.last_mut().unwrap() += 1;
+                    depth += 1;
+                }
+                Token::Plus
+                | Token::Minus
+                | Token::Mul
+                | Token::Div
+                | Token::Mod
+                | Token::Caret
+                | Token::StringConcat
+                | Token::Eq
+                | Token::DoubleEq
+                | Token::Neq
+                | Token::Lt
+                | Token::Gt
+                | Token::LtEq
+                | Token::GtEq
+                | Token::Spaceship
+                | Token::Ampersand
+                | Token::Pipe => {
+                    *operators.last_mut().unwrap() += 1;
+                    depth += 1;
+                }
+                _ => continue,
+            }
+            if depth > max_depth {
+                return Err(CalcError::ResourceLimit(format!(
+                    "expression is nested deeper than {} levels",
+                    max_depth
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keywords starting an expression of their own, ending the operator
+    /// chain before them.
+    const DEPTH_CLAUSES: &[Keyword] = &[
+        Keyword::SELECT,
+        Keyword::FROM,
+        Keyword::WHERE,
+        Keyword::HAVING,
+        Keyword::ON,
+        Keyword::BY,
+        Keyword::WHEN,
+        Keyword::THEN,
+        Keyword::ELSE,
+        Keyword::LIMIT,
+        Keyword::OFFSET,
+    ];
+
+    pub fn parse_with(
+        query: &str,
+        dialect: &dyn parser::Dialect,
+    ) -> Result<Vec<Statement>, CalcError> {
+        parse_limited(query, dialect, Limits::default().max_depth)
+    }
+
+    fn parse_limited(
+        query: &str,
+        dialect: &dyn parser::Dialect,
+        max_depth: usize,
+    ) -> Result<Vec<Statement>, CalcError> {
+        let tokens = match Tokenizer::new(dialect, query).tokenize() {
+            Ok(tokens) => source::desugar(array::desugar(tokens)),
+            Err(err) => return Err(CalcError::InvalidRequestFormat(err.to_string())),
+        };
+        check_depth(&tokens, max_depth)?;
+
+        let mut parser = Parser::new(tokens.clone(), dialect);
+        let mut ast = Vec::new();
+        let mut expecting_delimiter = false;
+        loop {
+            while parser.consume_token(&Token::SemiColon) {
+                expecting_delimiter = false;
+            }
+            if parser.peek_token() == Token::EOF {
+                break;
+            }
+
+            let res = if expecting_delimiter {
+                Err(ParserError::ParserError(format!(
+                    "Expected end of statement, found: {}",
+                    parser.peek_token()
+                )))
+            } else if parser.parse_keywords(&[Keyword::ASSERT, Keyword::SELECT]) {
+                // `ASSERT SELECT <expr>` reads naturally in test scripts.
+                parser.parse_assert()
+            } else {
+                parser.parse_statement()
+            };
+
+            match res {
+                Ok(statement) => {
+                    ast.push(statement);
+                    expecting_delimiter = true;
+                }
+                Err(ParserError::ParserError(message))
+                | Err(ParserError::TokenizerError(message)) => {
+                    let (line, col) = locate(&tokens, &mut parser, &message);
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "{} at Line: {}, Column {}",
+                        message, line, col
+                    )));
+                }
+            }
+        }
+
+        if ast.is_empty() {
+            return Err(CalcError::InvalidRequestFormat(String::from(
+                "It is not SQL, man",
+            )));
+        }
+
+        Ok(ast)
+    }
+
+    /// Clauses the evaluator would otherwise silently ignore.
+    fn unsupported_clauses(query: &Query) -> Vec<&'static str> {
+        let mut clauses = Vec::new();
+        if let SetExpr::Select(select) = &query.body {
+            clauses.extend([
+                (select.top.is_some(), "TOP"),
+                (!select.lateral_views.is_empty(), "LATERAL VIEW"),
+                (!select.cluster_by.is_empty(), "CLUSTER BY"),
+                (!select.distribute_by.is_empty(), "DISTRIBUTE BY"),
+                (!select.sort_by.is_empty(), "SORT BY"),
+            ]);
+        }
+        clauses.extend([
+            (!query.order_by.is_empty(), "ORDER BY"),
+            (query.limit.is_some(), "LIMIT"),
+            (query.offset.is_some(), "OFFSET"),
+            (query.fetch.is_some(), "FETCH"),
+        ]);
+
+        clauses
+            .iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, clause)| *clause)
+            .collect()
+    }
+
+    /// The query of a single-statement script.
+    fn query_statement(ast: &[Statement]) -> Result<&Query, CalcError> {
+        if ast.len() > 1 {
+            return Err(CalcError::InvalidRequestFormat(String::from(
+                "not yet supported: multiple statements",
+            )));
+        }
+
+        match &ast[0] {
+            Statement::Query(query) => Ok(query),
+            _ => Err(CalcError::InvalidRequestFormat(String::from(
+                "only Queries are supported",
+            ))),
+        }
+    }
+
+    /// The query an `EXPLAIN` describes, checked the way running it would
+    /// check it.
+    fn explained_query(explain: &Statement) -> Result<&Query, CalcError> {
+        let statement = match explain {
+            Statement::Explain { statement, .. } => statement,
+            statement => statement,
+        };
+        let query = query_statement(std::slice::from_ref(statement))?;
+        check_query(query)?;
+        Ok(query)
+    }
+
+    /// Checks that the engine can run a `CREATE TABLE`, `INSERT` or `DROP`
+    /// statement, without running it.
+    fn check_statement(statement: &Statement) -> Result<(), CalcError> {
+        let clauses = match statement {
+            Statement::CreateTable {
+                external,
+                columns,
+                constraints,
+                query,
+                like,
+                file_format,
+                location,
+                with_options,
+                table_properties,
+                ..
+            } => vec![
+                (*external, "EXTERNAL"),
+                (!constraints.is_empty(), "table constraints"),
+                (
+                    columns.iter().any(|column| !column.options.is_empty()),
+                    "column constraints",
+                ),
+                (query.is_some(), "CREATE TABLE AS"),
+                (like.is_some(), "CREATE TABLE LIKE"),
+                (
+                    !with_options.is_empty() || !table_properties.is_empty(),
+                    "table options",
+                ),
+                (file_format.is_some() || location.is_some(), "STORED AS"),
+            ],
+            Statement::Insert {
+                or,
+                overwrite,
+                partitioned,
+                on,
+                source,
+                ..
+            } => {
+                check_query(source)?;
+                vec![
+                    (or.is_some(), "INSERT OR"),
+                    (*overwrite, "OVERWRITE"),
+                    (partitioned.is_some(), "PARTITION"),
+                    (on.is_some(), "ON CONFLICT"),
+                ]
+            }
+            Statement::Drop {
+                object_type: ObjectType::Table,
+                cascade,
+                purge,
+                ..
+            } => vec![(*cascade, "CASCADE"), (*purge, "PURGE")],
+            Statement::Drop { object_type, .. } => {
+                return Err(CalcError::InvalidRequestFormat(format!(
+                    "not yet supported: DROP {}",
+                    object_type
+                )))
+            }
+            _ => Vec::new(),
+        };
+
+        let clauses: Vec<_> = clauses
+            .iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, clause)| *clause)
+            .collect();
+        match clauses.is_empty() {
+            true => Ok(()),
+            false => Err(CalcError::InvalidRequestFormat(format!(
+                "not yet supported: {}",
+                clauses.join(", ")
+            ))),
+        }
+    }
+
+    /// The conditions `expr` requires at once: the operands of its `AND`s.
+    fn conjuncts(expr: &Expr) -> Vec<Expr> {
+        match expr {
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::And,
+                right,
+            } => {
+                let mut conditions = conjuncts(left);
+                conditions.extend(conjuncts(right));
+                conditions
+            }
+            Expr::Nested(expr) => conjuncts(expr),
+            expr => vec![expr.clone()],
+        }
+    }
+
+    /// Checks that the engine can evaluate `query` and the queries it reads
+    /// from, without evaluating anything.
+    fn check_query(query: &Query) -> Result<(), CalcError> {
+        let clauses = unsupported_clauses(query);
+        if !clauses.is_empty() {
+            return Err(CalcError::InvalidRequestFormat(format!(
+                "not yet supported: {}",
+                clauses.join(", ")
+            )));
+        }
+
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                check_query(&cte.query)?;
+            }
+        }
+        check_set_expr(&query.body)
+    }
+
+    fn check_set_expr(body: &SetExpr) -> Result<(), CalcError> {
+        match body {
+            SetExpr::Select(select) if select.projection.is_empty() => Err(
+                CalcError::InvalidRequestFormat(String::from("only SELECT is supported")),
+            ),
+            SetExpr::Select(select) => select
+                .from
+                .iter()
+                .flat_map(|table| {
+                    std::iter::once(&table.relation)
+                        .chain(table.joins.iter().map(|join| &join.relation))
+                })
+                .try_for_each(|factor| match factor {
+                    TableFactor::Table { .. } => Ok(()),
+                    TableFactor::Derived { subquery, .. } => check_query(subquery),
+                    factor => Err(CalcError::InvalidRequestFormat(format!(
+                        "not yet supported: FROM {}",
+                        factor
+                    ))),
+                }),
+            SetExpr::Values(_) => Ok(()),
+            SetExpr::Query(query) => check_query(query),
+            SetExpr::SetOperation { left, right, .. } => {
+                check_set_expr(left)?;
+                check_set_expr(right)
+            }
+            _ => Err(CalcError::InvalidRequestFormat(String::from(
+                "only SELECT is supported",
+            ))),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::super::table::{Column, MemTable};
+        use super::*;
+        use std::sync::Mutex;
+
+        fn exec(query: String) -> Result<CalcResult, CalcError> {
+            Engine::default().exec(&query)
+        }
+
+        #[test]
+        fn apply_operator_plus() {
+            let res = apply(BinaryOperator::Plus, 1.5, 2.5);
+            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
+                assert_eq!(val, 4.0);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn apply_operator_minus() {
+            let res = apply(BinaryOperator::Minus, 1.0, 2.0);
+            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
+                assert_eq!(val, -1.0);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn apply_operator_modulo() {
+            let cases = [
+                (10.0, 3.0, 1.0),
+                (-7.0, 3.0, -1.0),
+                (7.0, -3.0, 1.0),
+                (5.5, 2.0, 1.5),
+                (-5.5, 2.0, -1.5),
+            ];
+            for (left, right, expected) in cases {
+                assert_eq!(
+                    apply(BinaryOperator::Modulo, left, right).unwrap(),
+                    CalcResult::Num(expected)
+                );
+            }
+            assert_eq!(
+                apply(BinaryOperator::Modulo, 1.0, 0.0).unwrap_err(),
+                CalcError::DivisionByZero
+            );
+        }
+
+        #[test]
+        fn exec_modulo() {
+            assert_eq!(
+                exec(String::from("SELECT 10 % 3")).unwrap(),
+                CalcResult::Int(1)
+            );
+            assert_eq!(
+                exec(String::from("SELECT 2 + 10 % 4 * 2")).unwrap(),
+                CalcResult::Int(6)
+            );
+            let engine = Engine::new(Options {
+                division_by_zero: OnDivisionByZero::Null,
+                ..Options::default()
+            });
+            assert_eq!(engine.exec("SELECT 1 % 0").unwrap(), CalcResult::Null);
+            assert_eq!(engine.exec("SELECT MOD(1, 0)").unwrap(), CalcResult::Null);
+            assert_eq!(
+                engine.exec("SELECT MOD(-7, 3)").unwrap(),
+                CalcResult::Int(-1)
+            );
+            assert_eq!(
+                engine.exec("SELECT POW(2, 3)").unwrap(),
+                CalcResult::Num(8.0)
+            );
+            assert_eq!(
+                exec(String::from("SELECT MOD(1, 0)")).unwrap_err(),
+                CalcError::DivisionByZero
+            );
+            assert_eq!(
+                exec(String::from("SELECT POW('a', 2)")).unwrap_err(),
+                CalcError::InvalidType(String::from("POW supports only Number"))
+            );
+        }
+
+        #[test]
+        fn exec_integer_arithmetic() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT 3 * 3", CalcResult::Int(9)),
+                // exact beyond the 2^53 an f64 holds
+                (
+                    "SELECT 9007199254740993 + 0",
+                    CalcResult::Int(9007199254740993),
+                ),
+                ("SELECT 7 % -3", CalcResult::Int(1)),
+                ("SELECT -9223372036854775807 - 1", CalcResult::Int(i64::MIN)),
+                ("SELECT 2 + 0.5", CalcResult::Num(2.5)),
+                ("SELECT 6 / 3", CalcResult::Int(2)),
+                ("SELECT -7 / 2", CalcResult::Int(-3)),
+                ("SELECT 7 / 2.0", CalcResult::Num(3.5)),
+                ("SELECT 2 ^ 3", CalcResult::Num(8.0)),
+                ("SELECT 1 = 1.0", CalcResult::Bool(true)),
+                (
+                    "SELECT 9223372036854775807 > 9223372036854775806",
+                    CalcResult::Bool(true),
+                ),
+                ("SELECT ABS(-5) + MOD(7, 2)", CalcResult::Int(6)),
+                ("SELECT SQRT(9)", CalcResult::Num(3.0)),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            let errors = [
+                (
+                    "SELECT 9223372036854775807 + 1",
+                    "Integer 9223372036854775807 + 1 is out of range",
+                ),
+                (
+                    "SELECT -(-9223372036854775807 - 1)",
+                    "Integer -(-9223372036854775808) is out of range",
+                ),
+                (
+                    "SELECT (-9223372036854775807 - 1) / -1",
+                    "Integer -9223372036854775808 / -1 is out of range",
+                ),
+                (
+                    "SELECT SUM(n) FROM (VALUES (9223372036854775807), (1)) t(n)",
+                    "Integer 9223372036854775807 + 1 is out of range",
+                ),
+            ];
+            for (query, message) in errors {
+                assert_eq!(
+                    engine.exec(query).unwrap_err(),
+                    CalcError::Overflow(String::from(message)),
+                    "{}",
+                    query
+                );
+            }
+        }
+
+        #[test]
+        fn exec_arrays() {
+            let engine = Engine::default();
+            let array = |items: &[i64]| {
+                CalcResult::Array(items.iter().copied().map(CalcResult::Int).collect())
+            };
+            let cases = [
+                ("SELECT ARRAY[1, 2, 3][2]", CalcResult::Int(2)),
+                ("SELECT ARRAY[1, 2, 3]", array(&[1, 2, 3])),
+                ("SELECT ARRAY[]", array(&[])),
+                ("SELECT ARRAY[1, 2][3]", CalcResult::Null),
+                (
+                    "SELECT ARRAY[ARRAY[1, 2], ARRAY[3]][1][2]",
+                    CalcResult::Int(2),
+                ),
+                ("SELECT (ARRAY[10, 20])[1 + 1] * 2", CalcResult::Int(40)),
+                ("SELECT ARRAY_LENGTH(ARRAY[1, 2, 3])", CalcResult::Int(3)),
+                ("SELECT ARRAY_LENGTH(ARRAY[1], 2)", CalcResult::Null),
+                (
+                    "SELECT ARRAY_CONTAINS(ARRAY[1, 2], 2.0)",
+                    CalcResult::Bool(true),
+                ),
+                (
+                    "SELECT ARRAY_CONTAINS(ARRAY['a'], 'b')",
+                    CalcResult::Bool(false),
+                ),
+                ("SELECT ARRAY_CONTAINS(ARRAY[1, NULL], 2)", CalcResult::Null),
+                (
+                    "SELECT CAST(ARRAY['a', NULL] AS TEXT)",
+                    CalcResult::Str(String::from("['a', NULL]")),
+                ),
+                ("SELECT ARRAY[1, 2] || ARRAY[3]", array(&[1, 2, 3])),
+                ("SELECT ARRAY[1] || 2", array(&[1, 2])),
+                ("SELECT 0 || ARRAY[1]", array(&[0, 1])),
+                ("SELECT ARRAY[1] || NULL", CalcResult::Null),
+                ("SELECT ARRAY[1, 2] = ARRAY[1, 2.0]", CalcResult::Bool(true)),
+                ("SELECT ARRAY[1, 2] <> ARRAY[1]", CalcResult::Bool(true)),
+                ("SELECT ARRAY['a'] = ARRAY['b']", CalcResult::Bool(false)),
+                ("SELECT ARRAY[1, NULL] = ARRAY[1, 2]", CalcResult::Null),
+                (
+                    "SELECT ARRAY[1, NULL] = ARRAY[2, 2]",
+                    CalcResult::Bool(false),
+                ),
+                (
+                    "SELECT ARRAY[ARRAY[1], ARRAY[2]] = ARRAY[ARRAY[1], ARRAY[2]]",
+                    CalcResult::Bool(true),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            assert_eq!(
+                engine.exec("SELECT 1[1]").unwrap_err(),
+                CalcError::InvalidType(String::from("ARRAY_EXTRACT supports only Array"))
+            );
+            assert_eq!(
+                engine.exec("SELECT ARRAY[1][1.5]").unwrap_err(),
+                CalcError::InvalidType(String::from("Array subscripts must be integers"))
+            );
+            assert_eq!(
+                engine.exec("SELECT ARRAY[1] < ARRAY[2]").unwrap_err(),
+                CalcError::InvalidType(String::from("Cannot compare Array with Array using <"))
+            );
+            assert_eq!(
+                engine.exec("SELECT ARRAY[1] = 1").unwrap_err(),
+                CalcError::InvalidType(String::from("Cannot compare Array with Integer using ="))
+            );
+        }
+
+        #[test]
+        fn exec_decimal_mode() {
+            let engine = Engine::new(Options {
+                numeric: Numeric::Decimal,
+                ..Options::default()
+            });
+            let decimal = |text: &str| CalcResult::Decimal(decimal::parse(text).unwrap());
+            let cases = [
+                ("SELECT 0.1 + 0.2", decimal("0.3")),
+                ("SELECT 0.1 + 0.2 = 0.3", CalcResult::Bool(true)),
+                ("SELECT 1 / 4", decimal("0.25")),
+                ("SELECT 1 / 3", decimal("0.3333333333333333333333333333")),
+                ("SELECT 3 * 3", CalcResult::Int(9)),
+                ("SELECT 1.1 ^ 2", decimal("1.21")),
+                ("SELECT 2 ^ 0.5", CalcResult::Num(2f64.sqrt())),
+                ("SELECT -(0.5) + 1", decimal("0.5")),
+                (
+                    "SELECT 12345678901234567890.5 + 1",
+                    decimal("12345678901234567891.5"),
+                ),
+                (
+                    "SELECT CAST(0.1 AS DOUBLE) + 0.2",
+                    CalcResult::Num(0.1 + 0.2),
+                ),
+                ("SELECT 0.1 BETWEEN 0 AND 0.2", CalcResult::Bool(true)),
+                ("SELECT ROUND(2.675, 2)", decimal("2.68")),
+                ("SELECT FLOOR(-2.5)", decimal("-3")),
+                ("SELECT SIGN(-0.1)", CalcResult::Int(-1)),
+                ("SELECT SQRT(0.25)", CalcResult::Num(0.5)),
+                ("SELECT 0.1 || ''", CalcResult::Str(String::from("0.1"))),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            assert_eq!(
+                engine.exec("SELECT 1.5 / 0").unwrap_err(),
+                CalcError::DivisionByZero
+            );
+            // without the option decimals only come from CAST
+            assert_eq!(
+                Engine::default().exec("SELECT 0.1 + 0.2").unwrap(),
+                CalcResult::Num(0.1 + 0.2)
+            );
+            assert_eq!(
+                Engine::default()
+                    .exec("SELECT CAST(0.1 AS DECIMAL) + CAST(0.2 AS DECIMAL)")
+                    .unwrap(),
+                decimal("0.3")
+            );
+        }
+
+        #[test]
+        fn exec_logical_operators() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT (1 > 0) AND (2 > 1)", true),
+                ("SELECT 1 > 0 AND 2 < 1", false),
+                ("SELECT 1 > 2 OR 2 > 1", true),
+                ("SELECT NOT 1 > 2", true),
+                ("SELECT NOT (1 > 0 AND 2 > 1) OR 1 = 2", false),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(
+                    engine.exec(query).unwrap(),
+                    CalcResult::Bool(expected),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine.exec("SELECT 1 AND 'a'").unwrap_err(),
+                CalcError::MultipleErrors(vec![
+                    CalcError::InvalidType(String::from("AND supports only Boolean, got 1")),
+                    CalcError::InvalidType(String::from("AND supports only Boolean, got 'a'")),
+                ])
+            );
+            assert_eq!(
+                engine.exec("SELECT NOT 1").unwrap_err(),
+                CalcError::UnsupportedOperator(String::from(
+                    "Unary NOT is not supported for Integer"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_unary_sign() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT -5 + 3", CalcResult::Int(-2)),
+                ("SELECT -(2 * 3) - -1", CalcResult::Int(-5)),
+                ("SELECT +4", CalcResult::Int(4)),
+                ("SELECT -2 ^ 2", CalcResult::Num(4.0)),
+                ("SELECT 2 ^ -1 * 4", CalcResult::Num(2.0)),
+                ("SELECT - -3", CalcResult::Int(3)),
+                ("SELECT -1.5", CalcResult::Num(-1.5)),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT -'a'").unwrap_err(),
+                CalcError::UnsupportedOperator(String::from("Unary - is not supported for String"))
+            );
+        }
+
+        #[test]
+        fn exec_string_operators() {
+            let engine = Engine::default();
+            let cases = [
+                (
+                    "SELECT 'foo' || 'bar'",
+                    CalcResult::Str(String::from("foobar")),
+                ),
+                (
+                    "SELECT 'x' || 1 + 2 || (1 = 1)",
+                    CalcResult::Str(String::from("x3true")),
+                ),
+                ("SELECT 'n' || 2 ^ 3", CalcResult::Str(String::from("n8"))),
+                ("SELECT 'foo' || 'bar' = 'foobar'", CalcResult::Bool(true)),
+                ("SELECT 'a' <> 'A'", CalcResult::Bool(true)),
+                ("SELECT 'a' < 'b'", CalcResult::Bool(true)),
+                ("SELECT 'b' <= 'a'", CalcResult::Bool(false)),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            let nocase = Engine::new(Options {
+                collation: Collation::NoCase,
+                ..Options::default()
+            });
+            assert_eq!(
+                nocase.exec("SELECT 'a' = 'A'").unwrap(),
+                CalcResult::Bool(true)
+            );
+        }
+
+        #[test]
+        fn exec_string_functions() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT UPPER('abc')", CalcResult::Str(String::from("ABC"))),
+                ("SELECT lower('ÀB')", CalcResult::Str(String::from("àb"))),
+                ("SELECT LENGTH('hello') + 1", CalcResult::Int(6)),
+                (
+                    "SELECT REVERSE('abc')",
+                    CalcResult::Str(String::from("cba")),
+                ),
+                ("SELECT LENGTH(NULL)", CalcResult::Null),
+                ("SELECT LENGTH('cafe\u{301}')", CalcResult::Int(5)),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT UPPER(1)").unwrap_err(),
+                CalcError::InvalidType(String::from("UPPER supports only String"))
+            );
+
+            let graphemes = Engine::new(Options {
+                string_semantics: StringSemantics::Graphemes,
+                ..Options::default()
+            });
+            assert_eq!(
+                graphemes.exec("SELECT LENGTH('cafe\u{301}')").unwrap(),
+                CalcResult::Int(4)
+            );
+        }
+
+        #[test]
+        fn exec_string_manipulation() {
+            let engine = Engine::default();
+            let str = |value: &str| CalcResult::Str(value.to_string());
+            let cases = [
+                ("SELECT SUBSTRING('hello' FROM 2 FOR 3)", str("ell")),
+                ("SELECT SUBSTRING('hello', 2, 3)", str("ell")),
+                ("SELECT SUBSTRING('hello' FROM 3)", str("llo")),
+                ("SELECT SUBSTRING('abc' FROM 0 FOR 2)", str("a")),
+                ("SELECT SUBSTRING('abc' FROM -5 FOR 2)", str("")),
+                ("SELECT SUBSTRING(NULL FROM 1)", CalcResult::Null),
+                ("SELECT TRIM('  a b  ')", str("a b")),
+                ("SELECT TRIM(BOTH 'xy' FROM 'xyaxy')", str("a")),
+                ("SELECT TRIM(LEADING 'x' FROM 'xax')", str("ax")),
+                ("SELECT TRIM(TRAILING 'x' FROM 'xax')", str("xa")),
+                ("SELECT REPLACE('a-b-c', '-', '+')", str("a+b+c")),
+                ("SELECT REPLACE('abc', '', 'x')", str("abc")),
+                ("SELECT LPAD('7', 3, '0')", str("007")),
+                ("SELECT RPAD('ab', 4)", str("ab  ")),
+                ("SELECT LPAD('hello', 2)", str("he")),
+                ("SELECT LPAD('a', 0 - 1)", str("")),
+                ("SELECT RPAD(NULL, 2)", CalcResult::Null),
+                ("SELECT CONCAT('a', 1, 'b')", str("a1b")),
+                ("SELECT CONCAT('x', 1 = 1, NULL, 0.5)", str("xtrue0.5")),
+                ("SELECT CONCAT_WS('-', 2026, 10, 17)", str("2026-10-17")),
+                ("SELECT REGEXP_REPLACE('a1b2', '[0-9]', '')", str("ab")),
+                (
+                    "SELECT MD5('password')",
+                    str("5f4dcc3b5aa765d61d8327deb882cf99"),
+                ),
+                ("SELECT MD5(NULL)", CalcResult::Null),
+                ("SELECT REGEXP_EXTRACT('v1.25', '[0-9.]+')", str("1.25")),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine
+                    .exec("SELECT SUBSTRING('abc' FROM 1 FOR -1)")
+                    .unwrap_err(),
+                CalcError::InvalidType(String::from(
+                    "SUBSTRING length must not be negative, got -1"
+                ))
+            );
+            assert!(matches!(
+                engine.exec("SELECT LPAD('a', 100000000000)").unwrap_err(),
+                CalcError::ResourceLimit(_)
+            ));
+            assert!(matches!(
+                engine
+                    .exec("SELECT REPLACE(RPAD('', 2000, 'a'), 'a', RPAD('', 1000, 'b'))")
+                    .unwrap_err(),
+                CalcError::ResourceLimit(_)
+            ));
+        }
+
+        #[test]
+        fn exec_between() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT 5 BETWEEN 1 AND 10", true),
+                ("SELECT 10 BETWEEN 1 AND 10", true),
+                ("SELECT 11 BETWEEN 1 AND 5 + 5", false),
+                ("SELECT 11 NOT BETWEEN 1 AND 10", true),
+                ("SELECT 'b' BETWEEN 'a' AND 'c'", true),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(
+                    engine.exec(query).unwrap(),
+                    CalcResult::Bool(expected),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine.exec("SELECT 1 BETWEEN 'a' AND 2").unwrap_err(),
+                CalcError::InvalidType(String::from("Cannot compare Integer with String"))
+            );
+        }
+
+        #[test]
+        fn exec_in_list() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT 3 IN (1, 2, 3)", true),
+                ("SELECT 3 IN (1, 2)", false),
+                ("SELECT 4 IN (1, 2 * 2)", true),
+                ("SELECT 'x' NOT IN ('a', 'b')", true),
+                ("SELECT 'a' NOT IN ('a', 'b')", false),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(
+                    engine.exec(query).unwrap(),
+                    CalcResult::Bool(expected),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine.exec("SELECT 1 IN ('a')").unwrap_err(),
+                CalcError::InvalidType(String::from("Cannot compare Integer with String"))
+            );
+        }
+
+        #[test]
+        fn exec_null_three_valued_logic() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT NULL", CalcResult::Null),
+                ("SELECT NULL + 1", CalcResult::Null),
+                ("SELECT -NULL", CalcResult::Null),
+                ("SELECT NULL = NULL", CalcResult::Null),
+                ("SELECT 'a' || NULL", CalcResult::Null),
+                ("SELECT SQRT(NULL)", CalcResult::Null),
+                ("SELECT CAST(NULL AS INT)", CalcResult::Null),
+                ("SELECT NULL IS NULL", CalcResult::Bool(true)),
+                ("SELECT 1 + NULL IS NOT NULL", CalcResult::Bool(false)),
+                ("SELECT NULL AND 1 > 2", CalcResult::Bool(false)),
+                ("SELECT NULL AND 1 < 2", CalcResult::Null),
+                ("SELECT NULL OR 1 < 2", CalcResult::Bool(true)),
+                ("SELECT NULL OR 1 > 2", CalcResult::Null),
+                ("SELECT NOT NULL", CalcResult::Null),
+                ("SELECT 2 IN (1, NULL)", CalcResult::Null),
+                ("SELECT 1 IN (1, NULL)", CalcResult::Bool(true)),
+                ("SELECT NULL BETWEEN 1 AND 2", CalcResult::Null),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT NULL + SQRT('a')").unwrap_err(),
+                CalcError::InvalidType(String::from("SQRT supports only Number"))
+            );
+        }
+
+        #[test]
+        fn exec_null_propagation() {
+            let mut engine = Engine::default();
+            engine.register_scalar_fn("twice", 1, |args| match args {
+                [CalcResult::Num(num)] => Ok(CalcResult::Num(num * 2.0)),
+                _ => Err("expects a Number"),
+            });
+            let operands = ["1", "'a'", "DATE '2024-01-01'", "INTERVAL '1 day'", "TRUE"];
+            let operators = [
+                "+", "-", "*", "/", "%", "^", "||", "=", "<>", "<", "<=", ">", ">=",
+            ];
+            for op in operators {
+                for operand in operands {
+                    for query in [
+                        format!("SELECT NULL {} {}", op, operand),
+                        format!("SELECT {} {} NULL", operand, op),
+                    ] {
+                        assert_eq!(engine.exec(&query), Ok(CalcResult::Null), "{}", query);
+                    }
+                }
+            }
+
+            let calls = [
+                "SELECT ABS(NULL)",
+                "SELECT POWER(2, NULL)",
+                "SELECT ROUND(1.5, NULL)",
+                "SELECT LOG(NULL, 8)",
+                "SELECT UPPER(NULL)",
+                "SELECT REPLACE('a', NULL, 'b')",
+                "SELECT LPAD('a', 3, NULL)",
+                "SELECT REGEXP_EXTRACT('a', 'a', NULL)",
+                "SELECT MD5(NULL)",
+                "SELECT DATE_TRUNC(NULL, NOW())",
+                "SELECT EXTRACT(YEAR FROM NULL)",
+                "SELECT SUBSTRING('abc' FROM 1 FOR NULL)",
+                "SELECT TRIM(NULL)",
+                "SELECT SETSEED(NULL)",
+                "SELECT TRY_CAST(NULL AS DATE)",
+                "SELECT TWICE(NULL)",
+            ];
+            for query in calls {
+                assert_eq!(engine.exec(query), Ok(CalcResult::Null), "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT TWICE(NULL) + 1 IS NULL"),
+                Ok(CalcResult::Bool(true))
+            );
+
+            // variadic functions skip NULLs instead
+            assert_eq!(
+                engine.exec("SELECT CONCAT('a', NULL)"),
+                Ok(CalcResult::Str(String::from("a")))
+            );
+        }
+
+        #[test]
+        fn exec_case() {
+            let engine = Engine::default();
+            let cases = [
+                (
+                    "SELECT CASE WHEN 2 > 1 THEN 'yes' ELSE 'no' END",
+                    CalcResult::Str(String::from("yes")),
+                ),
+                (
+                    "SELECT CASE WHEN NULL THEN 1 WHEN 1 > 2 THEN 2 ELSE 3 END",
+                    CalcResult::Int(3),
+                ),
+                ("SELECT CASE WHEN 1 > 2 THEN 1 END", CalcResult::Null),
+                (
+                    "SELECT CASE 1 + 1 WHEN 1 THEN 'one' WHEN 2 THEN 'two' END",
+                    CalcResult::Str(String::from("two")),
+                ),
+                (
+                    "SELECT CASE NULL WHEN NULL THEN 1 ELSE 0 END",
+                    CalcResult::Int(0),
+                ),
+                // branches not taken are never evaluated
+                (
+                    "SELECT CASE WHEN 1 = 1 THEN 1 ELSE 1 / 0 END",
+                    CalcResult::Int(1),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT CASE WHEN 1 THEN 1 END").unwrap_err(),
+                CalcError::InvalidType(String::from("CASE condition 1 is not Boolean"))
+            );
+        }
+
+        #[test]
+        fn exec_null_functions() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT COALESCE(NULL, NULL, 3, 1 / 0)", CalcResult::Int(3)),
+                ("SELECT COALESCE(NULL)", CalcResult::Null),
+                ("SELECT coalesce('a')", CalcResult::Str(String::from("a"))),
+                ("SELECT IFNULL(NULL, 2)", CalcResult::Int(2)),
+                ("SELECT IFNULL(1, 1 / 0)", CalcResult::Int(1)),
+                ("SELECT NULLIF(1, 1)", CalcResult::Null),
+                ("SELECT NULLIF(1, 2)", CalcResult::Int(1)),
+                (
+                    "SELECT NULLIF('a', NULL)",
+                    CalcResult::Str(String::from("a")),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT IFNULL(1)").unwrap_err(),
+                CalcError::InvalidType(String::from("IFNULL expects 2 argument(s), got 1"))
+            );
+            assert!(engine.exec("SELECT COALESCE()").is_err());
+        }
+
+        #[test]
+        fn exec_math_functions() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT ABS(-3)", CalcResult::Int(3)),
+                ("SELECT FLOOR(2.7) + CEIL(2.2)", CalcResult::Num(5.0)),
+                ("SELECT ROUND(2.567, 2)", CalcResult::Num(2.57)),
+                ("SELECT ROUND(2.5)", CalcResult::Num(3.0)),
+                ("SELECT TRUNC(-2.567, 1)", CalcResult::Num(-2.5)),
+                ("SELECT SIGN(-0.5)", CalcResult::Num(-1.0)),
+                ("SELECT ROUND(NULL, 2)", CalcResult::Null),
+                ("SELECT ABS(NULL)", CalcResult::Null),
+                ("SELECT ROUND(SIN(ASIN(0.5)), 6)", CalcResult::Num(0.5)),
+                ("SELECT ATAN2(0, 1)", CalcResult::Num(0.0)),
+                ("SELECT ATAN2(NULL, 1)", CalcResult::Null),
+                ("SELECT Log(2, 8)", CalcResult::Num(3.0)),
+                ("SELECT LN(EXP(2))", CalcResult::Num(2.0)),
+                (
+                    "SELECT PI() * 2 * 2",
+                    CalcResult::Num(std::f64::consts::PI * 4.0),
+                ),
+                ("SELECT LN(e())", CalcResult::Num(1.0)),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT ROUND(1, 2, 3)").unwrap_err(),
+                CalcError::InvalidType(String::from("ROUND expects 1 to 2 argument(s), got 3"))
+            );
+            assert_eq!(
+                engine.exec("SELECT PI(1)").unwrap_err(),
+                CalcError::InvalidType(String::from("PI expects 0 argument(s), got 1"))
+            );
+            assert_eq!(
+                engine.exec("SELECT ABS('a')").unwrap_err(),
+                CalcError::InvalidType(String::from("ABS supports only Number"))
+            );
+        }
+
+        #[test]
+        fn exec_power() {
+            let engine = Engine::default();
+            assert_eq!(
+                engine.exec("SELECT 2 ^ 10").unwrap(),
+                CalcResult::Num(1024.0)
+            );
+            assert_eq!(
+                engine.exec("SELECT POWER(2, 10)").unwrap(),
+                CalcResult::Num(1024.0)
+            );
+            assert_eq!(
+                engine.exec("SELECT 3 * 2 ^ 2").unwrap(),
+                CalcResult::Num(12.0)
+            );
+            assert_eq!(
+                engine.exec("SELECT POWER(0, 0 - 1)").unwrap_err(),
+                CalcError::DivisionByZero
+            );
+            assert_eq!(
+                engine.exec("SELECT power(2)").unwrap_err(),
+                CalcError::InvalidType(String::from("POWER expects 2 argument(s), got 1"))
+            );
+        }
+
+        #[test]
+        fn apply_operator_gt() {
+            let res = apply(BinaryOperator::Gt, 2.0, 1.0);
+            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(false)) {
+                assert!(val);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn apply_operator_multiply() {
+            let res = apply(BinaryOperator::Multiply, 3.0, 2.0);
+            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
+                assert_eq!(val, 6.0);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn apply_operator_error() {
+            if std::mem::discriminant(&CalcError::UnsupportedOperator(String::from("")))
+                != std::mem::discriminant(&apply(BinaryOperator::BitwiseAnd, 3.0, 2.0).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn format_num_round_trip() {
+            let cases = [
+                (2.0, "2"),
+                (-0.0, "-0"),
+                (0.5, "0.5"),
+                (0.1 + 0.2, "0.30000000000000004"),
+                (1.0 / 3.0, "0.3333333333333333"),
+                (1e15, "1000000000000000"),
+                (1e16, "1e+16"),
+                (1e300, "1e+300"),
+                (1e-7, "1e-7"),
+                (123456789012345680000.0, "1.2345678901234568e+20"),
+                (f64::NAN, "NaN"),
+                (f64::INFINITY, "Infinity"),
+                (f64::NEG_INFINITY, "-Infinity"),
+            ];
+            for (num, expected) in cases {
+                assert_eq!(format_num(num), expected);
+                if num.is_finite() {
+                    assert_eq!(format_num(num).parse::<f64>().unwrap(), num);
+                }
+            }
+        }
+
+        #[test]
+        fn display_num() {
+            assert_eq!(CalcResult::Num(18.0).to_string(), "Result: 18");
+            assert_eq!(CalcResult::Num(1e21).to_string(), "Result: 1e+21");
+        }
+
+        #[test]
+        fn parse_primitive_value_number() {
+            let res = parse_primitive_value(Value::Number(5.0.to_string(), false), Numeric::Float);
+            if let CalcResult::Int(val) = res.unwrap_or(CalcResult::Int(-1)) {
+                assert_eq!(val, 5);
+            } else {
+                panic!();
+            }
+            assert_eq!(
+                parse_primitive_value(Value::Number(String::from("5.5"), false), Numeric::Float),
+                Ok(CalcResult::Num(5.5))
+            );
+            // beyond i64 integers fall back to f64
+            assert_eq!(
+                parse_primitive_value(Value::Number(String::from("1e2"), false), Numeric::Float),
+                Ok(CalcResult::Num(100.0))
+            );
+            assert_eq!(
+                parse_primitive_value(
+                    Value::Number(String::from("9223372036854775808"), false),
+                    Numeric::Float
+                ),
+                Ok(CalcResult::Num(9223372036854775808.0))
+            );
+            assert_eq!(
+                parse_primitive_value(Value::Number(String::from("5.5"), false), Numeric::Decimal),
+                Ok(CalcResult::Decimal(decimal::parse("5.5").unwrap()))
+            );
+            // decimals reach past f64
+            assert_eq!(
+                parse_primitive_value(
+                    Value::Number(String::from("1e400"), false),
+                    Numeric::Decimal
+                ),
+                Ok(CalcResult::Decimal(decimal::parse("1e400").unwrap()))
+            );
+        }
+
+        #[test]
+        fn parse_primitive_value_str() {
+            let res = parse_primitive_value(
+                Value::SingleQuotedString(String::from("string")),
+                Numeric::Float,
+            );
+            if let CalcResult::Str(val) = res.unwrap_or_else(|_| CalcResult::Str(String::from(""))) {
+                assert_eq!(val, String::from("string"));
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn parse_primitive_value_malformed_number() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(
+                    &parse_primitive_value(
+                        Value::Number(String::from("1.2.3"), false),
+                        Numeric::Float,
+                    )
+                    .unwrap_err(),
+                )
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn parse_primitive_value_out_of_range_number() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(
+                    &parse_primitive_value(
+                        Value::Number(String::from("1e999"), false),
+                        Numeric::Float,
+                    )
+                    .unwrap_err(),
+                )
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn parse_primitive_value_bool() {
+            assert_eq!(
+                parse_primitive_value(Value::Boolean(true), Numeric::Float),
+                Ok(CalcResult::Bool(true))
+            );
+        }
+
+        #[test]
+        fn exec_bool_literals() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT TRUE", CalcResult::Bool(true)),
+                ("SELECT TRUE AND FALSE", CalcResult::Bool(false)),
+                ("SELECT NOT false OR FALSE", CalcResult::Bool(true)),
+                ("SELECT (1 < 2) AND TRUE", CalcResult::Bool(true)),
+                (
+                    "SELECT CASE WHEN FALSE THEN 1 ELSE 2 END",
+                    CalcResult::Int(2),
+                ),
+                ("SELECT TRUE IS NULL", CalcResult::Bool(false)),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+        }
+
+        #[test]
+        fn parse_primitive_value_unsupported() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(
+                    &parse_primitive_value(
+                        Value::HexStringLiteral(String::from("ff")),
+                        Numeric::Float,
+                    )
+                    .unwrap_err(),
+                )
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_single_operator() {
+            let res = exec(String::from("SELECT 1 + 1"));
+            if let CalcResult::Int(val) = res.unwrap_or(CalcResult::Int(-1)) {
+                assert_eq!(val, 2);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_multy_operator() {
+            let res = exec(String::from("SELECT 1 + 1 * 3"));
+            if let CalcResult::Int(val) = res.unwrap_or(CalcResult::Int(-1)) {
+                assert_eq!(val, 4);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_gt_operator() {
+            let res = exec(String::from("SELECT 2 > 3"));
+            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(true)) {
+                assert!(!val);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_gt_strings() {
+            let res = exec(String::from("SELECT 'b' > 'a'"));
+            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(false)) {
+                assert!(val);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_gt_strings_collation() {
+            assert!(matches!(
+                exec(String::from("SELECT 'b' > 'A'")).unwrap(),
+                CalcResult::Bool(true)
+            ));
+            let engine = Engine::new(Options {
+                collation: Collation::Binary,
+                ..Options::default()
+            });
+            assert!(matches!(
+                engine.exec("SELECT 'a' > 'B'").unwrap(),
+                CalcResult::Bool(true)
+            ));
+            let engine = Engine::new(Options {
+                collation: Collation::NoCase,
+                ..Options::default()
+            });
+            assert!(matches!(
+                engine.exec("SELECT 'a' > 'A'").unwrap(),
+                CalcResult::Bool(false)
+            ));
+        }
+
+        #[test]
+        fn exec_operators_with_quotas() {
+            let res = exec(String::from("SELECT (1 + (2+3+4)-5)+(6+7)"));
+            if let CalcResult::Int(val) = res.unwrap_or(CalcResult::Int(-1)) {
+                assert_eq!(val, 18);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_unsupported_operators() {
+            if std::mem::discriminant(&CalcError::UnsupportedOperator(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT 1 & 2")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_divide_operator() {
+            assert_eq!(
+                exec(String::from("SELECT 1 / 2")).unwrap(),
+                CalcResult::Int(0)
+            );
+            let res = exec(String::from("SELECT 1.0 / 2"));
+            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
+                assert_eq!(val, 0.5);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_division_by_zero() {
+            assert_eq!(
+                exec(String::from("SELECT 1 / (2 - 2)")).unwrap_err(),
+                CalcError::DivisionByZero
+            );
+        }
+
+        #[test]
+        fn exec_division_by_zero_null() {
+            let engine = Engine::new(Options {
+                division_by_zero: OnDivisionByZero::Null,
+                ..Options::default()
+            });
+            if let CalcResult::Null = engine.exec("SELECT 1 / 0").unwrap() {
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_overflow_modes() {
+            let overflowing = |overflow| {
+                let engine = Engine::new(Options {
+                    overflow,
+                    ..Options::default()
+                });
+                [
+                    "SELECT 9223372036854775807 + 1",
+                    "SELECT -9223372036854775807 - 2",
+                    "SELECT 4611686018427387904 * 2",
+                    "SELECT (-9223372036854775807 - 1) / -1",
+                    "SELECT -(-9223372036854775807 - 1)",
+                ]
+                .map(|query| engine.exec(query))
+            };
+            let (min, max) = (CalcResult::Int(i64::MIN), CalcResult::Int(i64::MAX));
+            assert_eq!(
+                overflowing(OnOverflow::Wrap),
+                [
+                    Ok(min.clone()),
+                    Ok(max.clone()),
+                    Ok(min.clone()),
+                    Ok(min.clone()),
+                    Ok(min.clone()),
+                ]
+            );
+            assert_eq!(
+                overflowing(OnOverflow::Saturate),
+                [
+                    Ok(max.clone()),
+                    Ok(min.clone()),
+                    Ok(max.clone()),
+                    Ok(max.clone()),
+                    Ok(max.clone()),
+                ]
+            );
+            assert!(overflowing(OnOverflow::Error)
+                .iter()
+                .all(|res| matches!(res, Err(CalcError::Overflow(_)))));
+            assert_eq!(
+                CalcError::Overflow(String::from("x")).to_string(),
+                "[Integer Overflow]: x"
+            );
+            assert_eq!(CalcError::Overflow(String::from("x")).exit_code(), 20);
+        }
+
+        #[test]
+        fn exec_cast_overflow_modes() {
+            let casting = |overflow| {
+                let engine = Engine::new(Options {
+                    overflow,
+                    ..Options::default()
+                });
+                engine.exec("CREATE TABLE t(x TINYINT)").unwrap();
+                [
+                    "SELECT CAST(300 AS TINYINT)",
+                    "SELECT CAST(-129 AS TINYINT)",
+                    "SELECT CAST('1e10' AS INT)",
+                    "SELECT CAST(2147483648.4 AS INT)",
+                    "SELECT CAST('1e30' AS BIGINT)",
+                    "INSERT INTO t VALUES (200)",
+                    "SELECT TRY_CAST(300 AS TINYINT)",
+                ]
+                .map(|query| match engine.exec(query) {
+                    Ok(CalcResult::Str(_)) => engine.exec("SELECT (SELECT MAX(x) FROM t)"),
+                    res => res,
+                })
+            };
+            let int = CalcResult::Int;
+            assert_eq!(
+                casting(OnOverflow::Wrap),
+                [
+                    Ok(int(44)),
+                    Ok(int(127)),
+                    Ok(int(1410065408)),
+                    Ok(int(i32::MIN as i64)),
+                    Ok(int(5076964154930102272)),
+                    Ok(int(-56)),
+                    Ok(int(44)),
+                ]
+            );
+            assert_eq!(
+                casting(OnOverflow::Saturate),
+                [
+                    Ok(int(127)),
+                    Ok(int(-128)),
+                    Ok(int(i32::MAX as i64)),
+                    Ok(int(i32::MAX as i64)),
+                    Ok(int(i64::MAX)),
+                    Ok(int(127)),
+                    Ok(int(127)),
+                ]
+            );
+            let errors = casting(OnOverflow::Error);
+            assert_eq!(
+                errors[0],
+                Err(CalcError::Overflow(String::from(
+                    "Value 300 is out of range for TINYINT"
+                )))
+            );
+            assert!(errors[1..6]
+                .iter()
+                .all(|res| matches!(res, Err(CalcError::Overflow(_)))));
+            assert_eq!(errors[6], Ok(CalcResult::Null));
+        }
+
+        #[test]
+        fn exec_strict_coercion() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT '2' + 3")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_lenient_coercion() {
+            let engine = Engine::new(Options {
+                coercion: Coercion::Lenient,
+                ..Options::default()
+            });
+            if let CalcResult::Int(val) = engine.exec("SELECT '2' + 3").unwrap() {
+                assert_eq!(val, 5);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn eval_coercion_warning() {
+            let engine = Engine::new(Options {
+                coercion: Coercion::Lenient,
+                ..Options::default()
+            });
+            let evaluation = engine.eval("SELECT '2' + 3").unwrap();
+            assert_eq!(
+                evaluation.warnings,
+                vec![Warning::ImplicitCoercion(String::from(
+                    "'2' was coerced to Number"
+                ))]
+            );
+        }
+
+        #[test]
+        fn eval_ignored_precision_warning() {
+            let evaluation = Engine::default()
+                .eval("SELECT CAST('2' AS INT(11))")
+                .unwrap();
+            assert_eq!(evaluation.warnings.len(), 1);
+            if std::mem::discriminant(&Warning::IgnoredPrecision(String::from("")))
+                != std::mem::discriminant(&evaluation.warnings[0])
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn eval_warnings_reset_between_queries() {
+            let engine = Engine::default();
+            engine.eval("SELECT CAST('2' AS INT(11))").unwrap();
+            assert!(engine.eval("SELECT 1 + 1").unwrap().warnings.is_empty());
+        }
+
+        #[test]
+        fn exec_lenient_coercion_invalid() {
+            let engine = Engine::new(Options {
+                coercion: Coercion::Lenient,
+                ..Options::default()
+            });
+            assert_eq!(
+                engine.exec("SELECT 'abc' + 3").unwrap_err(),
+                CalcError::InvalidType(String::from("Cannot coerce 'abc' to Number"))
+            );
+        }
+
+        #[test]
+        fn exec_nan_comparisons_are_unknown() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT 1 IN (SQRT(-1))", CalcResult::Null),
+                ("SELECT 1 NOT IN (SQRT(-1))", CalcResult::Null),
+                ("SELECT 1 IN (SQRT(-1), 1)", CalcResult::Bool(true)),
+                ("SELECT SQRT(-1) IN (SQRT(-1))", CalcResult::Null),
+                ("SELECT 3 IN (SELECT SQRT(-1))", CalcResult::Null),
+                ("SELECT 7 BETWEEN SQRT(-1) AND SQRT(-1)", CalcResult::Null),
+                ("SELECT 7 NOT BETWEEN SQRT(-1) AND 9", CalcResult::Null),
+                ("SELECT 7 BETWEEN SQRT(-1) AND 5", CalcResult::Bool(false)),
+                (
+                    "SELECT 7 NOT BETWEEN 8 AND SQRT(-1)",
+                    CalcResult::Bool(true),
+                ),
+                (
+                    "SELECT CASE 3 WHEN SQRT(-1) THEN 'nan' ELSE 'other' END",
+                    CalcResult::Str(String::from("other")),
+                ),
+                (
+                    "SELECT CASE SQRT(-1) WHEN SQRT(-1) THEN 'nan' END",
+                    CalcResult::Null,
+                ),
+                (
+                    "SELECT NULLIF(SQRT(-1), SQRT(-1)) IS NULL",
+                    CalcResult::Bool(false),
+                ),
+                ("SELECT SQRT(-1) = SQRT(-1)", CalcResult::Null),
+                ("SELECT SQRT(-1) <> 1", CalcResult::Null),
+                ("SELECT 1 < SQRT(-1)", CalcResult::Null),
+                (
+                    "SELECT MIN(x) FROM (VALUES (2.0), (SQRT(-1)), (3.0)) AS t(x)",
+                    CalcResult::Table(vec![vec![CalcResult::Num(2.0)]]),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+        }
+
+        #[test]
+        fn exec_operand_errors_aggregated() {
+            match exec(String::from("SELECT 'a' + 'b'")).unwrap_err() {
+                CalcError::MultipleErrors(errors) => {
+                    assert_eq!(errors.len(), 2);
+                    assert!(errors[0].to_string().contains("'a'"));
+                    assert!(errors[1].to_string().contains("'b'"));
+                }
+                _ => panic!(),
+            }
+        }
+
+        #[test]
+        fn exec_operand_errors_flattened() {
+            match exec(String::from("SELECT ('a' + 'b') + (1 / 0)")).unwrap_err() {
+                CalcError::MultipleErrors(errors) => assert_eq!(errors.len(), 3),
+                _ => panic!(),
+            }
+        }
+
+        #[test]
+        fn exec_operand_errors_before_type_errors() {
+            for query in [
+                "SELECT (1 / 0) + 'a'",
+                "SELECT 'a' * (1 / 0)",
+                "SELECT (1 / 0) AND 2",
+            ] {
+                assert_eq!(
+                    exec(String::from(query)).unwrap_err(),
+                    CalcError::DivisionByZero,
+                    "{}",
+                    query
+                );
+            }
+            let engine = Engine::new(Options {
+                coercion: Coercion::Lenient,
+                ..Options::default()
+            });
+            assert_eq!(
+                engine.eval("SELECT (1 / 0) + '2'").unwrap_err(),
+                CalcError::DivisionByZero
+            );
+        }
+
+        #[test]
+        fn exec_operand_single_error() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT 1 + 'b'")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_sqrt() {
+            let res = exec(String::from("SELECT SQRT(5 + 2 * 4)"));
+            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
+                assert_eq!(val, 3.605551275463989);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_sqrt_gt() {
+            let res = exec(String::from("SELECT SQRT(16) > SQRT(4)"));
+            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(false)) {
+                assert!(val);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_case_insensitive() {
+            let res = exec(String::from("SELECT sqrt(16) + Sqrt(9)"));
+            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
+                assert_eq!(val, 7.0);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_qualified_unsupported() {
+            if std::mem::discriminant(&CalcError::UnsupportedFunc(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT math.sqrt(4)")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_compound_name() {
+            assert_eq!(
+                exec(String::from("SELECT db.math.sqrt(4)")).unwrap_err(),
+                CalcError::UnsupportedFunc(String::from("Unknown function db.math.sqrt"))
+            );
+        }
+
+        #[test]
+        fn calc_function_empty_name() {
+            let func = Function {
+                name: ObjectName(vec![]),
+                args: vec![],
+                over: None,
+                distinct: false,
+            };
+            if std::mem::discriminant(&CalcError::UnsupportedFunc(String::from("")))
+                != std::mem::discriminant(&Engine::default().calc_function(func).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_wrong_arity() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT SQRT()")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_func_unsupported() {
+            if std::mem::discriminant(&CalcError::UnsupportedFunc(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT Gamma(2)")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_cast() {
+            let res = exec(String::from("SELECT CAST('2' as int)"));
+            if let CalcResult::Int(val) = res.unwrap_or(CalcResult::Int(-1)) {
+                assert_eq!(val, 2);
+            } else {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_cast_invalied() {
+            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT CAST('qwe' as int)")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_cast_matrix() {
+            let engine = Engine::default();
+            let str = |s: &str| CalcResult::Str(String::from(s));
+            let cases = [
+                ("SELECT CAST(3.7 AS TEXT)", str("3.7")),
+                ("SELECT CAST(1 + 1 AS VARCHAR(10))", str("2")),
+                ("SELECT CAST('2.5' AS FLOAT)", CalcResult::Num(2.5)),
+                ("SELECT CAST('-7' AS BIGINT)", CalcResult::Int(-7)),
+                ("SELECT CAST('2.9' AS INT)", CalcResult::Int(3)),
+                ("SELECT CAST(-2.5 AS INT)", CalcResult::Int(-3)),
+                ("SELECT CAST(7.0 / 2 AS SMALLINT)", CalcResult::Int(4)),
+                ("SELECT CAST('true' AS BOOLEAN)", CalcResult::Bool(true)),
+                ("SELECT CAST('off' AS BOOLEAN)", CalcResult::Bool(false)),
+                ("SELECT CAST(0 AS BOOLEAN)", CalcResult::Bool(false)),
+                ("SELECT CAST(1 = 1 AS INT)", CalcResult::Int(1)),
+                ("SELECT CAST(1 = 1 AS TEXT)", str("true")),
+                (
+                    "SELECT CAST('2024-02-29' AS DATE) + INTERVAL '1 day'",
+                    CalcResult::Timestamp(
+                        NaiveDate::from_ymd_opt(2024, 3, 1)
+                            .unwrap()
+                            .and_hms_opt(0, 0, 0)
+                            .unwrap(),
+                    ),
+                ),
+                (
+                    "SELECT CAST(TIMESTAMP '2024-02-29 10:30:00' AS DATE)",
+                    CalcResult::Date(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()),
+                ),
+                ("SELECT CAST(INTERVAL '2 hours' AS TEXT)", str("02:00:00")),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            let errors = [
+                ("SELECT CAST('abc' AS FLOAT)", "Cannot CAST 'abc' AS FLOAT"),
+                (
+                    "SELECT CAST('maybe' AS BOOLEAN)",
+                    "Cannot CAST 'maybe' AS BOOLEAN",
+                ),
+                ("SELECT CAST(1 AS DATE)", "Cannot CAST Integer AS DATE"),
+                ("SELECT CAST('x' AS DATE)", "Invalid DATE 'x'"),
+                ("SELECT CAST(1 AS UUID)", "CAST to UUID is not supported"),
+            ];
+            for (query, message) in errors {
+                assert_eq!(
+                    engine.exec(query).unwrap_err(),
+                    CalcError::InvalidType(String::from(message)),
+                    "{}",
+                    query
+                );
+            }
+        }
+
+        #[test]
+        fn exec_try_cast() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT TRY_CAST('abc' AS INT)", CalcResult::Null),
+                ("SELECT TRY_CAST('12' AS INT) + 1", CalcResult::Int(13)),
+                ("SELECT TRY_CAST('1e10' AS INT)", CalcResult::Null),
+                ("SELECT TRY_CAST('2024-13-01' AS DATE)", CalcResult::Null),
+                (
+                    "SELECT COALESCE(TRY_CAST('n/a' AS FLOAT), -1)",
+                    CalcResult::Int(-1),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            // only the conversion is forgiven, not errors in its operand
+            assert_eq!(
+                engine.exec("SELECT TRY_CAST(1 / 0 AS TEXT)").unwrap_err(),
+                CalcError::DivisionByZero
+            );
+        }
+
+        #[test]
+        fn exec_invalied_expr() {
+            if std::mem::discriminant(&CalcError::InvalidRequestFormat(String::from("")))
+                != std::mem::discriminant(&exec(String::from("SELECT * from table")).unwrap_err())
+            {
+                panic!();
+            }
+        }
+
+        #[test]
+        fn exec_ignored_clauses_rejected() {
+            assert_eq!(
+                exec(String::from("SELECT 1+1 FROM t WHERE x ORDER BY y")).unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from("not yet supported: ORDER BY"))
+            );
+        }
+
+        #[test]
+        fn exec_multiple_projections() {
+            let row = exec(String::from("SELECT 1+1, SQRT(4), 'x', NULL")).unwrap();
+            assert_eq!(
+                row,
+                CalcResult::Row(vec![
+                    CalcResult::Int(2),
+                    CalcResult::Num(2.0),
+                    CalcResult::Str(String::from("x")),
+                    CalcResult::Null,
+                ])
+            );
+            assert_eq!(row.to_string(), "Result: 2, 2, x, NULL");
+            assert_eq!(describe(&row), "(2, 2, 'x', NULL)");
+
+            assert_eq!(
+                exec(String::from("SELECT 1, 1 / 0")).unwrap_err(),
+                CalcError::DivisionByZero
+            );
+            assert_eq!(
+                exec(String::from("SELECT 1, *")).unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "SELECT * with no tables specified is not valid"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_values() {
+            let engine = Engine::default();
+            let table = |rows: &[&[i64]]| {
+                CalcResult::Table(
+                    rows.iter()
+                        .map(|row| row.iter().copied().map(CalcResult::Int).collect())
+                        .collect(),
+                )
+            };
+            let cases = [
+                ("VALUES (1+1)", table(&[&[2]])),
+                ("VALUES (1, 2), (3, 4)", table(&[&[1, 2], &[3, 4]])),
+                (
+                    "SELECT * FROM (VALUES (1, 2), (3, 4)) t(a, b)",
+                    table(&[&[1, 2], &[3, 4]]),
+                ),
+                (
+                    "SELECT b - a, t.a * 10 FROM (VALUES (1, 2), (3, 5)) AS t(a, b)",
+                    table(&[&[1, 10], &[2, 30]]),
+                ),
+                (
+                    "SELECT t.*, column2 FROM (VALUES (1, 2)) t(a)",
+                    table(&[&[1, 2, 2]]),
+                ),
+                ("SELECT v * 2 FROM (SELECT 1+1 AS v) x", table(&[&[4]])),
+                ("SELECT 1 FROM (VALUES (7), (8)) t", table(&[&[1], &[1]])),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            let evaluation = engine
+                .eval("SELECT a + b AS total, b FROM (VALUES (1, 2)) t(a, b)")
+                .unwrap();
+            assert_eq!(
+                evaluation.columns,
+                vec![Some(String::from("total")), Some(String::from("b"))]
+            );
+            assert_eq!(evaluation.to_string(), "Result: 1 row\ntotal, b\n3, 2");
+
+            let errors = [
+                ("SELECT x FROM (VALUES (1)) t(a)", "Unknown column x"),
+                ("SELECT u.a FROM (VALUES (1)) t(a)", "Unknown column u.a"),
+                ("SELECT u.* FROM (VALUES (1)) t(a)", "Unknown table u"),
+                (
+                    "SELECT a FROM (VALUES (1, 2)) t(a, a)",
+                    "Column reference a is ambiguous",
+                ),
+                (
+                    "VALUES (1), (2, 3)",
+                    "VALUES lists must all be the same length",
+                ),
+                (
+                    "SELECT * FROM (VALUES (1)) t(a, b)",
+                    "Table t has 1 column(s), 2 aliased",
+                ),
+                ("SELECT * FROM t", "Unknown table t"),
+                (
+                    "SELECT * FROM (VALUES (1)) a NATURAL JOIN (VALUES (2)) b",
+                    "not yet supported: NATURAL JOIN",
+                ),
+            ];
+            for (query, message) in errors {
+                assert_eq!(
+                    engine.exec(query).unwrap_err(),
+                    CalcError::InvalidRequestFormat(String::from(message)),
+                    "{}",
+                    query
+                );
+            }
+        }
+
+        #[test]
+        fn exec_tables() {
+            let mut engine = Engine::default();
+            let items = MemTable::new(
+                vec![Column::new("x", ValueType::Integer)],
+                vec![vec![CalcResult::Int(1)], vec![CalcResult::Int(5)]],
+            )
+            .unwrap();
+            engine.register_table("items", Arc::new(items));
+            let str = |value: &str| CalcResult::Str(String::from(value));
+            let statements = [
+                ("CREATE TABLE t(a INT, b TEXT)", str("CREATE TABLE t")),
+                ("CREATE TABLE IF NOT EXISTS t(c INT)", str("CREATE TABLE t")),
+                (
+                    "INSERT INTO t VALUES (1, 'x'), (2.4, NULL)",
+                    str("INSERT 2"),
+                ),
+                ("INSERT INTO t (b) VALUES ('y')", str("INSERT 1")),
+                (
+                    "SELECT a + 1, b FROM t",
+                    CalcResult::Table(vec![
+                        vec![CalcResult::Int(2), str("x")],
+                        vec![CalcResult::Int(3), CalcResult::Null],
+                        vec![CalcResult::Null, str("y")],
+                    ]),
+                ),
+                (
+                    "SELECT t.b FROM t WHERE a > 1 OR b = 'y'",
+                    CalcResult::Table(vec![vec![CalcResult::Null], vec![str("y")]]),
+                ),
+                (
+                    "SELECT x * 2 FROM items WHERE x > 2",
+                    CalcResult::Table(vec![vec![CalcResult::Int(10)]]),
+                ),
+                ("DROP TABLE t", str("DROP TABLE")),
+                ("DROP TABLE IF EXISTS t", str("DROP TABLE")),
+            ];
+            for (statement, expected) in statements {
+                assert_eq!(engine.exec(statement).unwrap(), expected, "{}", statement);
+            }
+
+            engine.exec("CREATE TABLE u(a INT)").unwrap();
+            engine.exec("INSERT INTO u VALUES (3)").unwrap();
+            let errors = [
+                ("CREATE TABLE u(b INT)", "Table u already exists"),
+                (
+                    "CREATE TABLE v(a INT, A TEXT)",
+                    "Column A specified more than once",
+                ),
+                (
+                    "INSERT INTO u VALUES (1, 2)",
+                    "INSERT has 2 value(s) for 1 column(s)",
+                ),
+                ("INSERT INTO u (b) VALUES (1)", "Unknown column b"),
+                ("INSERT INTO t VALUES (1)", "Unknown table t"),
+                ("DROP TABLE items", "Table items is read-only"),
+                ("DROP TABLE t", "Unknown table t"),
+            ];
+            for (statement, message) in errors {
+                assert_eq!(
+                    engine.exec(statement).unwrap_err(),
+                    CalcError::InvalidRequestFormat(String::from(message)),
+                    "{}",
+                    statement
+                );
+            }
+            assert_eq!(
+                engine.exec("SELECT a FROM u WHERE a").unwrap_err(),
+                CalcError::InvalidType(String::from("WHERE condition a is not Boolean"))
+            );
+            assert_eq!(
+                engine.exec("INSERT INTO u VALUES (1), ('x')").unwrap_err(),
+                engine.exec("SELECT CAST('x' AS INT)").unwrap_err()
+            );
+            assert_eq!(
+                engine.exec("SELECT COUNT(*) FROM u").unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(1)]])
+            );
+        }
+
+        #[test]
+        fn exec_read_json() {
+            let path = std::env::temp_dir().join("sql_engine_exec_read_json.ndjson");
+            std::fs::write(
+                &path,
+                "{\"id\": 1, \"user\": {\"name\": \"ann\", \"age\": 30}}\n\
+                 {\"id\": 2, \"user\": {\"name\": \"bob\"}}\n\
+                 {\"id\": 3, \"user\": {\"name\": \"cy\", \"age\": 41}}\n",
+            )
+            .unwrap();
+            let path = path.to_str().unwrap();
+
+            let engine = Engine::default();
+            let evaluation = engine
+                .eval(&format!(
+                    "SELECT id, p.user.name FROM read_json('{}') p WHERE user.age > 35 OR user.age IS NULL",
+                    path
+                ))
+                .unwrap();
+            assert_eq!(
+                evaluation.result,
+                CalcResult::Table(vec![
+                    vec![CalcResult::Int(2), CalcResult::Str(String::from("bob"))],
+                    vec![CalcResult::Int(3), CalcResult::Str(String::from("cy"))],
+                ])
+            );
+            assert_eq!(
+                evaluation.columns,
+                vec![Some(String::from("id")), Some(String::from("name"))]
+            );
+            assert_eq!(
+                engine
+                    .exec(&format!("SELECT * FROM READ_JSON('{}')", path))
+                    .unwrap(),
+                CalcResult::Table(vec![
+                    vec![
+                        CalcResult::Int(1),
+                        CalcResult::Str(String::from("ann")),
+                        CalcResult::Int(30),
+                    ],
+                    vec![
+                        CalcResult::Int(2),
+                        CalcResult::Str(String::from("bob")),
+                        CalcResult::Null,
+                    ],
+                    vec![
+                        CalcResult::Int(3),
+                        CalcResult::Str(String::from("cy")),
+                        CalcResult::Int(41),
+                    ],
+                ])
+            );
+            std::fs::remove_file(path).unwrap();
+
+            assert_eq!(
+                engine.exec("SELECT * FROM read_json(1)").unwrap_err(),
+                CalcError::InvalidType(String::from("READ_JSON expects a file path, got 1"))
+            );
+            assert_eq!(
+                engine
+                    .exec("SELECT * FROM read_json('a', 'b')")
+                    .unwrap_err(),
+                CalcError::InvalidType(String::from("READ_JSON expects 1 argument(s), got 2"))
+            );
+            assert_eq!(
+                engine.exec("SELECT * FROM read_xml('x')").unwrap_err(),
+                CalcError::UnsupportedFunc(String::from("Unknown table function READ_XML"))
+            );
+            assert_eq!(
+                engine.exec("SELECT read_json('x')").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "READ_JSON gives a table, read it with FROM"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_from_file() {
+            let path = std::env::temp_dir().join("sql_test_engine_from_file.csv");
+            std::fs::write(&path, "id,name\n1,ann\n2,bob\n").unwrap();
+            let path = path.to_string_lossy().into_owned();
+            let engine = Engine::default();
+            assert_eq!(
+                engine
+                    .exec(&format!(
+                        "SELECT name FROM '{}' t WHERE t.id = 2 UNION ALL SELECT name FROM READ_CSV('{}') WHERE id = 1",
+                        path, path
+                    ))
+                    .unwrap(),
+                CalcResult::Table(vec![
+                    vec![CalcResult::Str(String::from("bob"))],
+                    vec![CalcResult::Str(String::from("ann"))],
+                ])
+            );
+            std::fs::remove_file(&path).unwrap();
+            assert!(matches!(
+                engine
+                    .exec(&format!("SELECT * FROM '{}'", path))
+                    .unwrap_err(),
+                CalcError::FunctionFailed(_)
+            ));
+        }
+
+        #[cfg(feature = "http")]
+        #[test]
+        fn exec_from_url() {
+            use std::io::{BufRead, BufReader, Write};
+            use std::net::TcpListener;
+
+            // Serves one request, answering 401 unless it is authorized.
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/data.csv", listener.local_addr().unwrap());
+            let server = std::thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut authorized = false;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    authorized |= line.eq_ignore_ascii_case("authorization: Bearer t\r\n");
+                    line.clear();
+                }
+                let response = match authorized {
+                    true => "HTTP/1.1 200 OK\r\nContent-Length: 15\r\nConnection: close\r\n\r\nn\n1\n2\n3\n4\n5\n6\n7",
+                    false => "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                };
+                (&stream).write_all(response.as_bytes()).unwrap();
+            });
+
+            let cache = std::env::temp_dir().join("sql_test_engine_http_cache");
+            let _ = std::fs::remove_dir_all(&cache);
+            let engine = Engine::new(Options {
+                http: HttpOptions {
+                    headers: vec![(String::from("Authorization"), String::from("Bearer t"))],
+                    cache: Some(cache.clone()),
+                },
+                ..Options::default()
+            });
+            let query = format!("SELECT SUM(n) FROM '{}'", url);
+            assert_eq!(
+                engine.exec(&query).unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(28)]])
+            );
+            server.join().unwrap();
+            // The server is gone; the cached download answers.
+            assert_eq!(
+                engine.exec(&query).unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(28)]])
+            );
+            std::fs::remove_dir_all(&cache).unwrap();
+            assert!(matches!(
+                engine.exec(&query).unwrap_err(),
+                CalcError::FunctionFailed(_)
+            ));
+        }
+
+        #[test]
+        fn exec_group_by() {
+            let engine = Engine::default();
+            let source =
+                "(VALUES ('a', 1, 2.5), ('b', 2, NULL), ('a', 3, 1.5), (NULL, 4, 0.5)) t(k, n, x)";
+            let str = |value: &str| CalcResult::Str(String::from(value));
+            let cases = [
+                (
+                    "SELECT k, COUNT(*), COUNT(x), SUM(n), AVG(x), MIN(n), MAX(x) FROM {} GROUP BY k",
+                    vec![
+                        vec![
+                            str("a"),
+                            CalcResult::Int(2),
+                            CalcResult::Int(2),
+                            CalcResult::Int(4),
+                            CalcResult::Num(2.0),
+                            CalcResult::Int(1),
+                            CalcResult::Num(2.5),
+                        ],
+                        vec![
+                            str("b"),
+                            CalcResult::Int(1),
+                            CalcResult::Int(0),
+                            CalcResult::Int(2),
+                            CalcResult::Null,
+                            CalcResult::Int(2),
+                            CalcResult::Null,
+                        ],
+                        vec![
+                            CalcResult::Null,
+                            CalcResult::Int(1),
+                            CalcResult::Int(1),
+                            CalcResult::Int(4),
+                            CalcResult::Num(0.5),
+                            CalcResult::Int(4),
+                            CalcResult::Num(0.5),
+                        ],
+                    ],
+                ),
+                (
+                    "SELECT n % 2 AS parity, SUM(n) * 10 FROM {} GROUP BY parity HAVING COUNT(*) > 1",
+                    vec![
+                        vec![CalcResult::Int(1), CalcResult::Int(40)],
+                        vec![CalcResult::Int(0), CalcResult::Int(60)],
+                    ],
+                ),
+                (
+                    "SELECT UPPER(k), COUNT(DISTINCT n > 1) FROM {} WHERE k IS NOT NULL GROUP BY 1",
+                    vec![
+                        vec![str("A"), CalcResult::Int(2)],
+                        vec![str("B"), CalcResult::Int(1)],
+                    ],
+                ),
+                (
+                    "SELECT COUNT(*), SUM(n) FROM {} WHERE n > 10",
+                    vec![vec![CalcResult::Int(0), CalcResult::Null]],
+                ),
+                ("SELECT k FROM {} WHERE n > 10 GROUP BY k", vec![]),
+            ];
+            for (query, expected) in cases {
+                let query = query.replace("{}", source);
+                assert_eq!(
+                    engine.exec(&query).unwrap(),
+                    CalcResult::Table(expected),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(engine.exec("SELECT COUNT(*)").unwrap(), CalcResult::Int(1));
+
+            let errors = [
+                (
+                    "SELECT k, n FROM {} GROUP BY k",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "Column n must appear in GROUP BY or be used in an aggregate function",
+                    )),
+                ),
+                (
+                    "SELECT k FROM {} WHERE COUNT(*) > 1 GROUP BY k",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "Aggregate function COUNT is not allowed here",
+                    )),
+                ),
+                (
+                    "SELECT SUM(COUNT(*)) FROM {}",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "Aggregate function COUNT is not allowed here",
+                    )),
+                ),
+                (
+                    "SELECT COUNT(*) FROM {} GROUP BY 2",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "GROUP BY position 2 is not in the select list",
+                    )),
+                ),
+                (
+                    "SELECT SUM(k) FROM {}",
+                    CalcError::InvalidType(String::from("SUM supports only Numbers, got a")),
+                ),
+                (
+                    "SELECT k FROM {} GROUP BY k HAVING SUM(n)",
+                    CalcError::InvalidType(String::from("HAVING condition SUM(n) is not Boolean")),
+                ),
+            ];
+            for (query, expected) in errors {
+                let query = query.replace("{}", source);
+                assert_eq!(engine.exec(&query).unwrap_err(), expected, "{}", query);
+            }
+        }
+
+        #[test]
+        fn exec_distinct() {
+            let engine = Engine::default();
+            let source =
+                "(VALUES (1, 'a'), (1.0, 'a'), (NULL, NULL), (2, 'b'), (NULL, NULL)) t(n, s)";
+            let str = |value: &str| CalcResult::Str(String::from(value));
+            let cases = [
+                (
+                    "SELECT DISTINCT n, s FROM {}",
+                    vec![
+                        vec![CalcResult::Int(1), str("a")],
+                        vec![CalcResult::Null, CalcResult::Null],
+                        vec![CalcResult::Int(2), str("b")],
+                    ],
+                ),
+                (
+                    "SELECT DISTINCT n > 1 FROM {}",
+                    vec![
+                        vec![CalcResult::Bool(false)],
+                        vec![CalcResult::Null],
+                        vec![CalcResult::Bool(true)],
+                    ],
+                ),
+                (
+                    "SELECT DISTINCT COUNT(*) FROM {} GROUP BY s",
+                    vec![vec![CalcResult::Int(2)], vec![CalcResult::Int(1)]],
+                ),
+                (
+                    "SELECT DISTINCT * FROM (VALUES (ARRAY[1, 2]), (ARRAY[1, 2]), (ARRAY[2])) t",
+                    vec![
+                        vec![CalcResult::Array(vec![
+                            CalcResult::Int(1),
+                            CalcResult::Int(2),
+                        ])],
+                        vec![CalcResult::Array(vec![CalcResult::Int(2)])],
+                    ],
+                ),
+            ];
+            for (query, expected) in cases {
+                let query = query.replace("{}", source);
+                assert_eq!(
+                    engine.exec(&query).unwrap(),
+                    CalcResult::Table(expected),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine.exec("SELECT DISTINCT 1").unwrap(),
+                CalcResult::Int(1)
+            );
+        }
+
+        #[test]
+        fn exec_joins() {
+            let engine = Engine::default();
+            engine
+                .exec("CREATE TABLE users(id INT, name TEXT)")
+                .unwrap();
+            engine
+                .exec("INSERT INTO users VALUES (1, 'ann'), (2, 'bob'), (3, 'cy')")
+                .unwrap();
+            let orders = "(VALUES (1, 10), (1, 20), (4, 40)) o(user_id, total)";
+            let str = |value: &str| CalcResult::Str(String::from(value));
+            let int = CalcResult::Int;
+            let cases = [
+                (
+                    "SELECT name, total FROM users JOIN {} ON users.id = o.user_id",
+                    vec![vec![str("ann"), int(10)], vec![str("ann"), int(20)]],
+                ),
+                (
+                    "SELECT name, total FROM users u LEFT JOIN {} ON u.id = user_id WHERE u.id < 3",
+                    vec![
+                        vec![str("ann"), int(10)],
+                        vec![str("ann"), int(20)],
+                        vec![str("bob"), CalcResult::Null],
+                    ],
+                ),
+                (
+                    "SELECT name, total FROM users RIGHT JOIN {} ON id = user_id AND total > 10",
+                    vec![
+                        vec![str("ann"), int(20)],
+                        vec![CalcResult::Null, int(10)],
+                        vec![CalcResult::Null, int(40)],
+                    ],
+                ),
+                (
+                    "SELECT id, user_id FROM users FULL JOIN {} ON id = user_id WHERE total IS NULL OR id IS NULL",
+                    vec![
+                        vec![int(2), CalcResult::Null],
+                        vec![int(3), CalcResult::Null],
+                        vec![CalcResult::Null, int(4)],
+                    ],
+                ),
+                (
+                    "SELECT name, COUNT(total) FROM users LEFT JOIN {} ON id = user_id GROUP BY name",
+                    vec![
+                        vec![str("ann"), int(2)],
+                        vec![str("bob"), int(0)],
+                        vec![str("cy"), int(0)],
+                    ],
+                ),
+                (
+                    "SELECT COUNT(*) FROM users CROSS JOIN {}, (VALUES (1), (2)) t",
+                    vec![vec![int(18)]],
+                ),
+            ];
+            for (query, expected) in cases {
+                let query = query.replace("{}", orders);
+                assert_eq!(
+                    engine.exec(&query).unwrap(),
+                    CalcResult::Table(expected),
+                    "{}",
+                    query
+                );
+            }
+
+            let errors = [
+                (
+                    "SELECT * FROM users JOIN (VALUES (1)) t(id) ON id = 1",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "Column reference id is ambiguous",
+                    )),
+                ),
+                (
+                    "SELECT * FROM users JOIN (VALUES (1)) t(id) ON t.id",
+                    CalcError::InvalidType(String::from("ON condition t.id is not Boolean")),
+                ),
+                (
+                    "SELECT 1 FROM users JOIN (SELECT 1 ORDER BY 1) t ON TRUE",
+                    CalcError::InvalidRequestFormat(String::from("not yet supported: ORDER BY")),
+                ),
+                (
+                    "SELECT * FROM users JOIN (VALUES (1)) t(id) USING (id)",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "not yet supported: JOIN ... USING",
+                    )),
+                ),
+            ];
+            for (query, expected) in errors {
+                assert_eq!(engine.exec(query).unwrap_err(), expected, "{}", query);
+            }
+        }
+
+        #[test]
+        fn exec_set_operations() {
+            let engine = Engine::default();
+            let int = |n| vec![CalcResult::Int(n)];
+            let cases = [
+                ("SELECT 1 UNION SELECT 2", vec![int(1), int(2)]),
+                (
+                    "SELECT 1 UNION SELECT 1.0 UNION SELECT NULL UNION SELECT NULL",
+                    vec![int(1), vec![CalcResult::Null]],
+                ),
+                ("SELECT 1 UNION ALL SELECT 1", vec![int(1), int(1)]),
+                (
+                    "VALUES (1), (1), (2), (3) INTERSECT VALUES (1), (1), (2)",
+                    vec![int(1), int(2)],
+                ),
+                (
+                    "VALUES (1), (1), (1), (2) INTERSECT ALL VALUES (1), (1), (3)",
+                    vec![int(1), int(1)],
+                ),
+                (
+                    "VALUES (1), (1), (2), (3) EXCEPT VALUES (2)",
+                    vec![int(1), int(3)],
+                ),
+                (
+                    "VALUES (1), (1), (2), (3) EXCEPT ALL VALUES (1), (2)",
+                    vec![int(1), int(3)],
+                ),
+                (
+                    "SELECT * FROM (SELECT 1 AS a UNION SELECT 2) t WHERE a > 1",
+                    vec![int(2)],
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(
+                    engine.exec(query).unwrap(),
+                    CalcResult::Table(expected),
+                    "{}",
+                    query
+                );
+            }
+
+            let errors = [
+                (
+                    "SELECT 1 UNION SELECT 1, 2",
+                    CalcError::InvalidRequestFormat(String::from(
+                        "UNION branches have 1 and 2 column(s)",
+                    )),
+                ),
+                (
+                    "SELECT 1 AS n EXCEPT SELECT 'a'",
+                    CalcError::InvalidType(String::from(
+                        "EXCEPT column n holds both Integer and String values",
+                    )),
+                ),
+                (
+                    "SELECT 1 UNION (SELECT 2 ORDER BY 1)",
+                    CalcError::InvalidRequestFormat(String::from("not yet supported: ORDER BY")),
+                ),
+            ];
+            for (query, expected) in errors {
+                assert_eq!(engine.exec(query).unwrap_err(), expected, "{}", query);
+            }
         }
 
         #[test]
-        fn exec_multy_operator() {
-            let res = exec(String::from("SELECT 1 + 1 * 3"));
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 4.0);
-            } else {
-                panic!();
+        fn exec_scalar_subqueries() {
+            let engine = Engine::default();
+            engine.exec("CREATE TABLE t(n INT)").unwrap();
+            engine.exec("INSERT INTO t VALUES (1), (4), (9)").unwrap();
+            assert_eq!(
+                engine.exec("SELECT (SELECT 1 + 1) * 3").unwrap(),
+                CalcResult::Int(6)
+            );
+            assert_eq!(
+                engine.exec("SELECT (SELECT MAX(n) FROM t) - 1").unwrap(),
+                CalcResult::Int(8)
+            );
+            assert_eq!(
+                engine.exec("SELECT (SELECT n FROM t WHERE n > 9)").unwrap(),
+                CalcResult::Null
+            );
+            assert_eq!(
+                engine
+                    .exec("SELECT n FROM t WHERE n > (SELECT AVG(n) FROM t)")
+                    .unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(9)]])
+            );
+            // The subquery sees the columns of the row the outer query is on.
+            assert_eq!(
+                engine
+                    .exec("SELECT n, (SELECT COUNT(*) FROM t u WHERE u.n < t.n) FROM t")
+                    .unwrap(),
+                CalcResult::Table(vec![
+                    vec![CalcResult::Int(1), CalcResult::Int(0)],
+                    vec![CalcResult::Int(4), CalcResult::Int(1)],
+                    vec![CalcResult::Int(9), CalcResult::Int(2)],
+                ])
+            );
+
+            let errors = [
+                (
+                    "SELECT (SELECT n FROM t)",
+                    "Subquery (SELECT n FROM t) gives more than one row",
+                ),
+                (
+                    "SELECT (SELECT 1, 2)",
+                    "Subquery (SELECT 1, 2) gives 2 columns, expected 1",
+                ),
+                (
+                    "SELECT (SELECT 1 ORDER BY 1)",
+                    "not yet supported: ORDER BY",
+                ),
+            ];
+            for (query, message) in errors {
+                assert_eq!(
+                    engine.exec(query).unwrap_err(),
+                    CalcError::InvalidRequestFormat(String::from(message)),
+                    "{}",
+                    query
+                );
             }
         }
 
         #[test]
-        fn exec_gt_operator() {
-            let res = exec(String::from("SELECT 2 > 3"));
-            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(true)) {
-                assert!(!val);
-            } else {
-                panic!();
+        fn exec_subquery_predicates() {
+            let engine = Engine::default();
+            engine
+                .exec("CREATE TABLE users(id INT, name TEXT)")
+                .unwrap();
+            engine
+                .exec("INSERT INTO users VALUES (1, 'ann'), (2, 'bob'), (NULL, 'cy')")
+                .unwrap();
+            engine.exec("CREATE TABLE orders(user_id INT)").unwrap();
+            engine
+                .exec("INSERT INTO orders VALUES (1), (1), (NULL)")
+                .unwrap();
+            let names = |names: &[&str]| {
+                CalcResult::Table(
+                    names
+                        .iter()
+                        .map(|name| vec![CalcResult::Str(name.to_string())])
+                        .collect(),
+                )
+            };
+            let cases = [
+                (
+                    "SELECT name FROM users u WHERE EXISTS (SELECT 1 FROM orders WHERE user_id = u.id)",
+                    names(&["ann"]),
+                ),
+                (
+                    "SELECT name FROM users u WHERE NOT EXISTS (SELECT 1 FROM orders WHERE user_id = u.id)",
+                    names(&["bob", "cy"]),
+                ),
+                (
+                    "SELECT name FROM users WHERE id IN (SELECT user_id FROM orders)",
+                    names(&["ann"]),
+                ),
+                // NULL among the values leaves every other id unknown.
+                (
+                    "SELECT name FROM users WHERE id NOT IN (SELECT user_id FROM orders)",
+                    names(&[]),
+                ),
+                (
+                    "SELECT name FROM users WHERE id NOT IN (SELECT user_id FROM orders WHERE user_id IS NOT NULL)",
+                    names(&["bob"]),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
             }
+            assert_eq!(
+                engine.exec("SELECT 1 IN (SELECT 1, 2)").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "Subquery (SELECT 1, 2) gives 2 columns, expected 1"
+                ))
+            );
         }
 
         #[test]
-        fn exec_operators_with_quotas() {
-            let res = exec(String::from("SELECT (1 + (2+3+4)-5)+(6+7)"));
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 18.0);
-            } else {
-                panic!();
+        fn exec_common_table_expressions() {
+            let engine = Engine::default();
+            engine.exec("CREATE TABLE t(a INT)").unwrap();
+            engine.exec("INSERT INTO t VALUES (1), (2), (3)").unwrap();
+            let ints = |values: &[i64]| {
+                CalcResult::Table(values.iter().map(|v| vec![CalcResult::Int(*v)]).collect())
+            };
+            let cases = [
+                ("WITH x AS (SELECT 1+1 AS v) SELECT v * 2 FROM x", ints(&[4])),
+                (
+                    "WITH x(v) AS (SELECT a FROM t WHERE a > 1), y AS (SELECT v + 10 AS w FROM x) SELECT w FROM y",
+                    ints(&[12, 13]),
+                ),
+                // A common table expression hides a table of the same name.
+                ("WITH t AS (SELECT 7 AS a) SELECT a FROM t", ints(&[7])),
+                (
+                    "SELECT a FROM t WHERE a IN (WITH m AS (SELECT 2 AS a) SELECT a FROM m)",
+                    ints(&[2]),
+                ),
+                ("SELECT x.v FROM (WITH x AS (SELECT 5 AS v) SELECT v FROM x) x", ints(&[5])),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
             }
+
+            // The names go out of reach with the query.
+            assert_eq!(engine.exec("SELECT a FROM t").unwrap(), ints(&[1, 2, 3]));
+            assert_eq!(
+                engine.exec("SELECT v FROM x").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from("Unknown table x"))
+            );
         }
 
         #[test]
-        fn exec_unsupported_operators() {
-            if std::mem::discriminant(&CalcError::UnsupportedOperator(String::from("")))
-                != std::mem::discriminant(&exec(String::from("SELECT 1 / 2")).unwrap_err())
-            {
-                panic!();
+        fn exec_recursive_common_table_expressions() {
+            let engine = Engine::default();
+            let ints = |values: &[i64]| {
+                CalcResult::Table(values.iter().map(|v| vec![CalcResult::Int(*v)]).collect())
+            };
+            assert_eq!(
+                engine
+                    .exec("WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 5) SELECT i FROM n")
+                    .unwrap(),
+                ints(&[1, 2, 3, 4, 5])
+            );
+            // Without ALL, rows already found end the recursion.
+            assert_eq!(
+                engine
+                    .exec("WITH RECURSIVE n(i) AS (SELECT 0 UNION SELECT (i + 1) % 3 FROM n) SELECT i FROM n")
+                    .unwrap(),
+                ints(&[0, 1, 2])
+            );
+
+            engine.exec("CREATE TABLE staff(id INT, boss INT)").unwrap();
+            engine
+                .exec("INSERT INTO staff VALUES (1, NULL), (2, 1), (3, 2), (4, 1), (5, 3)")
+                .unwrap();
+            assert_eq!(
+                engine
+                    .exec("WITH RECURSIVE chain AS (SELECT id, 0 AS depth FROM staff WHERE id = 5 UNION ALL SELECT s.boss, c.depth + 1 FROM staff s JOIN chain c ON s.id = c.id WHERE s.boss IS NOT NULL) SELECT id FROM chain")
+                    .unwrap(),
+                ints(&[5, 3, 2, 1])
+            );
+            // Queries not referring to themselves still work.
+            assert_eq!(
+                engine
+                    .exec("WITH RECURSIVE x AS (SELECT 1 AS v) SELECT v FROM x")
+                    .unwrap(),
+                ints(&[1])
+            );
+
+            let engine = limited(Limits {
+                max_recursion: 10,
+                ..Limits::default()
+            });
+            assert_eq!(
+                engine
+                    .exec("WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT i FROM n")
+                    .unwrap_err(),
+                CalcError::ResourceLimit(String::from(
+                    "recursive query n did not finish within 10 iterations"
+                ))
+            );
+            assert_eq!(
+                engine
+                    .exec("WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i, i FROM n) SELECT i FROM n")
+                    .unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "UNION branches have 1 and 2 column(s)"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_window_functions() {
+            let engine = Engine::default();
+            engine
+                .exec("CREATE TABLE sales(region TEXT, amount INT)")
+                .unwrap();
+            engine
+                .exec(
+                    "INSERT INTO sales VALUES ('n', 10), ('s', 5), ('n', 30), ('n', 10), ('s', 7)",
+                )
+                .unwrap();
+            let table = |rows: &[&[i64]]| {
+                CalcResult::Table(
+                    rows.iter()
+                        .map(|row| row.iter().map(|v| CalcResult::Int(*v)).collect())
+                        .collect(),
+                )
+            };
+            let cases = [
+                (
+                    "SELECT amount, ROW_NUMBER() OVER (PARTITION BY region ORDER BY amount DESC) FROM sales",
+                    table(&[&[10, 2], &[5, 2], &[30, 1], &[10, 3], &[7, 1]]),
+                ),
+                (
+                    "SELECT amount, RANK() OVER (ORDER BY amount) FROM sales",
+                    table(&[&[10, 3], &[5, 1], &[30, 5], &[10, 3], &[7, 2]]),
+                ),
+                // Tied rows are summed together.
+                (
+                    "SELECT amount, SUM(amount) OVER (PARTITION BY region ORDER BY amount) FROM sales",
+                    table(&[&[10, 20], &[5, 5], &[30, 50], &[10, 20], &[7, 12]]),
+                ),
+                (
+                    "SELECT amount, SUM(amount) OVER (PARTITION BY region) FROM sales",
+                    table(&[&[10, 50], &[5, 12], &[30, 50], &[10, 50], &[7, 12]]),
+                ),
+                (
+                    "SELECT amount, LAG(amount, 1, 0) OVER (ORDER BY amount) + LEAD(amount, 1, 0) OVER (ORDER BY amount) FROM sales WHERE region = 'n'",
+                    table(&[&[10, 10], &[30, 10], &[10, 40]]),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
             }
+            assert_eq!(
+                engine
+                    .exec("SELECT AVG(amount) OVER (PARTITION BY region) FROM sales WHERE amount < 10")
+                    .unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Num(6.0)], vec![CalcResult::Num(6.0)]])
+            );
+            assert_eq!(
+                engine
+                    .exec("SELECT LEAD(amount) OVER (ORDER BY amount DESC NULLS LAST) FROM sales WHERE amount > 10")
+                    .unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Null]])
+            );
+
+            let errors = [
+                (
+                    "SELECT ROW_NUMBER() FROM sales",
+                    "ROW_NUMBER requires an OVER clause",
+                ),
+                (
+                    "SELECT amount FROM sales WHERE RANK() OVER () = 1",
+                    "Window function RANK is not allowed here",
+                ),
+                (
+                    "SELECT region, RANK() OVER () FROM sales GROUP BY region",
+                    "not yet supported: window functions with GROUP BY or aggregates",
+                ),
+            ];
+            for (query, message) in errors {
+                assert_eq!(
+                    engine.exec(query).unwrap_err(),
+                    CalcError::InvalidRequestFormat(String::from(message)),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine.exec("SELECT LAG() OVER () FROM sales").unwrap_err(),
+                CalcError::InvalidType(String::from("LAG expects 1 to 3 argument(s), got 0"))
+            );
+            assert_eq!(
+                engine
+                    .exec("SELECT NTILE(2) OVER () FROM sales")
+                    .unwrap_err(),
+                CalcError::UnsupportedFunc(String::from("Unknown window function NTILE"))
+            );
         }
 
         #[test]
-        fn exec_func_sqrt() {
-            let res = exec(String::from("SELECT SQRT(5 + 2 * 4)"));
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 3.605551275463989);
-            } else {
-                panic!();
+        fn exec_session_variables() {
+            let engine = Engine::default();
+            assert_eq!(
+                engine.exec("SET x = 5"),
+                Ok(CalcResult::Str(String::from("SET x")))
+            );
+            assert_eq!(engine.exec("SELECT @x * 2"), Ok(CalcResult::Int(10)));
+            assert_eq!(engine.exec("SELECT X * 2"), Ok(CalcResult::Int(10)));
+            engine
+                .exec("SET @rate TO @x / 2.0; SET x = 'five'")
+                .unwrap();
+            assert_eq!(engine.exec("SELECT @rate"), Ok(CalcResult::Num(2.5)));
+            assert_eq!(
+                engine.exec("SELECT @x"),
+                Ok(CalcResult::Str(String::from("five")))
+            );
+
+            // Columns come before variables of the same name.
+            engine.exec("CREATE TABLE t(x INT)").unwrap();
+            engine.exec("INSERT INTO t VALUES (1), (7)").unwrap();
+            engine
+                .exec("SET limit_value = (SELECT MAX(x) FROM t)")
+                .unwrap();
+            assert_eq!(
+                engine.exec("SELECT x FROM t WHERE x < limit_value"),
+                Ok(CalcResult::Table(vec![vec![CalcResult::Int(1)]]))
+            );
+
+            assert_eq!(
+                engine.exec("SELECT @y").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from("Unknown variable @y"))
+            );
+            assert_eq!(
+                engine.exec("SELECT y").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from("Unknown column y"))
+            );
+            assert!(engine.exec("SET y = 1 / 0").is_err());
+            assert!(engine.exec("SELECT y").is_err());
+            assert!(engine.validate("SET y = 1").is_ok());
+        }
+
+        #[test]
+        fn exec_explain() {
+            let engine = Engine::default();
+            let evaluation = engine.eval("EXPLAIN SELECT SQRT(2) * 3").unwrap();
+            assert_eq!(
+                evaluation.to_string(),
+                "Result: 6 rows
+QUERY PLAN
+Select
+  Projection
+    Operator *
+      Function SQRT
+        Literal 2
+      Literal 3"
+            );
+
+            // Explaining checks the query without evaluating it.
+            assert!(engine.exec("EXPLAIN SELECT x FROM missing").is_ok());
+            assert_eq!(
+                engine.exec("EXPLAIN SELECT 1 LIMIT 1").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from("not yet supported: LIMIT"))
+            );
+            assert_eq!(
+                engine.exec("EXPLAIN DROP TABLE t").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from("only Queries are supported"))
+            );
+            assert!(engine.validate("EXPLAIN SELECT 1").is_ok());
+        }
+
+        #[test]
+        fn exec_explain_analyze() {
+            let engine = Engine::new(Options::deterministic());
+            engine.exec("CREATE TABLE t(x INT)").unwrap();
+            engine.exec("INSERT INTO t VALUES (1), (4), (9)").unwrap();
+            let evaluation = engine
+                .eval("EXPLAIN ANALYZE SELECT SQRT(x) FROM t WHERE x > (SELECT 1)")
+                .unwrap();
+            assert_eq!(
+                evaluation.rows()[1..]
+                    .iter()
+                    .map(|row| to_text(&row[0]))
+                    .collect::<Vec<_>>(),
+                [
+                    "From",
+                    "  Table t (loops=1 rows=3)",
+                    "Where",
+                    "  Operator >",
+                    "    Column x",
+                    "    Subquery",
+                    "      Select (loops=3 rows=3)",
+                    "        Projection",
+                    "          Literal 1",
+                    "Projection",
+                    "  Function SQRT (calls=2)",
+                    "    Column x",
+                ]
+                .map(|line| format!("  {}", line))
+            );
+            assert_eq!(to_text(&evaluation.rows()[0][0]), "Select (loops=1 rows=2)");
+
+            // The query runs, so its errors are reported.
+            assert_eq!(
+                engine.exec("EXPLAIN ANALYZE SELECT 1 / 0").unwrap_err(),
+                CalcError::DivisionByZero
+            );
+            assert!(engine.exec("EXPLAIN ANALYZE SELECT * FROM t").is_ok());
+
+            // Without a deterministic engine, the time is measured too.
+            let engine = Engine::default();
+            let plan = engine.eval("EXPLAIN ANALYZE SELECT 1").unwrap();
+            assert!(to_text(&plan.rows()[0][0]).contains(" time="));
+        }
+
+        #[test]
+        fn eval_reports_timing() {
+            // A fixed clock only fixes NOW(), not the durations measured.
+            let engine = Engine::new(Options::deterministic());
+            let timing = engine
+                .eval("WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 200) SELECT SUM(x) FROM n")
+                .unwrap()
+                .timing;
+            assert!(timing.parse > Duration::ZERO);
+            assert!(timing.eval > Duration::ZERO);
+
+            // A script adds up the time of its statements.
+            let script = engine.eval("SET x = 1; SELECT @x").unwrap().timing;
+            assert!(script.parse > Duration::ZERO);
+        }
+
+        /// A table remembering the projection of every scan.
+        struct Recording(MemTable, Mutex<Vec<Option<Vec<usize>>>>);
+
+        impl TableProvider for Recording {
+            fn schema(&self) -> Vec<Column> {
+                self.0.schema()
+            }
+
+            fn scan(
+                &self,
+                filters: &[Expr],
+                projection: Option<&[usize]>,
+            ) -> Result<Vec<Row>, CalcError> {
+                self.1
+                    .lock()
+                    .unwrap()
+                    .push(projection.map(<[usize]>::to_vec));
+                self.0.scan(filters, projection)
             }
         }
 
         #[test]
-        fn exec_func_sqrt_gt() {
-            let res = exec(String::from("SELECT SQRT(16) > SQRT(4)"));
-            if let CalcResult::Bool(val) = res.unwrap_or(CalcResult::Bool(false)) {
-                assert!(val);
-            } else {
-                panic!();
+        fn exec_prunes_columns() {
+            let table = MemTable::new(
+                vec![
+                    Column::new("a", ValueType::Integer),
+                    Column::new("b", ValueType::Integer),
+                    Column::new("c", ValueType::Integer),
+                ],
+                vec![vec![
+                    CalcResult::Int(1),
+                    CalcResult::Int(2),
+                    CalcResult::Int(3),
+                ]],
+            )
+            .unwrap();
+            let table = Arc::new(Recording(table, Mutex::new(Vec::new())));
+            let mut engine = Engine::default();
+            engine.register_table("t", table.clone());
+
+            let queries = [
+                "SELECT c, a FROM t",
+                "SELECT b FROM t WHERE c > 2",
+                "SELECT 1 FROM t",
+                "SELECT * FROM t",
+            ];
+            for query in queries {
+                engine.exec(query).unwrap();
             }
+            assert_eq!(
+                *table.1.lock().unwrap(),
+                vec![Some(vec![0, 2]), Some(vec![1, 2]), Some(vec![]), None]
+            );
+            assert_eq!(
+                engine.exec("SELECT c - a FROM t").unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(2)]])
+            );
         }
 
         #[test]
-        fn exec_func_unsupported() {
-            if std::mem::discriminant(&CalcError::UnsupportedFunc(String::from("")))
-                != std::mem::discriminant(&exec(String::from("SELECT Log(2)")).unwrap_err())
+        fn eval_aliases() {
+            let engine = Engine::default();
+            let cases = [
+                (
+                    "SELECT 1+1 AS total",
+                    "Result: total = 2",
+                    vec![Some("total")],
+                ),
+                ("SELECT 1+1 total", "Result: total = 2", vec![Some("total")]),
+                (
+                    "SELECT 1 AS a, 'x', NULL AS \"Empty Value\"",
+                    "Result: a = 1, x, Empty Value = NULL",
+                    vec![Some("a"), None, Some("Empty Value")],
+                ),
+                ("SELECT 1, 2", "Result: 1, 2", vec![None, None]),
+            ];
+            for (query, printed, columns) in cases {
+                let evaluation = engine.eval(query).unwrap();
+                assert_eq!(evaluation.to_string(), printed, "{}", query);
+                assert_eq!(
+                    evaluation.columns,
+                    columns
+                        .into_iter()
+                        .map(|alias| alias.map(String::from))
+                        .collect::<Vec<_>>(),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine.eval("SELECT 1 + 1 AS total, 2").unwrap().headers,
+                vec![String::from("total"), String::from("2")]
+            );
+            assert_eq!(
+                engine.eval("SET @x = 1").unwrap().headers,
+                vec![String::from("result")]
+            );
+            assert_eq!(
+                engine.exec("SELECT 1+1 AS total").unwrap(),
+                CalcResult::Int(2)
+            );
+        }
+
+        #[test]
+        fn exec_multiple_statements() {
+            assert_eq!(
+                exec(String::from("SELECT 1; SELECT 2;")),
+                Ok(CalcResult::Int(2))
+            );
+
+            let engine = Engine::default();
+            assert_eq!(
+                engine.exec(
+                    "CREATE TABLE t(x INT); INSERT INTO t VALUES (1), (2); SELECT SUM(x) FROM t"
+                ),
+                Ok(CalcResult::Table(vec![vec![CalcResult::Int(3)]]))
+            );
+            let results = engine.eval_script("CREATE TABLE u(x INT); SELECT 1 / 0; DROP TABLE t");
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[1].as_ref().unwrap_err(), &CalcError::DivisionByZero);
+            assert!(engine.exec("SELECT x FROM t").is_ok());
+            assert!(engine.validate("SELECT 1; SELECT 2").is_ok());
+
+            let evaluation = Engine::new(Options {
+                coercion: Coercion::Lenient,
+                ..Options::default()
+            })
+            .eval("SELECT '1' + 1; SELECT '2' + 2")
+            .unwrap();
+            assert_eq!(evaluation.result, CalcResult::Int(4));
+            assert_eq!(evaluation.warnings.len(), 2);
+        }
+
+        #[test]
+        fn exec_invalied_statement() {
+            if std::mem::discriminant(&CalcError::InvalidRequestFormat(String::from("")))
+                != std::mem::discriminant(&exec(String::from("INSERT INTO table")).unwrap_err())
             {
                 panic!();
             }
         }
 
         #[test]
-        fn exec_cast() {
-            let res = exec(String::from("SELECT CAST('2' as int)"));
-            if let CalcResult::Num(val) = res.unwrap_or(CalcResult::Num(-1.0)) {
-                assert_eq!(val, 2.0);
+        fn exec_parser_error_location() {
+            assert_eq!(
+                exec(String::from("SELECT 1 +\n  )")).unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "Expected an expression:, found: ) at Line: 2, Column 3"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_parser_error_eof() {
+            assert_eq!(
+                exec(String::from("SELECT (1 + 2")).unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "Expected ), found: EOF at Line: 1, Column 14"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_parser_error_missing_delimiter() {
+            assert_eq!(
+                exec(String::from("SELECT 1 SELECT 2")).unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "Expected end of statement, found: SELECT at Line: 1, Column 10"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_tokenizer_error_location() {
+            assert_eq!(
+                exec(String::from("SELECT 'abc")).unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "Unterminated string literal at Line: 1, Column 8"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_comparison_operators() {
+            let cases = [
+                ("SELECT 2 >= 2", true),
+                ("SELECT 2 < 1", false),
+                ("SELECT 1 <= 2", true),
+                ("SELECT 1 + 1 = 2", true),
+                ("SELECT 1 <> 1", false),
+            ];
+            for (query, expected) in cases {
+                if let CalcResult::Bool(val) = exec(String::from(query)).unwrap() {
+                    assert_eq!(val, expected, "{}", query);
+                } else {
+                    panic!();
+                }
+            }
+        }
+
+        #[test]
+        fn exec_assert_passes() {
+            for query in ["ASSERT 1 + 1 = 2", "ASSERT SELECT 1 + 1 = 2"] {
+                assert!(matches!(
+                    exec(String::from(query)).unwrap(),
+                    CalcResult::Bool(true)
+                ));
+            }
+        }
+
+        #[test]
+        fn exec_assert_mismatch() {
+            assert_eq!(
+                exec(String::from("ASSERT SELECT 1 + 1 = 3")).unwrap_err(),
+                CalcError::AssertionFailed(String::from(
+                    "ASSERT failed: 1 + 1 = 3 (left: 2, right: 3)"
+                ))
+            );
+            assert_eq!(
+                exec(String::from("ASSERT SQRT(4) > 2 AS 'root too small'")).unwrap_err(),
+                CalcError::AssertionFailed(String::from(
+                    "root too small: SQRT(4) > 2 (left: 2, right: 2)"
+                ))
+            );
+        }
+
+        #[test]
+        fn exec_assert_evaluates_once() {
+            let reference = Engine::new(Options::deterministic());
+            let first = reference.exec("SELECT RANDOM()").unwrap();
+            let second = reference.exec("SELECT RANDOM()").unwrap();
+
+            let engine = Engine::new(Options::deterministic());
+            assert_eq!(
+                engine.exec("ASSERT RANDOM() < 0").unwrap_err(),
+                CalcError::AssertionFailed(format!(
+                    "ASSERT failed: RANDOM() < 0 (left: {}, right: 0)",
+                    describe(&first)
+                ))
+            );
+            assert_eq!(engine.exec("SELECT RANDOM()").unwrap(), second);
+        }
+
+        #[test]
+        fn exec_assert_not_boolean() {
+            assert_eq!(
+                exec(String::from("ASSERT 1 + 1")).unwrap_err(),
+                CalcError::InvalidType(String::from("ASSERT condition 1 + 1 is not Boolean"))
+            );
+        }
+
+        #[test]
+        fn coverage_accumulates_across_queries() {
+            let engine = Engine::default();
+            engine.exec("SELECT SQRT(4) + 1").unwrap();
+            engine.exec("ASSERT 1 + 1 = 2").unwrap();
+            let coverage = engine.coverage();
+            assert_eq!(coverage.hits(Category::Clause, "SELECT"), 1);
+            assert_eq!(coverage.hits(Category::Clause, "ASSERT"), 1);
+            assert_eq!(coverage.hits(Category::Operator, "+"), 2);
+            assert_eq!(coverage.hits(Category::Function, "SQRT"), 1);
+            assert_eq!(coverage.hits(Category::Type, "String"), 0);
+        }
+
+        #[test]
+        fn surface_lists_temporal_types() {
+            let engine = Engine::new(Options::deterministic());
+            engine
+                .exec("SELECT DATE '2024-01-01', NOW(), INTERVAL '1 day'")
+                .unwrap();
+            let coverage = engine.coverage();
+            let surface = engine.surface();
+            for name in ["Date", "Timestamp", "Interval"] {
+                assert_eq!(coverage.hits(Category::Type, name), 1, "{}", name);
+                assert!(
+                    surface.contains(&(Category::Type, name.to_string())),
+                    "{}",
+                    name
+                );
+            }
+        }
+
+        #[test]
+        fn deterministic_clock_and_random() {
+            let first = Engine::new(Options::deterministic());
+            let second = Engine::new(Options::deterministic());
+            assert_eq!(
+                first.now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+                946_684_800
+            );
+            for _ in 0..5 {
+                let random = first.random();
+                assert!((0.0..1.0).contains(&random));
+                assert_eq!(random, second.random());
+            }
+        }
+
+        #[test]
+        fn exec_clock_functions() {
+            let engine = Engine::new(Options::deterministic());
+            let timestamp = |text: &str| {
+                CalcResult::Timestamp(temporal::parse_timestamp(text, &Zone::default()).unwrap())
+            };
+            let cases = [
+                ("SELECT NOW()", timestamp("2000-01-01 00:00:00")),
+                ("SELECT CURRENT_TIMESTAMP", timestamp("2000-01-01 00:00:00")),
+                (
+                    "SELECT current_date",
+                    CalcResult::Date(temporal::parse_date("2000-01-01").unwrap()),
+                ),
+                ("SELECT CURRENT_DATE = NOW()", CalcResult::Bool(true)),
+                (
+                    "SELECT NOW() < TIMESTAMP '2000-01-01 00:00:01'",
+                    CalcResult::Bool(true),
+                ),
+                (
+                    "SELECT DATE '2024-05-01' BETWEEN DATE '2024-01-01' AND DATE '2024-12-31'",
+                    CalcResult::Bool(true),
+                ),
+                (
+                    "SELECT 'day ' || DATE '2024-05-01'",
+                    CalcResult::Str(String::from("day 2024-05-01")),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            let cases = [
+                (
+                    "SELECT EXTRACT(YEAR FROM TIMESTAMP '2024-05-01')",
+                    CalcResult::Num(2024.0),
+                ),
+                (
+                    "SELECT EXTRACT(DAY FROM DATE '2024-05-17') + 1",
+                    CalcResult::Num(18.0),
+                ),
+                ("SELECT EXTRACT(MONTH FROM NOW())", CalcResult::Num(1.0)),
+                (
+                    "SELECT DATE_TRUNC('month', TIMESTAMP '2024-05-17 10:20:30')",
+                    timestamp("2024-05-01 00:00:00"),
+                ),
+                (
+                    "SELECT DATE_TRUNC('year', DATE '2024-05-17')",
+                    timestamp("2024-01-01 00:00:00"),
+                ),
+                ("SELECT DATE_TRUNC('day', NULL)", CalcResult::Null),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine
+                    .exec("SELECT EXTRACT(YEAR FROM '2024-05-01')")
+                    .unwrap_err(),
+                CalcError::InvalidType(String::from(
+                    "EXTRACT supports only Date and Timestamp, got String"
+                ))
+            );
+            assert_eq!(
+                engine.exec("SELECT DATE '2024-13-01'").unwrap_err(),
+                CalcError::InvalidType(String::from("Invalid DATE '2024-13-01'"))
+            );
+            assert!(engine.exec("SELECT NOW(1)").is_err());
+            assert!(engine.exec("SELECT NOW() > 1").is_err());
+
+            // the system clock is read once per statement
+            let engine = Engine::default();
+            assert_eq!(
+                engine.exec("SELECT NOW() = NOW()").unwrap(),
+                CalcResult::Bool(true)
+            );
+        }
+
+        #[test]
+        fn exec_time_zone() {
+            let engine = Engine::new(Options {
+                time_zone: Zone::parse("Asia/Tokyo").unwrap(),
+                ..Options::deterministic()
+            });
+            let timestamp = |text: &str| {
+                CalcResult::Timestamp(temporal::parse_timestamp(text, &Zone::default()).unwrap())
+            };
+            let interval =
+                |text: &str| CalcResult::Interval(temporal::parse_interval(text, None).unwrap());
+            let cases = [
+                ("SELECT NOW()", timestamp("2000-01-01 09:00:00")),
+                (
+                    "SHOW TIME ZONE",
+                    CalcResult::Str(String::from("Asia/Tokyo")),
+                ),
+                (
+                    "SET TIME ZONE 'america/new_york'",
+                    CalcResult::Str(String::from("SET TIME ZONE")),
+                ),
+                (
+                    "SHOW TIMEZONE",
+                    CalcResult::Str(String::from("America/New_York")),
+                ),
+                ("SELECT NOW()", timestamp("1999-12-31 19:00:00")),
+                (
+                    "SELECT CURRENT_DATE",
+                    CalcResult::Date(temporal::parse_date("1999-12-31").unwrap()),
+                ),
+                (
+                    "SELECT TIMESTAMP '2024-05-01 12:00:00Z'",
+                    timestamp("2024-05-01 08:00:00"),
+                ),
+                (
+                    "SELECT CAST('2024-05-01T12:00:00+02:00' AS TIMESTAMP)",
+                    timestamp("2024-05-01 06:00:00"),
+                ),
+                (
+                    "SELECT TIMESTAMP '2024-03-10 02:30'",
+                    timestamp("2024-03-10 03:30:00"),
+                ),
+                (
+                    "SELECT CAST('2024-03-10 02:30' AS TIMESTAMP)",
+                    timestamp("2024-03-10 03:30:00"),
+                ),
+                (
+                    "SET TIME ZONE 'Europe/Berlin'",
+                    CalcResult::Str(String::from("SET TIME ZONE")),
+                ),
+                (
+                    "SELECT TIMESTAMP '2024-03-30 12:00' + INTERVAL '24 hours'",
+                    timestamp("2024-03-31 13:00:00"),
+                ),
+                (
+                    "SELECT TIMESTAMP '2024-03-30 12:00' + INTERVAL '1 day'",
+                    timestamp("2024-03-31 12:00:00"),
+                ),
+                (
+                    "SELECT TIMESTAMP '2024-03-31 12:00' - TIMESTAMP '2024-03-30 12:00'",
+                    interval("23 hours"),
+                ),
+                (
+                    "SET TIME ZONE '+05:30'",
+                    CalcResult::Str(String::from("SET TIME ZONE")),
+                ),
+                ("SELECT NOW()", timestamp("2000-01-01 05:30:00")),
+                (
+                    "SET TIME ZONE DEFAULT",
+                    CalcResult::Str(String::from("SET TIME ZONE")),
+                ),
+                (
+                    "SHOW TIME ZONE",
+                    CalcResult::Str(String::from("Asia/Tokyo")),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            let unknown = CalcError::InvalidType(String::from("Unknown time zone 'Mars/Base'"));
+            assert_eq!(
+                engine.exec("SET TIME ZONE 'Mars/Base'").unwrap_err(),
+                unknown
+            );
+            assert_eq!(
+                engine.validate("SET TIME ZONE 'Mars/Base'").unwrap_err(),
+                unknown
+            );
+            assert_eq!(engine.validate("SET TIME ZONE 'UTC'"), Ok(()));
+            assert_eq!(
+                engine.exec("SHOW TIME ZONE").unwrap(),
+                CalcResult::Str(String::from("Asia/Tokyo"))
+            );
+        }
+
+        #[test]
+        fn exec_date_arithmetic() {
+            let engine = Engine::new(Options::deterministic());
+            let timestamp = |text: &str| {
+                CalcResult::Timestamp(temporal::parse_timestamp(text, &Zone::default()).unwrap())
+            };
+            let interval =
+                |text: &str| CalcResult::Interval(temporal::parse_interval(text, None).unwrap());
+            let cases = [
+                (
+                    "SELECT DATE '2024-01-01' + INTERVAL '3 days'",
+                    timestamp("2024-01-04 00:00:00"),
+                ),
+                (
+                    "SELECT NOW() - INTERVAL '1' MONTH",
+                    timestamp("1999-12-01 00:00:00"),
+                ),
+                (
+                    "SELECT INTERVAL '1 day' + INTERVAL '90 minutes'",
+                    interval("1 day 1.5 hours"),
+                ),
+                ("SELECT - INTERVAL '2 hours'", interval("-2 hours")),
+                (
+                    "SELECT DATE '2024-03-01' - DATE '2024-02-01'",
+                    CalcResult::Int(29),
+                ),
+                (
+                    "SELECT TIMESTAMP '2024-01-02 06:00' - TIMESTAMP '2024-01-01 00:00'",
+                    interval("1 day 6 hours"),
+                ),
+                (
+                    "SELECT DATE '2024-01-31' + INTERVAL '1 month' = DATE '2024-02-29'",
+                    CalcResult::Bool(true),
+                ),
+                (
+                    "SELECT 'in ' || INTERVAL '1 year 2 months'",
+                    CalcResult::Str(String::from("in 1 year 2 months")),
+                ),
+                ("SELECT DATE '2024-01-01' + NULL", CalcResult::Null),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+            assert_eq!(
+                engine.exec("SELECT INTERVAL '3 fortnights'").unwrap_err(),
+                CalcError::InvalidType(String::from("Invalid INTERVAL '3 fortnights'"))
+            );
+            assert!(engine
+                .exec("SELECT INTERVAL '1 day' - DATE '2024-01-01'")
+                .is_err());
+            assert!(engine
+                .exec("SELECT DATE '2024-01-01' * INTERVAL '1 day'")
+                .is_err());
+        }
+
+        #[test]
+        fn exec_interval_comparisons() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT INTERVAL '1 day' < INTERVAL '2 days'", true),
+                ("SELECT INTERVAL '1 day' = INTERVAL '24 hours'", true),
+                ("SELECT INTERVAL '1 month' = INTERVAL '30 days'", true),
+                ("SELECT INTERVAL '1 day' <> INTERVAL '25 hours'", true),
+                ("SELECT INTERVAL '1 year' >= INTERVAL '11 months'", true),
+                ("SELECT - INTERVAL '1 hour' > INTERVAL '0 days'", false),
+                (
+                    "SELECT INTERVAL '36 hours' BETWEEN INTERVAL '1 day' AND INTERVAL '2 days'",
+                    true,
+                ),
+                ("SELECT INTERVAL '60 minutes' IN (INTERVAL '1 hour')", true),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(
+                    engine.exec(query).unwrap(),
+                    CalcResult::Bool(expected),
+                    "{}",
+                    query
+                );
+            }
+            assert_eq!(
+                engine
+                    .exec(
+                        "SELECT COUNT(DISTINCT i) FROM (VALUES (INTERVAL '1 day'), (INTERVAL '24 hours'), (INTERVAL '1 hour')) AS t(i)"
+                    )
+                    .unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(2)]])
+            );
+            assert!(engine
+                .exec("SELECT INTERVAL '1 day' < DATE '2024-01-01'")
+                .is_err());
+        }
+
+        #[test]
+        fn exec_boolean_comparisons() {
+            let engine = Engine::default();
+            let cases = [
+                ("SELECT TRUE = TRUE", CalcResult::Bool(true)),
+                ("SELECT TRUE <> FALSE", CalcResult::Bool(true)),
+                ("SELECT FALSE < TRUE", CalcResult::Bool(true)),
+                ("SELECT (1 < 2) = (3 > 4)", CalcResult::Bool(false)),
+                ("SELECT TRUE IN (TRUE, FALSE)", CalcResult::Bool(true)),
+                ("SELECT FALSE NOT IN (TRUE)", CalcResult::Bool(true)),
+                ("SELECT TRUE = NULL", CalcResult::Null),
+                (
+                    "SELECT CASE TRUE WHEN FALSE THEN 'no' WHEN TRUE THEN 'yes' END",
+                    CalcResult::Str(String::from("yes")),
+                ),
+                ("SELECT NULLIF(TRUE, TRUE)", CalcResult::Null),
+                (
+                    "SELECT FALSE BETWEEN FALSE AND TRUE",
+                    CalcResult::Bool(true),
+                ),
+            ];
+            for (query, expected) in cases {
+                assert_eq!(engine.exec(query).unwrap(), expected, "{}", query);
+            }
+
+            engine
+                .exec("CREATE TABLE flags (id INT, a BOOLEAN)")
+                .unwrap();
+            engine
+                .exec("INSERT INTO flags VALUES (1, TRUE), (2, FALSE), (3, NULL)")
+                .unwrap();
+            assert_eq!(
+                engine.exec("SELECT id FROM flags WHERE a = TRUE").unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Int(1)]])
+            );
+            assert_eq!(
+                engine.exec("SELECT MAX(a), MIN(a) FROM flags").unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Bool(true), CalcResult::Bool(false)]])
+            );
+            assert!(engine.exec("SELECT TRUE = 1").is_err());
+        }
+
+        #[test]
+        fn exec_random() {
+            let seeded = || {
+                Engine::new(Options {
+                    seed: Some(42),
+                    ..Options::default()
+                })
+            };
+            let expected = seeded().random();
+            let engine = seeded();
+            assert_eq!(
+                engine.exec("SELECT RANDOM()").unwrap(),
+                CalcResult::Num(expected)
+            );
+            assert_ne!(
+                engine.exec("SELECT RANDOM()").unwrap(),
+                CalcResult::Num(expected)
+            );
+
+            let engine = Engine::default();
+            assert_eq!(engine.exec("SELECT SETSEED(42)").unwrap(), CalcResult::Null);
+            assert_eq!(
+                engine.exec("SELECT random()").unwrap(),
+                CalcResult::Num(expected)
+            );
+            assert_eq!(
+                engine.exec("SELECT SETSEED(-2)").unwrap_err(),
+                CalcError::InvalidType(String::from(
+                    "SETSEED expects a non-negative integer or a number from -1 to 1, got -2"
+                ))
+            );
+
+            // PostgreSQL's seeds: the same one repeats the sequence, another
+            // one starts a different one.
+            let draw = |seed: &str| {
+                engine
+                    .exec(&format!("SELECT SETSEED({}); SELECT RANDOM()", seed))
+                    .unwrap()
+            };
+            assert_eq!(draw("0.5"), draw("0.5"));
+            assert_ne!(draw("0.5"), draw("-0.5"));
+            assert_ne!(draw("0.5"), draw("0.25"));
+            assert_eq!(draw("-1"), draw("-1"));
+            assert_eq!(
+                engine.exec("SELECT RANDOM(1)").unwrap_err(),
+                CalcError::InvalidType(String::from("RANDOM expects 0 argument(s), got 1"))
+            );
+        }
+
+        #[test]
+        fn register_scalar_fn_callable() {
+            let mut engine = Engine::default();
+            engine.register_scalar_fn("double", 1, |args| match args {
+                [CalcResult::Int(int)] => Ok(CalcResult::Int(int * 2)),
+                _ => Err(CalcError::InvalidType(String::from(
+                    "DOUBLE supports only Integer",
+                ))),
+            });
+            if let CalcResult::Int(val) = engine.exec("SELECT Double(21)").unwrap() {
+                assert_eq!(val, 42);
             } else {
                 panic!();
             }
+            assert_eq!(
+                engine.exec("SELECT DOUBLE('a')").unwrap_err(),
+                CalcError::InvalidType(String::from("DOUBLE supports only Integer"))
+            );
+            assert!(engine.function_names().contains(&"DOUBLE"));
         }
 
         #[test]
-        fn exec_cast_invalied() {
-            if std::mem::discriminant(&CalcError::InvalidType(String::from("")))
-                != std::mem::discriminant(&exec(String::from("SELECT CAST('qwe' as int)")).unwrap_err())
+        fn register_scalar_fn_message_error() {
+            let mut engine = Engine::default();
+            engine.register_scalar_fn("lookup", 1, |_| Err("no such key"));
+            assert_eq!(
+                engine.exec("SELECT LOOKUP(1)").unwrap_err(),
+                CalcError::FunctionFailed(String::from("LOOKUP: no such key"))
+            );
+        }
+
+        #[test]
+        fn register_binary_op_on_strings() {
+            let mut engine = Engine::default();
+            engine.register_binary_op(
+                BinaryOperator::Minus,
+                ValueType::String,
+                ValueType::String,
+                |l, r| match (l, r) {
+                    (CalcResult::Str(l), CalcResult::Str(r)) => {
+                        Ok(CalcResult::Str(l.replace(r.as_str(), "")))
+                    }
+                    _ => Err(CalcError::Unexpected),
+                },
+            );
+            assert_eq!(
+                engine.exec("SELECT 'banana' - 'an'").unwrap(),
+                CalcResult::Str(String::from("ba"))
+            );
+            // other operand types keep the built-in behavior
+            assert_eq!(engine.exec("SELECT 3 - 1").unwrap(), CalcResult::Int(2));
+        }
+
+        #[test]
+        fn register_binary_op_overrides_builtin() {
+            let mut engine = Engine::default();
+            engine.register_binary_op(
+                BinaryOperator::Divide,
+                ValueType::Integer,
+                ValueType::Integer,
+                |_, _| Ok(CalcResult::Null),
+            );
+            assert_eq!(engine.exec("SELECT 1 / 0").unwrap(), CalcResult::Null);
+        }
+
+        #[test]
+        fn register_unary_op() {
+            let mut engine = Engine::default();
+            assert_eq!(
+                engine.exec("SELECT NOT 'x'").unwrap_err(),
+                CalcError::UnsupportedOperator(String::from(
+                    "Unary NOT is not supported for String"
+                ))
+            );
+            engine.register_unary_op(UnaryOperator::Not, ValueType::String, |operand| {
+                Ok(CalcResult::Bool(
+                    matches!(operand, CalcResult::Str(s) if s.is_empty()),
+                ))
+            });
+            assert_eq!(
+                engine.exec("SELECT NOT 'x'").unwrap(),
+                CalcResult::Bool(false)
+            );
+        }
+
+        #[test]
+        fn register_table_lookup() {
+            let mut engine = Engine::default();
+            let table = MemTable::new(vec![Column::new("x", ValueType::Number)], vec![]).unwrap();
+            engine.register_table("Items", Arc::new(table));
+            let name = |ident: Ident| ObjectName(vec![ident]);
+            assert!(engine.table(&name(Ident::new("items"))).is_some());
+            assert!(engine
+                .table(&name(Ident::with_quote('"', "Items")))
+                .is_none());
+            assert_eq!(engine.table_names(), vec!["ITEMS"]);
+
+            // Quoted, the name keeps its case, as it does in queries.
+            let table = MemTable::new(vec![Column::new("x", ValueType::Number)], vec![]).unwrap();
+            engine.register_table("\"Data\"", Arc::new(table));
+            assert!(engine.exec("SELECT * FROM \"Data\"").is_ok());
+            assert!(engine.exec("SELECT * FROM Data").is_err());
+            assert_eq!(
+                engine.describe_table("\"Data\""),
+                Some(vec![(String::from("x"), String::from("Number"))])
+            );
+
+            // SQLite ignores quotes for case.
+            let mut engine = Engine::new(Options {
+                dialect: Arc::new(dialect::Sqlite),
+                ..Options::default()
+            });
+            let table = MemTable::new(vec![Column::new("x", ValueType::Number)], vec![]).unwrap();
+            engine.register_table("\"Data\"", Arc::new(table));
+            assert!(engine.exec("SELECT * FROM data").is_ok());
+        }
+
+        #[test]
+        fn describe_tables() {
+            let mut engine = Engine::default();
+            let table = MemTable::new(vec![Column::new("x", ValueType::Number)], vec![]).unwrap();
+            engine.register_table("items", Arc::new(table));
+            engine
+                .exec("CREATE TABLE users(id INT, name TEXT)")
+                .unwrap();
+            assert_eq!(engine.all_table_names(), vec!["ITEMS", "USERS"]);
+            let column = |name: &str, ty: &str| (String::from(name), String::from(ty));
+            assert_eq!(
+                engine.describe_table("users"),
+                Some(vec![column("id", "INT"), column("name", "TEXT")])
+            );
+            assert_eq!(
+                engine.describe_table("Items"),
+                Some(vec![column("x", "Number")])
+            );
+            assert_eq!(engine.describe_table("orders"), None);
+        }
+
+        #[test]
+        fn add_rewrite_bans_function() {
+            let mut engine = Engine::default();
+            engine.exec("CREATE FUNCTION early() AS SQRT(4)").unwrap();
+            engine.add_rewrite(|statement| {
+                if statement.to_string().to_uppercase().contains("SQRT(") {
+                    return Err(CalcError::UnsupportedFunc(String::from("SQRT is banned")));
+                }
+                Ok(statement)
+            });
+            let banned = CalcError::UnsupportedFunc(String::from("SQRT is banned"));
+            assert_eq!(engine.exec("SELECT 1 + sqrt(4)").unwrap_err(), banned);
+            assert_eq!(engine.validate("SELECT SQRT(4)").unwrap_err(), banned);
+            assert_eq!(engine.exec("SELECT 1 + 1").unwrap(), CalcResult::Int(2));
+
+            assert_eq!(engine.exec("SET x = SQRT(4)").unwrap_err(), banned);
+            assert_eq!(engine.validate("SET x = SQRT(4)").unwrap_err(), banned);
+            assert_eq!(
+                engine
+                    .exec("CREATE FUNCTION f() AS SQRT(4); SELECT f()")
+                    .unwrap_err(),
+                banned
+            );
+            assert_eq!(
+                engine
+                    .validate("CREATE FUNCTION f() AS SQRT(4)")
+                    .unwrap_err(),
+                banned
+            );
+            assert_eq!(engine.exec("SELECT early() + 1").unwrap_err(), banned);
+            engine.exec("CREATE FUNCTION h(x) AS x + 1").unwrap();
+            assert_eq!(engine.exec("SELECT h(2)").unwrap(), CalcResult::Int(3));
+        }
+
+        #[test]
+        fn add_rewrite_applies_to_expanded_bodies() {
+            let mut engine = Engine::default();
+            engine.add_rewrite(|statement| {
+                let text = statement.to_string().replace("PI()", "3");
+                Ok(parse(&text).unwrap().remove(0))
+            });
+            engine.exec("SET x = PI() * 2").unwrap();
+            assert_eq!(engine.exec("SELECT x").unwrap(), CalcResult::Int(6));
+            engine.exec("CREATE FUNCTION tau() AS PI() * 2").unwrap();
+            assert_eq!(engine.exec("SELECT tau() + 1").unwrap(), CalcResult::Int(7));
+        }
+
+        #[test]
+        fn add_rewrite_skips_statements_without_ast() {
+            let mut engine = Engine::default();
+            let rejected = CalcError::UnsupportedFunc(String::from("rejected"));
+            let error = rejected.clone();
+            engine.add_rewrite(move |_| Err(error.clone()));
+            assert_eq!(engine.exec("SELECT 1").unwrap_err(), rejected);
+            assert!(engine.exec("SET TIME ZONE 'Europe/Berlin'").is_ok());
+            assert_ne!(
+                engine.exec("ATTACH '/nonexistent/x.db' AS x").unwrap_err(),
+                rejected
+            );
+        }
+
+        #[test]
+        fn on_statement_sees_every_query() {
+            let log = Rc::new(RefCell::new(Vec::new()));
+            let mut engine = Engine::default();
+            let audit = Rc::clone(&log);
+            engine.on_statement(move |query, res| {
+                audit
+                    .borrow_mut()
+                    .push(format!("{} -> {}", query, res.is_ok()));
+            });
+            engine.exec("SELECT 1").unwrap();
+            engine.exec("SELECT 1 / 0").unwrap_err();
+            engine.exec("CREATE FUNCTION f(x) AS x").unwrap();
+            engine.exec("SET TIME ZONE DEFAULT").unwrap();
+            assert_eq!(
+                *log.borrow(),
+                vec![
+                    "SELECT 1 -> true",
+                    "SELECT 1 / 0 -> false",
+                    "CREATE FUNCTION f(x) AS x -> true",
+                    "SET TIME ZONE DEFAULT -> true"
+                ]
+            );
+        }
+
+        #[test]
+        fn create_function_persists_for_session() {
+            let engine = Engine::default();
+            assert_eq!(
+                engine
+                    .exec("CREATE FUNCTION area(r) AS (SQRT(r) * r * r)")
+                    .unwrap(),
+                CalcResult::Str(String::from("CREATE FUNCTION AREA"))
+            );
+            assert_eq!(
+                engine.exec("SELECT Area(4)").unwrap(),
+                CalcResult::Num(32.0)
+            );
+            assert_eq!(
+                engine.exec("SELECT area(1 + 3) + 1").unwrap(),
+                CalcResult::Num(33.0)
+            );
+
+            engine.exec("CREATE FUNCTION area(r) AS r").unwrap();
+            assert_eq!(engine.exec("SELECT area(4)").unwrap(), CalcResult::Int(4));
+            assert!(Engine::default().exec("SELECT area(4)").is_err());
+
+            engine
+                .exec("CREATE FUNCTION initials(s) AS UPPER(SUBSTRING(TRIM(s) FROM 1 FOR 2))")
+                .unwrap();
+            assert_eq!(
+                engine.exec("SELECT initials('  ada')").unwrap(),
+                CalcResult::Str(String::from("AD"))
+            );
+        }
+
+        #[test]
+        fn create_function_errors() {
+            let engine = Engine::default();
+            engine.exec("CREATE FUNCTION twice(x) AS x * 2").unwrap();
+            assert_eq!(
+                engine.exec("SELECT twice(1, 2)").unwrap_err(),
+                CalcError::InvalidType(String::from("TWICE expects 1 argument(s), got 2"))
+            );
+            assert_eq!(
+                engine.exec("CREATE FUNCTION twice AS 2").unwrap_err(),
+                CalcError::InvalidRequestFormat(String::from(
+                    "Expected a list of parameters in parentheses, found: AS at Line: 1, Column 23"
+                ))
+            );
+
+            engine.exec("CREATE FUNCTION loop(x) AS loop(x)").unwrap();
+            assert_eq!(
+                engine.exec("SELECT loop(1)").unwrap_err(),
+                CalcError::ResourceLimit(String::from("LOOP expands deeper than 64 levels"))
+            );
+        }
+
+        #[test]
+        fn create_function_language() {
+            let engine = Engine::default();
+            let res = engine.exec("CREATE FUNCTION f(x) LANGUAGE lua AS 'return x'");
+            assert_eq!(
+                res.unwrap_err(),
+                CalcError::UnsupportedFunc(String::from("Unsupported function language lua"))
+            );
+            assert!(engine.exec("SELECT f(1)").is_err());
+        }
+
+        #[cfg(feature = "rhai")]
+        #[test]
+        fn create_function_rhai() {
+            let engine = Engine::default();
+            engine
+                .exec("CREATE FUNCTION clamp(x, hi) LANGUAGE rhai AS 'if x > hi { hi } else { x }'")
+                .unwrap();
+            assert_eq!(
+                engine.exec("SELECT clamp(SQRT(16), 3) + 1").unwrap(),
+                CalcResult::Int(4)
+            );
+            assert_eq!(
+                engine.exec("SELECT CLAMP(1)").unwrap_err(),
+                CalcError::InvalidType(String::from("CLAMP expects 2 argument(s), got 1"))
+            );
+            engine
+                .exec("CREATE FUNCTION fail LANGUAGE rhai AS 'throw \"no\"'")
+                .unwrap();
+            assert!(matches!(
+                engine.exec("SELECT fail()").unwrap_err(),
+                CalcError::FunctionFailed(message) if message.starts_with("FAIL: ")
+            ));
+        }
+
+        #[cfg(feature = "sqlite")]
+        #[test]
+        fn exec_attach() {
+            let path = std::env::temp_dir().join("sql_engine_exec_attach.db");
+            let _ = std::fs::remove_file(&path);
+            let fixtures = rusqlite::Connection::open(&path).unwrap();
+            fixtures
+                .execute_batch(
+                    "CREATE TABLE users(id INTEGER, name TEXT, score REAL);
+                     INSERT INTO users VALUES (1, 'ann', 2.5), (2, 'bob', NULL);
+                     CREATE TABLE orders(user_id INT, total REAL);
+                     INSERT INTO orders VALUES (1, 10), (1, 5), (2, 7);",
+                )
+                .unwrap();
+            drop(fixtures);
+            let path = path.to_str().unwrap();
+
+            let engine = Engine::default();
+            assert_eq!(
+                engine.exec(&format!("ATTACH '{}' AS fx", path)).unwrap(),
+                CalcResult::Str(String::from("ATTACH fx"))
+            );
+            assert_eq!(
+                engine
+                    .exec(
+                        "SELECT u.name, SUM(o.total) FROM fx.users u \
+                         JOIN fx.orders o ON o.user_id = u.id GROUP BY u.name"
+                    )
+                    .unwrap(),
+                CalcResult::Table(vec![
+                    vec![CalcResult::Str(String::from("ann")), CalcResult::Num(15.0)],
+                    vec![CalcResult::Str(String::from("bob")), CalcResult::Num(7.0)],
+                ])
+            );
+            assert_eq!(
+                engine
+                    .exec("SELECT score FROM fx.users WHERE id = 2")
+                    .unwrap(),
+                CalcResult::Table(vec![vec![CalcResult::Null]])
+            );
+            assert_eq!(engine.all_table_names(), vec!["FX.ORDERS", "FX.USERS"]);
+            assert!(engine.exec("SELECT * FROM users").is_err());
+            assert!(engine.validate(&format!("ATTACH '{}' AS fx", path)).is_ok());
+            assert!(matches!(
+                engine.exec("ATTACH '/nonexistent/x.db' AS y").unwrap_err(),
+                CalcError::FunctionFailed(_)
+            ));
+        }
+
+        #[test]
+        fn show_functions() {
+            let engine = Engine::default();
+            engine.exec("CREATE FUNCTION area(r) AS r * r").unwrap();
+            let read = match cfg!(feature = "parquet") {
+                true => "READ_CSV, READ_JSON, READ_PARQUET",
+                false => "READ_CSV, READ_JSON",
+            };
+            assert_eq!(
+                engine.exec("SHOW FUNCTIONS").unwrap(),
+                CalcResult::Str(format!("ABS, ACOS, AREA, ARRAY, ARRAY_CONTAINS, ARRAY_EXTRACT, ARRAY_LENGTH, ASIN, ATAN, ATAN2, AVG, CEIL, CEILING, COALESCE, CONCAT, CONCAT_WS, COS, COUNT, CURRENT_DATE, CURRENT_TIMESTAMP, DATE_TRUNC, E, EXP, EXTRACT, FLOOR, IFNULL, LAG, LEAD, LENGTH, LN, LOG, LOG10, LOG2, LOWER, LPAD, MAX, MD5, MIN, MOD, NOW, NULLIF, PI, POW, POWER, RANDOM, RANK, {}, REGEXP_EXTRACT, REGEXP_MATCHES, REGEXP_REPLACE, REPLACE, REVERSE, ROUND, ROW_NUMBER, RPAD, SETSEED, SHA1, SHA256, SIGN, SIN, SQRT, SUBSTRING, SUM, TAN, TRIM, TRUNC, UPPER", read))
+            );
+            assert!(engine.validate("SHOW FUNCTIONS").is_ok());
+            assert!(engine.validate("CREATE FUNCTION f(x) AS x").is_ok());
+            assert!(engine.exec("SELECT f(1)").is_err());
+        }
+
+        fn with_dialect(dialect: impl Dialect + 'static) -> Engine {
+            Engine::new(Options {
+                dialect: Arc::new(dialect),
+                ..Options::default()
+            })
+        }
+
+        #[test]
+        fn dialect_plus_concatenation() {
+            assert!(exec(String::from("SELECT 'a' + 'b'")).is_err());
+            assert_eq!(
+                with_dialect(dialect::SqlServer)
+                    .exec("SELECT 'a' + 'b'")
+                    .unwrap(),
+                CalcResult::Str(String::from("ab"))
+            );
+        }
+
+        #[test]
+        fn dialect_line_comments() {
+            let query = "SELECT 4 // 2\n+ 1";
+            assert!(exec(String::from(query)).is_err());
+            assert_eq!(
+                with_dialect(dialect::Snowflake).exec(query).unwrap(),
+                CalcResult::Int(5)
+            );
+        }
+
+        #[test]
+        fn dialect_quoted_identifiers() {
+            let query = "SELECT \"sqrt\"(4)";
+            assert!(exec(String::from(query)).is_err());
+            assert_eq!(
+                with_dialect(dialect::Sqlite).exec(query).unwrap(),
+                CalcResult::Num(2.0)
+            );
+        }
+
+        #[test]
+        fn error_codes() {
+            let cases = [
+                ("SELECT 'a' + 1", "SQE-1001"),
+                ("SELECT 1 & 2", "SQE-1002"),
+                ("SELECT Gamma(2)", "SQE-1003"),
+                ("Give the data", "SQE-1004"),
+                ("SELECT 'a' + 'b'", "SQE-1005"),
+                ("SELECT 1 / 0", "SQE-1006"),
+                ("ASSERT 1 > 2", "SQE-1008"),
+                ("SELECT 9223372036854775807 * 2", "SQE-1010"),
+            ];
+            for (query, code) in cases {
+                assert_eq!(exec(String::from(query)).unwrap_err().code(), code);
+            }
+        }
+
+        fn limited(limits: Limits) -> Engine {
+            Engine::new(Options {
+                limits,
+                ..Options::default()
+            })
+        }
+
+        #[test]
+        fn exec_depth_limit_before_parsing() {
+            let query = format!("SELECT {}1{}", "(".repeat(100_000), ")".repeat(100_000));
+            if std::mem::discriminant(&CalcError::ResourceLimit(String::from("")))
+                != std::mem::discriminant(&exec(query).unwrap_err())
             {
                 panic!();
             }
         }
 
         #[test]
-        fn exec_invalied_expr() {
-            if std::mem::discriminant(&CalcError::InvalidRequestFormat(String::from("")))
-                != std::mem::discriminant(&exec(String::from("SELECT * from table")).unwrap_err())
+        fn exec_depth_limit_long_chains() {
+            let engine = Engine::default();
+            let terms = " + 1".repeat(100_000);
+            let queries = [
+                format!("SELECT 1{}", terms),
+                format!("SELECT {}1", "- ".repeat(100_000)),
+                format!("SELECT {}TRUE", "NOT ".repeat(100_000)),
+                format!("SET x = 1{}", terms),
+                format!("CREATE FUNCTION f() AS 1{}", terms),
+            ];
+            for query in queries {
+                assert!(
+                    matches!(engine.exec(&query), Err(CalcError::ResourceLimit(_))),
+                    "{}",
+                    &query[..20]
+                );
+            }
+            // Up to the limit the chain evaluates, on a test thread's stack.
+            assert_eq!(
+                engine
+                    .exec(&format!("SELECT 1{}", " + 1".repeat(499)))
+                    .unwrap(),
+                CalcResult::Int(500)
+            );
+            assert_eq!(
+                engine
+                    .exec(&format!("SELECT {}1", "- ".repeat(499)))
+                    .unwrap(),
+                CalcResult::Int(-1)
+            );
+            assert!(engine
+                .validate(&format!("SELECT {}1", "- ".repeat(499)))
+                .is_ok());
+        }
+
+        #[test]
+        fn exec_depth_limit_unparenthesized() {
+            let engine = limited(Limits {
+                max_depth: 10,
+                ..Limits::default()
+            });
+            let query = format!("SELECT 1{}", " + 1".repeat(20));
+            if std::mem::discriminant(&CalcError::ResourceLimit(String::from("")))
+                != std::mem::discriminant(&engine.exec(&query).unwrap_err())
             {
                 panic!();
             }
         }
 
         #[test]
-        fn exec_invalied_statement() {
-            if std::mem::discriminant(&CalcError::InvalidRequestFormat(String::from("")))
-                != std::mem::discriminant(&exec(String::from("INSERT INTO table")).unwrap_err())
+        fn exec_node_limit() {
+            let engine = limited(Limits {
+                max_nodes: 6,
+                ..Limits::default()
+            });
+            assert!(engine.exec("SELECT (1 + 1) * 2").is_ok());
+            if std::mem::discriminant(&CalcError::ResourceLimit(String::from("")))
+                != std::mem::discriminant(&engine.exec("SELECT (1 + 1) * (2 + 2)").unwrap_err())
             {
                 panic!();
             }
         }
 
+        #[test]
+        fn exec_string_limit() {
+            let engine = limited(Limits {
+                max_string_len: 3,
+                ..Limits::default()
+            });
+            assert!(engine.exec("SELECT CAST('123' AS INT)").is_ok());
+            assert_eq!(
+                engine.exec("SELECT CAST('1234' AS INT)").unwrap_err(),
+                CalcError::ResourceLimit(String::from(
+                    "string of 4 bytes exceeds the 3 byte limit"
+                ))
+            );
+        }
+
         #[test]
         fn exec_invalied_format() {
             if std::mem::discriminant(&CalcError::InvalidRequestFormat(String::from("")))
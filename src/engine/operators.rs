@@ -0,0 +1,150 @@
+use super::engine::{CalcError, CalcResult};
+use sqlparser::ast::{BinaryOperator, UnaryOperator};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Runtime type of a value, used to pick an operator implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Number,
+    Integer,
+    Decimal,
+    Boolean,
+    String,
+    Date,
+    Timestamp,
+    Interval,
+    Array,
+    Row,
+    Table,
+    Null,
+}
+
+impl ValueType {
+    pub fn of(value: &CalcResult) -> Self {
+        match value {
+            CalcResult::Num(_) => ValueType::Number,
+            CalcResult::Int(_) => ValueType::Integer,
+            CalcResult::Decimal(_) => ValueType::Decimal,
+            CalcResult::Bool(_) => ValueType::Boolean,
+            CalcResult::Str(_) => ValueType::String,
+            CalcResult::Date(_) => ValueType::Date,
+            CalcResult::Timestamp(_) => ValueType::Timestamp,
+            CalcResult::Interval(_) => ValueType::Interval,
+            CalcResult::Array(_) => ValueType::Array,
+            CalcResult::Row(_) => ValueType::Row,
+            CalcResult::Table(_) => ValueType::Table,
+            CalcResult::Null => ValueType::Null,
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ValueType::Number => "Number",
+            ValueType::Integer => "Integer",
+            ValueType::Decimal => "Decimal",
+            ValueType::Boolean => "Boolean",
+            ValueType::String => "String",
+            ValueType::Date => "Date",
+            ValueType::Timestamp => "Timestamp",
+            ValueType::Interval => "Interval",
+            ValueType::Array => "Array",
+            ValueType::Row => "Row",
+            ValueType::Table => "Table",
+            ValueType::Null => "NULL",
+        };
+        f.pad(name)
+    }
+}
+
+pub type BinaryFn = dyn Fn(&CalcResult, &CalcResult) -> Result<CalcResult, CalcError>;
+pub type UnaryFn = dyn Fn(&CalcResult) -> Result<CalcResult, CalcError>;
+
+/// Host-provided operator implementations for specific operand types. They
+/// take precedence over the built-in semantics of the operator.
+#[derive(Default)]
+pub struct OperatorRegistry {
+    binary: HashMap<(BinaryOperator, ValueType, ValueType), Box<BinaryFn>>,
+    unary: HashMap<(UnaryOperator, ValueType), Box<UnaryFn>>,
+}
+
+impl OperatorRegistry {
+    pub fn register_binary<F>(
+        &mut self,
+        op: BinaryOperator,
+        left: ValueType,
+        right: ValueType,
+        eval: F,
+    ) where
+        F: Fn(&CalcResult, &CalcResult) -> Result<CalcResult, CalcError> + 'static,
+    {
+        self.binary.insert((op, left, right), Box::new(eval));
+    }
+
+    pub fn register_unary<F>(&mut self, op: UnaryOperator, operand: ValueType, eval: F)
+    where
+        F: Fn(&CalcResult) -> Result<CalcResult, CalcError> + 'static,
+    {
+        self.unary.insert((op, operand), Box::new(eval));
+    }
+
+    pub fn binary(
+        &self,
+        op: &BinaryOperator,
+        left: &CalcResult,
+        right: &CalcResult,
+    ) -> Option<&BinaryFn> {
+        self.binary
+            .get(&(op.clone(), ValueType::of(left), ValueType::of(right)))
+            .map(Box::as_ref)
+    }
+
+    pub fn unary(&self, op: &UnaryOperator, operand: &CalcResult) -> Option<&UnaryFn> {
+        self.unary
+            .get(&(op.clone(), ValueType::of(operand)))
+            .map(Box::as_ref)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_matches_operand_types() {
+        let mut registry = OperatorRegistry::default();
+        registry.register_binary(
+            BinaryOperator::Minus,
+            ValueType::String,
+            ValueType::Number,
+            |_, _| Ok(CalcResult::Null),
+        );
+        let text = CalcResult::Str(String::from("a"));
+        let num = CalcResult::Num(1.0);
+        assert!(registry
+            .binary(&BinaryOperator::Minus, &text, &num)
+            .is_some());
+        assert!(registry
+            .binary(&BinaryOperator::Minus, &num, &text)
+            .is_none());
+        assert!(registry
+            .binary(&BinaryOperator::Plus, &text, &num)
+            .is_none());
+    }
+
+    #[test]
+    fn unary_matches_operand_type() {
+        let mut registry = OperatorRegistry::default();
+        registry.register_unary(UnaryOperator::Not, ValueType::Number, |_| {
+            Ok(CalcResult::Bool(true))
+        });
+        assert!(registry
+            .unary(&UnaryOperator::Not, &CalcResult::Num(0.0))
+            .is_some());
+        assert!(registry
+            .unary(&UnaryOperator::Not, &CalcResult::Bool(false))
+            .is_none());
+    }
+}
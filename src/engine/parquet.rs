@@ -0,0 +1,270 @@
+//! `read_parquet`: Parquet files as tables. Only the columns a query refers
+//! to are decoded.
+
+use super::engine::{CalcError, CalcResult};
+use super::operators::ValueType;
+use super::source::Local;
+use super::table::{Column, Row, TableProvider};
+use ::parquet::basic::{ConvertedType, Type as PhysicalType};
+use ::parquet::file::reader::{FileReader, SerializedFileReader};
+use ::parquet::record::Field;
+use ::parquet::schema::types::Type;
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Days, NaiveDate};
+use sqlparser::ast::Expr;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A Parquet file, its top-level fields being the columns. Rows are read
+/// from the file on every scan.
+pub struct ParquetTable {
+    path: String,
+    file: Local,
+    fields: Vec<Arc<Type>>,
+}
+
+impl ParquetTable {
+    /// Reads the schema of `file`, the local copy of `path`.
+    pub fn open(path: &str, file: Local) -> Result<Self, CalcError> {
+        let reader = reader(path, file.path())?;
+        let fields = reader
+            .metadata()
+            .file_metadata()
+            .schema()
+            .get_fields()
+            .to_vec();
+        Ok(ParquetTable {
+            path: path.to_string(),
+            file,
+            fields,
+        })
+    }
+}
+
+impl TableProvider for ParquetTable {
+    fn schema(&self) -> Vec<Column> {
+        self.fields
+            .iter()
+            .map(|field| Column::new(field.name(), column_type(field)))
+            .collect()
+    }
+
+    fn scan(&self, _filters: &[Expr], projection: Option<&[usize]>) -> Result<Vec<Row>, CalcError> {
+        let fields = match projection {
+            Some(columns) => {
+                let mut fields = Vec::with_capacity(columns.len());
+                for i in columns {
+                    match self.fields.get(*i) {
+                        Some(field) => fields.push(field.clone()),
+                        None => {
+                            return Err(CalcError::InvalidType(format!(
+                                "Column index {} is out of range for {} column(s)",
+                                i,
+                                self.fields.len()
+                            )))
+                        }
+                    }
+                }
+                fields
+            }
+            None => self.fields.clone(),
+        };
+
+        let reader = reader(&self.path, self.file.path())?;
+        let rows = reader.metadata().file_metadata().num_rows();
+        if fields.is_empty() {
+            return Ok(vec![Vec::new(); rows.max(0) as usize]);
+        }
+        // The projection lists the columns in the order the query asked
+        // for them, which the reader keeps.
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()
+            .map_err(|err| failed(&self.path, err))?;
+        reader
+            .get_row_iter(Some(schema))
+            .map_err(|err| failed(&self.path, err))?
+            .map(|row| {
+                let row = row.map_err(|err| failed(&self.path, err))?;
+                Ok(row
+                    .get_column_iter()
+                    .map(|(_, field)| to_value(field))
+                    .collect())
+            })
+            .collect()
+    }
+}
+
+fn reader(path: &str, file: &Path) -> Result<SerializedFileReader<File>, CalcError> {
+    let file = File::open(file).map_err(|err| {
+        CalcError::FunctionFailed(format!("READ_PARQUET cannot read {}: {}", path, err))
+    })?;
+    SerializedFileReader::new(file).map_err(|err| failed(path, err))
+}
+
+fn failed(path: &str, err: impl std::fmt::Display) -> CalcError {
+    CalcError::FunctionFailed(format!("READ_PARQUET {}: {}", path, err))
+}
+
+/// The type of the values a field's column holds.
+fn column_type(field: &Type) -> ValueType {
+    let info = field.get_basic_info();
+    match info.converted_type() {
+        ConvertedType::LIST => return ValueType::Array,
+        ConvertedType::DATE => return ValueType::Date,
+        ConvertedType::TIMESTAMP_MILLIS | ConvertedType::TIMESTAMP_MICROS => {
+            return ValueType::Timestamp
+        }
+        ConvertedType::DECIMAL => return ValueType::Decimal,
+        _ if field.is_group() => return ValueType::String,
+        _ => {}
+    }
+    match field.get_physical_type() {
+        PhysicalType::BOOLEAN => ValueType::Boolean,
+        PhysicalType::INT32 | PhysicalType::INT64 => ValueType::Integer,
+        PhysicalType::INT96 => ValueType::Timestamp,
+        PhysicalType::FLOAT | PhysicalType::DOUBLE => ValueType::Number,
+        PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => ValueType::String,
+    }
+}
+
+/// The engine value of a Parquet value. Times of day, structs and maps,
+/// which the engine has no type for, are kept as text.
+fn to_value(field: &Field) -> CalcResult {
+    match field {
+        Field::Null => CalcResult::Null,
+        Field::Bool(boolean) => CalcResult::Bool(*boolean),
+        Field::Byte(int) => CalcResult::Int(*int as i64),
+        Field::Short(int) => CalcResult::Int(*int as i64),
+        Field::Int(int) => CalcResult::Int(*int as i64),
+        Field::Long(int) => CalcResult::Int(*int),
+        Field::UByte(int) => CalcResult::Int(*int as i64),
+        Field::UShort(int) => CalcResult::Int(*int as i64),
+        Field::UInt(int) => CalcResult::Int(*int as i64),
+        Field::ULong(int) => match i64::try_from(*int) {
+            Ok(int) => CalcResult::Int(int),
+            Err(_) => CalcResult::Num(*int as f64),
+        },
+        Field::Float16(num) => CalcResult::Num(num.to_f64()),
+        Field::Float(num) => CalcResult::Num(*num as f64),
+        Field::Double(num) => CalcResult::Num(*num),
+        Field::Decimal(decimal) => CalcResult::Decimal(BigDecimal::new(
+            BigInt::from_signed_bytes_be(decimal.data()),
+            decimal.scale() as i64,
+        )),
+        Field::Str(str) => CalcResult::Str(str.clone()),
+        Field::Bytes(bytes) => CalcResult::Str(String::from_utf8_lossy(bytes.data()).into_owned()),
+        Field::Date(days) => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+            let date = match u64::try_from(*days) {
+                Ok(days) => epoch.checked_add_days(Days::new(days)),
+                Err(_) => epoch.checked_sub_days(Days::new(days.unsigned_abs() as u64)),
+            };
+            date.map_or(CalcResult::Null, CalcResult::Date)
+        }
+        Field::TimestampMillis(millis) => DateTime::from_timestamp_millis(*millis)
+            .map_or(CalcResult::Null, |at| CalcResult::Timestamp(at.naive_utc())),
+        Field::TimestampMicros(micros) => DateTime::from_timestamp_micros(*micros)
+            .map_or(CalcResult::Null, |at| CalcResult::Timestamp(at.naive_utc())),
+        Field::ListInternal(list) => {
+            CalcResult::Array(list.elements().iter().map(to_value).collect())
+        }
+        Field::TimeMillis(_) | Field::TimeMicros(_) | Field::Group(_) | Field::MapInternal(_) => {
+            CalcResult::Str(field.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use ::parquet::file::properties::WriterProperties;
+    use ::parquet::file::writer::SerializedFileWriter;
+    use ::parquet::schema::parser::parse_message_type;
+
+    /// Writes a file with an `id` and a `name` column, `name` NULL in the
+    /// second row.
+    fn write(name: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        let schema = parse_message_type(
+            "message schema { REQUIRED INT64 id; OPTIONAL BYTE_ARRAY name (UTF8); }",
+        )
+        .unwrap();
+        let file = File::create(&path).unwrap();
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, Arc::new(schema), props).unwrap();
+        let mut group = writer.next_row_group().unwrap();
+
+        let mut column = group.next_column().unwrap().unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&[1, 2], None, None)
+            .unwrap();
+        column.close().unwrap();
+        let mut column = group.next_column().unwrap().unwrap();
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(&[ByteArray::from("a")], Some(&[1, 0]), None)
+            .unwrap();
+        column.close().unwrap();
+
+        group.close().unwrap();
+        writer.close().unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn scan_reads_columns() {
+        let path = write("sql_engine_scan_reads_columns.parquet");
+        let table = ParquetTable::open(&path, Local::Path(path.clone().into())).unwrap();
+        assert_eq!(
+            table.schema(),
+            vec![
+                Column::new("id", ValueType::Integer),
+                Column::new("name", ValueType::String),
+            ]
+        );
+        assert_eq!(
+            table.scan(&[], None).unwrap(),
+            vec![
+                vec![CalcResult::Int(1), CalcResult::Str(String::from("a"))],
+                vec![CalcResult::Int(2), CalcResult::Null],
+            ]
+        );
+        assert_eq!(
+            table.scan(&[], Some(&[1, 0])).unwrap(),
+            vec![
+                vec![CalcResult::Str(String::from("a")), CalcResult::Int(1)],
+                vec![CalcResult::Null, CalcResult::Int(2)],
+            ]
+        );
+        assert_eq!(
+            table.scan(&[], Some(&[1])).unwrap(),
+            vec![
+                vec![CalcResult::Str(String::from("a"))],
+                vec![CalcResult::Null]
+            ]
+        );
+        assert_eq!(
+            table.scan(&[], Some(&[])).unwrap(),
+            vec![Vec::new(), Vec::new()]
+        );
+        assert!(table.scan(&[], Some(&[2])).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn open_errors() {
+        assert!(ParquetTable::open(
+            "/nonexistent/t.parquet",
+            Local::Path("/nonexistent/t.parquet".into())
+        )
+        .err()
+        .unwrap()
+        .to_string()
+        .starts_with("[Function Failed]: READ_PARQUET cannot read /nonexistent/t.parquet: "));
+    }
+}
@@ -0,0 +1,478 @@
+//! The tree of what a query evaluates, as `EXPLAIN` prints it: one node per
+//! clause, relation and expression, with the operands of an expression as
+//! its children. `EXPLAIN ANALYZE` runs the query too, and annotates the
+//! nodes with what the run measured.
+
+use super::aggregate;
+use super::precedence;
+use super::variables;
+use sqlparser::ast::{
+    Expr, FunctionArg, Join, JoinConstraint, JoinOperator, Query, Select, SelectItem, SetExpr,
+    TableFactor, TableWithJoins,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub label: String,
+    pub site: Option<Site>,
+    pub children: Vec<Node>,
+}
+
+/// What a node is measured by: the relation it evaluates or the function
+/// call it makes, each known by its SQL text (so nodes written the same
+/// share their statistics).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Site {
+    Relation(String),
+    Call(String),
+}
+
+/// How many times a relation was evaluated, the rows those evaluations
+/// gave and the time they took, when it was measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub loops: usize,
+    pub rows: usize,
+    pub time: Option<Duration>,
+}
+
+/// What running a query measured, by site.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    relations: HashMap<String, Stats>,
+    calls: HashMap<String, usize>,
+}
+
+impl Profile {
+    pub fn relation(&mut self, site: String, rows: usize, time: Option<Duration>) {
+        let stats = self.relations.entry(site).or_default();
+        stats.loops += 1;
+        stats.rows += rows;
+        if let Some(time) = time {
+            stats.time = Some(stats.time.unwrap_or_default() + time);
+        }
+    }
+
+    pub fn calls(&mut self, site: String, count: usize) {
+        *self.calls.entry(site).or_default() += count;
+    }
+
+    pub fn stats(&self, site: &str) -> Option<Stats> {
+        self.relations.get(site).copied()
+    }
+
+    pub fn call_count(&self, site: &str) -> usize {
+        self.calls.get(site).copied().unwrap_or(0)
+    }
+}
+
+impl Node {
+    fn new(label: impl Into<String>, children: Vec<Node>) -> Node {
+        Node {
+            label: label.into(),
+            site: None,
+            children,
+        }
+    }
+
+    fn at(mut self, site: Site) -> Node {
+        self.site = Some(site);
+        self
+    }
+
+    /// Appends to the label of every node with a site what `profile`
+    /// measured there.
+    pub fn annotate(&mut self, profile: &Profile) {
+        match &self.site {
+            Some(Site::Relation(site)) => match profile.stats(site) {
+                Some(stats) => {
+                    let time = match stats.time {
+                        Some(time) => format!(" time={:.3} ms", time.as_secs_f64() * 1000.0),
+                        None => String::new(),
+                    };
+                    self.label.push_str(&format!(
+                        " (loops={} rows={}{})",
+                        stats.loops, stats.rows, time
+                    ))
+                }
+                None => self.label.push_str(" (never executed)"),
+            },
+            Some(Site::Call(site)) => {
+                let calls = profile.call_count(site);
+                self.label.push_str(&format!(" (calls={})", calls));
+            }
+            None => {}
+        }
+        for child in &mut self.children {
+            child.annotate(profile);
+        }
+    }
+
+    fn leaf(label: impl Into<String>) -> Node {
+        Node::new(label, Vec::new())
+    }
+
+    /// The lines of the tree, each child indented two spaces under its
+    /// parent.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        self.push_lines(0, &mut lines);
+        lines
+    }
+
+    fn push_lines(&self, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{}", "  ".repeat(depth), self.label));
+        for child in &self.children {
+            child.push_lines(depth + 1, lines);
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.lines().join("\n"))
+    }
+}
+
+pub fn query(query: &Query) -> Node {
+    let body = set_expr(&query.body);
+    match &query.with {
+        Some(with) => {
+            let label = if with.recursive {
+                "With Recursive"
+            } else {
+                "With"
+            };
+            let mut children: Vec<_> = with
+                .cte_tables
+                .iter()
+                .map(|cte| Node::new(format!("CTE {}", cte.alias), vec![self::query(&cte.query)]))
+                .collect();
+            children.push(body);
+            Node::new(label, children).at(Site::Relation(query.to_string()))
+        }
+        None => body,
+    }
+}
+
+fn set_expr(body: &SetExpr) -> Node {
+    let node = match body {
+        SetExpr::Select(select) => self::select(select),
+        SetExpr::Query(query) => return self::query(query),
+        SetExpr::Values(values) => Node::new(
+            "Values",
+            values
+                .0
+                .iter()
+                .map(|row| Node::new("Row", row.iter().map(expr).collect()))
+                .collect(),
+        ),
+        SetExpr::SetOperation {
+            op,
+            all,
+            left,
+            right,
+        } => {
+            let label = format!("{}{}", op, if *all { " ALL" } else { "" });
+            Node::new(label, vec![set_expr(left), set_expr(right)])
+        }
+        body => Node::leaf(body.to_string()),
+    };
+    node.at(Site::Relation(body.to_string()))
+}
+
+fn select(select: &Select) -> Node {
+    let mut children = Vec::new();
+    if !select.from.is_empty() {
+        children.push(Node::new("From", select.from.iter().map(from).collect()));
+    }
+    if let Some(selection) = &select.selection {
+        children.push(Node::new("Where", vec![expr(selection)]));
+    }
+    if !select.group_by.is_empty() {
+        children.push(Node::new(
+            "Group By",
+            select.group_by.iter().map(expr).collect(),
+        ));
+    }
+    if let Some(having) = &select.having {
+        children.push(Node::new("Having", vec![expr(having)]));
+    }
+    children.push(Node::new(
+        "Projection",
+        select.projection.iter().map(item).collect(),
+    ));
+
+    let label = if select.distinct {
+        "Select Distinct"
+    } else {
+        "Select"
+    };
+    Node::new(label, children)
+}
+
+fn from(table: &TableWithJoins) -> Node {
+    table.joins.iter().fold(factor(&table.relation), join)
+}
+
+fn join(left: Node, join: &Join) -> Node {
+    let (label, constraint) = match &join.join_operator {
+        JoinOperator::Inner(constraint) => ("Inner Join", Some(constraint)),
+        JoinOperator::LeftOuter(constraint) => ("Left Join", Some(constraint)),
+        JoinOperator::RightOuter(constraint) => ("Right Join", Some(constraint)),
+        JoinOperator::FullOuter(constraint) => ("Full Join", Some(constraint)),
+        JoinOperator::CrossJoin => ("Cross Join", None),
+        JoinOperator::CrossApply => ("Cross Apply", None),
+        JoinOperator::OuterApply => ("Outer Apply", None),
+    };
+    let mut children = vec![left, factor(&join.relation)];
+    match constraint {
+        Some(JoinConstraint::On(on)) => children.push(Node::new("On", vec![expr(on)])),
+        Some(JoinConstraint::Using(columns)) => {
+            let columns: Vec<_> = columns.iter().map(ToString::to_string).collect();
+            children.push(Node::leaf(format!("Using {}", columns.join(", "))));
+        }
+        Some(JoinConstraint::Natural) => children.push(Node::leaf("Natural")),
+        Some(JoinConstraint::None) | None => {}
+    }
+    Node::new(label, children).at(Site::Relation(join.to_string()))
+}
+
+fn factor(factor: &TableFactor) -> Node {
+    let node = match factor {
+        TableFactor::Table { .. } => Node::leaf(format!("Table {}", factor)),
+        TableFactor::Derived {
+            subquery, alias, ..
+        } => {
+            let alias = match alias {
+                Some(alias) => format!(" AS {}", alias),
+                None => String::new(),
+            };
+            Node::new(format!("Subquery{}", alias), vec![query(subquery)])
+        }
+        factor => Node::leaf(factor.to_string()),
+    };
+    node.at(Site::Relation(factor.to_string()))
+}
+
+fn item(item: &SelectItem) -> Node {
+    match item {
+        SelectItem::UnnamedExpr(item) => expr(item),
+        SelectItem::ExprWithAlias { expr: item, alias } => {
+            Node::new(format!("Alias {}", alias), vec![expr(item)])
+        }
+        SelectItem::QualifiedWildcard(name) => Node::leaf(format!("Wildcard {}.*", name)),
+        SelectItem::Wildcard => Node::leaf("Wildcard *"),
+    }
+}
+
+/// The node of an expression, with its arithmetic chains associated the
+/// way the engine evaluates them.
+pub fn expr(expr: &Expr) -> Node {
+    node(&precedence::fix(expr.clone()))
+}
+
+fn node(expr: &Expr) -> Node {
+    let label =
+        match expr {
+            Expr::Nested(inner) => return node(inner),
+            Expr::Subquery(subquery) => return Node::new("Subquery", vec![query(subquery)]),
+            Expr::Exists(subquery) => return Node::new("Exists", vec![query(subquery)]),
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let label = if *negated {
+                    "Not In Subquery"
+                } else {
+                    "In Subquery"
+                };
+                return Node::new(label, vec![node(expr), query(subquery)]);
+            }
+            Expr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                let mut children: Vec<_> = operand.iter().map(|operand| node(operand)).collect();
+                children.extend(conditions.iter().zip(results).map(|(condition, result)| {
+                    Node::new("When", vec![node(condition), node(result)])
+                }));
+                children.extend(
+                    else_result
+                        .iter()
+                        .map(|result| Node::new("Else", vec![node(result)])),
+                );
+                return Node::new("Case", children);
+            }
+            Expr::Function(func) => {
+                let args: Vec<_> = func
+                    .args
+                    .iter()
+                    .map(|arg| match arg {
+                        FunctionArg::Named { name, arg } => {
+                            Node::new(format!("Argument {}", name), vec![node(arg)])
+                        }
+                        FunctionArg::Unnamed(arg) => node(arg),
+                    })
+                    .collect();
+                let kind = if func.over.is_some() {
+                    "Window Function"
+                } else if aggregate::is_aggregate(func) {
+                    "Aggregate"
+                } else {
+                    "Function"
+                };
+                let mut label = format!("{} {}", kind, func.name);
+                if func.distinct {
+                    label.push_str(" DISTINCT");
+                }
+                if let Some(over) = &func.over {
+                    label.push_str(&format!(" OVER ({})", over));
+                }
+                return Node::new(label, args).at(Site::Call(func.to_string()));
+            }
+            Expr::Identifier(ident) => match variables::reference(ident) {
+                Some(name) => format!("Variable @{}", name),
+                None => format!("Column {}", ident),
+            },
+            Expr::CompoundIdentifier(_) => format!("Column {}", expr),
+            Expr::Wildcard | Expr::QualifiedWildcard(_) => format!("Wildcard {}", expr),
+            Expr::Value(_) | Expr::TypedString { .. } => format!("Literal {}", expr),
+            Expr::BinaryOp { op, .. } => format!("Operator {}", op),
+            Expr::UnaryOp { op, .. } => format!("Operator {}", op),
+            Expr::IsNull(_) => String::from("Is Null"),
+            Expr::IsNotNull(_) => String::from("Is Not Null"),
+            Expr::IsDistinctFrom(..) => String::from("Is Distinct From"),
+            Expr::IsNotDistinctFrom(..) => String::from("Is Not Distinct From"),
+            Expr::InList { negated: true, .. } => String::from("Not In"),
+            Expr::InList { .. } => String::from("In"),
+            Expr::Between { negated: true, .. } => String::from("Not Between"),
+            Expr::Between { .. } => String::from("Between"),
+            Expr::Cast { data_type, .. } => format!("Cast AS {}", data_type),
+            Expr::TryCast { data_type, .. } => format!("Try Cast AS {}", data_type),
+            Expr::Extract { field, .. } => format!("Extract {}", field),
+            Expr::Collate { collation, .. } => format!("Collate {}", collation),
+            Expr::Substring { .. } => String::from("Substring"),
+            Expr::Trim { .. } => String::from("Trim"),
+            expr => return Node::leaf(expr.to_string()),
+        };
+    Node::new(
+        label,
+        aggregate::children(expr).into_iter().map(node).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::ast::Statement;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+
+    fn parse(sql: &str) -> Query {
+        match Parser::parse_sql(&GenericDialect {}, sql)
+            .unwrap()
+            .remove(0)
+        {
+            Statement::Query(q) => *q,
+            _ => unreachable!(),
+        }
+    }
+
+    fn plan(sql: &str) -> String {
+        query(&parse(sql)).to_string()
+    }
+
+    #[test]
+    fn expressions_nest_by_evaluation_order() {
+        assert_eq!(
+            plan("SELECT SQRT(2) * 3, (1 - 2) - @x AS y"),
+            "Select
+  Projection
+    Operator *
+      Function SQRT
+        Literal 2
+      Literal 3
+    Alias y
+      Operator -
+        Operator -
+          Literal 1
+          Literal 2
+        Variable @x"
+        );
+    }
+
+    #[test]
+    fn clauses_and_relations_are_children() {
+        assert_eq!(
+            plan(
+                "WITH t AS (VALUES (1)) SELECT DISTINCT COUNT(*) FROM t AS a \
+                 JOIN u ON a.x = u.x WHERE x IN (SELECT 1) GROUP BY x"
+            ),
+            "With
+  CTE t
+    Values
+      Row
+        Literal 1
+  Select Distinct
+    From
+      Inner Join
+        Table t AS a
+        Table u
+        On
+          Operator =
+            Column a.x
+            Column u.x
+    Where
+      In Subquery
+        Column x
+        Select
+          Projection
+            Literal 1
+    Group By
+      Column x
+    Projection
+      Aggregate COUNT
+        Wildcard *"
+        );
+    }
+
+    #[test]
+    fn annotate_appends_what_was_measured() {
+        let sql = "SELECT ABS(x) FROM t JOIN u ON TRUE WHERE ABS(1) > 0";
+        let parsed = parse(sql);
+        let select = parsed.body.to_string();
+        let mut profile = Profile::default();
+        profile.relation(select.clone(), 2, Some(Duration::from_micros(1500)));
+        profile.relation(select, 1, Some(Duration::from_micros(500)));
+        profile.relation(String::from("t"), 3, None);
+        profile.calls(String::from("ABS(x)"), 3);
+
+        let mut node = query(&parsed);
+        node.annotate(&profile);
+        assert_eq!(
+            node.to_string(),
+            "Select (loops=2 rows=3 time=2.000 ms)
+  From
+    Inner Join (never executed)
+      Table t (loops=1 rows=3)
+      Table u (never executed)
+      On
+        Literal true
+  Where
+    Operator >
+      Function ABS (calls=0)
+        Literal 1
+      Literal 0
+  Projection
+    Function ABS (calls=3)
+      Column x"
+        );
+    }
+}
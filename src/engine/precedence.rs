@@ -0,0 +1,212 @@
+//! sqlparser reads `^` as XOR, binding looser than `+`. As exponentiation it
+//! has to bind tighter than `*` (`3 * 2 ^ 2` is 12, `1 + 2 ^ 2` is 5) while
+//! staying left-associative and below unary minus, as in PostgreSQL. `||`
+//! likewise binds looser than `+` there (`'a' || 1 + 2` is `'a3'`), where
+//! sqlparser ranks it with `*`. This pass rebuilds every unparenthesized
+//! arithmetic chain with those rules.
+
+use sqlparser::ast::{BinaryOperator, Expr, Function, FunctionArg, UnaryOperator};
+
+fn precedence(op: &BinaryOperator) -> Option<u8> {
+    match op {
+        BinaryOperator::StringConcat => Some(1),
+        BinaryOperator::Plus | BinaryOperator::Minus => Some(2),
+        BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => Some(3),
+        BinaryOperator::BitwiseXor => Some(4),
+        _ => None,
+    }
+}
+
+fn is_chain(expr: &Expr) -> bool {
+    matches!(expr, Expr::BinaryOp { op, .. } if precedence(op).is_some())
+}
+
+/// Collects the operands and operators of an arithmetic chain in source
+/// order. A sign in front of a chain belongs to its first operand, since
+/// sqlparser lets `-` take in the `*` chain after it.
+fn flatten(expr: Expr, operands: &mut Vec<Expr>, ops: &mut Vec<BinaryOperator>) {
+    match expr {
+        Expr::BinaryOp { left, op, right } if precedence(&op).is_some() => {
+            flatten(*left, operands, ops);
+            ops.push(op);
+            flatten(*right, operands, ops);
+        }
+        Expr::UnaryOp {
+            op: op @ (UnaryOperator::Minus | UnaryOperator::Plus),
+            expr,
+        } if is_chain(&expr) => {
+            let first = operands.len();
+            flatten(*expr, operands, ops);
+            let operand = operands[first].clone();
+            operands[first] = Expr::UnaryOp {
+                op,
+                expr: Box::new(operand),
+            };
+        }
+        expr => operands.push(fix(expr)),
+    }
+}
+
+fn climb(
+    mut left: Expr,
+    min: u8,
+    operands: &mut impl Iterator<Item = Expr>,
+    ops: &mut std::iter::Peekable<impl Iterator<Item = BinaryOperator>>,
+) -> Expr {
+    while let Some(prec) = ops.peek().and_then(precedence).filter(|prec| *prec >= min) {
+        let op = ops.next().unwrap();
+        let mut right = operands.next().unwrap();
+        while ops
+            .peek()
+            .and_then(precedence)
+            .is_some_and(|next| next > prec)
+        {
+            right = climb(right, prec + 1, operands, ops);
+        }
+        left = Expr::BinaryOp {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        };
+    }
+    left
+}
+
+/// `expr` with every arithmetic chain inside it re-associated.
+pub fn fix(expr: Expr) -> Expr {
+    let walk = |expr: Box<Expr>| Box::new(fix(*expr));
+    match expr {
+        expr if is_chain(&expr) => {
+            let (mut operands, mut ops) = (Vec::new(), Vec::new());
+            flatten(expr, &mut operands, &mut ops);
+            let mut operands = operands.into_iter();
+            let first = operands.next().unwrap();
+            climb(first, 1, &mut operands, &mut ops.into_iter().peekable())
+        }
+        Expr::BinaryOp { left, op, right } => Expr::BinaryOp {
+            left: walk(left),
+            op,
+            right: walk(right),
+        },
+        Expr::UnaryOp { op, expr } => Expr::UnaryOp {
+            op,
+            expr: walk(expr),
+        },
+        Expr::Nested(expr) => Expr::Nested(walk(expr)),
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => Expr::Case {
+            operand: operand.map(walk),
+            conditions: conditions.into_iter().map(fix).collect(),
+            results: results.into_iter().map(fix).collect(),
+            else_result: else_result.map(walk),
+        },
+        Expr::IsNull(expr) => Expr::IsNull(walk(expr)),
+        Expr::IsNotNull(expr) => Expr::IsNotNull(walk(expr)),
+        Expr::InList {
+            expr,
+            list,
+            negated,
+        } => Expr::InList {
+            expr: walk(expr),
+            list: list.into_iter().map(fix).collect(),
+            negated,
+        },
+        Expr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => Expr::Between {
+            expr: walk(expr),
+            negated,
+            low: walk(low),
+            high: walk(high),
+        },
+        Expr::Cast { expr, data_type } => Expr::Cast {
+            expr: walk(expr),
+            data_type,
+        },
+        Expr::TryCast { expr, data_type } => Expr::TryCast {
+            expr: walk(expr),
+            data_type,
+        },
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+        } => Expr::Substring {
+            expr: walk(expr),
+            substring_from: substring_from.map(walk),
+            substring_for: substring_for.map(walk),
+        },
+        Expr::Trim { expr, trim_where } => Expr::Trim {
+            expr: walk(expr),
+            trim_where: trim_where.map(|(side, chars)| (side, walk(chars))),
+        },
+        Expr::Extract { field, expr } => Expr::Extract {
+            field,
+            expr: walk(expr),
+        },
+        Expr::Function(func) => Expr::Function(Function {
+            args: func
+                .args
+                .into_iter()
+                .map(|arg| match arg {
+                    FunctionArg::Named { name, arg } => FunctionArg::Named {
+                        name,
+                        arg: fix(arg),
+                    },
+                    FunctionArg::Unnamed(arg) => FunctionArg::Unnamed(fix(arg)),
+                })
+                .collect(),
+            ..func
+        }),
+        expr => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn fixed(expr: &str) -> String {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, expr).tokenize().unwrap();
+        let expr = Parser::new(tokens, &dialect).parse_expr().unwrap();
+        // print with explicit grouping of every binary operation
+        fn group(expr: &Expr) -> String {
+            match expr {
+                Expr::BinaryOp { left, op, right } => {
+                    format!("[{} {} {}]", group(left), op, group(right))
+                }
+                Expr::UnaryOp { op, expr } => format!("{}{}", op, group(expr)),
+                expr => expr.to_string(),
+            }
+        }
+        group(&fix(expr))
+    }
+
+    #[test]
+    fn fix_power_binds_tightest() {
+        assert_eq!(fixed("3 * 2 ^ 2"), "[3 * [2 ^ 2]]");
+        assert_eq!(fixed("1 + 2 ^ 2 * 3"), "[1 + [[2 ^ 2] * 3]]");
+        assert_eq!(fixed("2 ^ 3 ^ 2"), "[[2 ^ 3] ^ 2]");
+        assert_eq!(fixed("-2 ^ 2"), "[-2 ^ 2]");
+        assert_eq!(fixed("2 ^ -3 * 4"), "[[2 ^ -3] * 4]");
+        assert_eq!(fixed("(1 + 2) ^ 2 > 1 - 1"), "[[(1 + 2) ^ 2] > [1 - 1]]");
+    }
+
+    #[test]
+    fn fix_keeps_other_chains() {
+        assert_eq!(fixed("1 - 2 - 3 * 4 / 5"), "[[1 - 2] - [[3 * 4] / 5]]");
+        assert_eq!(fixed("-1 * 2 + 3"), "[[-1 * 2] + 3]");
+        assert_eq!(fixed("'a' || 1 + 2 * 3"), "['a' || [1 + [2 * 3]]]");
+    }
+}
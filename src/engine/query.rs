@@ -0,0 +1,496 @@
+//! Relations: the rows a `VALUES` list or a `FROM` source produces, with
+//! fields naming their columns so expressions can refer to them.
+
+use super::decimal;
+use super::dialect::Dialect;
+use super::engine::{to_text, CalcError, CalcResult};
+use super::operators::ValueType;
+use super::table::{Row, TableProvider};
+use sqlparser::ast::{Expr, Ident, ObjectName, Select, SelectItem, SetOperator, TableAlias};
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// A column of a relation. Identifiers are matched against `key` (see
+/// [`Dialect::normalize_identifier`]); `name` is how the column is shown.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    key: String,
+    table: Option<String>,
+}
+
+impl Field {
+    /// A field matched case-insensitively, like the names of registered
+    /// tables.
+    pub fn new(name: &str) -> Self {
+        Field {
+            name: name.to_string(),
+            key: name.to_uppercase(),
+            table: None,
+        }
+    }
+
+    /// A field named by an identifier, e.g. a column alias.
+    pub fn named(ident: &Ident, dialect: &dyn Dialect) -> Self {
+        Field {
+            name: ident.value.clone(),
+            key: dialect.normalize_identifier(ident),
+            table: None,
+        }
+    }
+
+    /// Whether `ident` names the field.
+    pub fn is(&self, ident: &Ident, dialect: &dyn Dialect) -> bool {
+        self.key == dialect.normalize_identifier(ident)
+    }
+
+    /// The field of a `SELECT` item without an alias: named after the
+    /// column it reads, or else after the expression itself.
+    pub fn of(expr: &Expr, dialect: &dyn Dialect) -> Self {
+        match expr {
+            Expr::Identifier(ident) => Field::named(ident, dialect),
+            Expr::CompoundIdentifier(idents) if !idents.is_empty() => {
+                Field::named(&idents[idents.len() - 1], dialect)
+            }
+            expr => Field::new(&expr.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Relation {
+    pub fields: Vec<Field>,
+    pub rows: Vec<Row>,
+}
+
+impl Relation {
+    /// The relation of a `VALUES` list, its columns named `column1`,
+    /// `column2` and so on.
+    pub fn values(rows: Vec<Row>) -> Result<Self, CalcError> {
+        let width = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(CalcError::InvalidRequestFormat(String::from(
+                "VALUES lists must all be the same length",
+            )));
+        }
+        Ok(Relation {
+            fields: (1..=width)
+                .map(|i| Field::new(&format!("column{}", i)))
+                .collect(),
+            rows,
+        })
+    }
+
+    /// The rows of a registered table or a table function's table, with
+    /// only the columns `pushdown` may need.
+    pub fn scan(table: &dyn TableProvider, pushdown: &Pushdown) -> Result<Self, CalcError> {
+        let schema = table.schema();
+        let projection: Option<Vec<_>> = pushdown.names.as_ref().map(|names| {
+            (0..schema.len())
+                .filter(|i| {
+                    let key = schema[*i].name.to_uppercase();
+                    let parent = key.split('.').next().unwrap_or_default();
+                    names.contains(&key) || names.contains(parent)
+                })
+                .collect()
+        });
+        let fields = match &projection {
+            Some(columns) => columns
+                .iter()
+                .map(|i| Field::new(&schema[*i].name))
+                .collect(),
+            None => schema
+                .iter()
+                .map(|column| Field::new(&column.name))
+                .collect(),
+        };
+        Ok(Relation {
+            fields,
+            rows: table.scan(&pushdown.filters, projection.as_deref())?,
+        })
+    }
+
+    /// Names the relation as `alias` says, renaming as many of its columns
+    /// as the alias lists.
+    pub fn alias(mut self, alias: &TableAlias, dialect: &dyn Dialect) -> Result<Self, CalcError> {
+        if alias.columns.len() > self.fields.len() {
+            return Err(CalcError::InvalidRequestFormat(format!(
+                "Table {} has {} column(s), {} aliased",
+                alias.name,
+                self.fields.len(),
+                alias.columns.len()
+            )));
+        }
+        for (field, column) in self.fields.iter_mut().zip(&alias.columns) {
+            *field = Field::named(column, dialect);
+        }
+        Ok(self.qualify(dialect.normalize_identifier(&alias.name)))
+    }
+
+    /// Drops rows equal to an earlier one, as `SELECT DISTINCT` does.
+    pub fn distinct(mut self) -> Self {
+        let mut seen = HashSet::new();
+        self.rows.retain(|row| seen.insert(Key(row.clone())));
+        self
+    }
+
+    /// Lets `table.column` refer to the columns, `table` being a lookup key.
+    pub fn qualify(mut self, table: String) -> Self {
+        for field in &mut self.fields {
+            field.table = Some(table.clone());
+        }
+        self
+    }
+}
+
+/// What a `SELECT` needs from the table it reads, passed on so the table
+/// can skip rows and columns early.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pushdown {
+    /// The conditions rows are checked against.
+    pub filters: Vec<Expr>,
+    /// Upper-cased names the query may refer to columns by, or `None` when
+    /// it needs every column.
+    pub names: Option<HashSet<String>>,
+}
+
+impl Pushdown {
+    pub fn new(select: &Select, filters: Vec<Expr>, dialect: &dyn Dialect) -> Self {
+        let wildcard = select.projection.iter().any(|item| {
+            matches!(
+                item,
+                SelectItem::Wildcard | SelectItem::QualifiedWildcard(_)
+            )
+        });
+        // Every word of the query may name a column, so none is missed,
+        // however deeply nested the expression using it is.
+        let tokens = Tokenizer::new(dialect.parser_dialect(), &select.to_string()).tokenize();
+        let names = match (wildcard, tokens) {
+            (false, Ok(tokens)) => Some(
+                tokens
+                    .into_iter()
+                    .filter_map(|token| match token {
+                        Token::Word(word) => Some(word.value.to_uppercase()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Pushdown { filters, names }
+    }
+}
+
+/// Indices of the fields `table.*` expands to.
+pub fn qualified_wildcard(
+    fields: &[Field],
+    table: &ObjectName,
+    dialect: &dyn Dialect,
+) -> Result<Vec<usize>, CalcError> {
+    let key = match &table.0[..] {
+        [ident] => dialect.normalize_identifier(ident),
+        _ => String::new(),
+    };
+    let indices: Vec<_> = (0..fields.len())
+        .filter(|i| fields[*i].table.as_ref() == Some(&key))
+        .collect();
+    match indices.is_empty() {
+        true => Err(CalcError::InvalidRequestFormat(format!(
+            "Unknown table {}",
+            table
+        ))),
+        false => Ok(indices),
+    }
+}
+
+/// Combines the rows of two queries as `UNION`, `INTERSECT` or `EXCEPT`
+/// does, the columns named after `left`'s. Without `ALL`, the result has
+/// no duplicate rows; with it, a row is kept as many times as it would be
+/// by matching each row of one side with at most one of the other.
+pub fn set_operation(
+    op: &SetOperator,
+    all: bool,
+    left: Relation,
+    right: Relation,
+) -> Result<Relation, CalcError> {
+    if left.fields.len() != right.fields.len() {
+        return Err(CalcError::InvalidRequestFormat(format!(
+            "{} branches have {} and {} column(s)",
+            op,
+            left.fields.len(),
+            right.fields.len()
+        )));
+    }
+    for (i, field) in left.fields.iter().enumerate() {
+        match (column_type(&left.rows, i), column_type(&right.rows, i)) {
+            (Some(l), Some(r)) if l != r && !(numeric(l) && numeric(r)) => {
+                return Err(CalcError::InvalidType(format!(
+                    "{} column {} holds both {} and {} values",
+                    op, field.name, l, r
+                )))
+            }
+            _ => {}
+        }
+    }
+
+    let rows = match op {
+        SetOperator::Union => {
+            let mut rows = left.rows;
+            rows.extend(right.rows);
+            rows
+        }
+        SetOperator::Intersect | SetOperator::Except => {
+            let mut counts: HashMap<Key, usize> = HashMap::new();
+            for row in right.rows {
+                *counts.entry(Key(row)).or_default() += 1;
+            }
+            let intersect = matches!(op, SetOperator::Intersect);
+            left.rows
+                .into_iter()
+                .filter(|row| match counts.get_mut(&Key(row.clone())) {
+                    Some(count) if *count > 0 => {
+                        if all {
+                            *count -= 1;
+                        }
+                        intersect
+                    }
+                    _ => !intersect,
+                })
+                .collect()
+        }
+    };
+    let relation = Relation {
+        fields: left.fields,
+        rows,
+    };
+    Ok(match all {
+        true => relation,
+        false => relation.distinct(),
+    })
+}
+
+/// The type of the first value of column `i` that is not NULL.
+fn column_type(rows: &[Row], i: usize) -> Option<ValueType> {
+    rows.iter()
+        .filter_map(|row| row.get(i))
+        .map(ValueType::of)
+        .find(|ty| *ty != ValueType::Null)
+}
+
+fn numeric(ty: ValueType) -> bool {
+    matches!(
+        ty,
+        ValueType::Integer | ValueType::Number | ValueType::Decimal
+    )
+}
+
+/// A row being evaluated, whose columns identifiers refer to.
+#[derive(Clone, Debug)]
+pub struct Scope {
+    pub fields: Rc<[Field]>,
+    pub row: Rc<[CalcResult]>,
+}
+
+impl Scope {
+    /// The value of the column `name` (`column` or `table.column`) refers
+    /// to, or `None` when there is no such column. Columns flattened from
+    /// nested fields are named by their path, `parent.child`, with or
+    /// without a table in front.
+    pub fn lookup(
+        &self,
+        name: &[Ident],
+        dialect: &dyn Dialect,
+    ) -> Result<Option<&CalcResult>, CalcError> {
+        let keys: Vec<_> = name
+            .iter()
+            .map(|ident| dialect.normalize_identifier(ident))
+            .collect();
+        let mut candidates = Vec::new();
+        if let [table, path @ ..] = &keys[..] {
+            if !path.is_empty() {
+                candidates.push((Some(table.clone()), path.join(".")));
+            }
+        }
+        candidates.push((None, keys.join(".")));
+
+        for (table, key) in candidates {
+            let mut matches = (0..self.fields.len()).filter(|i| {
+                let field = &self.fields[*i];
+                field.key == key && (table.is_none() || field.table == table)
+            });
+            match (matches.next(), matches.next()) {
+                (Some(i), None) => return Ok(Some(&self.row[i])),
+                (None, _) => continue,
+                (Some(_), Some(_)) => {
+                    return Err(CalcError::InvalidRequestFormat(format!(
+                        "Column reference {} is ambiguous",
+                        ObjectName(name.to_vec())
+                    )))
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Values compared as `GROUP BY` and `DISTINCT` compare them: NULLs are
+/// equal to each other, and numbers are equal when their values are, whatever their type.
+#[derive(Clone, Debug)]
+pub struct Key(pub Vec<CalcResult>);
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        same(&self.0, &other.0)
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_values(&self.0, state)
+    }
+}
+
+fn same(left: &[CalcResult], right: &[CalcResult]) -> bool {
+    left.len() == right.len()
+        && left.iter().zip(right).all(|pair| match pair {
+            (CalcResult::Array(l), CalcResult::Array(r))
+            | (CalcResult::Row(l), CalcResult::Row(r)) => same(l, r),
+            (CalcResult::Interval(l), CalcResult::Interval(r)) => l.span() == r.span(),
+            (l, r) => match (decimal::of(l), decimal::of(r), l.as_f64(), r.as_f64()) {
+                (Some(l), Some(r), _, _) => l == r,
+                (_, _, Some(l), Some(r)) => l == r || (l.is_nan() && r.is_nan()),
+                _ => l == r,
+            },
+        })
+}
+
+fn hash_values<H: Hasher>(values: &[CalcResult], state: &mut H) {
+    values.len().hash(state);
+    for value in values {
+        match value {
+            CalcResult::Array(items) | CalcResult::Row(items) => hash_values(items, state),
+            // Intervals of the same length are equal, see `same`.
+            CalcResult::Interval(interval) => interval.span().hash(state),
+            value => match value.as_f64() {
+                // Equal numbers of different types must hash alike.
+                Some(0.0) => 0_u64.hash(state),
+                Some(num) if num.is_nan() => f64::NAN.to_bits().hash(state),
+                Some(num) => num.to_bits().hash(state),
+                None => {
+                    std::mem::discriminant(value).hash(state);
+                    to_text(value).hash(state);
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::dialect::Generic;
+
+    fn scope(relation: Relation) -> Scope {
+        Scope {
+            fields: relation.fields.into(),
+            row: relation.rows[0].clone().into(),
+        }
+    }
+
+    #[test]
+    fn values_name_columns() {
+        let relation = Relation::values(vec![vec![CalcResult::Int(1), CalcResult::Null]]).unwrap();
+        let names: Vec<_> = relation
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(names, ["column1", "column2"]);
+        assert_eq!(
+            Relation::values(vec![vec![CalcResult::Int(1)], vec![]]).unwrap_err(),
+            CalcError::InvalidRequestFormat(String::from(
+                "VALUES lists must all be the same length"
+            ))
+        );
+    }
+
+    #[test]
+    fn lookup_resolves_names() {
+        let relation =
+            Relation::values(vec![vec![CalcResult::Int(1), CalcResult::Int(2)]]).unwrap();
+        let alias = TableAlias {
+            name: Ident::new("t"),
+            columns: vec![Ident::new("a")],
+        };
+        let scope = scope(relation.alias(&alias, &Generic).unwrap());
+        let lookup = |name: &[&str]| {
+            let name: Vec<_> = name.iter().map(|part| Ident::new(*part)).collect();
+            scope.lookup(&name, &Generic).unwrap().cloned()
+        };
+        assert_eq!(lookup(&["A"]), Some(CalcResult::Int(1)));
+        assert_eq!(lookup(&["t", "column2"]), Some(CalcResult::Int(2)));
+        assert_eq!(lookup(&["u", "a"]), None);
+        assert_eq!(lookup(&["b"]), None);
+        assert_eq!(
+            scope
+                .lookup(&[Ident::with_quote('"', "a")], &Generic)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn lookup_resolves_nested_paths() {
+        let relation = Relation {
+            fields: vec![Field::new("user.name"), Field::new("user")],
+            rows: vec![vec![CalcResult::Int(1), CalcResult::Int(2)]],
+        };
+        let scope = scope(relation.qualify(String::from("T")));
+        let lookup = |name: &[&str]| {
+            let name: Vec<_> = name.iter().map(|part| Ident::new(*part)).collect();
+            scope.lookup(&name, &Generic).unwrap().cloned()
+        };
+        assert_eq!(lookup(&["user", "name"]), Some(CalcResult::Int(1)));
+        assert_eq!(lookup(&["t", "user", "name"]), Some(CalcResult::Int(1)));
+        assert_eq!(lookup(&["t", "user"]), Some(CalcResult::Int(2)));
+        assert_eq!(lookup(&["user", "age"]), None);
+    }
+
+    #[test]
+    fn keys_compare_values() {
+        let key = |values: &[CalcResult]| Key(values.to_vec());
+        let keys: HashSet<_> = [
+            key(&[CalcResult::Int(1), CalcResult::Null]),
+            key(&[CalcResult::Num(1.0), CalcResult::Null]),
+            key(&[CalcResult::Decimal(1.into()), CalcResult::Null]),
+            key(&[CalcResult::Str(String::from("1")), CalcResult::Null]),
+            key(&[CalcResult::Num(f64::NAN)]),
+            key(&[CalcResult::Num(f64::NAN)]),
+            key(&[CalcResult::Array(vec![CalcResult::Int(2)])]),
+            key(&[CalcResult::Array(vec![CalcResult::Num(2.0)])]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(keys.len(), 4);
+    }
+
+    #[test]
+    fn lookup_rejects_ambiguous_names() {
+        let relation =
+            Relation::values(vec![vec![CalcResult::Int(1), CalcResult::Int(2)]]).unwrap();
+        let alias = TableAlias {
+            name: Ident::new("t"),
+            columns: vec![Ident::new("a"), Ident::new("a")],
+        };
+        assert_eq!(
+            scope(relation.alias(&alias, &Generic).unwrap())
+                .lookup(&[Ident::new("a")], &Generic)
+                .unwrap_err(),
+            CalcError::InvalidRequestFormat(String::from("Column reference a is ambiguous"))
+        );
+    }
+}
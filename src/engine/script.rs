@@ -0,0 +1,163 @@
+//! User functions written in Rhai:
+//! `CREATE FUNCTION f(x, y) LANGUAGE rhai AS 'x * y + 1'`.
+//!
+//! Arguments are bound to script variables named after the parameters, and
+//! the value of the script is the result. Scripts only see Rhai's standard
+//! library, and each call runs with an operation budget, so a runaway loop
+//! fails the query instead of hanging it.
+
+use super::engine::{to_text, CalcError, CalcResult, Limits};
+use super::functions::ScalarFunction;
+use rhai::{Dynamic, EvalAltResult, Scope, AST};
+
+/// Operations a single call may perform, roughly one per evaluated node.
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+fn to_dynamic(value: &CalcResult) -> Dynamic {
+    match value {
+        CalcResult::Num(num) => Dynamic::from_float(*num),
+        CalcResult::Int(int) => Dynamic::from_int(*int),
+        CalcResult::Decimal(_) => Dynamic::from_float(value.as_f64().unwrap_or_default()),
+        CalcResult::Bool(boolean) => Dynamic::from_bool(*boolean),
+        CalcResult::Str(str) => Dynamic::from(str.clone()),
+        CalcResult::Date(_) | CalcResult::Timestamp(_) | CalcResult::Interval(_) => {
+            Dynamic::from(to_text(value))
+        }
+        CalcResult::Array(items) | CalcResult::Row(items) => {
+            Dynamic::from_array(items.iter().map(to_dynamic).collect())
+        }
+        CalcResult::Table(rows) => Dynamic::from_array(
+            rows.iter()
+                .map(|row| Dynamic::from_array(row.iter().map(to_dynamic).collect()))
+                .collect(),
+        ),
+        CalcResult::Null => Dynamic::UNIT,
+    }
+}
+
+fn from_dynamic(name: &str, value: Dynamic) -> Result<CalcResult, CalcError> {
+    if let Ok(num) = value.as_float() {
+        Ok(CalcResult::Num(num))
+    } else if let Ok(int) = value.as_int() {
+        Ok(CalcResult::Int(int))
+    } else if let Ok(boolean) = value.as_bool() {
+        Ok(CalcResult::Bool(boolean))
+    } else if value.is_unit() {
+        Ok(CalcResult::Null)
+    } else if value.is_string() {
+        Ok(CalcResult::Str(value.into_string().unwrap_or_default()))
+    } else if value.is_array() {
+        let items = value.into_array().unwrap_or_default().into_iter();
+        Ok(CalcResult::Array(
+            items
+                .map(|item| from_dynamic(name, item))
+                .collect::<Result<_, _>>()?,
+        ))
+    } else {
+        Err(CalcError::FunctionFailed(format!(
+            "{}: unsupported result type {}",
+            name,
+            value.type_name()
+        )))
+    }
+}
+
+/// Compiles `source` into a function taking `params` (as written in the
+/// definition), reporting syntax errors right away.
+pub fn compile(
+    name: &str,
+    params: Vec<String>,
+    source: &str,
+    limits: &Limits,
+) -> Result<ScalarFunction, CalcError> {
+    let mut engine = rhai::Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_string_size(limits.max_string_len)
+        .disable_symbol("eval")
+        .on_print(|_| ())
+        .on_debug(|_, _, _| ());
+    let ast: AST = engine.compile(source).map_err(|err| {
+        CalcError::InvalidRequestFormat(format!("Invalid rhai function {}: {}", name, err))
+    })?;
+
+    let name = name.to_string();
+    Ok(ScalarFunction {
+        arity: params.len(),
+        optional: 0,
+        // scripts see NULL as `()` and decide what it means
+        strict: false,
+        eval: Box::new(move |args| {
+            let mut scope = Scope::new();
+            for (param, arg) in params.iter().zip(args) {
+                scope.push_dynamic(param.as_str(), to_dynamic(arg));
+            }
+            match engine.eval_ast_with_scope::<Dynamic>(&mut scope, &ast) {
+                Ok(value) => from_dynamic(&name, value),
+                Err(err) => match *err {
+                    EvalAltResult::ErrorTooManyOperations(_) => Err(CalcError::ResourceLimit(
+                        format!("{} exceeded {} operations", name, MAX_OPERATIONS),
+                    )),
+                    EvalAltResult::ErrorDataTooLarge(..) => {
+                        Err(CalcError::ResourceLimit(format!("{}: {}", name, err)))
+                    }
+                    _ => Err(CalcError::FunctionFailed(format!("{}: {}", name, err))),
+                },
+            }
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(source: &str, args: &[CalcResult]) -> Result<CalcResult, CalcError> {
+        let params = (0..args.len()).map(|i| format!("a{}", i)).collect();
+        let function = compile("F", params, source, &Limits::default())?;
+        function.call(args)
+    }
+
+    #[test]
+    fn compile_converts_values() {
+        assert_eq!(
+            call("a0 * 2.0", &[CalcResult::Num(1.5)]),
+            Ok(CalcResult::Num(3.0))
+        );
+        assert_eq!(call("40 + 2", &[]), Ok(CalcResult::Int(42)));
+        assert_eq!(
+            call("a0 + \"!\"", &[CalcResult::Str(String::from("hi"))]),
+            Ok(CalcResult::Str(String::from("hi!")))
+        );
+        assert_eq!(
+            call("a0 == ()", &[CalcResult::Null]),
+            Ok(CalcResult::Bool(true))
+        );
+        assert_eq!(call("()", &[]), Ok(CalcResult::Null));
+        assert_eq!(
+            call("a0 + [3]", &[CalcResult::Array(vec![CalcResult::Int(1)])]),
+            Ok(CalcResult::Array(vec![
+                CalcResult::Int(1),
+                CalcResult::Int(3)
+            ]))
+        );
+    }
+
+    #[test]
+    fn compile_errors() {
+        assert!(matches!(
+            call("1 +", &[]),
+            Err(CalcError::InvalidRequestFormat(_))
+        ));
+        assert_eq!(
+            call("loop {}", &[]),
+            Err(CalcError::ResourceLimit(String::from(
+                "F exceeded 1000000 operations"
+            )))
+        );
+        assert!(matches!(
+            call("#{a: 1}", &[]),
+            Err(CalcError::FunctionFailed(message)) if message == "F: unsupported result type map"
+        ));
+    }
+}
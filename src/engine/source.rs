@@ -0,0 +1,282 @@
+//! Files and URLs read as tables. `FROM 'data.csv'` reads a file with the
+//! table function its extension calls for, and the table functions read
+//! `http://` and `https://` URLs as well as paths, downloading them first.
+
+use super::engine::CalcError;
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::Token;
+use std::path::{Path, PathBuf};
+
+/// How URLs are downloaded.
+#[derive(Clone, Debug, Default)]
+pub struct HttpOptions {
+    /// Sent with every request, e.g. `Authorization`.
+    pub headers: Vec<(String, String)>,
+    /// Directory keeping each download, so a URL is fetched only once;
+    /// without one every query fetches it again.
+    pub cache: Option<PathBuf>,
+}
+
+pub fn is_url(path: &str) -> bool {
+    let scheme = path
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_lowercase());
+    matches!(scheme.as_deref(), Some("http" | "https"))
+}
+
+/// The table function reading a file or URL, by its extension.
+fn table_function(path: &str) -> &'static str {
+    let path = path
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if path.ends_with(".json") || path.ends_with(".ndjson") {
+        "READ_JSON"
+    } else if path.ends_with(".parquet") {
+        "READ_PARQUET"
+    } else {
+        "READ_CSV"
+    }
+}
+
+/// Rewrites a string naming a table after `FROM` or `JOIN` into a call of
+/// the table function reading it: `FROM 'data.csv'` into
+/// `FROM READ_CSV('data.csv')`. Only a query's own `FROM` counts, not the
+/// one of `EXTRACT(YEAR FROM '...')`, `TRIM(... FROM '...')` or
+/// `IS DISTINCT FROM '...'`.
+pub fn desugar(mut tokens: Vec<Token>) -> Vec<Token> {
+    let is = |token: &Token, keyword: Keyword| matches!(token, Token::Word(word) if word.keyword == keyword);
+    // Whether each open parenthesis, and the statement itself, is a query.
+    let mut queries = vec![true];
+    let mut previous: Option<usize> = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if matches!(token, Token::Whitespace(_)) {
+            i += 1;
+            continue;
+        }
+        let opened = previous.is_some_and(|previous| tokens[previous] == Token::LParen);
+        if opened && (is(token, Keyword::SELECT) || is(token, Keyword::WITH)) {
+            *queries.last_mut().unwrap() = true;
+        }
+        match token {
+            Token::LParen => queries.push(false),
+            Token::RParen if queries.len() > 1 => {
+                queries.pop();
+            }
+            _ => (),
+        }
+
+        let distinct = previous.is_some_and(|previous| is(&tokens[previous], Keyword::DISTINCT));
+        let names_table = (is(token, Keyword::FROM) && !distinct) || is(token, Keyword::JOIN);
+        let next = tokens[i + 1..]
+            .iter()
+            .position(|token| !matches!(token, Token::Whitespace(_)))
+            .map(|offset| i + 1 + offset);
+        if let (true, true, Some(next)) = (names_table, queries[queries.len() - 1], next) {
+            if let Token::SingleQuotedString(path) = &tokens[next] {
+                let call = [
+                    Token::make_word(table_function(path), None),
+                    Token::LParen,
+                    tokens[next].clone(),
+                    Token::RParen,
+                ];
+                tokens.splice(next..next + 1, call);
+            }
+        }
+        previous = Some(i);
+        i += 1;
+    }
+    tokens
+}
+
+/// A file a table is read from.
+pub enum Local {
+    /// A path as given, or a download kept in the cache.
+    Path(PathBuf),
+    /// A download of this query alone, deleted once dropped.
+    #[cfg(feature = "http")]
+    Download(tempfile::TempPath),
+}
+
+impl Local {
+    pub fn path(&self) -> &Path {
+        match self {
+            Local::Path(path) => path,
+            #[cfg(feature = "http")]
+            Local::Download(path) => path,
+        }
+    }
+}
+
+/// The local file holding `path`: the path itself, or the download of a
+/// URL. `function` names the table function reading it in errors.
+pub fn local(path: &str, function: &str, http: &HttpOptions) -> Result<Local, CalcError> {
+    match is_url(path) {
+        true => download(path, function, http),
+        false => Ok(Local::Path(PathBuf::from(path))),
+    }
+}
+
+/// The text of the file or URL at `path`.
+pub fn read_to_string(path: &str, function: &str, http: &HttpOptions) -> Result<String, CalcError> {
+    std::fs::read_to_string(local(path, function, http)?.path()).map_err(|err| {
+        CalcError::FunctionFailed(format!("{} cannot read {}: {}", function, path, err))
+    })
+}
+
+#[cfg(feature = "http")]
+fn download(url: &str, function: &str, http: &HttpOptions) -> Result<Local, CalcError> {
+    use sha2::{Digest, Sha256};
+
+    let failed = |err: String| CalcError::FunctionFailed(format!("{} {}: {}", function, url, err));
+    let cached = match &http.cache {
+        Some(dir) => {
+            let name: String = Sha256::digest(url.as_bytes())
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let path = dir.join(name);
+            if path.exists() {
+                return Ok(Local::Path(path));
+            }
+            std::fs::create_dir_all(dir).map_err(|err| failed(err.to_string()))?;
+            Some(path)
+        }
+        None => None,
+    };
+
+    let mut request = ureq::get(url);
+    for (name, value) in &http.headers {
+        request = request.set(name, value);
+    }
+    let response = request.call().map_err(|err| failed(err.to_string()))?;
+    // Each download gets a file of its own, next to the cache entry so it
+    // is moved there whole: workers fetching the same URL never share one.
+    let dir = http.cache.clone().unwrap_or_else(std::env::temp_dir);
+    let mut file = tempfile::Builder::new()
+        .prefix("sql-test-engine-")
+        .tempfile_in(dir)
+        .map_err(|err| failed(err.to_string()))?;
+    std::io::copy(&mut response.into_reader(), &mut file).map_err(|err| failed(err.to_string()))?;
+    match cached {
+        Some(path) => {
+            file.persist(&path).map_err(|err| failed(err.to_string()))?;
+            Ok(Local::Path(path))
+        }
+        None => Ok(Local::Download(file.into_temp_path())),
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &str, function: &str, _: &HttpOptions) -> Result<Local, CalcError> {
+    Err(CalcError::FunctionFailed(format!(
+        "{} {}: URLs require the http feature",
+        function, url
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn desugared(sql: &str) -> String {
+        let tokens = Tokenizer::new(&GenericDialect {}, sql).tokenize().unwrap();
+        desugar(tokens).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn desugar_names_the_reader() {
+        assert_eq!(
+            desugared("SELECT * FROM 'https://x.com/a.csv?v=1' a JOIN 'b.NDJSON' b ON true"),
+            "SELECT * FROM READ_CSV('https://x.com/a.csv?v=1') a JOIN READ_JSON('b.NDJSON') b ON true"
+        );
+        assert_eq!(
+            desugared("SELECT 'a.parquet' FROM\n'a.parquet'"),
+            "SELECT 'a.parquet' FROM\nREAD_PARQUET('a.parquet')"
+        );
+        assert_eq!(
+            desugared("SELECT (SELECT n FROM 'a.csv'), EXTRACT(YEAR FROM '2024-05-01'), TRIM(BOTH FROM ' x ') FROM t WHERE a IS DISTINCT FROM 'b'"),
+            "SELECT (SELECT n FROM READ_CSV('a.csv')), EXTRACT(YEAR FROM '2024-05-01'), TRIM(BOTH FROM ' x ') FROM t WHERE a IS DISTINCT FROM 'b'"
+        );
+    }
+
+    #[test]
+    fn urls() {
+        assert!(is_url("https://example.com/data.csv"));
+        assert!(is_url("HTTP://example.com"));
+        assert!(!is_url("ftp://example.com"));
+        assert!(!is_url("data.csv"));
+        assert_eq!(
+            local("data.csv", "READ_CSV", &HttpOptions::default())
+                .unwrap()
+                .path(),
+            Path::new("data.csv")
+        );
+        assert!(
+            read_to_string("/nonexistent/t.json", "READ_JSON", &HttpOptions::default())
+                .unwrap_err()
+                .to_string()
+                .starts_with("[Function Failed]: READ_JSON cannot read /nonexistent/t.json: ")
+        );
+    }
+
+    /// Answers `requests` requests on a local port with `body`, giving the
+    /// URL to fetch it from.
+    #[cfg(feature = "http")]
+    fn serve(body: &'static str, requests: usize) -> String {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.csv", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let head = "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length";
+                write!(stream, "{}: {}\r\n\r\n{}", head, body.len(), body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn downloads_of_one_url_do_not_collide() {
+        let url = serve("n\n1\n", 2);
+        let http = HttpOptions::default();
+        let first = local(&url, "READ_CSV", &http).unwrap();
+        let second = local(&url, "READ_CSV", &http).unwrap();
+        assert_ne!(first.path(), second.path());
+        assert_eq!(std::fs::read_to_string(second.path()).unwrap(), "n\n1\n");
+
+        // Uncached downloads go once the table reading them does.
+        let path = first.path().to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn downloads_are_cached() {
+        let cache = tempfile::tempdir().unwrap();
+        let http = HttpOptions {
+            cache: Some(cache.path().to_path_buf()),
+            ..HttpOptions::default()
+        };
+        // Only one request is answered, so the second read hits the cache.
+        let url = serve("n\n1\n", 1);
+        assert_eq!(read_to_string(&url, "READ_CSV", &http).unwrap(), "n\n1\n");
+        assert_eq!(read_to_string(&url, "READ_CSV", &http).unwrap(), "n\n1\n");
+        assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 1);
+    }
+}
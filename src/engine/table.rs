@@ -0,0 +1,163 @@
+use super::engine::{CalcError, CalcResult};
+use super::operators::ValueType;
+use sqlparser::ast::Expr;
+
+/// A column of a table, typed like the values it holds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub ty: ValueType,
+}
+
+impl Column {
+    pub fn new(name: &str, ty: ValueType) -> Self {
+        Column {
+            name: name.to_string(),
+            ty,
+        }
+    }
+}
+
+pub type Row = Vec<CalcResult>;
+
+/// A source of rows queries can read with `FROM`: an in-memory table, a
+/// file, a REST API. Implementations only have to describe their columns
+/// and produce rows; everything else is done by the engine.
+pub trait TableProvider: Send + Sync {
+    fn schema(&self) -> Vec<Column>;
+
+    /// Rows of the table, holding only the columns at the `projection`
+    /// indices (in that order) or every column when it is `None`.
+    ///
+    /// `filters` are the conditions the query applies to the rows. A
+    /// provider may use them to skip rows early, but does not have to: the
+    /// engine applies them again to whatever comes back.
+    fn scan(&self, filters: &[Expr], projection: Option<&[usize]>) -> Result<Vec<Row>, CalcError>;
+}
+
+/// A table held in memory, e.g. a fixture built by a test.
+#[derive(Debug, Default, PartialEq)]
+pub struct MemTable {
+    schema: Vec<Column>,
+    rows: Vec<Row>,
+}
+
+impl MemTable {
+    /// Fails when a row does not have exactly one value per column, or a
+    /// value that is neither NULL nor of its column's type.
+    pub fn new(schema: Vec<Column>, rows: Vec<Row>) -> Result<Self, CalcError> {
+        let mut table = MemTable {
+            schema,
+            rows: Vec::new(),
+        };
+        table.insert(rows)?;
+        Ok(table)
+    }
+
+    /// Appends `rows`, checked as [`MemTable::new`] checks them. Nothing is
+    /// appended when any row fails.
+    pub fn insert(&mut self, rows: Vec<Row>) -> Result<(), CalcError> {
+        for row in &rows {
+            if row.len() != self.schema.len() {
+                return Err(CalcError::InvalidType(format!(
+                    "Row has {} value(s), table has {} column(s)",
+                    row.len(),
+                    self.schema.len()
+                )));
+            }
+            for (column, value) in self.schema.iter().zip(row) {
+                let ty = ValueType::of(value);
+                if ty != ValueType::Null && ty != column.ty {
+                    return Err(CalcError::InvalidType(format!(
+                        "Column {} is {}, got {}",
+                        column.name, column.ty, ty
+                    )));
+                }
+            }
+        }
+        self.rows.extend(rows);
+        Ok(())
+    }
+}
+
+impl TableProvider for MemTable {
+    fn schema(&self) -> Vec<Column> {
+        self.schema.clone()
+    }
+
+    fn scan(&self, _filters: &[Expr], projection: Option<&[usize]>) -> Result<Vec<Row>, CalcError> {
+        let width = self.schema.len();
+        if let Some(index) = projection.and_then(|columns| columns.iter().find(|i| **i >= width)) {
+            return Err(CalcError::InvalidType(format!(
+                "Column index {} is out of range for {} column(s)",
+                index, width
+            )));
+        }
+
+        Ok(self
+            .rows
+            .iter()
+            .map(|row| match projection {
+                Some(columns) => columns.iter().map(|i| row[*i].clone()).collect(),
+                None => row.clone(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> MemTable {
+        MemTable::new(
+            vec![
+                Column::new("id", ValueType::Number),
+                Column::new("name", ValueType::String),
+            ],
+            vec![
+                vec![CalcResult::Num(1.0), CalcResult::Str(String::from("a"))],
+                vec![CalcResult::Num(2.0), CalcResult::Null],
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn mem_table_scan_projection() {
+        assert_eq!(
+            table().scan(&[], Some(&[1, 0])).unwrap(),
+            vec![
+                vec![CalcResult::Str(String::from("a")), CalcResult::Num(1.0)],
+                vec![CalcResult::Null, CalcResult::Num(2.0)],
+            ]
+        );
+        assert_eq!(table().scan(&[], None).unwrap().len(), 2);
+        assert!(table().scan(&[], Some(&[2])).is_err());
+    }
+
+    #[test]
+    fn mem_table_checks_rows() {
+        let schema = vec![Column::new("id", ValueType::Number)];
+        assert_eq!(
+            MemTable::new(schema.clone(), vec![vec![CalcResult::Bool(true)]]).unwrap_err(),
+            CalcError::InvalidType(String::from("Column id is Number, got Boolean"))
+        );
+        assert!(MemTable::new(schema, vec![vec![]]).is_err());
+    }
+
+    #[test]
+    fn mem_table_insert_is_atomic() {
+        let mut table = table();
+        let rows = vec![
+            vec![CalcResult::Num(3.0), CalcResult::Null],
+            vec![CalcResult::Num(4.0), CalcResult::Num(4.0)],
+        ];
+        assert!(table.insert(rows).is_err());
+        assert_eq!(table.scan(&[], None).unwrap().len(), 2);
+        table
+            .insert(vec![vec![CalcResult::Null, CalcResult::Null]])
+            .unwrap();
+        assert_eq!(table.scan(&[], None).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,720 @@
+//! Dates, timestamps and intervals. Timestamps hold the wall-clock time of
+//! the session time zone (`SET TIME ZONE`, UTC unless set): "now" and
+//! timestamps written with an offset are converted into it, and the time
+//! part of an interval is elapsed time there, so `+ INTERVAL '24 hours'`
+//! across a DST change moves the clock by 23 or 25.
+
+use super::engine::{CalcError, CalcResult};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, LocalResult, Months, NaiveDate, NaiveDateTime,
+    Offset, TimeZone, Timelike,
+};
+use chrono_tz::Tz;
+use sqlparser::ast::{BinaryOperator, DateTimeField};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// A span of time kept in three parts, as in PostgreSQL, since neither a
+/// month nor a day has a fixed length once added to a date: `INTERVAL '1
+/// month'` moves Jan 31 to the end of February, `INTERVAL '1 day'` across a
+/// leap second is still a calendar day.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Interval {
+    pub months: i64,
+    pub days: i64,
+    pub nanos: i64,
+}
+
+impl Interval {
+    fn checked_add(self, other: Interval) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.checked_add(other.months)?,
+            days: self.days.checked_add(other.days)?,
+            nanos: self.nanos.checked_add(other.nanos)?,
+        })
+    }
+
+    /// The length intervals are ordered by, in nanoseconds, taking a month
+    /// as 30 days and a day as 24 hours as PostgreSQL does: `INTERVAL '1
+    /// day'` equals `INTERVAL '24 hours'`.
+    pub fn span(&self) -> i128 {
+        let day = 24 * 3600 * NANOS_PER_SECOND as i128;
+        (self.months as i128 * 30 + self.days as i128) * day + self.nanos as i128
+    }
+
+    pub fn negate(self) -> Interval {
+        Interval {
+            months: -self.months,
+            days: -self.days,
+            nanos: -self.nanos,
+        }
+    }
+}
+
+/// `1 year 2 months 3 days 04:05:06`, leaving out zero parts.
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |count: i64, unit: &str| match count.abs() {
+            1 => format!("{} {}", count, unit),
+            _ => format!("{} {}s", count, unit),
+        };
+        let mut parts = Vec::new();
+        if self.months / 12 != 0 {
+            parts.push(plural(self.months / 12, "year"));
+        }
+        if self.months % 12 != 0 {
+            parts.push(plural(self.months % 12, "month"));
+        }
+        if self.days != 0 {
+            parts.push(plural(self.days, "day"));
+        }
+        if self.nanos != 0 || parts.is_empty() {
+            let sign = if self.nanos < 0 { "-" } else { "" };
+            let nanos = self.nanos.unsigned_abs();
+            let secs = nanos / NANOS_PER_SECOND as u64;
+            let mut time = format!(
+                "{}{:02}:{:02}:{:02}",
+                sign,
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            );
+            let fraction = nanos % NANOS_PER_SECOND as u64;
+            if fraction != 0 {
+                let digits = format!("{:09}", fraction);
+                time.push('.');
+                time.push_str(digits.trim_end_matches('0'));
+            }
+            parts.push(time);
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// A session time zone: an IANA zone such as `Europe/Berlin`, or a fixed
+/// offset from UTC such as `+05:30`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Zone::Named(Tz::UTC)
+    }
+}
+
+impl Zone {
+    /// Reads a zone name (case-insensitively) or an offset: `+05:30`,
+    /// `-08`, `+0100`.
+    pub fn parse(name: &str) -> Result<Zone, CalcError> {
+        let name = name.trim();
+        let named = chrono_tz::TZ_VARIANTS
+            .iter()
+            .find(|tz| tz.name().eq_ignore_ascii_case(name));
+        if let Some(tz) = named {
+            return Ok(Zone::Named(*tz));
+        }
+        parse_offset(name)
+            .map(Zone::Fixed)
+            .ok_or_else(|| CalcError::InvalidType(format!("Unknown time zone '{}'", name)))
+    }
+
+    /// The wall-clock time in the zone at the UTC time `utc`.
+    pub fn local(&self, utc: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Zone::Named(tz) => tz.from_utc_datetime(&utc).naive_local(),
+            Zone::Fixed(offset) => offset.from_utc_datetime(&utc).naive_local(),
+        }
+    }
+
+    /// The UTC time of the wall-clock time `local` in the zone. A time
+    /// repeated when the clocks go back is the earlier one; a time skipped
+    /// when they go forward reads with the offset before the change, as in
+    /// PostgreSQL (02:30 in a gap from 02:00 to 03:00 is 03:30).
+    pub fn utc(&self, local: NaiveDateTime) -> NaiveDateTime {
+        let offset = match self {
+            Zone::Named(tz) => match tz.offset_from_local_datetime(&local) {
+                LocalResult::Single(offset) | LocalResult::Ambiguous(offset, _) => offset.fix(),
+                LocalResult::None => tz
+                    .offset_from_utc_datetime(&(local - Duration::days(1)))
+                    .fix(),
+            },
+            Zone::Fixed(offset) => *offset,
+        };
+        local - Duration::seconds(offset.local_minus_utc() as i64)
+    }
+}
+
+/// `UTC`, `Europe/Berlin`, `+05:30`.
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Named(tz) => f.write_str(tz.name()),
+            Zone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+/// `+05:30`, `+0530`, `-08` or `Z`.
+fn parse_offset(text: &str) -> Option<FixedOffset> {
+    if text.eq_ignore_ascii_case("z") {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match text.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let digits = text[1..].replace(':', "");
+    if !digits.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse().ok()?),
+        _ => return None,
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIMESTAMP_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+pub fn parse_date(text: &str) -> Result<NaiveDate, CalcError> {
+    NaiveDate::parse_from_str(text.trim(), DATE_FORMAT)
+        .map_err(|_| CalcError::InvalidType(format!("Invalid DATE '{}'", text)))
+}
+
+/// Accepts `YYYY-MM-DD[( |T)HH:MM[:SS[.fraction]]][offset]`; a bare date is
+/// midnight. A time with an offset (`Z`, `+02:00`, `-0800`) is converted
+/// into `zone`, one without is already the time there, moved forward as
+/// [`Zone::utc`] says when the clocks skip it: `2024-03-10 02:30` in
+/// America/New_York reads as `03:30`.
+pub fn parse_timestamp(text: &str, zone: &Zone) -> Result<NaiveDateTime, CalcError> {
+    let trimmed = text.trim();
+    let wall_clock = |local: NaiveDateTime| zone.local(zone.utc(local));
+    let naive = |text: &str| {
+        TIMESTAMP_FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    };
+    let with_offset = || {
+        let split = trimmed.rfind(['+', '-', 'Z', 'z']).filter(|at| *at > 10)?;
+        let offset = parse_offset(trimmed[split..].trim())?;
+        let utc =
+            naive(trimmed[..split].trim())? - Duration::seconds(offset.local_minus_utc() as i64);
+        Some(zone.local(utc))
+    };
+    naive(trimmed)
+        .map(wall_clock)
+        .or_else(with_offset)
+        .or_else(|| {
+            NaiveDate::parse_from_str(trimmed, DATE_FORMAT)
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(wall_clock)
+        })
+        .ok_or_else(|| CalcError::InvalidType(format!("Invalid TIMESTAMP '{}'", text)))
+}
+
+pub fn format_date(date: &NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+/// `2024-05-01 10:00:00`, with a fraction of a second only when there is
+/// one.
+pub fn format_timestamp(timestamp: &NaiveDateTime) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S%.f").to_string()
+}
+
+fn unit_interval(unit: &str) -> Option<Interval> {
+    let unit = unit.to_lowercase();
+    let unit = unit.strip_suffix('s').unwrap_or(&unit);
+    let (months, days, secs) = match unit {
+        "year" => (12, 0, 0),
+        "month" | "mon" => (1, 0, 0),
+        "week" => (0, 7, 0),
+        "day" => (0, 1, 0),
+        "hour" => (0, 0, 3600),
+        "minute" | "min" => (0, 0, 60),
+        "second" | "sec" => (0, 0, 1),
+        _ => return None,
+    };
+    Some(Interval {
+        months,
+        days,
+        nanos: secs * NANOS_PER_SECOND,
+    })
+}
+
+/// `count` times `unit`. Fractions of months and days are not carried over
+/// into smaller units; fractions of seconds are kept to the nanosecond.
+fn scale(unit: Interval, count: f64) -> Option<Interval> {
+    let whole = |value: i64| {
+        let scaled = value as f64 * count;
+        (scaled.fract() == 0.0 && scaled.abs() < i64::MAX as f64).then_some(scaled as i64)
+    };
+    let nanos = unit.nanos as f64 * count;
+    Some(Interval {
+        months: whole(unit.months)?,
+        days: whole(unit.days)?,
+        nanos: (nanos.abs() < i64::MAX as f64).then_some(nanos.round() as i64)?,
+    })
+}
+
+/// Parses the text of an `INTERVAL` literal: `'3 days'`, `'1 year 2
+/// months'`, or a bare number when the literal names its unit, as in
+/// `INTERVAL '3' DAY`.
+pub fn parse_interval(text: &str, unit: Option<&DateTimeField>) -> Result<Interval, CalcError> {
+    let invalid = || CalcError::InvalidType(format!("Invalid INTERVAL '{}'", text));
+    let words: Vec<_> = text.split_whitespace().collect();
+    let pairs: Vec<(&str, String)> = match (unit, &words[..]) {
+        (Some(unit), [count]) => vec![(*count, unit.to_string())],
+        (Some(_), _) => return Err(invalid()),
+        (None, words) if !words.is_empty() && words.len() % 2 == 0 => words
+            .chunks(2)
+            .map(|pair| (pair[0], pair[1].to_string()))
+            .collect(),
+        (None, _) => return Err(invalid()),
+    };
+
+    pairs
+        .into_iter()
+        .try_fold(Interval::default(), |interval, (count, unit)| {
+            let count: f64 = count.parse().map_err(|_| invalid())?;
+            let part = unit_interval(&unit)
+                .and_then(|unit| scale(unit, count))
+                .ok_or_else(invalid)?;
+            interval.checked_add(part).ok_or_else(invalid)
+        })
+}
+
+/// `timestamp` moved by `interval`: months first (clamping to the end of a
+/// shorter month), then days, both on the calendar, then the time, as it
+/// elapses in `zone`.
+pub fn add_interval(
+    timestamp: NaiveDateTime,
+    interval: Interval,
+    zone: &Zone,
+) -> Result<NaiveDateTime, CalcError> {
+    let months = |timestamp: NaiveDateTime| {
+        let months = Months::new(u32::try_from(interval.months.unsigned_abs()).ok()?);
+        match interval.months < 0 {
+            true => timestamp.checked_sub_months(months),
+            false => timestamp.checked_add_months(months),
+        }
+    };
+    let time = |moved: NaiveDateTime| match interval.nanos {
+        0 => Some(moved),
+        nanos => zone
+            .utc(moved)
+            .checked_add_signed(Duration::nanoseconds(nanos))
+            .map(|utc| zone.local(utc)),
+    };
+    months(timestamp)
+        .and_then(|moved| moved.checked_add_signed(Duration::try_days(interval.days)?))
+        .and_then(time)
+        .ok_or_else(|| CalcError::InvalidType(String::from("TIMESTAMP is out of range")))
+}
+
+/// The result of `left op right` when either side is a date, timestamp or
+/// interval and the operation is defined for them, `None` otherwise.
+///
+/// * a date or timestamp plus or minus an interval is a timestamp
+/// * an interval plus or minus an interval is an interval
+/// * a timestamp minus a timestamp is an interval of days and time, the
+///   time elapsed between them in `zone`
+/// * a date minus a date is a number of days
+pub fn arithmetic(
+    op: &BinaryOperator,
+    left: &CalcResult,
+    right: &CalcResult,
+    zone: &Zone,
+) -> Option<Result<CalcResult, CalcError>> {
+    let sign = match op {
+        BinaryOperator::Plus => 1,
+        BinaryOperator::Minus => -1,
+        _ => return None,
+    };
+    let signed = |interval: Interval| match sign {
+        1 => interval,
+        _ => interval.negate(),
+    };
+    match (left, right) {
+        (CalcResult::Interval(l), CalcResult::Interval(r)) => Some(
+            l.checked_add(signed(*r))
+                .map(CalcResult::Interval)
+                .ok_or_else(|| CalcError::InvalidType(String::from("INTERVAL is out of range"))),
+        ),
+        (CalcResult::Interval(interval), value) if sign == 1 => {
+            let timestamp = as_timestamp(value)?;
+            Some(add_interval(timestamp, *interval, zone).map(CalcResult::Timestamp))
+        }
+        (value, CalcResult::Interval(interval)) => {
+            let timestamp = as_timestamp(value)?;
+            Some(add_interval(timestamp, signed(*interval), zone).map(CalcResult::Timestamp))
+        }
+        (CalcResult::Date(l), CalcResult::Date(r)) if sign == -1 => {
+            Some(Ok(CalcResult::Int(l.signed_duration_since(*r).num_days())))
+        }
+        (l, r) if sign == -1 => {
+            let elapsed = zone
+                .utc(as_timestamp(l)?)
+                .signed_duration_since(zone.utc(as_timestamp(r)?));
+            let days = elapsed.num_days();
+            let rest = elapsed - Duration::days(days);
+            Some(Ok(CalcResult::Interval(Interval {
+                months: 0,
+                days,
+                nanos: rest.num_nanoseconds().unwrap_or_default(),
+            })))
+        }
+        _ => None,
+    }
+}
+
+/// A date or timestamp as a timestamp, dates being midnight; `None` for
+/// other values.
+pub fn as_timestamp(value: &CalcResult) -> Option<NaiveDateTime> {
+    match value {
+        CalcResult::Date(date) => date.and_hms_opt(0, 0, 0),
+        CalcResult::Timestamp(timestamp) => Some(*timestamp),
+        _ => None,
+    }
+}
+
+/// `EXTRACT(field FROM timestamp)`; seconds include their fraction.
+pub fn extract(field: &DateTimeField, timestamp: &NaiveDateTime) -> f64 {
+    match field {
+        DateTimeField::Year => timestamp.year() as f64,
+        DateTimeField::Month => timestamp.month() as f64,
+        DateTimeField::Day => timestamp.day() as f64,
+        DateTimeField::Hour => timestamp.hour() as f64,
+        DateTimeField::Minute => timestamp.minute() as f64,
+        DateTimeField::Second => timestamp.second() as f64 + timestamp.nanosecond() as f64 / 1e9,
+    }
+}
+
+/// `DATE_TRUNC(unit, timestamp)`: the start of the `unit` (case-insensitive)
+/// containing `timestamp`. Weeks start on Monday.
+pub fn truncate(unit: &str, timestamp: &NaiveDateTime) -> Result<NaiveDateTime, CalcError> {
+    let date = timestamp.date();
+    let (hour, minute, second) = (timestamp.hour(), timestamp.minute(), timestamp.second());
+    let start_of_day = |date: NaiveDate| date.and_hms_opt(0, 0, 0);
+    let truncated = match unit.to_lowercase().as_str() {
+        "year" => date.with_ordinal(1).and_then(start_of_day),
+        "quarter" => date
+            .with_day(1)
+            .and_then(|date| date.with_month(date.month0() / 3 * 3 + 1))
+            .and_then(start_of_day),
+        "month" => date.with_day(1).and_then(start_of_day),
+        "week" => start_of_day(date - Duration::days(date.weekday().num_days_from_monday() as i64)),
+        "day" => start_of_day(date),
+        "hour" => date.and_hms_opt(hour, 0, 0),
+        "minute" => date.and_hms_opt(hour, minute, 0),
+        "second" => date.and_hms_opt(hour, minute, second),
+        _ => {
+            return Err(CalcError::InvalidType(format!(
+                "Unknown DATE_TRUNC unit '{}'",
+                unit
+            )))
+        }
+    };
+    // only fails out of chrono's range, where there is nothing to truncate
+    Ok(truncated.unwrap_or(*timestamp))
+}
+
+/// The UTC date and time of `instant`.
+pub fn from_system_time(instant: SystemTime) -> NaiveDateTime {
+    let (secs, nanos) = match instant.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => (elapsed.as_secs() as i64, elapsed.subsec_nanos()),
+        Err(before) => {
+            let before = before.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    DateTime::from_timestamp(secs, nanos)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const UTC: Zone = Zone::Named(Tz::UTC);
+
+    #[test]
+    fn parse_and_format() {
+        let timestamp = parse_timestamp("2024-05-01T10:20:30.5", &UTC).unwrap();
+        assert_eq!(format_timestamp(&timestamp), "2024-05-01 10:20:30.500");
+        let midnight = parse_timestamp("2024-05-01", &UTC).unwrap();
+        assert_eq!(format_timestamp(&midnight), "2024-05-01 00:00:00");
+        assert_eq!(
+            format_date(&parse_date(" 2024-02-29 ").unwrap()),
+            "2024-02-29"
+        );
+        assert_eq!(
+            parse_date("2023-02-29").unwrap_err(),
+            CalcError::InvalidType(String::from("Invalid DATE '2023-02-29'"))
+        );
+        assert!(parse_timestamp("yesterday", &UTC).is_err());
+    }
+
+    #[test]
+    fn extract_fields() {
+        let timestamp = parse_timestamp("2024-05-17 10:20:30.25", &UTC).unwrap();
+        assert_eq!(extract(&DateTimeField::Year, &timestamp), 2024.0);
+        assert_eq!(extract(&DateTimeField::Month, &timestamp), 5.0);
+        assert_eq!(extract(&DateTimeField::Day, &timestamp), 17.0);
+        assert_eq!(extract(&DateTimeField::Hour, &timestamp), 10.0);
+        assert_eq!(extract(&DateTimeField::Second, &timestamp), 30.25);
+    }
+
+    #[test]
+    fn truncate_units() {
+        // a Friday
+        let timestamp = parse_timestamp("2024-05-17 10:20:30.25", &UTC).unwrap();
+        let truncated = |unit: &str| format_timestamp(&truncate(unit, &timestamp).unwrap());
+        assert_eq!(truncated("YEAR"), "2024-01-01 00:00:00");
+        assert_eq!(truncated("quarter"), "2024-04-01 00:00:00");
+        assert_eq!(truncated("month"), "2024-05-01 00:00:00");
+        assert_eq!(truncated("week"), "2024-05-13 00:00:00");
+        assert_eq!(truncated("day"), "2024-05-17 00:00:00");
+        assert_eq!(truncated("hour"), "2024-05-17 10:00:00");
+        assert_eq!(truncated("minute"), "2024-05-17 10:20:00");
+        assert_eq!(truncated("second"), "2024-05-17 10:20:30");
+        assert_eq!(
+            truncate("fortnight", &timestamp).unwrap_err(),
+            CalcError::InvalidType(String::from("Unknown DATE_TRUNC unit 'fortnight'"))
+        );
+    }
+
+    #[test]
+    fn parse_and_format_intervals() {
+        let formatted = |text: &str| parse_interval(text, None).unwrap().to_string();
+        assert_eq!(formatted("3 days"), "3 days");
+        assert_eq!(formatted("1 year 14 MONTHS"), "2 years 2 months");
+        assert_eq!(formatted("1 week 1.5 hours"), "7 days 01:30:00");
+        assert_eq!(formatted("-1 day 2 secs"), "-1 day 00:00:02");
+        assert_eq!(formatted("0.25 second"), "00:00:00.25");
+        assert_eq!(formatted("0 days"), "00:00:00");
+        assert_eq!(
+            parse_interval("-3", Some(&DateTimeField::Hour)).unwrap(),
+            Interval {
+                months: 0,
+                days: 0,
+                nanos: -3 * 3600 * NANOS_PER_SECOND,
+            }
+        );
+        for invalid in ["", "3", "3 fortnights", "1.5 months", "day 3"] {
+            assert_eq!(
+                parse_interval(invalid, None).unwrap_err(),
+                CalcError::InvalidType(format!("Invalid INTERVAL '{}'", invalid))
+            );
+        }
+    }
+
+    #[test]
+    fn interval_spans() {
+        let span = |text: &str| parse_interval(text, None).unwrap().span();
+        assert_eq!(span("1 day"), span("24 hours"));
+        assert_eq!(span("1 month"), span("30 days"));
+        assert_eq!(span("1 year"), span("360 days"));
+        assert!(span("1 day") < span("2 days"));
+        assert!(span("-1 day 25 hours") > span("0 days"));
+    }
+
+    #[test]
+    fn add_interval_clamps_months() {
+        let timestamp = parse_timestamp("2024-01-31 12:00", &UTC).unwrap();
+        let month = parse_interval("1 month", None).unwrap();
+        assert_eq!(
+            format_timestamp(&add_interval(timestamp, month, &UTC).unwrap()),
+            "2024-02-29 12:00:00"
+        );
+        assert_eq!(
+            format_timestamp(&add_interval(timestamp, month.negate(), &UTC).unwrap()),
+            "2023-12-31 12:00:00"
+        );
+    }
+
+    #[test]
+    fn arithmetic_dispatches_on_types() {
+        let date = |text: &str| CalcResult::Date(parse_date(text).unwrap());
+        let timestamp = |text: &str| CalcResult::Timestamp(parse_timestamp(text, &UTC).unwrap());
+        let interval = |text: &str| CalcResult::Interval(parse_interval(text, None).unwrap());
+        let cases = [
+            (
+                date("2024-01-01"),
+                BinaryOperator::Plus,
+                interval("3 days"),
+                timestamp("2024-01-04"),
+            ),
+            (
+                interval("1 hour"),
+                BinaryOperator::Plus,
+                date("2024-01-01"),
+                timestamp("2024-01-01 01:00"),
+            ),
+            (
+                timestamp("2024-03-01 00:00"),
+                BinaryOperator::Minus,
+                interval("1 minute"),
+                timestamp("2024-02-29 23:59"),
+            ),
+            (
+                interval("1 day"),
+                BinaryOperator::Minus,
+                interval("2 hours"),
+                interval("1 day -2 hours"),
+            ),
+            (
+                date("2024-03-01"),
+                BinaryOperator::Minus,
+                date("2024-02-01"),
+                CalcResult::Int(29),
+            ),
+            (
+                timestamp("2024-01-02 06:00"),
+                BinaryOperator::Minus,
+                date("2024-01-01"),
+                interval("1 day 6 hours"),
+            ),
+        ];
+        for (left, op, right, expected) in cases {
+            assert_eq!(
+                arithmetic(&op, &left, &right, &UTC),
+                Some(Ok(expected)),
+                "{:?} {} {:?}",
+                left,
+                op,
+                right
+            );
+        }
+        assert_eq!(
+            arithmetic(
+                &BinaryOperator::Minus,
+                &interval("1 day"),
+                &date("2024-01-01"),
+                &UTC
+            ),
+            None
+        );
+        assert_eq!(
+            arithmetic(
+                &BinaryOperator::Multiply,
+                &date("2024-01-01"),
+                &interval("1 day"),
+                &UTC
+            ),
+            None
+        );
+        assert_eq!(
+            arithmetic(
+                &BinaryOperator::Plus,
+                &date("2024-01-01"),
+                &CalcResult::Num(1.0),
+                &UTC
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn zones() {
+        let berlin = Zone::parse("europe/berlin").unwrap();
+        assert_eq!(berlin.to_string(), "Europe/Berlin");
+        assert_eq!(Zone::parse("+05:30").unwrap().to_string(), "+05:30");
+        assert_eq!(Zone::parse("-08").unwrap().to_string(), "-08:00");
+        assert_eq!(
+            Zone::parse("Z").unwrap(),
+            Zone::Fixed(FixedOffset::east_opt(0).unwrap())
+        );
+        for invalid in ["Mars/Base", "+25", "+05:75", ""] {
+            assert_eq!(
+                Zone::parse(invalid).unwrap_err(),
+                CalcError::InvalidType(format!("Unknown time zone '{}'", invalid))
+            );
+        }
+        let timestamp =
+            |text: &str, zone: &Zone| format_timestamp(&parse_timestamp(text, zone).unwrap());
+        assert_eq!(
+            timestamp("2024-05-01 12:00:00Z", &berlin),
+            "2024-05-01 14:00:00"
+        );
+        assert_eq!(
+            timestamp("2024-05-01T12:00:00-04:00", &UTC),
+            "2024-05-01 16:00:00"
+        );
+        assert_eq!(
+            timestamp("2024-05-01 12:00+0530", &UTC),
+            "2024-05-01 06:30:00"
+        );
+        assert_eq!(
+            timestamp("2024-05-01 12:00", &berlin),
+            "2024-05-01 12:00:00"
+        );
+
+        // the clocks go forward at 02:00 on 2024-03-31 in Berlin
+        let before = parse_timestamp("2024-03-30 12:00", &berlin).unwrap();
+        let added = |text: &str| {
+            let interval = parse_interval(text, None).unwrap();
+            format_timestamp(&add_interval(before, interval, &berlin).unwrap())
+        };
+        assert_eq!(added("24 hours"), "2024-03-31 13:00:00");
+        assert_eq!(added("1 day"), "2024-03-31 12:00:00");
+        assert_eq!(added("1 day 1 hour"), "2024-03-31 13:00:00");
+        let after = CalcResult::Timestamp(parse_timestamp("2024-03-31 12:00", &berlin).unwrap());
+        assert_eq!(
+            arithmetic(
+                &BinaryOperator::Minus,
+                &after,
+                &CalcResult::Timestamp(before),
+                &berlin
+            ),
+            Some(Ok(CalcResult::Interval(
+                parse_interval("23 hours", None).unwrap()
+            )))
+        );
+        let gap = parse_timestamp("2024-03-31 02:30", &berlin).unwrap();
+        assert_eq!(format_timestamp(&gap), "2024-03-31 03:30:00");
+        let new_york = Zone::parse("America/New_York").unwrap();
+        let parsed = |text: &str| format_timestamp(&parse_timestamp(text, &new_york).unwrap());
+        assert_eq!(parsed("2024-03-10 02:30"), "2024-03-10 03:30:00");
+        assert_eq!(parsed("2024-03-10 01:59:59"), "2024-03-10 01:59:59");
+        assert_eq!(parsed("2024-03-10 03:00"), "2024-03-10 03:00:00");
+        // a repeated time stays as written
+        assert_eq!(parsed("2024-11-03 01:30"), "2024-11-03 01:30:00");
+    }
+
+    #[test]
+    fn system_time_is_utc() {
+        let instant = UNIX_EPOCH + Duration::from_secs(946_684_800);
+        assert_eq!(
+            format_timestamp(&from_system_time(instant)),
+            "2000-01-01 00:00:00"
+        );
+        let before = UNIX_EPOCH - Duration::from_millis(1500);
+        assert_eq!(
+            format_timestamp(&from_system_time(before)),
+            "1969-12-31 23:59:58.500"
+        );
+    }
+}
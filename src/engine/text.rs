@@ -0,0 +1,177 @@
+use std::cmp::Ordering;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What a "character" is for LENGTH, SUBSTRING and friends.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StringSemantics {
+    /// Unicode scalar values: `'é'` written as `e` + U+0301 has length 2.
+    #[default]
+    CodePoints,
+    /// Extended grapheme clusters, i.e. what a reader sees as one character:
+    /// the same `'é'` has length 1.
+    Graphemes,
+}
+
+/// Ordering used when comparing strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Collation {
+    /// Code point order (`'B' < 'a'`), identical to UTF-8 byte order.
+    #[default]
+    Binary,
+    /// Code point order after Unicode lowercasing, so `'a' = 'A'`.
+    NoCase,
+}
+
+fn units(str: &str, semantics: StringSemantics) -> Vec<&str> {
+    match semantics {
+        StringSemantics::CodePoints => str
+            .char_indices()
+            .map(|(i, ch)| &str[i..i + ch.len_utf8()])
+            .collect(),
+        StringSemantics::Graphemes => str.graphemes(true).collect(),
+    }
+}
+
+pub fn length(str: &str, semantics: StringSemantics) -> usize {
+    match semantics {
+        StringSemantics::CodePoints => str.chars().count(),
+        StringSemantics::Graphemes => str.graphemes(true).count(),
+    }
+}
+
+/// Returns `len` characters starting at the 1-based position `start`, or the
+/// rest of the string when `len` is `None`.
+pub fn substring(
+    str: &str,
+    start: usize,
+    len: Option<usize>,
+    semantics: StringSemantics,
+) -> String {
+    let units = units(str, semantics);
+    let start = start.saturating_sub(1).min(units.len());
+    let end = match len {
+        Some(len) => start.saturating_add(len).min(units.len()),
+        None => units.len(),
+    };
+    units[start..end].concat()
+}
+
+/// The characters of `str` in reverse order; with grapheme semantics a
+/// combining mark stays on its base character.
+pub fn reverse(str: &str, semantics: StringSemantics) -> String {
+    units(str, semantics).into_iter().rev().collect()
+}
+
+/// Pads `str` on the left (or right) to `len` characters by repeating
+/// `fill`, or cuts it down to `len` characters if it is longer.
+pub fn pad(str: &str, len: usize, fill: &str, left: bool, semantics: StringSemantics) -> String {
+    let chars = units(str, semantics);
+    if chars.len() >= len {
+        return chars[..len].concat();
+    }
+    let fill = units(fill, semantics);
+    if fill.is_empty() {
+        return str.to_string();
+    }
+    let padding: String = fill
+        .iter()
+        .cycle()
+        .take(len - chars.len())
+        .copied()
+        .collect();
+    match left {
+        true => format!("{}{}", padding, str),
+        false => format!("{}{}", str, padding),
+    }
+}
+
+pub fn compare(left: &str, right: &str, collation: Collation) -> Ordering {
+    match collation {
+        Collation::Binary => left.cmp(right),
+        Collation::NoCase => left.to_lowercase().cmp(&right.to_lowercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DECOMPOSED: &str = "cafe\u{301}";
+
+    #[test]
+    fn length_code_points() {
+        assert_eq!(length(DECOMPOSED, StringSemantics::CodePoints), 5);
+    }
+
+    #[test]
+    fn length_graphemes() {
+        assert_eq!(length(DECOMPOSED, StringSemantics::Graphemes), 4);
+    }
+
+    #[test]
+    fn substring_code_points() {
+        assert_eq!(
+            substring(DECOMPOSED, 4, Some(1), StringSemantics::CodePoints),
+            "e"
+        );
+        assert_eq!(
+            substring("héllo", 2, None, StringSemantics::CodePoints),
+            "éllo"
+        );
+    }
+
+    #[test]
+    fn substring_graphemes() {
+        assert_eq!(
+            substring(DECOMPOSED, 4, Some(1), StringSemantics::Graphemes),
+            "e\u{301}"
+        );
+    }
+
+    #[test]
+    fn substring_out_of_range() {
+        assert_eq!(
+            substring("abc", 10, Some(2), StringSemantics::CodePoints),
+            ""
+        );
+        assert_eq!(
+            substring("abc", 0, Some(2), StringSemantics::CodePoints),
+            "ab"
+        );
+    }
+
+    #[test]
+    fn reverse_semantics() {
+        assert_eq!(
+            reverse(DECOMPOSED, StringSemantics::CodePoints),
+            "\u{301}efac"
+        );
+        assert_eq!(
+            reverse(DECOMPOSED, StringSemantics::Graphemes),
+            "e\u{301}fac"
+        );
+    }
+
+    #[test]
+    fn pad_fills_and_cuts() {
+        let semantics = StringSemantics::CodePoints;
+        assert_eq!(pad("7", 3, "0", true, semantics), "007");
+        assert_eq!(pad("ab", 5, "xy", false, semantics), "abxyx");
+        assert_eq!(pad("hello", 2, "x", true, semantics), "he");
+        assert_eq!(pad("ab", 4, "", true, semantics), "ab");
+        assert_eq!(
+            pad("a", 5, DECOMPOSED, true, StringSemantics::Graphemes),
+            "cafe\u{301}a"
+        );
+    }
+
+    #[test]
+    fn compare_collations() {
+        assert_eq!(compare("B", "a", Collation::Binary), Ordering::Less);
+        assert_eq!(compare("B", "a", Collation::NoCase), Ordering::Greater);
+        assert_eq!(
+            compare("Straße", "STRASSE", Collation::NoCase),
+            Ordering::Greater
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! `SET name = expr` session variables and `SET TIME ZONE`.
+
+use sqlparser::ast::{Expr, Ident};
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::Token;
+
+/// A parsed `SET` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    /// The variable, without any `@` in front.
+    pub name: Ident,
+    pub value: Expr,
+}
+
+/// The zone a `SET TIME ZONE` statement sets.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ZoneSetting {
+    Zone(String),
+    /// `DEFAULT` or `LOCAL`: the zone the session started with.
+    Default,
+}
+
+/// Parses `SET [SESSION] TIME ZONE '<zone>' | DEFAULT | LOCAL`.
+pub fn parse_time_zone(parser: &mut Parser) -> Result<Option<ZoneSetting>, ParserError> {
+    if !parser.parse_keywords(&[Keyword::SET, Keyword::TIME, Keyword::ZONE])
+        && !parser.parse_keywords(&[Keyword::SET, Keyword::SESSION, Keyword::TIME, Keyword::ZONE])
+    {
+        return Ok(None);
+    }
+
+    let setting = match parser.parse_one_of_keywords(&[Keyword::DEFAULT, Keyword::LOCAL]) {
+        Some(_) => ZoneSetting::Default,
+        None => ZoneSetting::Zone(parser.parse_literal_string()?),
+    };
+    while parser.consume_token(&Token::SemiColon) {}
+    if parser.peek_token() != Token::EOF {
+        return Err(ParserError::ParserError(format!(
+            "Expected end of statement, found: {}",
+            parser.peek_token()
+        )));
+    }
+
+    Ok(Some(setting))
+}
+
+/// Parses `SET [SESSION] [@]name = expr` (or `TO expr`).
+pub fn parse_assignment(parser: &mut Parser) -> Result<Option<Assignment>, ParserError> {
+    if !parser.parse_keyword(Keyword::SET) {
+        return Ok(None);
+    }
+
+    let _ = parser.parse_keyword(Keyword::SESSION);
+    let mut name = parser.parse_identifier()?;
+    if let Some(stripped) = name.value.strip_prefix('@') {
+        name.value = stripped.to_string();
+    }
+    if !parser.consume_token(&Token::Eq) && !parser.parse_keyword(Keyword::TO) {
+        return Err(ParserError::ParserError(format!(
+            "Expected = or TO, found: {}",
+            parser.peek_token()
+        )));
+    }
+    let value = parser.parse_expr()?;
+    while parser.consume_token(&Token::SemiColon) {}
+    if parser.peek_token() != Token::EOF {
+        return Err(ParserError::ParserError(format!(
+            "Expected end of statement, found: {}",
+            parser.peek_token()
+        )));
+    }
+
+    Ok(Some(Assignment { name, value }))
+}
+
+/// The variable an identifier written `@name` refers to.
+pub fn reference(ident: &Ident) -> Option<Ident> {
+    match ident.quote_style {
+        Some(_) => None,
+        None => ident.value.strip_prefix('@').map(Ident::new),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn parse(sql: &str) -> Result<Option<Assignment>, ParserError> {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, sql).tokenize().unwrap();
+        parse_assignment(&mut Parser::new(tokens, &dialect))
+    }
+
+    #[test]
+    fn parse_assignments() {
+        let assignment = parse("SET @rate TO 2 * 3;").unwrap().unwrap();
+        assert_eq!(assignment.name, Ident::new("rate"));
+        assert_eq!(assignment.value.to_string(), "2 * 3");
+        assert_eq!(
+            parse("SET SESSION x = -1").unwrap().unwrap().name,
+            Ident::new("x")
+        );
+        assert_eq!(parse("SELECT 1").unwrap(), None);
+        assert!(parse("SET x 1").is_err());
+        assert!(parse("SET x = 1 2").is_err());
+    }
+
+    #[test]
+    fn parse_time_zones() {
+        let parse = |sql: &str| {
+            let dialect = GenericDialect {};
+            let tokens = Tokenizer::new(&dialect, sql).tokenize().unwrap();
+            parse_time_zone(&mut Parser::new(tokens, &dialect))
+        };
+        assert_eq!(
+            parse("SET TIME ZONE 'Europe/Berlin';").unwrap(),
+            Some(ZoneSetting::Zone(String::from("Europe/Berlin")))
+        );
+        assert_eq!(
+            parse("set session time zone local").unwrap(),
+            Some(ZoneSetting::Default)
+        );
+        assert_eq!(parse("SET x = 1").unwrap(), None);
+        assert!(parse("SET TIME ZONE 'UTC' 'UTC'").is_err());
+    }
+
+    #[test]
+    fn references_need_an_at_sign() {
+        assert_eq!(reference(&Ident::new("@x")), Some(Ident::new("x")));
+        assert_eq!(reference(&Ident::new("x")), None);
+        assert_eq!(reference(&Ident::with_quote('"', "@x")), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! User functions compiled to WebAssembly.
+//!
+//! ABI: every exported function taking only `f64` parameters and returning a
+//! single `f64` becomes a SQL function of the same name. Modules cannot
+//! import anything, and each call runs with a fuel budget and capped linear
+//! memory, so untrusted code can neither reach the host nor run away.
+
+use super::engine::{CalcError, CalcResult, Engine};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasmi::{
+    Config, Extern, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TrapCode, Val, ValType,
+};
+
+/// Sandbox applied to every call into a module.
+#[derive(Clone, Debug)]
+pub struct WasmLimits {
+    /// Fuel available to a single call, roughly one unit per instruction.
+    pub fuel: u64,
+    /// Largest linear memory a module may grow to, in bytes.
+    pub memory: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        WasmLimits {
+            fuel: 10_000_000,
+            memory: 16 * 1024 * 1024,
+        }
+    }
+}
+
+fn invalid_module(err: impl ToString) -> CalcError {
+    CalcError::FunctionFailed(format!("invalid WASM module: {}", err.to_string()))
+}
+
+impl Engine {
+    /// Loads a WebAssembly module and registers its exported functions,
+    /// returning their SQL names.
+    pub fn load_wasm(&mut self, wasm: &[u8], limits: WasmLimits) -> Result<Vec<String>, CalcError> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let runtime = wasmi::Engine::new(&config);
+        let module = Module::new(&runtime, wasm).map_err(invalid_module)?;
+
+        let mut store: Store<StoreLimits> = Store::new(
+            &runtime,
+            StoreLimitsBuilder::new().memory_size(limits.memory).build(),
+        );
+        store.limiter(|limits| limits);
+        store.set_fuel(limits.fuel).map_err(invalid_module)?;
+        let instance = Linker::new(&runtime)
+            .instantiate_and_start(&mut store, &module)
+            .map_err(invalid_module)?;
+
+        let mut functions = Vec::new();
+        for export in instance.exports(&store) {
+            let name = export.name().to_string();
+            if let Extern::Func(func) = export.into_extern() {
+                let ty = func.ty(&store);
+                let is_f64 = |types: &[ValType]| types.iter().all(|ty| *ty == ValType::F64);
+                if is_f64(ty.params()) && ty.results() == [ValType::F64] {
+                    functions.push((name, func, ty.params().len()));
+                }
+            }
+        }
+        if functions.is_empty() {
+            return Err(invalid_module("no exported f64 functions"));
+        }
+
+        let store = Rc::new(RefCell::new(store));
+        let mut names = Vec::new();
+        for (name, func, arity) in functions {
+            let store = Rc::clone(&store);
+            let fuel = limits.fuel;
+            let upper = name.to_uppercase();
+            self.register_scalar_fn(&name, arity, move |args| {
+                let params = args
+                    .iter()
+                    .map(|arg| match arg {
+                        CalcResult::Num(_) | CalcResult::Int(_) | CalcResult::Decimal(_) => {
+                            Ok(Val::from(arg.as_f64().unwrap_or_default()))
+                        }
+                        _ => Err(CalcError::InvalidType(format!(
+                            "{} supports only Number",
+                            upper
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut store = store.borrow_mut();
+                store
+                    .set_fuel(fuel)
+                    .map_err(|err| CalcError::FunctionFailed(err.to_string()))?;
+                let mut results = [Val::F64(0.0.into())];
+                match func.call(&mut *store, &params, &mut results) {
+                    Ok(()) => Ok(CalcResult::Num(results[0].f64().map_or(0.0, f64::from))),
+                    Err(err) if err.as_trap_code() == Some(TrapCode::OutOfFuel) => {
+                        Err(CalcError::ResourceLimit(format!(
+                            "{} ran out of fuel ({} units)",
+                            upper, fuel
+                        )))
+                    }
+                    Err(err) => Err(CalcError::FunctionFailed(format!("{}: {}", upper, err))),
+                }
+            });
+            names.push(name.to_uppercase());
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"
+        (module
+          (func (export "hypot") (param f64 f64) (result f64)
+            (f64.sqrt (f64.add (f64.mul (local.get 0) (local.get 0))
+                               (f64.mul (local.get 1) (local.get 1)))))
+          (func (export "spin") (param f64) (result f64)
+            (loop (br 0))
+            (local.get 0))
+          (func (export "trap") (param f64) (result f64)
+            unreachable)
+          (func (export "count") (result i32)
+            (i32.const 1)))
+    "#;
+
+    fn engine() -> Engine {
+        let mut engine = Engine::default();
+        let limits = WasmLimits {
+            fuel: 10_000,
+            ..WasmLimits::default()
+        };
+        let names = engine
+            .load_wasm(&wat::parse_str(MODULE).unwrap(), limits)
+            .unwrap();
+        assert_eq!(names, vec!["HYPOT", "SPIN", "TRAP"]);
+        engine
+    }
+
+    #[test]
+    fn load_wasm_callable() {
+        if let CalcResult::Num(val) = engine().exec("SELECT hypot(3, 4)").unwrap() {
+            assert_eq!(val, 5.0);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn load_wasm_fuel_limit() {
+        assert_eq!(
+            engine().exec("SELECT SPIN(1)").unwrap_err(),
+            CalcError::ResourceLimit(String::from("SPIN ran out of fuel (10000 units)"))
+        );
+    }
+
+    #[test]
+    fn load_wasm_trap() {
+        if let CalcError::FunctionFailed(message) = engine().exec("SELECT TRAP(1)").unwrap_err() {
+            assert!(message.starts_with("TRAP: "));
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn load_wasm_rejects_imports() {
+        let wasm = wat::parse_str(r#"(module (import "env" "f" (func)))"#).unwrap();
+        assert!(Engine::default()
+            .load_wasm(&wasm, WasmLimits::default())
+            .is_err());
+    }
+}
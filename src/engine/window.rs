@@ -0,0 +1,127 @@
+//! Window functions (`ROW_NUMBER() OVER (...)` and the like): functions
+//! computed for each row over the rows of its partition, rather than over
+//! a group collapsing them.
+
+use super::aggregate;
+use super::engine::CalcResult;
+use sqlparser::ast::{Expr, Function};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+/// Functions only callable with an `OVER` clause. Aggregate functions may
+/// be called with one too.
+pub const WINDOW_FUNCTIONS: [&str; 4] = ["LAG", "LEAD", "RANK", "ROW_NUMBER"];
+
+/// The values of a `SELECT`'s window function calls for the row being
+/// evaluated.
+#[derive(Clone, Debug)]
+pub struct Window {
+    pub calls: Rc<[Expr]>,
+    pub values: Vec<CalcResult>,
+}
+
+impl Window {
+    /// The value of `expr` when it is one of the window function calls.
+    pub fn value(&self, expr: &Expr) -> Option<&CalcResult> {
+        let i = self.calls.iter().position(|call| call == expr)?;
+        self.values.get(i)
+    }
+}
+
+/// The window function calls in `expr`, outside of any subquery, each
+/// listed once.
+pub fn calls(expr: &Expr, found: &mut Vec<Expr>) {
+    match expr {
+        Expr::Function(Function { over: Some(_), .. }) => {
+            if !found.contains(expr) {
+                found.push(expr.clone());
+            }
+        }
+        expr => {
+            for child in aggregate::children(expr) {
+                calls(child, found);
+            }
+        }
+    }
+}
+
+/// Orders two values as `ORDER BY` does: ascending unless `asc` says
+/// otherwise, and with NULLs last when ascending and first when
+/// descending unless `nulls_first` says otherwise. `compare` orders values
+/// that are not NULL.
+pub fn order<E>(
+    left: &CalcResult,
+    right: &CalcResult,
+    asc: Option<bool>,
+    nulls_first: Option<bool>,
+    compare: impl FnOnce(&CalcResult, &CalcResult) -> Result<Ordering, E>,
+) -> Result<Ordering, E> {
+    let asc = asc.unwrap_or(true);
+    let nulls_first = nulls_first.unwrap_or(!asc);
+    match (left, right) {
+        (CalcResult::Null, CalcResult::Null) => Ok(Ordering::Equal),
+        (CalcResult::Null, _) if nulls_first => Ok(Ordering::Less),
+        (CalcResult::Null, _) => Ok(Ordering::Greater),
+        (_, CalcResult::Null) if nulls_first => Ok(Ordering::Greater),
+        (_, CalcResult::Null) => Ok(Ordering::Less),
+        (left, right) => {
+            let ordering = compare(left, right)?;
+            Ok(if asc { ordering } else { ordering.reverse() })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::dialect::GenericDialect;
+    use sqlparser::parser::Parser;
+    use sqlparser::tokenizer::Tokenizer;
+
+    fn parse(expr: &str) -> Expr {
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, expr).tokenize().unwrap();
+        Parser::new(tokens, &dialect).parse_expr().unwrap()
+    }
+
+    #[test]
+    fn calls_are_found_once() {
+        let mut found = Vec::new();
+        calls(
+            &parse("RANK() OVER (ORDER BY a) + RANK() OVER (ORDER BY a) + SUM(b) OVER ()"),
+            &mut found,
+        );
+        calls(&parse("SUM(b) + (SELECT ROW_NUMBER() OVER ())"), &mut found);
+        let found: Vec<_> = found.iter().map(ToString::to_string).collect();
+        assert_eq!(found, ["RANK() OVER (ORDER BY a)", "SUM(b) OVER ()"]);
+    }
+
+    #[test]
+    fn order_places_nulls() {
+        let compare = |l: &CalcResult, r: &CalcResult| -> Result<Ordering, ()> {
+            Ok(l.as_f64().partial_cmp(&r.as_f64()).unwrap())
+        };
+        let (one, two, null) = (CalcResult::Int(1), CalcResult::Int(2), CalcResult::Null);
+        assert_eq!(order(&one, &two, None, None, compare), Ok(Ordering::Less));
+        assert_eq!(
+            order(&one, &two, Some(false), None, compare),
+            Ok(Ordering::Greater)
+        );
+        assert_eq!(
+            order(&null, &one, None, None, compare),
+            Ok(Ordering::Greater)
+        );
+        assert_eq!(
+            order(&null, &one, Some(false), None, compare),
+            Ok(Ordering::Less)
+        );
+        assert_eq!(
+            order(&null, &one, None, Some(true), compare),
+            Ok(Ordering::Less)
+        );
+        assert_eq!(
+            order(&null, &null, None, None, compare),
+            Ok(Ordering::Equal)
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Declarative fixtures: the tables a golden or sqllogictest script
+//! declares in a sibling `<script>.fixtures.yaml`, created before the
+//! script runs and dropped after it.
+//!
+//! ```yaml
+//! tables:
+//!   users:
+//!     columns: {id: INT, name: TEXT, joined: DATE}
+//!     rows:
+//!       - [1, alice, 2024-01-01]
+//!       - {id: 2, name: bob}
+//!   orders:
+//!     csv: orders.csv
+//! ```
+//!
+//! A row lists its values in column order, or maps column names to values
+//! (the columns it leaves out are NULL). A `csv` file, relative to the
+//! manifest, is read as `READ_CSV` reads it: its header names the columns
+//! and their types are inferred, unless `columns` declares them.
+
+use crate::engine::csv;
+use crate::engine::engine::{CalcResult, Engine};
+use crate::engine::operators::ValueType;
+use crate::engine::table::{Row, TableProvider};
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The tables loaded for a script, in the order they were created.
+#[derive(Debug, Default, PartialEq)]
+pub struct Fixtures {
+    tables: Vec<String>,
+}
+
+impl Fixtures {
+    pub fn tables(&self) -> &[String] {
+        &self.tables
+    }
+
+    /// Drops every table loaded, including any the script changed.
+    pub fn tear_down(self, engine: &Engine) {
+        for table in self.tables.iter().rev() {
+            engine.drop_table(table);
+        }
+    }
+}
+
+/// The manifest path for `script`: `orders.sql` has `orders.fixtures.yaml`.
+pub fn manifest(script: &Path) -> PathBuf {
+    script.with_extension("fixtures.yaml")
+}
+
+/// Creates the tables the manifest of `script` declares, if it has one.
+/// Nothing stays loaded when any table fails.
+pub fn load(engine: &Engine, script: &Path) -> Result<Fixtures, String> {
+    let path = manifest(script);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Fixtures::default()),
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+
+    let mut fixtures = Fixtures::default();
+    let loaded = load_manifest(engine, &path, &text, &mut fixtures);
+    match loaded {
+        Ok(()) => Ok(fixtures),
+        Err(err) => {
+            fixtures.tear_down(engine);
+            Err(format!("{}: {}", path.display(), err))
+        }
+    }
+}
+
+fn load_manifest(
+    engine: &Engine,
+    path: &Path,
+    text: &str,
+    fixtures: &mut Fixtures,
+) -> Result<(), String> {
+    let manifest: Value = serde_yaml::from_str(text).map_err(|err| err.to_string())?;
+    let tables = match manifest.get("tables") {
+        Some(Value::Mapping(tables)) => tables,
+        Some(Value::Null) => return Ok(()),
+        _ => return Err(String::from("expected a `tables` mapping")),
+    };
+
+    for (name, table) in tables {
+        let name = name
+            .as_str()
+            .ok_or_else(|| format!("expected a table name, got {:?}", name))?;
+        let failed = |message: String| format!("table {}: {}", name, message);
+        let table = match table {
+            Value::Mapping(table) => table,
+            _ => return Err(failed(String::from("expected a mapping"))),
+        };
+
+        let declared = match table.get("columns") {
+            Some(columns) => Some(columns_of(columns).map_err(failed)?),
+            None => None,
+        };
+        let (columns, rows) = match (table.get("csv"), table.get("rows")) {
+            (Some(_), Some(_)) => {
+                return Err(failed(String::from("expected either `csv` or `rows`")))
+            }
+            (Some(Value::String(file)), None) => {
+                let file = path.parent().unwrap_or(Path::new("")).join(file);
+                read_csv(&file, declared).map_err(failed)?
+            }
+            (Some(_), None) => return Err(failed(String::from("expected a `csv` file name"))),
+            (None, rows) => {
+                let columns = declared.ok_or_else(|| failed(String::from("expected `columns`")))?;
+                let rows = match rows {
+                    Some(rows) => rows_of(rows, &columns).map_err(failed)?,
+                    None => Vec::new(),
+                };
+                (columns, rows)
+            }
+        };
+
+        engine
+            .load_table(name, &columns, rows)
+            .map_err(|err| failed(err.to_string()))?;
+        fixtures.tables.push(name.to_string());
+    }
+    Ok(())
+}
+
+/// `columns: {<name>: <type>, ...}`, in order.
+fn columns_of(columns: &Value) -> Result<Columns, String> {
+    let columns = match columns {
+        Value::Mapping(columns) => columns,
+        _ => return Err(String::from("expected `columns` to map names to types")),
+    };
+    columns
+        .iter()
+        .map(|(name, ty)| match (name.as_str(), ty.as_str()) {
+            (Some(name), Some(ty)) => Ok((name.to_string(), ty.to_string())),
+            _ => Err(format!("expected a column name and type, got {:?}", name)),
+        })
+        .collect()
+}
+
+fn rows_of(rows: &Value, columns: &[(String, String)]) -> Result<Vec<Row>, String> {
+    let rows = match rows {
+        Value::Sequence(rows) => rows,
+        _ => return Err(String::from("expected `rows` to be a list")),
+    };
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let failed = |message: String| format!("row {}: {}", i + 1, message);
+            match row {
+                Value::Sequence(values) if values.len() == columns.len() => values
+                    .iter()
+                    .map(value_of)
+                    .collect::<Result<_, _>>()
+                    .map_err(failed),
+                Value::Sequence(values) => Err(failed(format!(
+                    "expected {} value(s), got {}",
+                    columns.len(),
+                    values.len()
+                ))),
+                Value::Mapping(values) => row_of(values, columns).map_err(failed),
+                _ => Err(failed(String::from("expected a list or a mapping"))),
+            }
+        })
+        .collect()
+}
+
+/// A row given as `{<column>: <value>, ...}`.
+fn row_of(values: &Mapping, columns: &[(String, String)]) -> Result<Row, String> {
+    let mut row = vec![CalcResult::Null; columns.len()];
+    for (name, value) in values {
+        let position = name.as_str().and_then(|name| {
+            columns
+                .iter()
+                .position(|(column, _)| column.eq_ignore_ascii_case(name))
+        });
+        match position {
+            Some(i) => row[i] = value_of(value)?,
+            None => return Err(format!("unknown column {:?}", name)),
+        }
+    }
+    Ok(row)
+}
+
+fn value_of(value: &Value) -> Result<CalcResult, String> {
+    match value {
+        Value::Null => Ok(CalcResult::Null),
+        Value::Bool(boolean) => Ok(CalcResult::Bool(*boolean)),
+        Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+            (Some(int), _) => Ok(CalcResult::Int(int)),
+            (None, Some(num)) => Ok(CalcResult::Num(num)),
+            _ => Err(format!("unsupported number {}", number)),
+        },
+        Value::String(str) => Ok(CalcResult::Str(str.clone())),
+        value => Err(format!("unsupported value {:?}", value)),
+    }
+}
+
+/// Column names with the SQL names of their types.
+type Columns = Vec<(String, String)>;
+
+/// The columns and rows of a CSV file. Declared columns take the values
+/// of the CSV columns of the same name, and NULL when there is none.
+fn read_csv(file: &Path, declared: Option<Columns>) -> Result<(Columns, Vec<Row>), String> {
+    let text = fs::read_to_string(file).map_err(|err| format!("{}: {}", file.display(), err))?;
+    let table = csv::parse(&text, &file.display().to_string()).map_err(|err| err.to_string())?;
+    let schema = table.schema();
+    let rows = table.scan(&[], None).map_err(|err| err.to_string())?;
+
+    let columns = match declared {
+        Some(columns) => columns,
+        None => {
+            let columns = schema
+                .iter()
+                .map(|column| (column.name.clone(), type_name(column.ty).to_string()))
+                .collect();
+            return Ok((columns, rows));
+        }
+    };
+    let mut positions = Vec::with_capacity(columns.len());
+    for (name, _) in &columns {
+        positions.push(
+            schema
+                .iter()
+                .position(|column| column.name.eq_ignore_ascii_case(name)),
+        );
+    }
+    if let Some(column) = schema.iter().find(|column| {
+        !columns
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(&column.name))
+    }) {
+        return Err(format!("unknown column {}", column.name));
+    }
+    let rows = rows
+        .into_iter()
+        .map(|row| {
+            positions
+                .iter()
+                .map(|position| position.map_or(CalcResult::Null, |i| row[i].clone()))
+                .collect()
+        })
+        .collect();
+    Ok((columns, rows))
+}
+
+/// The SQL type a column `READ_CSV` inferred as `ty` is created with.
+fn type_name(ty: ValueType) -> &'static str {
+    match ty {
+        ValueType::Integer => "BIGINT",
+        ValueType::Number => "DOUBLE",
+        ValueType::Boolean => "BOOLEAN",
+        _ => "TEXT",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("fixtures-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn load_rows_and_csv() {
+        let dir = scratch("load");
+        let script = dir.join("orders.sql");
+        fs::write(
+            manifest(&script),
+            "tables:\n  users:\n    columns: {id: INT, name: TEXT, joined: DATE}\n    rows:\n      - [1, alice, 2024-01-01]\n      - {id: 2, name: bob}\n  orders:\n    csv: orders.csv\n  totals:\n    columns:\n      user_id: INT\n      total: DECIMAL(10, 2)\n    csv: orders.csv\n",
+        )
+        .unwrap();
+        fs::write(dir.join("orders.csv"), "user_id,total\n1,9.5\n2,\n1,0.25\n").unwrap();
+
+        let engine = Engine::default();
+        let fixtures = load(&engine, &script).unwrap();
+        assert_eq!(fixtures.tables(), ["users", "orders", "totals"]);
+        assert_eq!(
+            engine
+                .exec("SELECT name FROM users WHERE joined IS NULL")
+                .unwrap(),
+            CalcResult::Table(vec![vec![CalcResult::Str(String::from("bob"))]])
+        );
+        assert_eq!(
+            engine
+                .exec("SELECT COUNT(*) FROM users JOIN orders ON users.id = orders.user_id")
+                .unwrap(),
+            CalcResult::Table(vec![vec![CalcResult::Int(3)]])
+        );
+        assert_eq!(
+            engine.describe_table("orders").unwrap(),
+            [
+                (String::from("user_id"), String::from("BIGINT")),
+                (String::from("total"), String::from("DOUBLE")),
+            ]
+        );
+        assert_eq!(
+            engine
+                .exec("SELECT SUM(total) FROM totals")
+                .unwrap()
+                .to_string(),
+            "Result: 1 row\n9.75"
+        );
+
+        fixtures.tear_down(&engine);
+        assert!(engine.all_table_names().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_without_manifest() {
+        let dir = scratch("missing");
+        let fixtures = load(&Engine::default(), &dir.join("plain.sql")).unwrap();
+        assert_eq!(fixtures, Fixtures::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_failure_leaves_nothing() {
+        let dir = scratch("failure");
+        let script = dir.join("bad.test");
+        let path = manifest(&script);
+        let engine = Engine::default();
+        let cases = [
+            (
+                "tables:\n  a:\n    columns: {x: INT}\n    rows: [[1]]\n  b:\n    columns: {x: INT}\n    rows: [[1, 2]]\n",
+                "table b: row 1: expected 1 value(s), got 2",
+            ),
+            (
+                "tables:\n  a:\n    columns: {x: INT}\n    rows: [[abc]]\n",
+                "table a: [Invalid Type]: Cannot CAST 'abc' AS INT",
+            ),
+            (
+                "tables:\n  a:\n    columns: {x: BLOB}\n",
+                "table a: [Invalid Type]: Column x has unsupported type BLOB",
+            ),
+            (
+                "tables:\n  a:\n    rows: [[1]]\n",
+                "table a: expected `columns`",
+            ),
+            ("- a\n", "expected a `tables` mapping"),
+        ];
+        for (text, message) in cases {
+            fs::write(&path, text).unwrap();
+            assert_eq!(
+                load(&engine, &script).unwrap_err(),
+                format!("{}: {}", path.display(), message)
+            );
+            assert!(engine.all_table_names().is_empty(), "{}", text);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
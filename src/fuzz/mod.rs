@@ -0,0 +1,149 @@
+//! Random expression generation over the supported grammar, plus the
+//! properties every generated query must satisfy.
+
+use crate::engine::engine::{parse, Engine, Options};
+use std::panic::{self, AssertUnwindSafe};
+
+const OPERATORS: &[&str] = &[
+    "+", "-", "*", "/", "%", "^", "||", ">", ">=", "<", "<=", "=", "<>", "AND", "OR",
+];
+
+/// Deterministic expression generator; the same seed always yields the same
+/// sequence of queries, so a failure can be replayed.
+pub struct Generator {
+    state: u64,
+    max_depth: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            // xorshift never leaves an all-zero state
+            state: seed.max(1),
+            max_depth: 4,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn literal(&mut self) -> String {
+        match self.below(4) {
+            0 => self.below(1000).to_string(),
+            1 => format!("{}.{}", self.below(100), self.below(100)),
+            2 => format!("'{}'", self.below(100)),
+            _ => format!("'{}'", ["a", "B", "é", ""][self.below(4) as usize]),
+        }
+    }
+
+    fn expr(&mut self, depth: usize) -> String {
+        if depth >= self.max_depth {
+            return self.literal();
+        }
+
+        match self.below(6) {
+            0 | 1 => {
+                let op = OPERATORS[self.below(OPERATORS.len() as u64) as usize];
+                format!("{} {} {}", self.expr(depth + 1), op, self.expr(depth + 1))
+            }
+            2 => format!("({})", self.expr(depth + 1)),
+            3 => format!("SQRT({})", self.expr(depth + 1)),
+            4 => format!("CAST({} AS INT)", self.expr(depth + 1)),
+            _ => self.literal(),
+        }
+    }
+
+    /// Returns the next `SELECT <expression>` query.
+    pub fn query(&mut self) -> String {
+        format!("SELECT {}", self.expr(0))
+    }
+}
+
+/// A generated query that broke one of the properties.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub query: String,
+    pub message: String,
+}
+
+/// Checks the properties for one query: evaluating it does not panic, and
+/// printing its AST and parsing that again yields the same AST.
+pub fn check(engine: &Engine, query: &str) -> Result<(), String> {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| engine.eval(query))) {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        return Err(format!("evaluation panicked: {}", message));
+    }
+
+    let ast = match parse(query) {
+        Ok(ast) => ast,
+        Err(_) => return Ok(()),
+    };
+    let printed: Vec<_> = ast.iter().map(ToString::to_string).collect();
+    let printed = printed.join("; ");
+    match parse(&printed) {
+        Ok(reparsed) if reparsed == ast => Ok(()),
+        Ok(_) => Err(format!("{} does not round-trip", printed)),
+        Err(err) => Err(format!("{} does not parse again: {}", printed, err)),
+    }
+}
+
+/// Generates `iterations` queries from `seed` and returns those violating a
+/// property.
+pub fn run(seed: u64, iterations: usize, options: &Options) -> Vec<Failure> {
+    let engine = Engine::new(options.clone());
+    let mut generator = Generator::new(seed);
+
+    (0..iterations)
+        .filter_map(|_| {
+            let query = generator.query();
+            check(&engine, &query)
+                .err()
+                .map(|message| Failure { query, message })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generator_is_deterministic() {
+        let mut first = Generator::new(42);
+        let mut second = Generator::new(42);
+        for _ in 0..10 {
+            assert_eq!(first.query(), second.query());
+        }
+    }
+
+    #[test]
+    fn generated_queries_parse() {
+        let mut generator = Generator::new(7);
+        for _ in 0..200 {
+            let query = generator.query();
+            assert!(parse(&query).is_ok(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn run_finds_no_failures() {
+        assert_eq!(run(1, 500, &Options::default()), vec![]);
+    }
+
+    #[test]
+    fn check_accepts_invalid_sql() {
+        assert_eq!(check(&Engine::default(), "Give the data"), Ok(()));
+    }
+}
@@ -0,0 +1,223 @@
+//! Golden-file harness: every `<case>.sql` is executed and its rendered
+//! output compared with the sibling `<case>.expected`. The tables a
+//! `<case>.fixtures.yaml` declares are loaded first, see [`fixtures`].
+
+use crate::engine::engine::{CalcError, Engine, Options};
+use crate::fixtures;
+use crate::runner::{self, Report};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Passed,
+    /// The baseline was (re)written from the actual output.
+    Updated,
+    /// Line diff between the baseline and the actual output.
+    Failed(String),
+    /// No `.expected` file exists and baselines are not being updated.
+    Missing,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub case: PathBuf,
+    pub status: Status,
+    pub duration: Duration,
+}
+
+/// Renders a query the way the CLI prints it: the result or error on the
+/// first line, followed by one line per warning.
+pub fn render(engine: &Engine, sql: &str) -> String {
+    let mut lines = Vec::new();
+    match engine.eval(sql) {
+        Ok(evaluation) => {
+            lines.push(evaluation.to_string());
+            lines.extend(evaluation.warnings.iter().map(ToString::to_string));
+        }
+        Err(err) => lines.push(err.to_string()),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Compares the outputs line by line, marking removed baseline lines with
+/// `-` and added actual lines with `+`.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut out = Vec::new();
+
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(left), Some(right)) if left == right => out.push(format!(" {}", left)),
+            (left, right) => {
+                if let Some(left) = left {
+                    out.push(format!("-{}", left));
+                }
+                if let Some(right) = right {
+                    out.push(format!("+{}", right));
+                }
+            }
+        }
+    }
+
+    out.join("\n")
+}
+
+fn check(engine: &Engine, case: &Path, update: bool) -> io::Result<Status> {
+    let sql = fs::read_to_string(case)?;
+    let fixtures = match fixtures::load(engine, case) {
+        Ok(fixtures) => fixtures,
+        Err(err) => return Ok(Status::Failed(err)),
+    };
+    let actual = render(engine, &sql);
+    fixtures.tear_down(engine);
+    let baseline = case.with_extension("expected");
+
+    let expected = match fs::read_to_string(&baseline) {
+        Ok(expected) => Some(expected),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err),
+    };
+
+    match expected {
+        Some(expected) if expected == actual => Ok(Status::Passed),
+        _ if update => {
+            fs::write(&baseline, actual)?;
+            Ok(Status::Updated)
+        }
+        Some(expected) => Ok(Status::Failed(diff(&expected, &actual))),
+        None => Ok(Status::Missing),
+    }
+}
+
+/// The `.sql` cases in `dir`, in file name order.
+pub fn cases(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut cases = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("sql") {
+            cases.push(path);
+        }
+    }
+    cases.sort();
+    Ok(cases)
+}
+
+/// Runs every case in `dir` on `jobs` threads. With `update`, mismatching
+/// or missing baselines are overwritten instead of failing.
+pub fn run(
+    dir: &Path,
+    options: &Options,
+    update: bool,
+    jobs: usize,
+) -> io::Result<Report<Outcome>> {
+    run_with_setup(dir, options, update, jobs, runner::no_setup)
+}
+
+/// [`run`], applying `setup` to each case's engine.
+pub fn run_with_setup(
+    dir: &Path,
+    options: &Options,
+    update: bool,
+    jobs: usize,
+    setup: impl Fn(&mut Engine) -> Result<(), CalcError> + Sync,
+) -> io::Result<Report<Outcome>> {
+    runner::run_isolated(&cases(dir)?, options, jobs, setup, |engine, case| {
+        let start = Instant::now();
+        let status = check(engine, case, update)?;
+        Ok(vec![Outcome {
+            case: case.to_path_buf(),
+            status,
+            duration: start.elapsed(),
+        }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("golden-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn render_error() {
+        assert_eq!(
+            render(&Engine::default(), "SELECT 1 / 0"),
+            "[Division By Zero]: Division by zero\n"
+        );
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(diff("a\nb\n", "a\nc\nd\n"), " a\n-b\n+c\n+d");
+    }
+
+    #[test]
+    fn run_update_then_pass() {
+        let dir = scratch("update");
+        fs::write(dir.join("add.sql"), "SELECT 1 + 1").unwrap();
+
+        let outcomes = run(&dir, &Options::default(), false, 1).unwrap().outcomes;
+        assert_eq!(outcomes[0].status, Status::Missing);
+
+        let outcomes = run(&dir, &Options::default(), true, 1).unwrap().outcomes;
+        assert_eq!(outcomes[0].status, Status::Updated);
+        assert_eq!(
+            fs::read_to_string(dir.join("add.expected")).unwrap(),
+            "Result: 2\n"
+        );
+
+        let outcomes = run(&dir, &Options::default(), false, 1).unwrap().outcomes;
+        assert_eq!(outcomes[0].status, Status::Passed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_with_fixtures() {
+        let dir = scratch("fixtures");
+        fs::write(dir.join("users.sql"), "SELECT SUM(id) FROM users").unwrap();
+        fs::write(dir.join("users.expected"), "Result: 1 row\nSUM(id)\n3\n").unwrap();
+        fs::write(
+            dir.join("users.fixtures.yaml"),
+            "tables:\n  users:\n    columns: {id: INT}\n    rows: [[1], [2]]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.sql"), "SELECT 1").unwrap();
+        fs::write(dir.join("broken.fixtures.yaml"), "tables: [users]\n").unwrap();
+
+        let outcomes = run(&dir, &Options::default(), false, 1).unwrap().outcomes;
+        assert_eq!(
+            outcomes[0].status,
+            Status::Failed(format!(
+                "{}: expected a `tables` mapping",
+                dir.join("broken.fixtures.yaml").display()
+            ))
+        );
+        assert_eq!(outcomes[1].status, Status::Passed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_mismatch() {
+        let dir = scratch("mismatch");
+        fs::write(dir.join("add.sql"), "SELECT 1 + 1").unwrap();
+        fs::write(dir.join("add.expected"), "Result: 3\n").unwrap();
+
+        let outcomes = run(&dir, &Options::default(), false, 1).unwrap().outcomes;
+        assert_eq!(
+            outcomes[0].status,
+            Status::Failed(String::from("-Result: 3\n+Result: 2"))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
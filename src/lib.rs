@@ -0,0 +1,10 @@
+#[cfg(feature = "sqlite")]
+pub mod differential;
+pub mod engine;
+pub mod fixtures;
+pub mod fuzz;
+pub mod golden;
+pub mod logictest;
+pub mod report;
+pub mod runner;
+pub mod testing;
@@ -0,0 +1,544 @@
+//! Runner for [sqllogictest](https://www.sqlite.org/sqllogictest/doc/trunk/about.wiki)
+//! scripts: `statement ok|error` and `query <types> [sort]` records checked
+//! against the engine, by their values or by `N values hashing to <md5>`.
+
+use crate::engine::decimal;
+use crate::engine::engine::{format_num, to_text, CalcError, CalcResult, Engine, Options};
+use crate::fixtures;
+use crate::runner::{self, Report};
+use bigdecimal::RoundingMode;
+use md5::{Digest, Md5};
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Name matched by `skipif` / `onlyif` conditions.
+pub const ENGINE_NAME: &str = "sql_test_engine";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortMode {
+    NoSort,
+    RowSort,
+    ValueSort,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordKind {
+    Statement {
+        expect_error: bool,
+    },
+    Query {
+        types: String,
+        sort: SortMode,
+        /// One value per line, or the single line of a hashed result.
+        expected: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    /// 1-based line of the record header.
+    pub line: usize,
+    pub kind: RecordKind,
+    pub sql: String,
+    pub skip: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Passed,
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub file: PathBuf,
+    pub line: usize,
+    pub sql: String,
+    pub status: Status,
+    pub duration: Duration,
+}
+
+fn parse_sort(mode: Option<&str>, line: usize) -> Result<SortMode, String> {
+    match mode {
+        None | Some("nosort") => Ok(SortMode::NoSort),
+        Some("rowsort") => Ok(SortMode::RowSort),
+        Some("valuesort") => Ok(SortMode::ValueSort),
+        Some(mode) => Err(format!("line {}: unknown sort mode {}", line, mode)),
+    }
+}
+
+/// Splits a script into records. Conditions (`skipif`/`onlyif`) are folded
+/// into `Record::skip`; `halt` drops everything after it.
+pub fn parse(script: &str) -> Result<Vec<Record>, String> {
+    let lines: Vec<&str> = script.lines().collect();
+    let mut records = Vec::new();
+    let mut skip = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        let number = i + 1;
+        i += 1;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let kind = match words.first().copied() {
+            None => continue,
+            Some(word) if word.starts_with('#') => continue,
+            Some("halt") => break,
+            Some("hash-threshold") => continue,
+            Some("skipif") => {
+                skip |= words.get(1) == Some(&ENGINE_NAME);
+                continue;
+            }
+            Some("onlyif") => {
+                skip |= words.get(1) != Some(&ENGINE_NAME);
+                continue;
+            }
+            Some("statement") => match words.get(1).copied() {
+                Some("ok") => RecordKind::Statement {
+                    expect_error: false,
+                },
+                Some("error") => RecordKind::Statement { expect_error: true },
+                _ => return Err(format!("line {}: expected statement ok|error", number)),
+            },
+            Some("query") => RecordKind::Query {
+                types: words.get(1).copied().unwrap_or("").to_string(),
+                sort: parse_sort(words.get(2).copied(), number)?,
+                expected: Vec::new(),
+            },
+            Some(word) => return Err(format!("line {}: unknown record type {}", number, word)),
+        };
+
+        let mut sql = Vec::new();
+        while i < lines.len() && !lines[i].trim().is_empty() && lines[i].trim() != "----" {
+            sql.push(lines[i]);
+            i += 1;
+        }
+
+        let kind = match kind {
+            RecordKind::Query { types, sort, .. } => {
+                let mut expected = Vec::new();
+                if i < lines.len() && lines[i].trim() == "----" {
+                    i += 1;
+                    while i < lines.len() && !lines[i].trim().is_empty() {
+                        expected.push(lines[i].trim().to_string());
+                        i += 1;
+                    }
+                }
+                RecordKind::Query {
+                    types,
+                    sort,
+                    expected,
+                }
+            }
+            kind => kind,
+        };
+
+        records.push(Record {
+            line: number,
+            kind,
+            sql: sql.join("\n"),
+            skip,
+        });
+        skip = false;
+    }
+
+    Ok(records)
+}
+
+/// Renders a value the way sqllogictest expects for the column type:
+/// `I` integers, `R` reals with three decimals, anything else as text.
+fn format_value(value: &CalcResult, column_type: char) -> String {
+    match (value, column_type) {
+        (CalcResult::Null, _) => String::from("NULL"),
+        (CalcResult::Num(num), 'I') => format_num(num.trunc()),
+        (CalcResult::Num(num), 'R') => format!("{:.3}", num),
+        (CalcResult::Num(num), _) => format_num(*num),
+        (CalcResult::Int(int), 'R') => format!("{:.3}", *int as f64),
+        (CalcResult::Decimal(_), 'I') => format_num(value.as_f64().unwrap_or_default().trunc()),
+        (CalcResult::Decimal(decimal), 'R') => {
+            decimal::round(decimal, 3, RoundingMode::HalfUp).to_plain_string()
+        }
+        (CalcResult::Bool(boolean), _) => boolean.to_string(),
+        (CalcResult::Str(str), _) if str.is_empty() => String::from("(empty)"),
+        (value, _) => to_text(value),
+    }
+}
+
+/// The count and hash of a hashed result, `N values hashing to <md5>`.
+fn hashed(expected: &[String]) -> Option<(usize, &str)> {
+    match expected {
+        [line] => match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [count, "values", "hashing", "to", hash] => Some((count.parse().ok()?, hash)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The lowercase hex MD5 of `values`, each followed by a line break, as
+/// sqllogictest hashes them.
+fn hash(values: &[String]) -> String {
+    let mut md5 = Md5::new();
+    for value in values {
+        md5.update(value.as_bytes());
+        md5.update(b"\n");
+    }
+    md5.finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn check(engine: &Engine, record: &Record) -> Status {
+    if record.skip {
+        return Status::Skipped;
+    }
+
+    let res = engine.exec(&record.sql);
+    match (&record.kind, res) {
+        (
+            RecordKind::Statement {
+                expect_error: false,
+            },
+            Err(err),
+        ) => Status::Failed(err.to_string()),
+        (RecordKind::Statement { expect_error: true }, Ok(_)) => Status::Failed(String::from(
+            "statement succeeded but an error was expected",
+        )),
+        (RecordKind::Statement { .. }, _) => Status::Passed,
+        (RecordKind::Query { .. }, Err(err)) => Status::Failed(err.to_string()),
+        (
+            RecordKind::Query {
+                types,
+                sort,
+                expected,
+            },
+            Ok(value),
+        ) => {
+            let format_row = |values: &[CalcResult]| -> Vec<String> {
+                let types = types.chars().chain(iter::repeat('T'));
+                values
+                    .iter()
+                    .zip(types)
+                    .map(|(value, ty)| format_value(value, ty))
+                    .collect()
+            };
+            let mut rows = match value {
+                CalcResult::Table(rows) => rows.iter().map(|row| format_row(row)).collect(),
+                CalcResult::Row(values) => vec![format_row(&values)],
+                value => vec![format_row(&[value])],
+            };
+            if *sort == SortMode::RowSort {
+                rows.sort();
+            }
+            let mut actual = rows.concat();
+            if *sort == SortMode::ValueSort {
+                actual.sort();
+            }
+            if let Some((count, expected_hash)) = hashed(expected) {
+                let actual_hash = hash(&actual);
+                if actual.len() == count && actual_hash == expected_hash {
+                    return Status::Passed;
+                }
+                return Status::Failed(format!(
+                    "expected {} values hashing to {}, got {} values hashing to {}",
+                    count,
+                    expected_hash,
+                    actual.len(),
+                    actual_hash
+                ));
+            }
+
+            let mut expected = expected.clone();
+            if *sort == SortMode::RowSort {
+                let width = types.chars().count().max(1);
+                let mut expected_rows: Vec<_> = expected.chunks(width).collect();
+                expected_rows.sort();
+                expected = expected_rows.concat();
+            }
+            if *sort == SortMode::ValueSort {
+                expected.sort();
+            }
+
+            if actual == expected {
+                Status::Passed
+            } else {
+                Status::Failed(format!(
+                    "expected [{}], got [{}]",
+                    expected.join(", "),
+                    actual.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Runs every record of `script` against `engine`.
+pub fn run_script(file: &Path, script: &str, engine: &Engine) -> Vec<Outcome> {
+    let records = match parse(script) {
+        Ok(records) => records,
+        Err(err) => {
+            return vec![Outcome {
+                file: file.to_path_buf(),
+                line: 0,
+                sql: String::new(),
+                status: Status::Failed(err),
+                duration: Duration::ZERO,
+            }]
+        }
+    };
+
+    records
+        .iter()
+        .map(|record| {
+            let start = Instant::now();
+            let status = check(engine, record);
+            Outcome {
+                file: file.to_path_buf(),
+                line: record.line,
+                sql: record.sql.clone(),
+                status,
+                duration: start.elapsed(),
+            }
+        })
+        .collect()
+}
+
+fn collect_scripts(path: &Path, scripts: &mut Vec<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect_scripts(&entry?.path(), scripts)?;
+        }
+    } else if matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("test") | Some("slt")
+    ) {
+        scripts.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Runs a single script, or every `.test`/`.slt` file below a directory on
+/// `jobs` threads, reporting outcomes in path order. Each script gets the
+/// tables its `<script>.fixtures.yaml` declares, see [`fixtures`].
+pub fn run(path: &Path, options: &Options, jobs: usize) -> io::Result<Report<Outcome>> {
+    run_with_setup(path, options, jobs, runner::no_setup)
+}
+
+/// [`run`], preparing each engine with `setup` first.
+pub fn run_with_setup(
+    path: &Path,
+    options: &Options,
+    jobs: usize,
+    setup: impl Fn(&mut Engine) -> Result<(), CalcError> + Sync,
+) -> io::Result<Report<Outcome>> {
+    let mut scripts = Vec::new();
+    if path.is_dir() {
+        collect_scripts(path, &mut scripts)?;
+        scripts.sort();
+    } else {
+        scripts.push(path.to_path_buf());
+    }
+
+    runner::run_isolated(&scripts, options, jobs, setup, |engine, script| {
+        let content = fs::read_to_string(script)?;
+        let fixtures = match fixtures::load(engine, script) {
+            Ok(fixtures) => fixtures,
+            Err(err) => {
+                return Ok(vec![Outcome {
+                    file: script.to_path_buf(),
+                    line: 0,
+                    sql: String::new(),
+                    status: Status::Failed(err),
+                    duration: Duration::ZERO,
+                }])
+            }
+        };
+        let outcomes = run_script(script, &content, engine);
+        fixtures.tear_down(engine);
+        Ok(outcomes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "\
+# arithmetic
+query I
+SELECT 1 + 1
+----
+2
+
+query R
+SELECT 1.0 / 3
+----
+0.333
+
+statement error
+SELECT 1 / 0
+
+skipif sql_test_engine
+query T
+SELECT 'skipped'
+----
+nope
+
+query T
+SELECT 'a'
+----
+b
+
+halt
+
+query I
+SELECT 1
+----
+1
+";
+
+    fn statuses(script: &str) -> Vec<Status> {
+        run_script(Path::new("inline.test"), script, &Engine::default())
+            .into_iter()
+            .map(|outcome| outcome.status)
+            .collect()
+    }
+
+    #[test]
+    fn parse_records() {
+        let records = parse(SCRIPT).unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].line, 2);
+        assert_eq!(records[0].sql, "SELECT 1 + 1");
+        assert_eq!(
+            records[0].kind,
+            RecordKind::Query {
+                types: String::from("I"),
+                sort: SortMode::NoSort,
+                expected: vec![String::from("2")],
+            }
+        );
+        assert!(records[3].skip);
+    }
+
+    #[test]
+    fn parse_unknown_record() {
+        assert!(parse("select 1\n").is_err());
+    }
+
+    #[test]
+    fn run_script_outcomes() {
+        assert_eq!(
+            statuses(SCRIPT),
+            vec![
+                Status::Passed,
+                Status::Passed,
+                Status::Passed,
+                Status::Skipped,
+                Status::Failed(String::from("expected [b], got [a]")),
+            ]
+        );
+    }
+
+    #[test]
+    fn run_script_multiple_columns() {
+        assert_eq!(
+            statuses("query IRT\nSELECT 1, 1.0 / 4, 'x'\n----\n1\n0.250\nx\n"),
+            vec![Status::Passed]
+        );
+        assert_eq!(
+            statuses("query II valuesort\nSELECT 2, 1\n----\n1\n2\n"),
+            vec![Status::Passed]
+        );
+        assert_eq!(
+            statuses("query II\nSELECT 2, 1\n----\n1\n2\n"),
+            vec![Status::Failed(String::from("expected [1, 2], got [2, 1]"))]
+        );
+    }
+
+    #[test]
+    fn run_script_multiple_rows() {
+        let sql = "SELECT * FROM (VALUES (2, 'b'), (1, 'a')) t";
+        assert_eq!(
+            statuses(&format!("query IT\n{}\n----\n2\nb\n1\na\n", sql)),
+            vec![Status::Passed]
+        );
+        assert_eq!(
+            statuses(&format!("query IT rowsort\n{}\n----\n1\na\n2\nb\n", sql)),
+            vec![Status::Passed]
+        );
+        assert_eq!(
+            statuses(&format!("query IT rowsort\n{}\n----\n1\nb\n2\na\n", sql)),
+            vec![Status::Failed(String::from(
+                "expected [1, b, 2, a], got [1, a, 2, b]"
+            ))]
+        );
+    }
+
+    #[test]
+    fn run_script_hashed_results() {
+        let sql = "SELECT * FROM (VALUES (2, 'b'), (1, 'a')) t";
+        let hashed = "4 values hashing to dcb405d9ab483907f29736a07728fa2d";
+        assert_eq!(
+            statuses(&format!("query IT rowsort\n{}\n----\n{}\n", sql, hashed)),
+            vec![Status::Passed]
+        );
+        assert_eq!(
+            statuses(&format!("query IT\n{}\n----\n{}\n", sql, hashed)),
+            vec![Status::Failed(String::from(
+                "expected 4 values hashing to dcb405d9ab483907f29736a07728fa2d, got 4 values hashing to c6582c03e7603a670e1510be9ab3bf16"
+            ))]
+        );
+    }
+
+    #[test]
+    fn run_script_statement_ok_failure() {
+        let statuses = statuses("statement ok\nSELECT 1 / 0\n");
+        if let [Status::Failed(message)] = &statuses[..] {
+            assert_eq!(message, "[Division By Zero]: Division by zero");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn run_with_fixtures() {
+        let dir = std::env::temp_dir().join(format!("logictest-fixtures-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("users.test");
+        fs::write(
+            &script,
+            "statement ok\nINSERT INTO users VALUES (3, 'carol')\n\nquery IT rowsort\nSELECT id, name FROM users\n----\n1\nalice\n2\nbob\n3\ncarol\n",
+        )
+        .unwrap();
+        fs::write(dir.join("users.csv"), "id,name\n1,alice\n2,bob\n").unwrap();
+        fs::write(
+            dir.join("users.fixtures.yaml"),
+            "tables:\n  users:\n    csv: users.csv\n",
+        )
+        .unwrap();
+
+        let outcomes = run(&script, &Options::default(), 1).unwrap().outcomes;
+        let statuses: Vec<_> = outcomes.into_iter().map(|outcome| outcome.status).collect();
+        assert_eq!(statuses, vec![Status::Passed, Status::Passed]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn format_value_types() {
+        assert_eq!(format_value(&CalcResult::Num(2.7), 'I'), "2");
+        assert_eq!(format_value(&CalcResult::Num(2.0), 'R'), "2.000");
+        assert_eq!(
+            format_value(&CalcResult::Str(String::new()), 'T'),
+            "(empty)"
+        );
+        assert_eq!(format_value(&CalcResult::Null, 'I'), "NULL");
+    }
+}
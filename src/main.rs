@@ -1,21 +1,720 @@
-mod engine;
+mod output;
+mod repl;
+mod rpc;
+mod terminal;
 
-use engine::engine::*;
+use output::format::{self, Format};
+#[cfg(feature = "sqlite")]
+use sql_test_engine::differential::{self, Reference};
+use sql_test_engine::engine::coverage::Coverage;
+use sql_test_engine::engine::dialect;
+use sql_test_engine::engine::engine::*;
+use sql_test_engine::engine::temporal::Zone;
+use sql_test_engine::engine::text::{Collation, StringSemantics};
+#[cfg(feature = "wasm")]
+use sql_test_engine::engine::wasm::WasmLimits;
+use sql_test_engine::fuzz;
+use sql_test_engine::golden;
+use sql_test_engine::logictest::{self, Status};
+use sql_test_engine::report::{self, Case, CaseStatus};
 use std::env;
+use std::fs;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::panic;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use terminal::Terminal;
 
-fn print_result(res: Result<CalcResult, CalcError>) {
+#[derive(Clone, Copy)]
+enum ReportFormat {
+    Junit,
+    Tap,
+}
+
+#[derive(Default)]
+struct Args {
+    format: Option<String>,
+    output: Option<String>,
+    output_format: Format,
+    no_color: bool,
+    timing: bool,
+    on_error: OnError,
+    file: Option<String>,
+    stdio: bool,
+    logictest: Option<String>,
+    golden: Option<String>,
+    update: bool,
+    fuzz: Option<usize>,
+    fuzz_seed: Option<u64>,
+    coverage: bool,
+    differential: Option<String>,
+    jobs: usize,
+    report: Option<ReportFormat>,
+    wasm: Vec<String>,
+    wasm_fuel: Option<u64>,
+    engine: Options,
+}
+
+fn parse_limit<'a>(
+    iter: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<usize, String> {
+    match iter.next().map(|value| value.parse()) {
+        Some(Ok(limit)) => Ok(limit),
+        _ => Err(format!("{} expects a non-negative integer", flag)),
+    }
+}
+
+/// Whether `arg` looks like a flag rather than a word of the query: `-x`
+/// or `--name`, where a negative number is still a word.
+fn is_flag(arg: &str) -> bool {
+    match arg.strip_prefix('-') {
+        Some(rest) => {
+            rest.starts_with('-')
+                || (rest.len() == 1 && rest.chars().all(|c| c.is_ascii_alphabetic()))
+        }
+        None => false,
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(Args, String), String> {
+    let mut options = Args {
+        jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        ..Args::default()
+    };
+    let mut query = Vec::new();
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_ref() {
+            "--format" => match iter.next() {
+                Some(format) => options.format = Some(format.clone()),
+                None => return Err(String::from("--format requires a value")),
+            },
+            "--output" => match iter.next() {
+                Some(path) => options.output = Some(path.clone()),
+                None => return Err(String::from("--output requires a value")),
+            },
+            "--json" => options.output_format = Format::Json,
+            "--csv" => options.output_format = Format::Csv,
+            "--table" => options.output_format = Format::Table,
+            "--no-color" => options.no_color = true,
+            "--timing" => options.timing = true,
+            "--on-error" => options.on_error = parse_on_error(iter.next().map(String::as_str))?,
+            arg if arg.starts_with("--on-error=") => {
+                options.on_error = parse_on_error(arg.strip_prefix("--on-error="))?
+            }
+            "-f" | "--file" => match iter.next() {
+                Some(path) => options.file = Some(path.clone()),
+                None => return Err(format!("{} requires a path", arg)),
+            },
+            "--division-by-zero" => match iter.next().map(String::as_str) {
+                Some("error") => options.engine.division_by_zero = OnDivisionByZero::Error,
+                Some("null") => options.engine.division_by_zero = OnDivisionByZero::Null,
+                _ => return Err(String::from("--division-by-zero expects error or null")),
+            },
+            "--overflow" => match iter.next().map(String::as_str) {
+                Some("error") => options.engine.overflow = OnOverflow::Error,
+                Some("wrap") => options.engine.overflow = OnOverflow::Wrap,
+                Some("saturate") => options.engine.overflow = OnOverflow::Saturate,
+                _ => return Err(String::from("--overflow expects error, wrap or saturate")),
+            },
+            "--coercion" => match iter.next().map(String::as_str) {
+                Some("strict") => options.engine.coercion = Coercion::Strict,
+                Some("lenient") => options.engine.coercion = Coercion::Lenient,
+                _ => return Err(String::from("--coercion expects strict or lenient")),
+            },
+            "--numeric" => match iter.next().map(String::as_str) {
+                Some("float") => options.engine.numeric = Numeric::Float,
+                Some("decimal") => options.engine.numeric = Numeric::Decimal,
+                _ => return Err(String::from("--numeric expects float or decimal")),
+            },
+            "--string-semantics" => match iter.next().map(String::as_str) {
+                Some("codepoints") => options.engine.string_semantics = StringSemantics::CodePoints,
+                Some("graphemes") => options.engine.string_semantics = StringSemantics::Graphemes,
+                _ => {
+                    return Err(String::from(
+                        "--string-semantics expects codepoints or graphemes",
+                    ))
+                }
+            },
+            "--dialect" => {
+                options.engine.dialect = match iter.next().map(String::as_str) {
+                    Some("generic") => Arc::new(dialect::Generic),
+                    Some("sqlserver") => Arc::new(dialect::SqlServer),
+                    Some("snowflake") => Arc::new(dialect::Snowflake),
+                    Some("sqlite") => Arc::new(dialect::Sqlite),
+                    _ => {
+                        return Err(String::from(
+                            "--dialect expects generic, sqlserver, snowflake or sqlite",
+                        ))
+                    }
+                }
+            }
+            "--collation" => match iter.next().map(String::as_str) {
+                Some("binary") => options.engine.collation = Collation::Binary,
+                Some("nocase") => options.engine.collation = Collation::NoCase,
+                _ => return Err(String::from("--collation expects binary or nocase")),
+            },
+            "--max-depth" => options.engine.limits.max_depth = parse_limit(&mut iter, arg)?,
+            "--max-nodes" => options.engine.limits.max_nodes = parse_limit(&mut iter, arg)?,
+            "--max-string-len" => {
+                options.engine.limits.max_string_len = parse_limit(&mut iter, arg)?
+            }
+            "--max-recursion" => options.engine.limits.max_recursion = parse_limit(&mut iter, arg)?,
+            "--stdio" => options.stdio = true,
+            "--logictest" => match iter.next() {
+                Some(path) => options.logictest = Some(path.clone()),
+                None => return Err(String::from("--logictest requires a path")),
+            },
+            "--golden" => match iter.next() {
+                Some(path) => options.golden = Some(path.clone()),
+                None => return Err(String::from("--golden requires a directory")),
+            },
+            "--update" => options.update = true,
+            "--coverage" => options.coverage = true,
+            "--jobs" => options.jobs = parse_limit(&mut iter, arg)?,
+            "--wasm" => match iter.next() {
+                Some(path) => options.wasm.push(path.clone()),
+                None => return Err(String::from("--wasm requires a path")),
+            },
+            "--wasm-fuel" => match iter.next().map(|value| value.parse()) {
+                Some(Ok(fuel)) => options.wasm_fuel = Some(fuel),
+                _ => return Err(String::from("--wasm-fuel expects a non-negative integer")),
+            },
+            "--report" => match iter.next().map(String::as_str) {
+                Some("junit") => options.report = Some(ReportFormat::Junit),
+                Some("tap") => options.report = Some(ReportFormat::Tap),
+                _ => return Err(String::from("--report expects junit or tap")),
+            },
+            "--deterministic" => {
+                let Options {
+                    clock,
+                    explain_timing,
+                    seed,
+                    ..
+                } = Options::deterministic();
+                options.engine.clock = clock;
+                options.engine.explain_timing = explain_timing;
+                options.engine.seed = seed;
+            }
+            "--now" => match iter.next().map(|value| value.parse()) {
+                Some(Ok(secs)) => {
+                    options.engine.clock = Clock::Fixed(UNIX_EPOCH + Duration::from_secs(secs))
+                }
+                _ => return Err(String::from("--now expects seconds since the Unix epoch")),
+            },
+            "--seed" => match iter.next().map(|value| value.parse()) {
+                Some(Ok(seed)) => options.engine.seed = Some(seed),
+                _ => return Err(String::from("--seed expects a non-negative integer")),
+            },
+            "--tz" => match iter.next().map(|name| Zone::parse(name)) {
+                Some(Ok(zone)) => options.engine.time_zone = zone,
+                Some(Err(error)) => return Err(error.to_string()),
+                None => return Err(String::from("--tz expects a time zone name or offset")),
+            },
+            "--http-header" => match iter.next().and_then(|header| header.split_once(':')) {
+                Some((name, value)) => options
+                    .engine
+                    .http
+                    .headers
+                    .push((name.trim().to_string(), value.trim().to_string())),
+                None => return Err(String::from("--http-header expects 'Name: value'")),
+            },
+            "--http-cache" => match iter.next() {
+                Some(dir) => options.engine.http.cache = Some(dir.into()),
+                None => return Err(String::from("--http-cache requires a directory")),
+            },
+            "--differential" => match iter.next() {
+                Some(connection) => options.differential = Some(connection.clone()),
+                None => return Err(String::from("--differential requires a connection")),
+            },
+            "--fuzz" => options.fuzz = Some(parse_limit(&mut iter, arg)?),
+            "--fuzz-seed" => match iter.next().map(|value| value.parse()) {
+                Some(Ok(seed)) => options.fuzz_seed = Some(seed),
+                _ => return Err(String::from("--fuzz-seed expects a non-negative integer")),
+            },
+            flag if is_flag(flag) => {
+                return Err(format!(
+                    "unknown flag {} (quote the query if it is part of it)",
+                    flag
+                ))
+            }
+            _ => query.push(arg.as_str()),
+        }
+    }
+
+    if options.file.is_some() && !query.is_empty() {
+        return Err(String::from("--file cannot be combined with a query"));
+    }
+    Ok((options, query.join(" ")))
+}
+
+/// Exit status when a test run has failing records.
+const EXIT_TEST_FAILURE: i32 = 1;
+/// Exit status for invalid command line arguments.
+const EXIT_USAGE: i32 = 2;
+/// Exit status for failures outside the engine, e.g. writing output.
+const EXIT_IO: i32 = 3;
+
+fn print_warnings(warnings: &[Warning]) {
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+}
+
+/// How the result of each statement is printed.
+#[derive(Clone, Copy)]
+struct Printer {
+    format: Format,
+    terminal: Terminal,
+    /// Whether to follow each result with how long it took to parse and
+    /// to evaluate.
+    timing: bool,
+}
+
+fn print_timing(timing: &Timing) {
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    eprintln!(
+        "Time: parse {:.3} ms, eval {:.3} ms",
+        millis(timing.parse),
+        millis(timing.eval)
+    );
+}
+
+/// Writes the result to `out`, and its warnings and timing to stderr.
+fn print_result(res: Result<Evaluation, CalcError>, printer: Printer, out: &mut dyn Write) -> i32 {
+    let rendered = format::render(printer.format, &res, printer.terminal);
+    if let Err(err) = writeln!(out, "{}", rendered) {
+        println!("[Output Error]: {}", err);
+        return EXIT_IO;
+    }
     match res {
-        Ok(res) => println!("{}", res),
-        Err(err) => println!("{}", err),
+        Ok(evaluation) => {
+            print_warnings(&evaluation.warnings);
+            if printer.timing {
+                print_timing(&evaluation.timing);
+            }
+            0
+        }
+        Err(err) => err.exit_code(),
     }
 }
 
+/// What a script does after one of its statements fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum OnError {
+    #[default]
+    Stop,
+    Continue,
+}
+
+fn parse_on_error(value: Option<&str>) -> Result<OnError, String> {
+    match value {
+        Some("stop") => Ok(OnError::Stop),
+        Some("continue") => Ok(OnError::Continue),
+        _ => Err(String::from("--on-error expects stop or continue")),
+    }
+}
+
+/// Runs the statements of `script` in order, printing the result of each
+/// (in text, after the line it starts on when the script comes from the
+/// file at `path`) to `out`. A script of several statements stops at the
+/// first that fails unless `on_error` says to continue, and ends with a
+/// count of the statements that succeeded, failed and were skipped.
+/// Returns the exit status of the first failure, or 0.
+fn run_script(
+    engine: &Engine,
+    script: &str,
+    path: Option<&str>,
+    printer: Printer,
+    on_error: OnError,
+    out: &mut dyn Write,
+) -> i32 {
+    let statements = engine.split(script);
+    if statements.len() < 2 && path.is_none() {
+        return print_result(engine.eval(script), printer, out);
+    }
+
+    let (mut succeeded, mut failed, mut code) = (0, 0, 0);
+    for (line, statement) in &statements {
+        if let (Some(path), Format::Text) = (path, printer.format) {
+            if let Err(err) = write!(out, "{}:{}: ", path, line) {
+                println!("[Output Error]: {}", err);
+                return EXIT_IO;
+            }
+        }
+        match print_result(engine.eval(statement), printer, out) {
+            0 => succeeded += 1,
+            status => {
+                failed += 1;
+                if code == 0 {
+                    code = status;
+                }
+                if on_error == OnError::Stop {
+                    break;
+                }
+            }
+        }
+    }
+
+    if statements.len() > 1 {
+        let skipped = statements.len() - succeeded - failed;
+        let mut summary = format!("{} succeeded, {} failed", succeeded, failed);
+        if skipped > 0 {
+            summary.push_str(&format!(", {} skipped", skipped));
+        }
+        eprintln!("{}", summary);
+    }
+    code
+}
+
+fn export_xlsx(res: Result<Evaluation, CalcError>, path: Option<String>) -> i32 {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            println!("[Invalid Argument]: --format xlsx requires --output");
+            return EXIT_USAGE;
+        }
+    };
+
+    match res {
+        Ok(evaluation) => match output::xlsx::write(&path, &evaluation) {
+            Ok(()) => {
+                print_warnings(&evaluation.warnings);
+                0
+            }
+            Err(err) => {
+                println!("[Output Error]: {}", err);
+                EXIT_IO
+            }
+        },
+        Err(err) => {
+            println!("{}", err);
+            err.exit_code()
+        }
+    }
+}
+
+/// Runs `f` writing to the file at `path`, created anew, or to stdout
+/// without one, and returns its exit status.
+fn write_output(path: Option<&str>, f: impl FnOnce(&mut dyn Write) -> i32) -> i32 {
+    let path = match path {
+        Some(path) => path,
+        None => return f(&mut io::stdout()),
+    };
+    let mut out = match fs::File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => {
+            println!("[Output Error]: {}: {}", path, err);
+            return EXIT_IO;
+        }
+    };
+    let code = f(&mut out);
+    match out.flush() {
+        Ok(()) => code,
+        Err(err) => {
+            println!("[Output Error]: {}: {}", path, err);
+            EXIT_IO
+        }
+    }
+}
+
+fn print_coverage(coverage: &Coverage, options: &Options) {
+    let surface = Engine::new(options.clone()).surface();
+    println!("\n{}", coverage.report(&surface));
+}
+
+/// Writes a JUnit or TAP report to `--output`, or stdout without it.
+fn emit_report(format: ReportFormat, cases: &[Case], coverage: &Coverage, args: &Args) -> i32 {
+    let rendered = match format {
+        ReportFormat::Junit => report::junit(cases),
+        ReportFormat::Tap => report::tap(cases),
+    };
+    match &args.output {
+        Some(path) => {
+            if let Err(err) = fs::write(path, rendered) {
+                println!("[Output Error]: {}", err);
+                return EXIT_IO;
+            }
+        }
+        None => print!("{}", rendered),
+    }
+    if args.coverage {
+        print_coverage(coverage, &args.engine);
+    }
+
+    let failed = cases
+        .iter()
+        .any(|case| matches!(case.status, CaseStatus::Failed(_)));
+    if failed {
+        EXIT_TEST_FAILURE
+    } else {
+        0
+    }
+}
+
+fn run_logictest(
+    path: &str,
+    args: &Args,
+    setup: impl Fn(&mut Engine) -> Result<(), CalcError> + Sync,
+) -> i32 {
+    let report = match logictest::run_with_setup(Path::new(path), &args.engine, args.jobs, setup) {
+        Ok(report) => report,
+        Err(err) => {
+            println!("[IO Error]: {}", err);
+            return EXIT_IO;
+        }
+    };
+
+    if let Some(format) = args.report {
+        let cases: Vec<_> = report
+            .outcomes
+            .iter()
+            .map(|outcome| Case {
+                suite: outcome.file.display().to_string(),
+                name: format!("line {}", outcome.line),
+                status: match &outcome.status {
+                    Status::Passed => CaseStatus::Passed,
+                    Status::Failed(message) => CaseStatus::Failed(message.clone()),
+                    Status::Skipped => CaseStatus::Skipped,
+                },
+                duration: outcome.duration,
+            })
+            .collect();
+        return emit_report(format, &cases, &report.coverage, args);
+    }
+
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for outcome in &report.outcomes {
+        let location = format!("{}:{}", outcome.file.display(), outcome.line);
+        match &outcome.status {
+            Status::Passed => {
+                passed += 1;
+                println!("PASS {}", location);
+            }
+            Status::Failed(message) => {
+                failed += 1;
+                println!("FAIL {}: {}", location, message);
+            }
+            Status::Skipped => {
+                skipped += 1;
+                println!("SKIP {}", location);
+            }
+        }
+    }
+    println!(
+        "\n{} passed, {} failed, {} skipped",
+        passed, failed, skipped
+    );
+    if args.coverage {
+        print_coverage(&report.coverage, &args.engine);
+    }
+
+    if failed > 0 {
+        EXIT_TEST_FAILURE
+    } else {
+        0
+    }
+}
+
+fn run_golden(
+    dir: &str,
+    args: &Args,
+    setup: impl Fn(&mut Engine) -> Result<(), CalcError> + Sync,
+) -> i32 {
+    let report =
+        match golden::run_with_setup(Path::new(dir), &args.engine, args.update, args.jobs, setup) {
+            Ok(report) => report,
+            Err(err) => {
+                println!("[IO Error]: {}", err);
+                return EXIT_IO;
+            }
+        };
+
+    if let Some(format) = args.report {
+        let cases: Vec<_> = report
+            .outcomes
+            .iter()
+            .map(|outcome| Case {
+                suite: dir.to_string(),
+                name: outcome
+                    .case
+                    .strip_prefix(dir)
+                    .unwrap_or(&outcome.case)
+                    .display()
+                    .to_string(),
+                status: match &outcome.status {
+                    golden::Status::Passed | golden::Status::Updated => CaseStatus::Passed,
+                    golden::Status::Failed(diff) => CaseStatus::Failed(diff.clone()),
+                    golden::Status::Missing => {
+                        CaseStatus::Failed(String::from("no .expected file, rerun with --update"))
+                    }
+                },
+                duration: outcome.duration,
+            })
+            .collect();
+        return emit_report(format, &cases, &report.coverage, args);
+    }
+
+    let mut failed = 0;
+    for outcome in &report.outcomes {
+        let case = outcome.case.display();
+        match &outcome.status {
+            golden::Status::Passed => println!("PASS {}", case),
+            golden::Status::Updated => println!("UPDATE {}", case),
+            golden::Status::Failed(diff) => {
+                failed += 1;
+                println!("FAIL {}\n{}", case, diff);
+            }
+            golden::Status::Missing => {
+                failed += 1;
+                println!("FAIL {}: no .expected file, rerun with --update", case);
+            }
+        }
+    }
+    println!("\n{} cases, {} failed", report.outcomes.len(), failed);
+    if args.coverage {
+        print_coverage(&report.coverage, &args.engine);
+    }
+
+    if failed > 0 {
+        EXIT_TEST_FAILURE
+    } else {
+        0
+    }
+}
+
+fn run_fuzz(iterations: usize, seed: Option<u64>, options: &Options) -> i32 {
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |elapsed| elapsed.as_nanos() as u64)
+    });
+
+    // panics are reported as failures, not printed by the default hook
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let failures = fuzz::run(seed, iterations, options);
+    panic::set_hook(hook);
+
+    for failure in &failures {
+        println!("FAIL {}: {}", failure.query, failure.message);
+    }
+    println!(
+        "\n{} queries, {} failed (--fuzz-seed {})",
+        iterations,
+        failures.len(),
+        seed
+    );
+
+    if failures.is_empty() {
+        0
+    } else {
+        EXIT_TEST_FAILURE
+    }
+}
+
+/// Compares `queries`, labelled for the report, against the reference
+/// database.
+#[cfg(feature = "sqlite")]
+fn run_differential(connection: &str, engine: &Engine, queries: &[(String, String)]) -> i32 {
+    let reference = match Reference::connect(connection) {
+        Ok(reference) => reference,
+        Err(err) => {
+            println!("[Invalid Argument]: {}", err);
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut diverged = 0;
+    for (label, query) in queries {
+        match differential::compare(engine, &reference, query) {
+            Some(divergence) => {
+                diverged += 1;
+                println!(
+                    "DIVERGE {}\n  ours:      {}\n  reference: {}",
+                    label, divergence.ours, divergence.reference
+                );
+            }
+            None => println!("MATCH {}", label),
+        }
+    }
+    println!("\n{} queries, {} diverged", queries.len(), diverged);
+
+    if diverged > 0 {
+        EXIT_TEST_FAILURE
+    } else {
+        0
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn run_differential(_: &str, _: &Engine, _: &[(String, String)]) -> i32 {
+    println!("[Invalid Argument]: --differential requires the sqlite feature");
+    EXIT_USAGE
+}
+
+fn differential_queries(
+    golden: Option<&String>,
+    query: String,
+) -> std::io::Result<Vec<(String, String)>> {
+    match golden {
+        Some(dir) => golden::cases(Path::new(dir))?
+            .into_iter()
+            .map(|case| {
+                let query = std::fs::read_to_string(&case)?;
+                Ok((case.display().to_string(), query))
+            })
+            .collect(),
+        None => Ok(vec![(query.clone(), query)]),
+    }
+}
+
+/// The contents of the `--wasm` modules, printing the error of one that
+/// cannot be read.
+fn read_wasm(paths: &[String]) -> Result<Vec<Vec<u8>>, i32> {
+    if !paths.is_empty() && !cfg!(feature = "wasm") {
+        println!("[Invalid Argument]: --wasm requires the wasm feature");
+        return Err(EXIT_USAGE);
+    }
+    paths
+        .iter()
+        .map(|path| {
+            fs::read(path).map_err(|err| {
+                println!("[IO Error]: {}", err);
+                EXIT_IO
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "wasm")]
+fn load_wasm(engine: &mut Engine, modules: &[Vec<u8>], fuel: Option<u64>) -> Result<(), CalcError> {
+    let mut limits = WasmLimits::default();
+    if let Some(fuel) = fuel {
+        limits.fuel = fuel;
+    }
+    for wasm in modules {
+        engine.load_wasm(wasm, limits.clone())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "wasm"))]
+fn load_wasm(_: &mut Engine, _: &[Vec<u8>], _: Option<u64>) -> Result<(), CalcError> {
+    Ok(())
+}
+
 fn print_version() {
     println!("0.0.1");
 }
 
 fn print_help() {
-    println!("\n****************************************\nWELCOME TO THE SQL ENGINE\n\nHELP: -h, --help\nGET VERSION: -v, --version\n\n\n----------------------------------------\nSTATEMENTS: SELECT\nOPERATORS: +, -, *, >\nFUNCS: SQRT\n****************************************\n");
+    println!("\n****************************************\nWELCOME TO THE SQL ENGINE\n\nHELP: -h, --help\nGET VERSION: -v, --version\nRUN A SCRIPT FILE: -f, --file <script.sql>\nREAD FROM STDIN: <command> | sql_test_engine (when no query is given)\nINTERACTIVE: sql_test_engine (on a terminal, with no query); statements end with ; and may span lines; \\h lists the commands (\\q, \\d, \\timing, \\i)\nEXPORT: --format xlsx --output <file>\nOUTPUT FORMAT: --json, --csv, --table (default: text), to --output <file> when given\nCOLORS: --no-color (also off when NO_COLOR is set or output is not a terminal)\nTIMING: --timing (parse and eval time of each statement, on stderr)\nSCRIPTS: --on-error stop|continue (default: stop), with a summary of the statements run on stderr\nJSON-RPC OVER STDIO: --stdio\nSQLLOGICTEST: --logictest <file|dir>\nGOLDEN FILES: --golden <dir> [--update]\nFIXTURES: <script>.fixtures.yaml next to a --golden or --logictest script declares tables (columns and rows, or a csv file) loaded before it runs\nCOVERAGE REPORT: --coverage (with --logictest or --golden)\nPARALLEL TEST FILES: --jobs <n> (default: available cores)\nCI REPORT: --report junit|tap [--output <file>]\nDIFFERENTIAL: --differential sqlite::memory:|sqlite:<path> [--golden <dir>]\nFUZZING: --fuzz <n> [--fuzz-seed <seed>]\nDIVISION BY ZERO: --division-by-zero error|null\nINTEGER OVERFLOW: --overflow error|wrap|saturate\nSTRING TO NUMBER COERCION: --coercion strict|lenient\nEXACT DECIMAL ARITHMETIC: --numeric float|decimal\nSTRING CHARACTERS: --string-semantics codepoints|graphemes\nSTRING ORDERING: --collation binary|nocase\nDIALECT: --dialect generic|sqlserver|snowflake|sqlite\nLIMITS: --max-depth <n>, --max-nodes <n>, --max-string-len <bytes>, --max-recursion <n>\nDETERMINISM: --deterministic, --now <unix-seconds>, --seed <n>\nTIME ZONE: --tz <zone> (default: UTC; e.g. Europe/Berlin, +05:30), or SET TIME ZONE '<zone>'|DEFAULT\nHTTP SOURCES: --http-header 'Name: value' (repeatable), --http-cache <dir>\nWASM FUNCTIONS: --wasm <file.wasm> (repeatable), --wasm-fuel <n>\nEXIT CODES: 0 success, 1 failing tests, 2 invalid arguments, 3 IO error, 10-20 engine error SQE-1000-SQE-1010 (e.g. 16 division by zero)\n\n\n----------------------------------------\nSTATEMENTS: [WITH [RECURSIVE] <name>[(<column>, ...)] AS (<query>), ...] SELECT [DISTINCT] <expr> [[AS] <alias>], ... [FROM <table>|(<query>)|'<file|url>'|READ_CSV('<file.csv|url>')|READ_JSON('<file.json|file.ndjson|url>')|READ_PARQUET('<file.parquet|url>') [[AS] <name>[(<column>, ...)]] [[INNER|LEFT|RIGHT|FULL] JOIN <table> ON <condition>|CROSS JOIN <table>|, <table> ...]] [WHERE <condition>] [GROUP BY <expr>|<position>|<alias>, ...] [HAVING <condition>], VALUES (<expr>, ...), ..., <query> UNION|INTERSECT|EXCEPT [ALL] <query>, CREATE TABLE [IF NOT EXISTS] <table>(<column> <type>, ...), INSERT INTO <table> [(<column>, ...)] VALUES (<expr>, ...), ..., DROP TABLE [IF EXISTS] <table>, ASSERT [SELECT] <condition> [AS <message>], CREATE FUNCTION <name>(<params>) AS <expr>, CREATE FUNCTION <name>(<params>) LANGUAGE rhai AS '<script>', SHOW FUNCTIONS, SET [@]<name> = <expr>, SET TIME ZONE '<zone>'|DEFAULT, SHOW TIME ZONE, ATTACH '<file.db>' AS <name> (SQLite tables, read-only, as <name>.<table>), EXPLAIN [ANALYZE] <query>\nOPERATORS: +, -, *, /, %, ^, ||, >, >=, <, <=, =, <>, AND, OR, NOT, [NOT] BETWEEN, [NOT] IN (...|<query>), [NOT] EXISTS (<query>), IS [NOT] NULL, <array>[<n>], CASE [<expr>] WHEN ... THEN ... [ELSE ...] END, (<query>), [TRY_]CAST(<expr> AS INT|BIGINT|FLOAT|DOUBLE|DECIMAL[(<p>[, <s>])]|TEXT|VARCHAR|BOOLEAN|DATE|TIMESTAMP|INTERVAL)\nVARIABLES: @<name>, or <name> when no column has that name\nLITERALS: TRUE, FALSE, DATE 'YYYY-MM-DD', TIMESTAMP 'YYYY-MM-DD HH:MM:SS', INTERVAL '<n> <unit> [...]', INTERVAL '<n>' <unit>, ARRAY[<expr>, ...]\nFUNCS: NOW(), CURRENT_DATE, CURRENT_TIMESTAMP, EXTRACT(YEAR|MONTH|DAY|HOUR|MINUTE|SECOND FROM <date>), DATE_TRUNC(<unit>, <date>), SQRT, POWER, POW, MOD, ABS, FLOOR, CEIL, ROUND(<x>[, <digits>]), TRUNC(<x>[, <digits>]), SIGN, SIN, COS, TAN, ASIN, ACOS, ATAN, ATAN2(<y>, <x>), EXP, LN, LOG([<base>, ]<x>), LOG10, LOG2, PI(), E(), RANDOM(), SETSEED(<n>), UPPER, LOWER, LENGTH, REVERSE, SUBSTRING(<s> FROM <start> [FOR <len>]), TRIM([BOTH|LEADING|TRAILING <chars> FROM] <s>), REPLACE, LPAD, RPAD, CONCAT(...), CONCAT_WS(<sep>, ...), REGEXP_MATCHES, REGEXP_REPLACE, REGEXP_EXTRACT(<s>, <pattern>[, <group>]), MD5, SHA1, SHA256, ARRAY_LENGTH(<array>[, 1]), ARRAY_CONTAINS(<array>, <value>), ARRAY_EXTRACT(<array>, <n>), COALESCE, IFNULL, NULLIF\nAGGREGATES: COUNT(*), COUNT([DISTINCT] <expr>), SUM, AVG, MIN, MAX\nWINDOW FUNCTIONS: ROW_NUMBER(), RANK(), LAG|LEAD(<expr>[, <offset>[, <default>]]), or an aggregate, followed by OVER ([PARTITION BY <expr>, ...] [ORDER BY <expr> [ASC|DESC] [NULLS FIRST|LAST], ...])\n****************************************\n");
 }
 
 fn print_default() {
@@ -23,14 +722,11 @@ fn print_default() {
 }
 
 fn main() {
-    
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 {
-        return print_default(); 
-    }
+    let piped = !io::stdin().is_terminal();
 
-    match args[1].as_ref() {
+    match args.get(1).map(String::as_str).unwrap_or_default() {
         "-v" => return print_version(),
         "--version" => return print_version(),
         "-h" => return print_help(),
@@ -38,5 +734,113 @@ fn main() {
         _ => (),
     }
 
-    print_result(exec(args[1..].join(" ")));
+    let (options, query) = match parse_args(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("[Invalid Argument]: {}", err);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    if let Some(iterations) = options.fuzz {
+        process::exit(run_fuzz(iterations, options.fuzz_seed, &options.engine));
+    }
+
+    let modules = match read_wasm(&options.wasm) {
+        Ok(modules) => modules,
+        Err(code) => process::exit(code),
+    };
+    // Test runners give every file an engine of its own, each loading the
+    // modules too.
+    let setup = |engine: &mut Engine| load_wasm(engine, &modules, options.wasm_fuel);
+    let mut engine = Engine::new(options.engine.clone());
+    if let Err(err) = setup(&mut engine) {
+        println!("{}", err);
+        process::exit(err.exit_code());
+    }
+
+    if options.differential.is_none() {
+        if let Some(path) = &options.logictest {
+            process::exit(run_logictest(path, &options, setup));
+        }
+        if let Some(dir) = &options.golden {
+            process::exit(run_golden(dir, &options, setup));
+        }
+    }
+
+    if let Some(connection) = &options.differential {
+        let code = match differential_queries(options.golden.as_ref(), query) {
+            Ok(queries) => run_differential(connection, &engine, &queries),
+            Err(err) => {
+                println!("[IO Error]: {}", err);
+                EXIT_IO
+            }
+        };
+        process::exit(code);
+    }
+
+    if options.stdio {
+        if let Err(err) = rpc::serve(&engine) {
+            println!("[IO Error]: {}", err);
+            process::exit(EXIT_IO);
+        }
+        return;
+    }
+
+    let printer = Printer {
+        format: options.output_format,
+        terminal: Terminal::detect(options.no_color),
+        timing: options.timing,
+    };
+    let interactive = query.is_empty() && options.file.is_none() && !piped;
+    if interactive && options.format.is_none() && options.output.is_none() {
+        print_default();
+        if let Err(err) = repl::run(&engine, printer, options.on_error) {
+            println!("[IO Error]: {}", err);
+            process::exit(EXIT_IO);
+        }
+        return;
+    }
+
+    let query = match &options.file {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(err) => {
+                println!("[IO Error]: {}: {}", path, err);
+                process::exit(EXIT_IO);
+            }
+        },
+        None if query.is_empty() && piped => {
+            let mut script = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut script) {
+                println!("[IO Error]: {}", err);
+                process::exit(EXIT_IO);
+            }
+            // Nothing piped in (e.g. stdin redirected from /dev/null).
+            if script.trim().is_empty() && args.len() < 2 {
+                return print_default();
+            }
+            script
+        }
+        None => query,
+    };
+
+    let code = match (options.format.as_deref(), &options.file) {
+        (None, path) => write_output(options.output.as_deref(), |out| {
+            run_script(
+                &engine,
+                &query,
+                path.as_deref(),
+                printer,
+                options.on_error,
+                out,
+            )
+        }),
+        (Some("xlsx"), _) => export_xlsx(engine.eval(&query), options.output),
+        (Some(format), _) => {
+            println!("[Invalid Argument]: unsupported format {}", format);
+            EXIT_USAGE
+        }
+    };
+    process::exit(code);
 }
@@ -0,0 +1,233 @@
+use crate::terminal::Terminal;
+use serde_json::{json, Value};
+use sql_test_engine::engine::decimal;
+use sql_test_engine::engine::engine::{format_num, to_text, CalcError, CalcResult, Evaluation};
+
+/// How the CLI prints the result of a statement.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// `Result: ...`, as the engine displays an evaluation.
+    #[default]
+    Text,
+    /// One JSON object per statement, with its column names and rows.
+    Json,
+    /// A header line of column names, then a line per row.
+    Csv,
+    /// The columns aligned in a bordered table.
+    Table,
+}
+
+/// Renders the result of a statement, or its error, in `format`. Text and
+/// tables are colored for `terminal`: results green with bold headers, and
+/// errors red; JSON and CSV never are.
+pub fn render(format: Format, res: &Result<Evaluation, CalcError>, terminal: Terminal) -> String {
+    match (format, res) {
+        (Format::Text, Ok(evaluation)) => text(evaluation, terminal),
+        (Format::Json, Ok(evaluation)) => json(evaluation).to_string(),
+        (Format::Csv, Ok(evaluation)) => csv(evaluation),
+        (Format::Table, Ok(evaluation)) => table(evaluation, terminal),
+        (Format::Json, Err(err)) => {
+            json!({ "error": { "code": err.code(), "message": err.to_string() } }).to_string()
+        }
+        (Format::Csv, Err(err)) => err.to_string(),
+        (_, Err(err)) => terminal.error(&err.to_string()),
+    }
+}
+
+/// A table result displays its column names on its second line.
+fn text(evaluation: &Evaluation, terminal: Terminal) -> String {
+    let text = evaluation.to_string();
+    if !matches!(evaluation.result, CalcResult::Table(_)) {
+        return terminal.result(&text);
+    }
+    let lines: Vec<_> = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match i {
+            1 => terminal.header(line),
+            _ => terminal.result(line),
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// NaN and the infinities, which JSON has no numbers for, are the strings
+/// the other formats print.
+pub fn to_json(result: &CalcResult) -> Value {
+    match result {
+        CalcResult::Num(num) => {
+            let text = format_num(*num);
+            serde_json::from_str(&text).unwrap_or(Value::String(text))
+        }
+        CalcResult::Int(int) => Value::from(*int),
+        CalcResult::Decimal(decimal) => {
+            serde_json::from_str(&decimal::format(decimal)).unwrap_or(Value::Null)
+        }
+        CalcResult::Bool(boolean) => json!(boolean),
+        CalcResult::Str(str) => json!(str),
+        CalcResult::Date(_) | CalcResult::Timestamp(_) | CalcResult::Interval(_) => {
+            json!(to_text(result))
+        }
+        CalcResult::Array(items) | CalcResult::Row(items) => {
+            Value::Array(items.iter().map(to_json).collect())
+        }
+        CalcResult::Table(rows) => Value::Array(
+            rows.iter()
+                .map(|row| Value::Array(row.iter().map(to_json).collect()))
+                .collect(),
+        ),
+        CalcResult::Null => Value::Null,
+    }
+}
+
+fn json(evaluation: &Evaluation) -> Value {
+    let rows: Vec<_> = evaluation
+        .rows()
+        .iter()
+        .map(|row| Value::Array(row.iter().map(to_json).collect()))
+        .collect();
+    json!({ "columns": evaluation.headers, "rows": rows })
+}
+
+/// Quotes a field holding a separator, a quote or a line break, doubling
+/// its quotes.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// NULLs are left empty.
+fn csv(evaluation: &Evaluation) -> String {
+    let mut lines = vec![evaluation.headers.clone()];
+    lines.extend(evaluation.rows().iter().map(|row| {
+        row.iter()
+            .map(|value| match value {
+                CalcResult::Null => String::new(),
+                value => to_text(value),
+            })
+            .collect()
+    }));
+    lines
+        .iter()
+        .map(|fields| {
+            let fields: Vec<_> = fields.iter().map(|field| csv_field(field)).collect();
+            fields.join(",")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn table(evaluation: &Evaluation, terminal: Terminal) -> String {
+    let headers = &evaluation.headers;
+    let rows: Vec<Vec<String>> = evaluation
+        .rows()
+        .iter()
+        .map(|row| row.iter().map(to_text).collect())
+        .collect();
+    let mut widths: Vec<_> = headers.iter().map(|name| name.chars().count()).collect();
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let border: Vec<_> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+    let border = format!("+{}+", border.join("+"));
+    let line = |values: &[String]| {
+        let cells: Vec<_> = values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!(" {:<width$} ", value, width = width))
+            .collect();
+        format!("|{}|", cells.join("|"))
+    };
+
+    let mut lines = vec![
+        border.clone(),
+        terminal.header(&line(headers)),
+        border.clone(),
+    ];
+    lines.extend(rows.iter().map(|row| terminal.result(&line(row))));
+    lines.push(border);
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sql_test_engine::engine::engine::Engine;
+
+    fn eval(sql: &str) -> Result<Evaluation, CalcError> {
+        Engine::default().eval(sql)
+    }
+
+    #[test]
+    fn to_json_matches_display() {
+        let cases = [
+            (2.0, "2"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e300, "1e+300"),
+            (1e-7, "1e-7"),
+            (1.5e16, "1.5e+16"),
+        ];
+        for (num, expected) in cases {
+            assert_eq!(to_json(&CalcResult::Num(num)).to_string(), expected);
+        }
+        assert_eq!(to_json(&CalcResult::Num(f64::NAN)), json!("NaN"));
+        assert_eq!(
+            to_json(&CalcResult::Num(f64::NEG_INFINITY)),
+            json!("-Infinity")
+        );
+    }
+
+    #[test]
+    fn render_formats() {
+        let plain = Terminal::default();
+        let res = eval("SELECT 1 AS a, 'x, \"y\"', NULL, SQRT(-1)");
+        assert_eq!(
+            render(Format::Text, &res, plain),
+            "Result: a = 1, x, \"y\", NULL, NaN"
+        );
+        assert_eq!(
+            render(Format::Json, &res, plain),
+            r#"{"columns":["a","'x, \"y\"'","NULL","SQRT(- 1)"],"rows":[[1,"x, \"y\"",null,"NaN"]]}"#
+        );
+        assert_eq!(
+            render(Format::Csv, &res, plain),
+            "a,\"'x, \"\"y\"\"'\",NULL,SQRT(- 1)\n1,\"x, \"\"y\"\"\",,NaN"
+        );
+        assert_eq!(
+            render(Format::Table, &res, plain),
+            "+---+----------+------+-----------+
+| a | 'x, \"y\"' | NULL | SQRT(- 1) |
++---+----------+------+-----------+
+| 1 | x, \"y\"   | NULL | NaN       |
++---+----------+------+-----------+"
+        );
+        assert_eq!(
+            render(Format::Table, &eval("VALUES (1, 'abc'), (22, NULL)"), plain),
+            "+---------+---------+
+| column1 | column2 |
++---------+---------+
+| 1       | abc     |
+| 22      | NULL    |
++---------+---------+"
+        );
+    }
+
+    #[test]
+    fn render_errors() {
+        let plain = Terminal::default();
+        let res = eval("SELECT 1 / 0");
+        assert_eq!(
+            render(Format::Json, &res, plain),
+            r#"{"error":{"code":"SQE-1006","message":"[Division By Zero]: Division by zero"}}"#
+        );
+        assert_eq!(
+            render(Format::Csv, &res, plain),
+            "[Division By Zero]: Division by zero"
+        );
+    }
+}
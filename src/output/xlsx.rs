@@ -0,0 +1,72 @@
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use sql_test_engine::engine::engine::{to_text, CalcResult, Evaluation};
+
+/// Writes the result table to `path` as a single worksheet: a bold header
+/// row followed by the value rows, with a column per value of a row or
+/// table, headed as the other formats head them.
+pub fn write(path: &str, evaluation: &Evaluation) -> Result<(), XlsxError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+
+    let bold = Format::new().set_bold();
+    for (col, header) in (0..).zip(&evaluation.headers) {
+        worksheet.write_string_with_format(0, col, header, &bold)?;
+    }
+    for (row, values) in (1..).zip(evaluation.rows()) {
+        for (col, value) in (0..).zip(values) {
+            write_value(worksheet, row, col, value)?;
+        }
+    }
+
+    workbook.save(path)
+}
+
+fn write_value(
+    worksheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: &CalcResult,
+) -> Result<(), XlsxError> {
+    match value {
+        CalcResult::Num(num) => worksheet.write_number(row, col, *num)?,
+        CalcResult::Int(int) => worksheet.write_number(row, col, *int as f64)?,
+        CalcResult::Decimal(_) => {
+            worksheet.write_number(row, col, value.as_f64().unwrap_or_default())?
+        }
+        CalcResult::Bool(boolean) => worksheet.write_boolean(row, col, *boolean)?,
+        CalcResult::Str(str) => worksheet.write_string(row, col, str)?,
+        CalcResult::Date(_)
+        | CalcResult::Timestamp(_)
+        | CalcResult::Interval(_)
+        | CalcResult::Array(_)
+        | CalcResult::Row(_)
+        | CalcResult::Table(_) => worksheet.write_string(row, col, to_text(value))?,
+        CalcResult::Null => worksheet,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sql_test_engine::engine::engine::Timing;
+
+    #[test]
+    fn write_creates_workbook() {
+        let path = std::env::temp_dir().join("sql_engine_write_creates_workbook.xlsx");
+        let path = path.to_str().unwrap();
+
+        let evaluation = Evaluation {
+            result: CalcResult::Row(vec![CalcResult::Num(2.0), CalcResult::Null]),
+            columns: vec![Some(String::from("total")), None],
+            headers: vec![String::from("total"), String::from("NULL")],
+            warnings: Vec::new(),
+            timing: Timing::default(),
+        };
+        write(path, &evaluation).unwrap();
+
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(&bytes[..2], b"PK");
+        std::fs::remove_file(path).unwrap();
+    }
+}
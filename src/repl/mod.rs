@@ -0,0 +1,222 @@
+use crate::{print_result, run_script, OnError, Printer};
+use sql_test_engine::engine::engine::Engine;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "sql> ";
+/// Shown while a statement spans several lines, until its semicolon.
+const CONTINUATION: &str = "...> ";
+
+const HELP: &str = "\\q               quit
+\\h               show this help (--help lists the SQL the engine runs)
+\\d [table]       list the tables, or the columns of one
+\\timing [on|off] toggle printing how long each statement takes
+\\i <file>        run the statements of a script file";
+
+/// A backslash command, given on a line of its own as in psql.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Quit,
+    Help,
+    Describe(Option<String>),
+    /// Turns timing on or off, or toggles it.
+    Timing(Option<bool>),
+    Include(String),
+}
+
+/// Parses a line starting with a backslash into the command it gives.
+fn parse_command(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let arg = words.next().map(String::from);
+    let command = match (name, arg) {
+        ("\\q", None) => Command::Quit,
+        ("\\h" | "\\?", None) => Command::Help,
+        ("\\d", table) => Command::Describe(table),
+        ("\\timing", None) => Command::Timing(None),
+        ("\\timing", Some(arg)) => match arg.as_str() {
+            "on" => Command::Timing(Some(true)),
+            "off" => Command::Timing(Some(false)),
+            _ => return Err(String::from("\\timing expects on or off")),
+        },
+        ("\\i", Some(path)) => Command::Include(path),
+        ("\\i", None) => return Err(String::from("\\i expects a file")),
+        (name, _) => return Err(format!("Invalid command {}. Try \\h for help.", name)),
+    };
+    match words.next() {
+        Some(extra) => Err(format!("Unexpected argument {}", extra)),
+        None => Ok(command),
+    }
+}
+
+/// Prints the tables, or the columns of `table` with their types.
+fn describe(engine: &Engine, table: Option<&str>, printer: Printer) {
+    let table = match table {
+        Some(table) => table,
+        None => {
+            let names = engine.all_table_names();
+            match names.is_empty() {
+                true => println!("No tables."),
+                false => println!("{}", names.join("\n")),
+            }
+            return;
+        }
+    };
+    match engine.describe_table(table) {
+        Some(columns) => {
+            let width = columns.iter().map(|(name, _)| name.chars().count()).max();
+            for (name, ty) in &columns {
+                println!("{:<width$}  {}", name, ty, width = width.unwrap_or(0));
+            }
+        }
+        None => println!(
+            "{}",
+            printer
+                .terminal
+                .error(&format!("[Invalid Argument]: Unknown table {}", table))
+        ),
+    }
+}
+
+/// The lines typed since the last statement a semicolon ended.
+#[derive(Debug, Default)]
+struct Buffer {
+    text: String,
+}
+
+impl Buffer {
+    /// Adds a line, returning the statements its semicolons end.
+    fn push(&mut self, engine: &Engine, line: &str) -> Vec<String> {
+        self.text.push_str(line);
+        self.text.push('\n');
+        let (statements, rest) = engine.split_terminated(&self.text);
+        let statements = statements
+            .into_iter()
+            .map(|(_, statement)| statement.to_string())
+            .collect();
+        self.text = rest.to_string();
+        statements
+    }
+
+    /// Whether a statement has been started but not ended yet.
+    fn pending(&self, engine: &Engine) -> bool {
+        !engine.split(&self.text).is_empty()
+    }
+
+    /// The statement left without a semicolon when the input ends.
+    fn finish(self, engine: &Engine) -> Option<String> {
+        let (_, statement) = engine.split(&self.text).pop()?;
+        Some(statement.to_string())
+    }
+}
+
+/// Reads statements from the terminal until it closes or `\\q`, printing
+/// the result of each. A statement runs once a semicolon ends it, so it may
+/// span lines; the prompt changes while one is open. A line starting with a
+/// backslash is a command instead, such as `\\i` running a script file,
+/// which stops at a failing statement as `on_error` says.
+pub fn run(engine: &Engine, mut printer: Printer, on_error: OnError) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut lines = stdin.lock().lines();
+    let mut buffer = Buffer::default();
+
+    loop {
+        let prompt = match buffer.pending(engine) {
+            true => CONTINUATION,
+            false => PROMPT,
+        };
+        write!(stdout, "{}", prompt)?;
+        stdout.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if line.trim_start().starts_with('\\') {
+            match parse_command(&line) {
+                Ok(Command::Quit) => return Ok(()),
+                Ok(Command::Help) => println!("{}", HELP),
+                Ok(Command::Describe(table)) => describe(engine, table.as_deref(), printer),
+                Ok(Command::Timing(timing)) => {
+                    printer.timing = timing.unwrap_or(!printer.timing);
+                    match printer.timing {
+                        true => println!("Timing is on."),
+                        false => println!("Timing is off."),
+                    }
+                }
+                Ok(Command::Include(path)) => match fs::read_to_string(&path) {
+                    Ok(script) => {
+                        run_script(engine, &script, Some(&path), printer, on_error, &mut stdout);
+                    }
+                    Err(err) => println!(
+                        "{}",
+                        printer
+                            .terminal
+                            .error(&format!("[IO Error]: {}: {}", path, err))
+                    ),
+                },
+                Err(err) => println!("{}", printer.terminal.error(&err)),
+            }
+            continue;
+        }
+        for statement in buffer.push(engine, &line) {
+            print_result(engine.eval(&statement), printer, &mut stdout);
+        }
+    }
+
+    // Leave the shell prompt on a line of its own after Ctrl-D.
+    writeln!(stdout)?;
+    if let Some(statement) = buffer.finish(engine) {
+        print_result(engine.eval(&statement), printer, &mut stdout);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse_command("\\q"), Ok(Command::Quit));
+        assert_eq!(parse_command("  \\? "), Ok(Command::Help));
+        assert_eq!(parse_command("\\d"), Ok(Command::Describe(None)));
+        assert_eq!(
+            parse_command("\\d users"),
+            Ok(Command::Describe(Some(String::from("users"))))
+        );
+        assert_eq!(parse_command("\\timing"), Ok(Command::Timing(None)));
+        assert_eq!(
+            parse_command("\\timing off"),
+            Ok(Command::Timing(Some(false)))
+        );
+        assert_eq!(
+            parse_command("\\i a.sql"),
+            Ok(Command::Include(String::from("a.sql")))
+        );
+        assert!(parse_command("\\i").is_err());
+        assert!(parse_command("\\q now").is_err());
+        assert_eq!(
+            parse_command("\\x"),
+            Err(String::from("Invalid command \\x. Try \\h for help."))
+        );
+    }
+
+    #[test]
+    fn buffer_waits_for_semicolons() {
+        let engine = Engine::default();
+        let mut buffer = Buffer::default();
+        assert!(!buffer.pending(&engine));
+        assert!(buffer.push(&engine, "SELECT 1 +").is_empty());
+        assert!(buffer.pending(&engine));
+        assert!(buffer.push(&engine, "'a;").is_empty());
+        assert_eq!(
+            buffer.push(&engine, "b'; SELECT 2; -- done"),
+            ["SELECT 1 +\n'a;\nb'", "SELECT 2"]
+        );
+        assert!(!buffer.pending(&engine));
+        assert!(buffer.push(&engine, "SELECT").is_empty());
+        assert_eq!(buffer.finish(&engine).as_deref(), Some("SELECT\n"));
+    }
+}
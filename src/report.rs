@@ -0,0 +1,179 @@
+//! Machine-readable test reports (JUnit XML and TAP) for CI systems.
+
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CaseStatus {
+    Passed,
+    /// Failure message, e.g. a diff of expected and actual output.
+    Failed(String),
+    Skipped,
+}
+
+/// One test case, independent of the runner that produced it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Case {
+    /// File the case belongs to; cases of one file form a suite.
+    pub suite: String,
+    pub name: String,
+    pub status: CaseStatus,
+    pub duration: Duration,
+}
+
+/// Escapes `text` for XML. Characters XML 1.0 does not allow at all, such
+/// as control characters other than tab and line breaks, become U+FFFD.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(ch),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {
+                escaped.push(char::REPLACEMENT_CHARACTER)
+            }
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Renders the cases as JUnit XML, one `<testsuite>` per file in order of
+/// first appearance.
+pub fn junit(cases: &[Case]) -> String {
+    let mut suites: Vec<(&str, Vec<&Case>)> = Vec::new();
+    for case in cases {
+        match suites.iter_mut().find(|(suite, _)| *suite == case.suite) {
+            Some((_, members)) => members.push(case),
+            None => suites.push((&case.suite, vec![case])),
+        }
+    }
+
+    let count = |members: &[&Case], wanted: fn(&CaseStatus) -> bool| {
+        members.iter().filter(|case| wanted(&case.status)).count()
+    };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    for (suite, members) in &suites {
+        let time: Duration = members.iter().map(|case| case.duration).sum();
+        xml += &format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.6}\">\n",
+            escape(suite),
+            members.len(),
+            count(members, |status| matches!(status, CaseStatus::Failed(_))),
+            count(members, |status| *status == CaseStatus::Skipped),
+            time.as_secs_f64()
+        );
+        for case in members {
+            xml += &format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.6}\"",
+                escape(suite),
+                escape(&case.name),
+                case.duration.as_secs_f64()
+            );
+            match &case.status {
+                CaseStatus::Passed => xml += "/>\n",
+                CaseStatus::Failed(message) => {
+                    xml += &format!(
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                        escape(message.lines().next().unwrap_or("")),
+                        escape(message)
+                    )
+                }
+                CaseStatus::Skipped => xml += ">\n      <skipped/>\n    </testcase>\n",
+            }
+        }
+        xml += "  </testsuite>\n";
+    }
+    xml + "</testsuites>\n"
+}
+
+/// Renders the cases as TAP version 13, failure messages going into YAML
+/// diagnostic blocks.
+pub fn tap(cases: &[Case]) -> String {
+    let mut out = format!("TAP version 13\n1..{}\n", cases.len());
+    for (i, case) in cases.iter().enumerate() {
+        let name = format!("{} - {}", case.suite, case.name);
+        match &case.status {
+            CaseStatus::Passed => out += &format!("ok {} {}\n", i + 1, name),
+            CaseStatus::Skipped => out += &format!("ok {} {} # SKIP\n", i + 1, name),
+            CaseStatus::Failed(message) => {
+                out += &format!("not ok {} {}\n  ---\n  message: |\n", i + 1, name);
+                for line in message.lines() {
+                    out += &format!("    {}\n", line);
+                }
+                out += &format!("  duration_ms: {}\n  ...\n", case.duration.as_millis());
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cases() -> Vec<Case> {
+        let case = |name: &str, status| Case {
+            suite: String::from("a.test"),
+            name: String::from(name),
+            status,
+            duration: Duration::from_millis(2),
+        };
+        vec![
+            case("1", CaseStatus::Passed),
+            case("2", CaseStatus::Failed(String::from("-1 < 2\n+3"))),
+            case("3", CaseStatus::Skipped),
+        ]
+    }
+
+    #[test]
+    fn junit_suites() {
+        assert_eq!(
+            junit(&cases()),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuites>
+  <testsuite name=\"a.test\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"0.006000\">
+    <testcase classname=\"a.test\" name=\"1\" time=\"0.002000\"/>
+    <testcase classname=\"a.test\" name=\"2\" time=\"0.002000\">
+      <failure message=\"-1 &lt; 2\">-1 &lt; 2
++3</failure>
+    </testcase>
+    <testcase classname=\"a.test\" name=\"3\" time=\"0.002000\">
+      <skipped/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"
+        );
+    }
+
+    #[test]
+    fn escape_invalid_xml_characters() {
+        assert_eq!(
+            escape("\u{1b}[31mred\u{1b}[0m\u{0}\tok\r\n\u{7f}\u{ffff}"),
+            "\u{fffd}[31mred\u{fffd}[0m\u{fffd}\tok\r\n\u{7f}\u{fffd}"
+        );
+    }
+
+    #[test]
+    fn tap_plan_and_diagnostics() {
+        assert_eq!(
+            tap(&cases()),
+            "TAP version 13
+1..3
+ok 1 a.test - 1
+not ok 2 a.test - 2
+  ---
+  message: |
+    -1 < 2
+    +3
+  duration_ms: 2
+  ...
+ok 3 a.test - 3 # SKIP
+"
+        );
+    }
+}
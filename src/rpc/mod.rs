@@ -0,0 +1,268 @@
+use crate::output::format::to_json;
+use serde_json::{json, Value};
+use sql_test_engine::engine::engine::*;
+use sqlparser::keywords::ALL_KEYWORDS;
+use std::io::{self, BufRead, Write};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const EXECUTION_ERROR: i64 = -32000;
+
+fn success(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn failure(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn engine_failure(id: Value, err: CalcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": EXECUTION_ERROR,
+            "message": err.to_string(),
+            "data": { "code": err.code() },
+        },
+    })
+}
+
+/// Keywords and function names, session functions included, starting with
+/// the word that ends at character `position` of `query`.
+fn complete(engine: &Engine, query: &str, position: usize) -> Vec<String> {
+    let before: String = query.chars().take(position).collect();
+    let word = before
+        .rsplit(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or("")
+        .to_uppercase();
+
+    let mut items: Vec<_> = ALL_KEYWORDS
+        .iter()
+        .map(|keyword| keyword.to_string())
+        .chain(engine.all_function_names())
+        .filter(|candidate| candidate.starts_with(&word))
+        .collect();
+    items.sort_unstable();
+    items.dedup();
+    items
+}
+
+/// Handles a single JSON-RPC request line and returns the response to send
+/// back, if any: a notification, which has no `id`, gets none.
+pub fn handle(engine: &Engine, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return Some(failure(Value::Null, PARSE_ERROR, err.to_string())),
+    };
+
+    let id = request.get("id").cloned();
+    let method = match request.get("method").and_then(Value::as_str) {
+        Some(method) => method,
+        None => {
+            let message = String::from("method is missing");
+            return Some(failure(id.unwrap_or(Value::Null), INVALID_REQUEST, message));
+        }
+    };
+    let response = respond(engine, id.clone().unwrap_or(Value::Null), method, &request)
+        .unwrap_or_else(|failure| failure);
+    id.map(|_| response)
+}
+
+/// Runs `method`, after checking the params it takes; invalid ones give the
+/// failure to send back instead.
+fn respond(engine: &Engine, id: Value, method: &str, request: &Value) -> Result<Value, Value> {
+    let query = || {
+        request
+            .pointer("/params/query")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                let message = String::from("params.query is missing");
+                failure(id.clone(), INVALID_PARAMS, message)
+            })
+    };
+
+    Ok(match method {
+        "parse" => match engine.parse(query()?) {
+            Ok(ast) => {
+                let statements: Vec<_> = ast.iter().map(ToString::to_string).collect();
+                success(id, json!({ "statements": statements }))
+            }
+            Err(err) => engine_failure(id, err),
+        },
+        "validate" => match engine.validate(query()?) {
+            Ok(()) => success(id, json!({ "valid": true })),
+            Err(err) => success(
+                id,
+                json!({ "valid": false, "message": err.to_string(), "code": err.code() }),
+            ),
+        },
+        "execute" => match engine.eval(query()?) {
+            Ok(evaluation) => {
+                let warnings: Vec<_> = evaluation
+                    .warnings
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                success(
+                    id,
+                    json!({
+                        "value": to_json(&evaluation.result),
+                        "columns": evaluation.columns,
+                        "warnings": warnings,
+                    }),
+                )
+            }
+            Err(err) => engine_failure(id, err),
+        },
+        "complete" => {
+            let query = query()?;
+            let length = query.chars().count();
+            let position = match request.pointer("/params/position") {
+                None => length,
+                Some(position) => match position.as_u64() {
+                    Some(position) if position as usize <= length => position as usize,
+                    _ => {
+                        let message = format!(
+                            "params.position must be a character offset from 0 to {}",
+                            length
+                        );
+                        return Err(failure(id, INVALID_PARAMS, message));
+                    }
+                },
+            };
+            success(id, json!({ "items": complete(engine, query, position) }))
+        }
+        _ => failure(id, METHOD_NOT_FOUND, format!("unknown method {}", method)),
+    })
+}
+
+/// Serves newline-delimited JSON-RPC 2.0 requests from stdin until EOF.
+pub fn serve(engine: &Engine) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle(engine, &line) {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn respond_to(line: &str) -> Value {
+        handle(&Engine::default(), line).unwrap()
+    }
+
+    #[test]
+    fn handle_execute() {
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":1,"method":"execute","params":{"query":"SELECT 1 + 1"}}"#,
+        );
+        assert_eq!(res["id"], json!(1));
+        assert_eq!(res["result"]["value"], json!(2));
+        assert_eq!(res["result"]["warnings"], json!([]));
+    }
+
+    #[test]
+    fn handle_execute_row() {
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":1,"method":"execute","params":{"query":"SELECT 1 AS a, 'x'"}}"#,
+        );
+        assert_eq!(res["result"]["value"], json!([1, "x"]));
+        assert_eq!(res["result"]["columns"], json!(["a", null]));
+    }
+
+    #[test]
+    fn handle_execute_error() {
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":2,"method":"execute","params":{"query":"SELECT 1 / 0"}}"#,
+        );
+        assert_eq!(res["error"]["code"], json!(EXECUTION_ERROR));
+        assert_eq!(res["error"]["data"]["code"], json!("SQE-1006"));
+    }
+
+    #[test]
+    fn handle_validate() {
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":3,"method":"validate","params":{"query":"Give the data"}}"#,
+        );
+        assert_eq!(res["result"]["valid"], json!(false));
+    }
+
+    #[test]
+    fn handle_complete() {
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":4,"method":"complete","params":{"query":"SELECT sqr"}}"#,
+        );
+        assert_eq!(res["result"]["items"], json!(["SQRT"]));
+
+        let engine = Engine::default();
+        engine
+            .exec("CREATE FUNCTION double_it(x) AS x * 2")
+            .unwrap();
+        let items = |query: &str, position: usize| {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "complete",
+                "params": { "query": query, "position": position },
+            });
+            handle(&engine, &request.to_string()).unwrap()["result"]["items"].clone()
+        };
+        assert_eq!(items("SELECT doub", 11), json!(["DOUBLE", "DOUBLE_IT"]));
+        assert!(items("SELECT 1 FR", 11)
+            .as_array()
+            .unwrap()
+            .contains(&json!("FROM")));
+        // Only the part of the word before the cursor counts.
+        assert_eq!(items("SELECT sqrt(1) FROM t", 10), json!(["SQRT"]));
+
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":6,"method":"complete","params":{"query":"SE","position":3}}"#,
+        );
+        assert_eq!(res["error"]["code"], json!(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn handle_unknown_method() {
+        let res = respond_to(
+            r#"{"jsonrpc":"2.0","id":5,"method":"format","params":{"query":"SELECT 1"}}"#,
+        );
+        assert_eq!(res["error"]["code"], json!(METHOD_NOT_FOUND));
+
+        let res = respond_to(r#"{"jsonrpc":"2.0","id":6,"method":"shutdown"}"#);
+        assert_eq!(res["error"]["code"], json!(METHOD_NOT_FOUND));
+
+        let res = respond_to(r#"{"jsonrpc":"2.0","id":7,"method":"execute"}"#);
+        assert_eq!(res["error"]["code"], json!(INVALID_PARAMS));
+    }
+
+    #[test]
+    fn handle_notification() {
+        let engine = Engine::default();
+        let notification = r#"{"jsonrpc":"2.0","method":"execute","params":{"query":"SET x = 1"}}"#;
+        assert_eq!(handle(&engine, notification), None);
+        assert_eq!(engine.exec("SELECT @x").unwrap(), CalcResult::Int(1));
+    }
+
+    #[test]
+    fn handle_malformed() {
+        let res = respond_to("{");
+        assert_eq!(res["error"]["code"], json!(PARSE_ERROR));
+    }
+}
@@ -0,0 +1,121 @@
+//! Runs independent test files concurrently, each against its own engine.
+
+use crate::engine::coverage::Coverage;
+use crate::engine::engine::{CalcError, Engine, Options};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Per-file results in input order, plus the coverage of all engines.
+#[derive(Debug, Default)]
+pub struct Report<T> {
+    pub outcomes: Vec<T>,
+    pub coverage: Coverage,
+}
+
+/// Applies `run` to every file on up to `jobs` threads. Each file gets a
+/// fresh engine, prepared by `setup`, so no state leaks between files;
+/// outcomes keep the order of `files` regardless of which finishes first.
+pub fn run_isolated<T, S, F>(
+    files: &[PathBuf],
+    options: &Options,
+    jobs: usize,
+    setup: S,
+    run: F,
+) -> io::Result<Report<T>>
+where
+    T: Send,
+    S: Fn(&mut Engine) -> Result<(), CalcError> + Sync,
+    F: Fn(&Engine, &Path) -> io::Result<Vec<T>> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<io::Result<Vec<T>>>>> =
+        Mutex::new(files.iter().map(|_| None).collect());
+    let coverage = Mutex::new(Coverage::default());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let file = match files.get(index) {
+                    Some(file) => file,
+                    None => break,
+                };
+
+                let mut engine = Engine::new(options.clone());
+                let res = match setup(&mut engine) {
+                    Ok(()) => run(&engine, file),
+                    Err(err) => Err(io::Error::other(err.to_string())),
+                };
+                coverage.lock().unwrap().merge(&engine.coverage());
+                results.lock().unwrap()[index] = Some(res);
+            });
+        }
+    });
+
+    let mut outcomes = Vec::new();
+    for res in results.into_inner().unwrap().into_iter().flatten() {
+        outcomes.extend(res?);
+    }
+    Ok(Report {
+        outcomes,
+        coverage: coverage.into_inner().unwrap(),
+    })
+}
+
+/// A `setup` for [`run_isolated`] leaving the engines as they are.
+pub fn no_setup(_: &mut Engine) -> Result<(), CalcError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_isolated_keeps_order() {
+        let files: Vec<_> = (0..20).map(|i| PathBuf::from(i.to_string())).collect();
+        let report = run_isolated(&files, &Options::default(), 4, no_setup, |engine, file| {
+            let query = format!("SELECT {} + 0", file.display());
+            Ok(vec![engine.exec(&query).unwrap().to_string()])
+        })
+        .unwrap();
+
+        let expected: Vec<_> = (0..20).map(|i| format!("Result: {}", i)).collect();
+        assert_eq!(report.outcomes, expected);
+        assert_eq!(
+            report
+                .coverage
+                .hits(crate::engine::coverage::Category::Operator, "+"),
+            20
+        );
+    }
+
+    #[test]
+    fn run_isolated_propagates_errors() {
+        let files = vec![PathBuf::from("missing")];
+        let res = run_isolated(&files, &Options::default(), 2, no_setup, |_, file| {
+            std::fs::read_to_string(file).map(|_| vec![()])
+        });
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn run_isolated_sets_up_every_engine() {
+        let files: Vec<_> = (0..4).map(|i| PathBuf::from(i.to_string())).collect();
+        let setup = |engine: &mut Engine| engine.exec("SET x = 10").map(|_| ());
+        let report = run_isolated(&files, &Options::default(), 2, setup, |engine, file| {
+            let query = format!("SELECT @x + {}", file.display());
+            Ok(vec![engine.exec(&query).unwrap().to_string()])
+        })
+        .unwrap();
+        assert_eq!(report.outcomes[3], "Result: 13");
+
+        let failing = |_: &mut Engine| Err(CalcError::Unexpected);
+        assert!(
+            run_isolated(&files, &Options::default(), 2, failing, |_, _| Ok(vec![()])).is_err()
+        );
+    }
+}
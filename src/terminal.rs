@@ -0,0 +1,58 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+const RED: &str = "31";
+const GREEN: &str = "32";
+const BOLD: &str = "1";
+
+/// What the terminal results are printed to can show: ANSI colors, unless
+/// stdout is redirected, `NO_COLOR` is set or `--no-color` was given. The
+/// default shows none.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Terminal {
+    color: bool,
+}
+
+impl Terminal {
+    pub fn detect(no_color: bool) -> Terminal {
+        // https://no-color.org: any non-empty value turns colors off.
+        let no_color = no_color || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Terminal {
+            color: !no_color && io::stdout().is_terminal(),
+        }
+    }
+
+    pub fn error(&self, text: &str) -> String {
+        self.paint(RED, text)
+    }
+
+    pub fn result(&self, text: &str) -> String {
+        self.paint(GREEN, text)
+    }
+
+    pub fn header(&self, text: &str) -> String {
+        self.paint(BOLD, text)
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        match self.color {
+            true => format!("\x1b[{}m{}\x1b[0m", style, text),
+            false => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_only_with_color() {
+        let color = Terminal { color: true };
+        assert_eq!(color.error("x"), "\x1b[31mx\x1b[0m");
+        assert_eq!(color.result("x"), "\x1b[32mx\x1b[0m");
+        assert_eq!(color.header("x"), "\x1b[1mx\x1b[0m");
+        assert_eq!(Terminal::default().error("x"), "x");
+        assert_eq!(Terminal::detect(true), Terminal::default());
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers behind the [`sql_test!`](crate::sql_test) macro.
+
+use crate::engine::engine::{CalcResult, Engine};
+
+/// Panics unless `query` evaluates to `expected`.
+pub fn expect_value(query: &str, expected: CalcResult) {
+    match Engine::default().exec(query) {
+        Ok(actual) => assert_eq!(actual, expected, "{}", query),
+        Err(err) => panic!("{}: expected {:?}, got {}", query, expected, err),
+    }
+}
+
+/// Panics unless `query` fails with the error `code`, e.g. `"SQE-1006"`.
+pub fn expect_error(query: &str, code: &str) {
+    match Engine::default().exec(query) {
+        Ok(actual) => panic!("{}: expected error {}, got {:?}", query, code, actual),
+        Err(err) => assert_eq!(err.code(), code, "{}: {}", query, err),
+    }
+}
+
+/// Declares a `#[test]` evaluating a query with the default options.
+///
+/// ```
+/// # use sql_test_engine::sql_test;
+/// sql_test!(adds, "SELECT 1 + 1", Int(2));
+/// sql_test!(divides_by_zero, "SELECT 1 / 0", Err("SQE-1006"));
+/// ```
+#[macro_export]
+macro_rules! sql_test {
+    ($name:ident, $query:expr, Err($code:expr)) => {
+        #[test]
+        fn $name() {
+            $crate::testing::expect_error($query, $code);
+        }
+    };
+    ($name:ident, $query:expr, $expected:expr) => {
+        #[test]
+        fn $name() {
+            #[allow(unused_imports)]
+            use $crate::engine::engine::CalcResult::*;
+            $crate::testing::expect_value($query, $expected);
+        }
+    };
+}
@@ -0,0 +1 @@
+ASSERT SELECT 2 * 3 = 7 AS 'multiplication'
@@ -0,0 +1 @@
+SELECT CAST('42' AS INT(11))
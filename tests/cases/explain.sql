@@ -0,0 +1,4 @@
+EXPLAIN SELECT region, SUM(amount) * 2 AS doubled
+FROM (VALUES (1, 2)) AS sales (region, amount)
+WHERE amount > 0
+GROUP BY region
@@ -0,0 +1 @@
+EXPLAIN ANALYZE SELECT k, COUNT(*) FROM (VALUES ('a', 1), ('b', 2), ('a', 4)) t(k, n) WHERE n > 1 GROUP BY k
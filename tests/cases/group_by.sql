@@ -0,0 +1 @@
+SELECT k, COUNT(*), SUM(n), AVG(n) FROM (VALUES ('a', 1), ('b', 2), ('a', 4)) t(k, n) GROUP BY k HAVING COUNT(*) > 1
@@ -0,0 +1 @@
+SELECT u.name, o.total FROM (VALUES (1, 'ann'), (2, 'bob')) u(id, name) LEFT JOIN (VALUES (1, 10), (1, 20), (3, 30)) o(user_id, total) ON u.id = o.user_id
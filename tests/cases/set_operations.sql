@@ -0,0 +1 @@
+SELECT 1 AS n UNION SELECT 2 UNION ALL SELECT 1.0 EXCEPT VALUES (2)
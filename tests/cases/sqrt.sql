@@ -0,0 +1 @@
+SELECT SQRT(16)
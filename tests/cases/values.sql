@@ -0,0 +1 @@
+SELECT a * 10, t.* FROM (VALUES (1, 'x'), (2, NULL)) t(a, b)
@@ -0,0 +1,21 @@
+use sql_test_engine::engine::engine::Options;
+use sql_test_engine::golden::{self, Status};
+use std::env;
+use std::path::Path;
+
+/// Runs `tests/cases`; set `UPDATE_GOLDEN=1` to regenerate the baselines.
+#[test]
+fn golden_cases() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases");
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let outcomes = golden::run(&dir, &Options::deterministic(), update, 4)
+        .unwrap()
+        .outcomes;
+    assert!(!outcomes.is_empty());
+
+    let failures: Vec<_> = outcomes
+        .iter()
+        .filter(|outcome| matches!(outcome.status, Status::Failed(_) | Status::Missing))
+        .collect();
+    assert!(failures.is_empty(), "{:#?}", failures);
+}
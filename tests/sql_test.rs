@@ -0,0 +1,15 @@
+use sql_test_engine::sql_test;
+
+sql_test!(adds, "SELECT 1 + 1", Int(2));
+sql_test!(respects_precedence, "SELECT 1 + 2 * 3", Int(7));
+sql_test!(divides, "SELECT 1.0 / 4", Num(0.25));
+sql_test!(divides_integers, "SELECT 7 / 2", Int(3));
+sql_test!(compares_numbers, "SELECT 2 > 3", Bool(false));
+sql_test!(compares_strings, "SELECT 'b' > 'a'", Bool(true));
+sql_test!(returns_strings, "SELECT 'abc'", Str(String::from("abc")));
+sql_test!(takes_square_roots, "SELECT SQRT(16)", Num(4.0));
+sql_test!(casts_strings, "SELECT CAST('42' AS INT)", Int(42));
+sql_test!(asserts, "ASSERT SELECT 2 * 3 = 6", Bool(true));
+sql_test!(divides_by_zero, "SELECT 1 / 0", Err("SQE-1006"));
+sql_test!(rejects_unknown_functions, "SELECT NOPE(1)", Err("SQE-1003"));
+sql_test!(fails_assertions, "ASSERT 1 > 2", Err("SQE-1008"));